@@ -1,4 +1,35 @@
+use std::cell::OnceCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// --- Source Spans ---
+
+// Position of a parsed form in its source text. Lines and columns are 1-based,
+// byte offsets are a half-open range into the input string.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    pub file: Option<Rc<str>>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.line, self.column, self.end_line, self.end_column
+        )
+    }
+}
 
 // --- Literal, Symbol, Keyword ---
 
@@ -15,11 +46,12 @@ pub enum Literal {
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Symbol(pub String);
 
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+#[derive(Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
 pub struct Keyword(pub String);
 
 // --- Map Key ---
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+// Ordered keywords first, then strings, then integers
+#[derive(Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
 pub enum MapKey {
     Keyword(Keyword),
     String(String),
@@ -27,7 +59,7 @@ pub enum MapKey {
 }
 
 // --- Patterns for Destructuring (let, fn params) ---
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum Pattern {
     Symbol(Symbol),
     Wildcard, // _
@@ -44,17 +76,18 @@ pub enum Pattern {
         as_symbol: Option<Symbol>, // For :as binding
     },
     // Literal(Literal), // Literals are not typically part of binding patterns directly, but MatchPattern
+    Spanned(Box<Pattern>, Box<Span>), // Source position of the wrapped pattern
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum MapDestructuringEntry {
     KeyBinding { key: MapKey, pattern: Box<Pattern> },
     Keys(Vec<Symbol>), // For :keys [s1 s2]
-                       // TODO: Consider :or { default-val literal } if needed for destructuring
+    Defaults(Vec<(Symbol, Expression)>), // For :or {s1 default}, used when the key of s1 is absent
 }
 
 // --- Patterns for Matching (match clauses) ---
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum MatchPattern {
     Literal(Literal),
     Symbol(Symbol),                 // Binds the matched value to the symbol
@@ -72,6 +105,7 @@ pub enum MatchPattern {
         rest: Option<Symbol>, // For ..rest or &rest
     },
     As(Symbol, Box<MatchPattern>), // :as pattern
+    Spanned(Box<MatchPattern>, Box<Span>), // Source position of the wrapped pattern
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -110,12 +144,16 @@ pub enum ParamType {
     // Variadic(Box<TypeExpr>), // Represented by FnExpr.variadic_param_type now
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum TypeExpr {
     Primitive(PrimitiveType),
     Alias(Symbol),         // Type alias like MyType or my.namespace/MyType
     Vector(Box<TypeExpr>), // Vector type, e.g., [:vector :int]
     Tuple(Vec<TypeExpr>),  // Tuple type, e.g., [:tuple :int :string :bool]
+    Array {
+        element_type: Box<TypeExpr>,
+        shape: Option<Vec<Option<usize>>>, // None is an unknown `?` dimension
+    }, // E.g., [:array float [2 ? 3]]
     Map {
         entries: Vec<MapTypeEntry>,
         wildcard: Option<Box<TypeExpr>>, // For [:* AnyType]
@@ -128,9 +166,106 @@ pub enum TypeExpr {
     Resource(Symbol),            // E.g., [:resource my.pkg/Handle]
     Union(Vec<TypeExpr>),        // E.g., [:or :int :string]
     Intersection(Vec<TypeExpr>), // E.g., [:and HasName HasId]
+    Refined {
+        base: Box<TypeExpr>,
+        predicates: Vec<TypePredicate>,
+    }, // E.g., [:and int [:> 0]]
     Literal(Literal),            // E.g., [:val 123] or [:val "hello"]
+    TypeVar(Symbol),             // E.g., the T in [:=> [[:vector T]] T]
     Any,                         // :any type
     Never,                       // :never type
+    Spanned(Box<TypeExpr>, Box<Span>), // Source position of the wrapped type
+}
+
+impl TypeExpr {
+    /// Whether a type name stands for a type variable: a single uppercase letter,
+    /// optionally followed by digits, such as `T`, `K`, `V` or `T2`
+    pub fn is_type_variable_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars.next().is_some_and(|c| c.is_ascii_uppercase()) && chars.all(|c| c.is_ascii_digit())
+    }
+}
+
+/// A constraint refining a base type, e.g. the `[:> 0]` in `[:and int [:> 0]]`
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypePredicate {
+    GreaterThan(Literal),    // [:> 0]
+    GreaterOrEqual(Literal), // [:>= 0]
+    LessThan(Literal),       // [:< 100]
+    LessOrEqual(Literal),    // [:<= 100]
+    MinLength(i64),          // [:min-length 1], for strings and collections
+    MaxLength(i64),          // [:max-length 255]
+    MatchesRegex(Regex),     // [:matches-regex "^[a-z]+$"]
+    OneOf(Vec<Literal>),     // [:one-of :low :high]
+}
+
+/// The pattern of a `[:matches-regex ...]` predicate, compiled once when the predicate
+/// is built. Patterns are equal when they are written the same.
+#[derive(Debug, Clone)]
+pub struct Regex(pub regex::Regex);
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl TypePredicate {
+    /// Build a predicate from its name (without the leading `:`) and literal arguments
+    pub fn from_parts(name: &str, args: Vec<Literal>) -> Result<Self, String> {
+        let number = |args: &[Literal]| match args {
+            [literal @ (Literal::Integer(_) | Literal::Float(_))] => Ok(literal.clone()),
+            _ => Err(format!("[:{} ...] takes one number", name)),
+        };
+        let length = |args: &[Literal]| match args {
+            [Literal::Integer(n)] if *n >= 0 => Ok(*n),
+            _ => Err(format!("[:{} ...] takes one non-negative integer", name)),
+        };
+        match name {
+            ">" => number(&args).map(TypePredicate::GreaterThan),
+            ">=" => number(&args).map(TypePredicate::GreaterOrEqual),
+            "<" => number(&args).map(TypePredicate::LessThan),
+            "<=" => number(&args).map(TypePredicate::LessOrEqual),
+            "min-length" => length(&args).map(TypePredicate::MinLength),
+            "max-length" => length(&args).map(TypePredicate::MaxLength),
+            "matches-regex" => match args.as_slice() {
+                [Literal::String(pattern)] => regex::Regex::new(pattern)
+                    .map(|regex| TypePredicate::MatchesRegex(Regex(regex)))
+                    .map_err(|e| format!("invalid regex in [:matches-regex ...]: {}", e)),
+                _ => Err("[:matches-regex ...] takes one string".to_string()),
+            },
+            "one-of" if !args.is_empty() => Ok(TypePredicate::OneOf(args)),
+            "one-of" => Err("[:one-of ...] takes at least one literal".to_string()),
+            other => Err(format!("unknown type predicate :{}", other)),
+        }
+    }
+
+    /// The predicate's name, without the leading `:`
+    pub fn name(&self) -> &'static str {
+        match self {
+            TypePredicate::GreaterThan(_) => ">",
+            TypePredicate::GreaterOrEqual(_) => ">=",
+            TypePredicate::LessThan(_) => "<",
+            TypePredicate::LessOrEqual(_) => "<=",
+            TypePredicate::MinLength(_) => "min-length",
+            TypePredicate::MaxLength(_) => "max-length",
+            TypePredicate::MatchesRegex(_) => "matches-regex",
+            TypePredicate::OneOf(_) => "one-of",
+        }
+    }
+
+    /// The predicate's arguments, as written
+    pub fn args(&self) -> Vec<Literal> {
+        match self {
+            TypePredicate::GreaterThan(bound)
+            | TypePredicate::GreaterOrEqual(bound)
+            | TypePredicate::LessThan(bound)
+            | TypePredicate::LessOrEqual(bound) => vec![bound.clone()],
+            TypePredicate::MinLength(n) | TypePredicate::MaxLength(n) => vec![Literal::Integer(*n)],
+            TypePredicate::MatchesRegex(Regex(regex)) => vec![Literal::String(regex.as_str().to_string())],
+            TypePredicate::OneOf(options) => options.clone(),
+        }
+    }
 }
 
 // --- Core Expression Structure ---
@@ -144,7 +279,7 @@ pub struct LetBinding {
 }
 
 // Represents the main expression types
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum Expression {
     Literal(Literal),
     Symbol(Symbol),
@@ -158,6 +293,7 @@ pub enum Expression {
     },
     If(IfExpr),
     Let(LetExpr),
+    LetFn(LetFnExpr), // Functions that may call each other, visible in their own bodies
     Do(DoExpr),
     Match(Box<MatchExpr>),     // Changed to Box<MatchExpr>
     LogStep(Box<LogStepExpr>), // Changed to Box<LogStepExpr>
@@ -167,6 +303,15 @@ pub enum Expression {
     Parallel(ParallelExpr),
     Def(Box<DefExpr>),   // Added for def as an expression
     Defn(Box<DefnExpr>), // Added for defn as an expression
+    DefType(Box<DefTypeExpr>), // Named type alias, e.g. (deftype User [:map [:id :string]])
+    Loop(LoopExpr),
+    Recur(Vec<Expression>), // Only in tail position of a loop body, checked by the parser
+    For(Box<ForExpr>),   // Collects the body's value for each iteration
+    DoSeq(Box<ForExpr>), // Runs the body for its effects and returns nil
+    DoTimes(Box<DoTimesExpr>),
+    Sugar(Rc<SugarForm>), // cond, when, if-let, case, ->, ...: expanded by `crate::desugar`
+    TaskContextAccess(Keyword), // @field access to the running task's context
+    Spanned(Box<Expression>, Box<Span>), // Source position of the wrapped expression
 }
 
 // Struct for Match Expression
@@ -191,6 +336,112 @@ pub struct LetExpr {
     pub body: Vec<Expression>,
 }
 
+// `(letfn [(name [params] body) ...] body)`: every function is in scope in all of
+// the function bodies as well as in the body, so they may be mutually recursive
+#[derive(Debug, Clone, PartialEq)]
+pub struct LetFnExpr {
+    pub functions: Vec<DefnExpr>,
+    pub body: Vec<Expression>,
+}
+
+// `(loop [bindings] body)`: `recur` runs the body again with the bindings rebound
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopExpr {
+    pub bindings: Vec<LetBinding>,
+    pub body: Vec<Expression>,
+}
+
+// A clause in the bindings vector of `for` and `doseq`
+#[derive(Debug, Clone, PartialEq)]
+pub enum IterationClause {
+    // Bind each item of a vector, or each [key value] entry of a map
+    Binding { pattern: Pattern, collection: Box<Expression> },
+    When(Box<Expression>), // :when test, skips the items for which it is falsy
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForExpr {
+    pub clauses: Vec<IterationClause>, // Later bindings iterate inside earlier ones
+    pub body: Vec<Expression>,
+}
+
+// `(dotimes [i n] body)`: run the body with i bound to 0 through n - 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoTimesExpr {
+    pub symbol: Symbol,
+    pub count: Box<Expression>,
+    pub body: Vec<Expression>,
+}
+
+// A conditional or threading form as written, with its expansion to the core forms.
+// The expansion is computed the first time it is needed and shared by every clone
+// of the expression, so a function body expands its forms once, not on every call.
+#[derive(Debug)]
+pub struct SugarForm {
+    pub form: SugarExpr,
+    expansion: OnceCell<Expression>,
+}
+
+impl SugarForm {
+    pub fn new(form: SugarExpr) -> Self {
+        SugarForm { form, expansion: OnceCell::new() }
+    }
+
+    /// The core forms this form stands for
+    pub fn expansion(&self) -> &Expression {
+        self.expansion.get_or_init(|| crate::desugar::expand(&self.form))
+    }
+}
+
+// Forms are equal when they are written the same, expanded or not
+impl PartialEq for SugarForm {
+    fn eq(&self, other: &Self) -> bool {
+        self.form == other.form
+    }
+}
+
+// Conditional and threading forms. They are kept as written so that they print
+// back as written, and are expanded to the core forms before evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub enum SugarExpr {
+    // `(cond test expr ...)`: the expr of the first truthy test, else nil
+    Cond(Vec<(Expression, Expression)>),
+    // `(when test body...)`, or `(when-not test body...)` when negated
+    When { negated: bool, test: Box<Expression>, body: Vec<Expression> },
+    // `(if-let [pattern value] then else?)`: then runs with the pattern bound
+    IfLet { binding: LetBinding, then_branch: Box<Expression>, else_branch: Option<Box<Expression>> },
+    // `(when-let [pattern value] body...)`
+    WhenLet { binding: LetBinding, body: Vec<Expression> },
+    // `(case value key expr ... default?)`: keys are literals, compared with `=`
+    Case { value: Box<Expression>, clauses: Vec<CaseClause>, default: Option<Box<Expression>> },
+    // `(-> value form...)`, `(->> value form...)` and `(some-> value form...)`
+    Thread { kind: ThreadKind, value: Box<Expression>, forms: Vec<Expression> },
+}
+
+// One `key expr` pair of a `case`; `(k1 k2) expr` lists several keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseClause {
+    pub keys: Vec<Literal>,
+    pub body: Expression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadKind {
+    First,     // `->`: the value becomes the first argument of each form
+    Last,      // `->>`: the value becomes the last argument of each form
+    SomeFirst, // `some->`: as `->`, but stops at the first nil
+}
+
+impl ThreadKind {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            ThreadKind::First => "->",
+            ThreadKind::Last => "->>",
+            ThreadKind::SomeFirst => "some->",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct IfExpr {
     pub condition: Box<Expression>,
@@ -234,6 +485,24 @@ pub struct DefnExpr {
     pub body: Vec<Expression>,
 }
 
+// Binds a name to a type so that annotations can refer to it, e.g. `User` or `my.module/User`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefTypeExpr {
+    pub name: Symbol,
+    pub type_expr: TypeExpr,
+}
+
+impl DefTypeExpr {
+    /// Names of the builtin types, which `deftype` cannot redefine
+    pub const RESERVED_NAMES: [&'static str; 9] =
+        ["int", "float", "string", "bool", "nil", "keyword", "symbol", "any", "never"];
+
+    /// Whether `deftype` cannot define a type with this name: a builtin type or a type variable
+    pub fn is_reserved(name: &str) -> bool {
+        Self::RESERVED_NAMES.contains(&name) || TypeExpr::is_type_variable_name(name)
+    }
+}
+
 // --- New Special Form Structs ---
 
 #[derive(Debug, Clone, PartialEq)]
@@ -319,6 +588,7 @@ pub struct ModuleDefinition {
 pub enum ModuleLevelDefinition {
     Def(DefExpr),
     Defn(DefnExpr),
+    DefType(DefTypeExpr),
     Import(ImportDefinition),
 }
 
@@ -328,3 +598,243 @@ pub struct ImportDefinition {
     pub alias: Option<Symbol>,     // :as alias
     pub only: Option<Vec<Symbol>>, // :only [sym1 sym2]
 }
+
+// --- Span Handling ---
+//
+// `Spanned` wrappers are metadata only: they are transparent to equality so that
+// hand-built ASTs compare equal to parsed ones, and consumers that only care
+// about structure can peel them with `unspanned()`.
+
+macro_rules! impl_spanned {
+    ($ty:ident) => {
+        impl $ty {
+            // Wrap this node with its source span (re-wrapping replaces the span)
+            pub fn with_span(self, span: Span) -> $ty {
+                $ty::Spanned(Box::new(self.into_unspanned()), Box::new(span))
+            }
+
+            // The span of the outermost wrapper, if any
+            pub fn span(&self) -> Option<&Span> {
+                match self {
+                    $ty::Spanned(_, span) => Some(span),
+                    _ => None,
+                }
+            }
+
+            // Peel all span wrappers off this node (children keep theirs)
+            pub fn unspanned(&self) -> &$ty {
+                let mut node = self;
+                while let $ty::Spanned(inner, _) = node {
+                    node = inner;
+                }
+                node
+            }
+
+            pub fn into_unspanned(self) -> $ty {
+                let mut node = self;
+                while let $ty::Spanned(inner, _) = node {
+                    node = *inner;
+                }
+                node
+            }
+        }
+    };
+}
+
+impl_spanned!(Expression);
+impl_spanned!(Pattern);
+impl_spanned!(MatchPattern);
+impl_spanned!(TypeExpr);
+
+impl Expression {
+    // The expressions directly inside this one
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Literal(_) | Expression::Symbol(_) | Expression::TaskContextAccess(_) | Expression::DefType(_) => {
+                Vec::new()
+            }
+            Expression::Spanned(inner, _) => vec![inner],
+            Expression::List(items) | Expression::Vector(items) => items.iter().collect(),
+            Expression::Map(entries) => entries.values().collect(),
+            Expression::FunctionCall { callee, arguments } => {
+                std::iter::once(callee.as_ref()).chain(arguments).collect()
+            }
+            Expression::If(if_expr) => std::iter::once(if_expr.condition.as_ref())
+                .chain(std::iter::once(if_expr.then_branch.as_ref()))
+                .chain(if_expr.else_branch.as_deref())
+                .collect(),
+            Expression::Let(let_expr) => let_expr
+                .bindings
+                .iter()
+                .map(|binding| binding.value.as_ref())
+                .chain(&let_expr.body)
+                .collect(),
+            Expression::LetFn(letfn) => letfn
+                .functions
+                .iter()
+                .flat_map(|function| &function.body)
+                .chain(&letfn.body)
+                .collect(),
+            Expression::Do(do_expr) => do_expr.expressions.iter().collect(),
+            Expression::Match(match_expr) => std::iter::once(match_expr.expression.as_ref())
+                .chain(match_expr.clauses.iter().flat_map(|clause| {
+                    clause.guard.as_deref().into_iter().chain(std::iter::once(clause.body.as_ref()))
+                }))
+                .collect(),
+            Expression::LogStep(log_step) => log_step.values.iter().collect(),
+            Expression::TryCatch(try_catch) => try_catch
+                .try_body
+                .iter()
+                .chain(try_catch.catch_clauses.iter().flat_map(|clause| &clause.body))
+                .chain(try_catch.finally_body.iter().flatten())
+                .collect(),
+            Expression::Fn(fn_expr) => fn_expr.body.iter().collect(),
+            Expression::WithResource(with_resource) => std::iter::once(with_resource.resource_init.as_ref())
+                .chain(&with_resource.body)
+                .collect(),
+            Expression::Parallel(parallel) => parallel.bindings.iter().map(|binding| binding.expression.as_ref()).collect(),
+            Expression::Def(def_expr) => vec![def_expr.value.as_ref()],
+            Expression::Defn(defn_expr) => defn_expr.body.iter().collect(),
+            Expression::Loop(loop_expr) => loop_expr
+                .bindings
+                .iter()
+                .map(|binding| binding.value.as_ref())
+                .chain(&loop_expr.body)
+                .collect(),
+            Expression::Recur(arguments) => arguments.iter().collect(),
+            Expression::For(for_expr) | Expression::DoSeq(for_expr) => for_expr
+                .clauses
+                .iter()
+                .map(|clause| match clause {
+                    IterationClause::Binding { collection, .. } => collection.as_ref(),
+                    IterationClause::When(test) => test.as_ref(),
+                })
+                .chain(&for_expr.body)
+                .collect(),
+            Expression::DoTimes(dotimes) => std::iter::once(dotimes.count.as_ref()).chain(&dotimes.body).collect(),
+            Expression::Sugar(sugar) => match &sugar.form {
+                SugarExpr::Cond(clauses) => clauses.iter().flat_map(|(test, expr)| [test, expr]).collect(),
+                SugarExpr::When { test, body, .. } => std::iter::once(test.as_ref()).chain(body).collect(),
+                SugarExpr::IfLet { binding, then_branch, else_branch } => std::iter::once(binding.value.as_ref())
+                    .chain(std::iter::once(then_branch.as_ref()))
+                    .chain(else_branch.as_deref())
+                    .collect(),
+                SugarExpr::WhenLet { binding, body } => std::iter::once(binding.value.as_ref()).chain(body).collect(),
+                SugarExpr::Case { value, clauses, default } => std::iter::once(value.as_ref())
+                    .chain(clauses.iter().map(|clause| &clause.body))
+                    .chain(default.as_deref())
+                    .collect(),
+                SugarExpr::Thread { value, forms, .. } => std::iter::once(value.as_ref()).chain(forms).collect(),
+            },
+        }
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        use Expression::*;
+        match (self.unspanned(), other.unspanned()) {
+            (Literal(a), Literal(b)) => a == b,
+            (Symbol(a), Symbol(b)) => a == b,
+            (List(a), List(b)) => a == b,
+            (Vector(a), Vector(b)) => a == b,
+            (Map(a), Map(b)) => a == b,
+            (
+                FunctionCall { callee: ca, arguments: aa },
+                FunctionCall { callee: cb, arguments: ab },
+            ) => ca == cb && aa == ab,
+            (If(a), If(b)) => a == b,
+            (Let(a), Let(b)) => a == b,
+            (LetFn(a), LetFn(b)) => a == b,
+            (Do(a), Do(b)) => a == b,
+            (Match(a), Match(b)) => a == b,
+            (LogStep(a), LogStep(b)) => a == b,
+            (TryCatch(a), TryCatch(b)) => a == b,
+            (Fn(a), Fn(b)) => a == b,
+            (WithResource(a), WithResource(b)) => a == b,
+            (Parallel(a), Parallel(b)) => a == b,
+            (Def(a), Def(b)) => a == b,
+            (Defn(a), Defn(b)) => a == b,
+            (DefType(a), DefType(b)) => a == b,
+            (Loop(a), Loop(b)) => a == b,
+            (Recur(a), Recur(b)) => a == b,
+            (For(a), For(b)) => a == b,
+            (DoSeq(a), DoSeq(b)) => a == b,
+            (DoTimes(a), DoTimes(b)) => a == b,
+            (Sugar(a), Sugar(b)) => a == b,
+            (TaskContextAccess(a), TaskContextAccess(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        use Pattern::*;
+        match (self.unspanned(), other.unspanned()) {
+            (Symbol(a), Symbol(b)) => a == b,
+            (Wildcard, Wildcard) => true,
+            (
+                VectorDestructuring { elements: ea, rest: ra, as_symbol: sa },
+                VectorDestructuring { elements: eb, rest: rb, as_symbol: sb },
+            ) => ea == eb && ra == rb && sa == sb,
+            (
+                MapDestructuring { entries: ea, rest: ra, as_symbol: sa },
+                MapDestructuring { entries: eb, rest: rb, as_symbol: sb },
+            ) => ea == eb && ra == rb && sa == sb,
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for MatchPattern {
+    fn eq(&self, other: &Self) -> bool {
+        use MatchPattern::*;
+        match (self.unspanned(), other.unspanned()) {
+            (Literal(a), Literal(b)) => a == b,
+            (Symbol(a), Symbol(b)) => a == b,
+            (Keyword(a), Keyword(b)) => a == b,
+            (Wildcard, Wildcard) => true,
+            (Type(ta, ba), Type(tb, bb)) => ta == tb && ba == bb,
+            (Vector { elements: ea, rest: ra }, Vector { elements: eb, rest: rb }) => {
+                ea == eb && ra == rb
+            }
+            (Map { entries: ea, rest: ra }, Map { entries: eb, rest: rb }) => ea == eb && ra == rb,
+            (As(sa, pa), As(sb, pb)) => sa == sb && pa == pb,
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for TypeExpr {
+    fn eq(&self, other: &Self) -> bool {
+        use TypeExpr::*;
+        match (self.unspanned(), other.unspanned()) {
+            (Primitive(a), Primitive(b)) => a == b,
+            (Alias(a), Alias(b)) => a == b,
+            (Vector(a), Vector(b)) => a == b,
+            (Tuple(a), Tuple(b)) => a == b,
+            (Array { element_type: ea, shape: sa }, Array { element_type: eb, shape: sb }) => {
+                ea == eb && sa == sb
+            }
+            (Map { entries: ea, wildcard: wa }, Map { entries: eb, wildcard: wb }) => {
+                ea == eb && wa == wb
+            }
+            (
+                Function { param_types: pa, variadic_param_type: va, return_type: ra },
+                Function { param_types: pb, variadic_param_type: vb, return_type: rb },
+            ) => pa == pb && va == vb && ra == rb,
+            (Resource(a), Resource(b)) => a == b,
+            (Union(a), Union(b)) => a == b,
+            (Intersection(a), Intersection(b)) => a == b,
+            (Refined { base: ba, predicates: pa }, Refined { base: bb, predicates: pb }) => {
+                ba == bb && pa == pb
+            }
+            (Literal(a), Literal(b)) => a == b,
+            (TypeVar(a), TypeVar(b)) => a == b,
+            (Any, Any) => true,
+            (Never, Never) => true,
+            _ => false,
+        }
+    }
+}
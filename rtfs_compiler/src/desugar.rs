@@ -0,0 +1,239 @@
+// Desugaring
+// Expands the conditional and threading forms (`cond`, `when`, `when-not`, `if-let`,
+// `when-let`, `case`, `->`, `->>`, `some->`) into `if`, `let`, `do`, `match` and calls.
+//
+// The parser keeps these forms as `Expression::Sugar` so they print back as written.
+// The AST evaluator and the IR converter both use their expansion when they reach one,
+// so the IR runtime, the optimizers and the checks over the IR only see core forms.
+// Expansion is one level deep: the forms written inside are expanded when reached,
+// and each form keeps its expansion (`SugarForm::expansion`) once computed.
+
+use crate::ast::{
+    CaseClause, DoExpr, Expression, IfExpr, Keyword, LetBinding, LetExpr, Literal, MatchClause,
+    MatchExpr, MatchPattern, Pattern, SugarExpr, Symbol, ThreadKind,
+};
+
+// Names bound by the expansions. `#` cannot appear in a symbol written in source,
+// so these never shadow a binding the program can see.
+const IF_LET_VALUE: &str = "if-let#";
+const SOME_VALUE: &str = "some->#";
+
+/// Expand a conditional or threading form into the core forms it stands for
+pub fn expand(sugar: &SugarExpr) -> Expression {
+    match sugar {
+        SugarExpr::Cond(clauses) => expand_cond(clauses),
+        SugarExpr::When { negated: false, test, body } => if_expr(test.as_ref().clone(), body_expr(body), None),
+        SugarExpr::When { negated: true, test, body } => {
+            if_expr(test.as_ref().clone(), nil(), Some(body_expr(body)))
+        }
+        SugarExpr::IfLet { binding, then_branch, else_branch } => expand_if_let(
+            binding,
+            then_branch.as_ref().clone(),
+            else_branch.as_deref().cloned(),
+        ),
+        SugarExpr::WhenLet { binding, body } => expand_if_let(binding, body_expr(body), None),
+        SugarExpr::Case { value, clauses, default } => expand_case(value, clauses, default.as_deref()),
+        SugarExpr::Thread { kind: ThreadKind::SomeFirst, value, forms } => {
+            expand_some_thread(value.as_ref().clone(), forms)
+        }
+        SugarExpr::Thread { kind, value, forms } => forms.iter().fold(value.as_ref().clone(), |threaded, form| {
+            thread_into(form, threaded, *kind == ThreadKind::Last)
+        }),
+    }
+}
+
+// `(if t1 e1 (if t2 e2 ...))`. A test that is a keyword (`:else`) or `true` is
+// always taken, so the clauses after it are dropped.
+fn expand_cond(clauses: &[(Expression, Expression)]) -> Expression {
+    clauses.iter().rev().fold(nil(), |otherwise, (test, expr)| match test.unspanned() {
+        Expression::Literal(Literal::Keyword(_)) | Expression::Literal(Literal::Boolean(true)) => expr.clone(),
+        _ => if_expr(test.clone(), expr.clone(), Some(otherwise)),
+    })
+}
+
+// `(let [v value] (if v (let [pattern v] then) else))`: the test is on the whole
+// value, and the pattern is only bound in the then branch
+fn expand_if_let(binding: &LetBinding, then_branch: Expression, else_branch: Option<Expression>) -> Expression {
+    let value = Symbol(IF_LET_VALUE.to_string());
+    let bound = Expression::Let(LetExpr {
+        bindings: vec![LetBinding {
+            pattern: binding.pattern.clone(),
+            type_annotation: binding.type_annotation.clone(),
+            value: Box::new(Expression::Symbol(value.clone())),
+        }],
+        body: vec![then_branch],
+    });
+    Expression::Let(LetExpr {
+        bindings: vec![LetBinding {
+            pattern: Pattern::Symbol(value.clone()),
+            type_annotation: None,
+            value: binding.value.clone(),
+        }],
+        body: vec![if_expr(Expression::Symbol(value), bound, else_branch)],
+    })
+}
+
+// A `match` with one literal pattern per key. Without a default, a value that
+// matches no key is a match error, as in Clojure.
+fn expand_case(value: &Expression, clauses: &[CaseClause], default: Option<&Expression>) -> Expression {
+    let mut match_clauses: Vec<MatchClause> = clauses
+        .iter()
+        .flat_map(|clause| {
+            clause.keys.iter().map(move |key| MatchClause {
+                pattern: MatchPattern::Literal(key.clone()),
+                guard: None,
+                body: Box::new(clause.body.clone()),
+            })
+        })
+        .collect();
+    if let Some(default) = default {
+        match_clauses.push(MatchClause {
+            pattern: MatchPattern::Wildcard,
+            guard: None,
+            body: Box::new(default.clone()),
+        });
+    }
+    Expression::Match(Box::new(MatchExpr {
+        expression: Box::new(value.clone()),
+        clauses: match_clauses,
+    }))
+}
+
+// `(match value nil nil v (some-> (form v) ...))`
+fn expand_some_thread(value: Expression, forms: &[Expression]) -> Expression {
+    let Some((form, rest)) = forms.split_first() else {
+        return value;
+    };
+    let bound = Symbol(SOME_VALUE.to_string());
+    let next = thread_into(form, Expression::Symbol(bound.clone()), false);
+    Expression::Match(Box::new(MatchExpr {
+        expression: Box::new(value),
+        clauses: vec![
+            MatchClause {
+                pattern: MatchPattern::Literal(Literal::Nil),
+                guard: None,
+                body: Box::new(nil()),
+            },
+            MatchClause {
+                pattern: MatchPattern::Symbol(bound),
+                guard: None,
+                body: Box::new(expand_some_thread(next, rest)),
+            },
+        ],
+    }))
+}
+
+// Put `threaded` into `form` as its first (or last) argument. A keyword reads that
+// key, as `(:k m)` does in Clojure; a symbol or other value is called with it. The
+// parser rejects special forms as steps, so none reaches the last arm.
+fn thread_into(form: &Expression, threaded: Expression, last: bool) -> Expression {
+    let call = |callee: Expression, mut arguments: Vec<Expression>| {
+        if last {
+            arguments.push(threaded.clone());
+        } else {
+            arguments.insert(0, threaded.clone());
+        }
+        Expression::FunctionCall { callee: Box::new(callee), arguments }
+    };
+    let get = |key: &Keyword, default: &[Expression]| Expression::FunctionCall {
+        callee: Box::new(Expression::Symbol(Symbol("get".to_string()))),
+        arguments: [threaded.clone(), Expression::Literal(Literal::Keyword(key.clone()))]
+            .into_iter()
+            .chain(default.iter().cloned())
+            .collect(),
+    };
+    let expanded = match form.unspanned() {
+        Expression::FunctionCall { callee, arguments } => call(callee.as_ref().clone(), arguments.clone()),
+        Expression::Literal(Literal::Keyword(key)) => get(key, &[]),
+        Expression::List(items) => match items.split_first().map(|(head, rest)| (head.unspanned(), rest)) {
+            Some((Expression::Literal(Literal::Keyword(key)), default)) => get(key, default),
+            _ => call(form.clone(), Vec::new()),
+        },
+        _ => call(form.clone(), Vec::new()),
+    };
+    match form.span() {
+        Some(span) => expanded.with_span(span.clone()),
+        None => expanded,
+    }
+}
+
+fn if_expr(condition: Expression, then_branch: Expression, else_branch: Option<Expression>) -> Expression {
+    Expression::If(IfExpr {
+        condition: Box::new(condition),
+        then_branch: Box::new(then_branch),
+        else_branch: else_branch.map(Box::new),
+    })
+}
+
+fn body_expr(body: &[Expression]) -> Expression {
+    match body {
+        [single] => single.clone(),
+        _ => Expression::Do(DoExpr { expressions: body.to_vec() }),
+    }
+}
+
+fn nil() -> Expression {
+    Expression::Literal(Literal::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+    use crate::runtime::evaluator::Evaluator;
+    use crate::runtime::Value;
+    use crate::ast::SugarForm;
+    use std::rc::Rc;
+
+    fn find_sugar(expr: &Expression) -> Option<Rc<SugarForm>> {
+        match expr.unspanned() {
+            Expression::Sugar(sugar) => Some(sugar.clone()),
+            other => other.children().into_iter().find_map(find_sugar),
+        }
+    }
+
+    fn expand_source(source: &str) -> Expression {
+        match parse_expression(source).unwrap().into_unspanned() {
+            Expression::Sugar(sugar) => expand(&sugar.form),
+            other => panic!("expected a sugar form, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_expansions_are_core_forms() {
+        let cases = [
+            ("(cond (< x 0) :neg (= x 0) :zero :else :pos)", "(if (< x 0) :neg (if (= x 0) :zero :pos))"),
+            ("(cond)", "nil"),
+            ("(when ok (a) (b))", "(if ok (do (a) (b)))"),
+            ("(when-not ok (a))", "(if ok nil (a))"),
+            ("(case k 1 :one (2 3) :few :many)", "(match k 1 :one 2 :few 3 :few _ :many)"),
+            ("(-> m :user (get-in [:a]) (str \"!\") count)", "(count (str (get-in (get m :user) [:a]) \"!\"))"),
+            ("(->> xs (filter odd?) (map-fn inc))", "(map-fn inc (filter odd? xs))"),
+            ("(-> m (:k 0))", "(get m :k 0)"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(expand_source(source), parse_expression(expected).unwrap(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_if_let_binds_the_pattern_only_in_the_then_branch() {
+        let expanded = expand_source("(if-let [[a b] (pair)] (+ a b) a)");
+        let Expression::Let(outer) = expanded else { panic!("expected a let") };
+        assert_eq!(outer.bindings[0].pattern, Pattern::Symbol(Symbol(IF_LET_VALUE.to_string())));
+        let Expression::If(if_expr) = &outer.body[0] else { panic!("expected an if") };
+        assert!(matches!(if_expr.then_branch.as_ref(), Expression::Let(_)));
+        assert_eq!(if_expr.else_branch.as_deref(), Some(&parse_expression("a").unwrap()));
+    }
+
+    #[test]
+    fn test_forms_are_expanded_once() {
+        let program = parse_expression("(let [f (fn [x] (when x (+ x 1)))] (+ (f 1) (f 2) (f 3)))").unwrap();
+        let sugar = find_sugar(&program).unwrap();
+        let expansion: *const Expression = sugar.expansion();
+        assert_eq!(Evaluator::new().evaluate(&program).unwrap(), Value::Integer(9));
+        // Every call, and every clone of the program, uses the expansion kept in the form
+        assert!(std::ptr::eq(expansion, sugar.expansion()));
+        assert!(std::ptr::eq(expansion, find_sugar(&program.clone()).unwrap().expansion()));
+    }
+}
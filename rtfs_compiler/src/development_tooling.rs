@@ -5,7 +5,9 @@ use std::collections::HashMap;
 use std::io::{self, Write};
 use crate::parser::parse_expression;
 use crate::runtime::{Runtime, RuntimeStrategy};
+use crate::ir::IrNode;
 use crate::ir_converter::IrConverter;
+use crate::match_analysis::check_plan;
 use crate::enhanced_ir_optimizer::EnhancedOptimizationPipeline;
 
 /// RTFS Read-Eval-Print Loop (REPL) interface
@@ -225,7 +227,7 @@ impl RtfsRepl {
                     match converter.convert(&ast) {
                         Ok(ir) => {
                             if self.context.show_ir {
-                                println!("⚡ IR: {:?}", ir);
+                                println!("⚡ IR:\n{}", Self::format_typed_ir(&ir));
                             }
 
                             // Apply optimizations if enabled
@@ -241,6 +243,11 @@ impl RtfsRepl {
                     }
                 }
 
+                // Flag matches that miss cases before anything runs
+                for warning in check_plan(&ast) {
+                    println!("⚠️  {}", warning);
+                }
+
                 // Evaluate with runtime
                 match self.runtime.evaluate_expression(&ast) {
                     Ok(result) => {
@@ -257,6 +264,58 @@ impl RtfsRepl {
         }
     }
 
+    /// Render an IR tree one node per line, indented by depth, as `kind : type`
+    fn format_typed_ir(node: &IrNode) -> String {
+        fn write_node(node: &IrNode, depth: usize, out: &mut String) {
+            let label = match node {
+                IrNode::Program { .. } => "Program".to_string(),
+                IrNode::Literal { value, .. } => format!("Literal {}", crate::parser::printer::print_literal(value)),
+                IrNode::Vector { .. } => "Vector".to_string(),
+                IrNode::Map { .. } => "Map".to_string(),
+                IrNode::VariableRef { name, .. } => format!("VariableRef {}", name),
+                IrNode::VariableBinding { name, .. } => format!("VariableBinding {}", name),
+                IrNode::Destructure { .. } => "Destructure".to_string(),
+                IrNode::Apply { .. } => "Apply".to_string(),
+                IrNode::Arithmetic { op, .. } => format!("Arithmetic {}", op.name()),
+                IrNode::Lambda { .. } => "Lambda".to_string(),
+                IrNode::Param { .. } => "Param".to_string(),
+                IrNode::If { .. } => "If".to_string(),
+                IrNode::Let { .. } => "Let".to_string(),
+                IrNode::Do { .. } => "Do".to_string(),
+                IrNode::Loop { .. } => "Loop".to_string(),
+                IrNode::Recur { .. } => "Recur".to_string(),
+                IrNode::Iterate { collect: true, .. } => "Iterate collect".to_string(),
+                IrNode::Iterate { .. } => "Iterate".to_string(),
+                IrNode::Match { .. } => "Match".to_string(),
+                IrNode::TryCatch { .. } => "TryCatch".to_string(),
+                IrNode::Parallel { .. } => "Parallel".to_string(),
+                IrNode::WithResource { .. } => "WithResource".to_string(),
+                IrNode::LogStep { .. } => "LogStep".to_string(),
+                IrNode::Module { name, .. } => format!("Module {}", name),
+                IrNode::FunctionDef { name, .. } => format!("FunctionDef {}", name),
+                IrNode::VariableDef { name, .. } => format!("VariableDef {}", name),
+                IrNode::TypeDef { name, definition, .. } => format!("TypeDef {} = {}", name, definition),
+                IrNode::Import { module_name, .. } => format!("Import {}", module_name),
+                IrNode::Task { .. } => "Task".to_string(),
+                IrNode::TaskContextAccess { field_name, .. } => format!("TaskContextAccess @{}", field_name.0),
+            };
+            out.push_str(&"  ".repeat(depth));
+            out.push_str(&label);
+            if let Some(ir_type) = node.ir_type() {
+                out.push_str(&format!(" : {}", ir_type));
+            }
+            out.push('\n');
+            for child in node.children() {
+                write_node(child, depth + 1, out);
+            }
+        }
+
+        let mut out = String::new();
+        write_node(node, 0, &mut out);
+        out.pop();
+        out
+    }
+
     fn run_test_suite(&mut self) {
         println!("🧪 Running RTFS Test Suite...");
         
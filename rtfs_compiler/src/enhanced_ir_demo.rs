@@ -128,7 +128,7 @@ fn test_function_inlining() {
                 ir_type: IrType::Int,
                 source_location: None,
             }
-        ],
+        ].into(),
         variadic_param: None,
         body: vec![
             IrNode::VariableRef {
@@ -138,7 +138,7 @@ fn test_function_inlining() {
                 ir_type: IrType::Int,
                 source_location: None,
             }
-        ],
+        ].into(),
         captures: vec![],
         ir_type: IrType::Function {
             param_types: vec![IrType::Int],
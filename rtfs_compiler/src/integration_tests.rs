@@ -1473,7 +1473,7 @@ mod tests {
         assert!(result.compilation_time_microseconds > 0);
         
         // Should have parsed successfully
-        match result.ast {
+        match result.ast.unspanned() {
             Expression::FunctionCall { .. } => {}, // Expected
             _ => panic!("Expected function call AST"),
         }
@@ -1485,7 +1485,7 @@ mod tests {
         let result = runner.run_pipeline_test("(let [x 10] x)").unwrap();
         
         // Should have parsed as let expression
-        match result.ast {
+        match result.ast.unspanned() {
             Expression::Let(_) => {}, // Expected
             _ => panic!("Expected let expression AST"),
         }
@@ -3,7 +3,8 @@
 
 use std::collections::HashMap;
 use std::rc::Rc;
-use crate::ast::{Symbol, Keyword, MapKey, Literal};
+use std::fmt;
+use crate::ast::{Symbol, Keyword, MapKey, Literal, MapTypeEntry, ParamType, PrimitiveType, Span, TypeExpr, TypePredicate};
 
 /// Unique identifier for IR nodes (for scope resolution and linking)
 pub type NodeId = u64;
@@ -13,7 +14,30 @@ pub type NodeId = u64;
 pub struct SourceLocation {
     pub line: usize,
     pub column: usize,
-    pub file: Option<String>,
+    pub file: Option<Rc<str>>,
+    pub start: usize, // Byte offset of the first character
+    pub end: usize,   // Byte offset one past the last character
+}
+
+impl From<&Span> for SourceLocation {
+    fn from(span: &Span) -> Self {
+        SourceLocation {
+            line: span.line,
+            column: span.column,
+            file: span.file.clone(),
+            start: span.start,
+            end: span.end,
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}:{}", file, self.line, self.column),
+            None => write!(f, "{}:{}", self.line, self.column),
+        }
+    }
 }
 
 /// IR Type system - represents resolved types
@@ -34,6 +58,10 @@ pub enum IrType {
     Vector(Box<IrType>),
     List(Box<IrType>),
     Tuple(Vec<IrType>),
+    Array {
+        element_type: Box<IrType>,
+        shape: Option<Vec<Option<usize>>>,
+    },
     Map {
         entries: Vec<IrMapTypeEntry>,
         wildcard: Option<Box<IrType>>,
@@ -49,11 +77,126 @@ pub enum IrType {
     // Advanced types
     Union(Vec<IrType>),
     Intersection(Vec<IrType>),
+    Refined {
+        base: Box<IrType>,
+        predicates: Vec<TypePredicate>,
+    },
     Resource(String),
     LiteralValue(Literal),
     
     // Type references (for aliases and forward declarations)
     TypeRef(String),
+
+    // Type variables of generic function types, e.g. the T in [:=> [[:vector T]] T]
+    TypeVar(String),
+}
+
+impl fmt::Display for IrType {
+    /// Writes the type in RTFS type-expression syntax
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list(types: &[IrType]) -> String {
+            types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ")
+        }
+        match self {
+            IrType::Int => write!(f, "int"),
+            IrType::Float => write!(f, "float"),
+            IrType::String => write!(f, "string"),
+            IrType::Bool => write!(f, "bool"),
+            IrType::Nil => write!(f, "nil"),
+            IrType::Keyword => write!(f, "keyword"),
+            IrType::Symbol => write!(f, "symbol"),
+            IrType::Any => write!(f, "any"),
+            IrType::Never => write!(f, "never"),
+            IrType::Vector(element) => write!(f, "[:vector {}]", element),
+            IrType::List(element) => write!(f, "[:list {}]", element),
+            IrType::Tuple(elements) => write!(f, "[:tuple {}]", list(elements)),
+            IrType::Array { element_type, shape } => match shape {
+                Some(shape) => write!(f, "[:array {} {}]", element_type, crate::parser::printer::print_array_shape(shape)),
+                None => write!(f, "[:array {}]", element_type),
+            },
+            IrType::Map { entries, wildcard } => {
+                write!(f, "[:map")?;
+                for entry in entries {
+                    let optional = if entry.optional { " ?" } else { "" };
+                    write!(f, " [:{} {}{}]", entry.key.0, entry.value_type, optional)?;
+                }
+                if let Some(wildcard) = wildcard {
+                    write!(f, " [:* {}]", wildcard)?;
+                }
+                write!(f, "]")
+            }
+            IrType::Function { param_types, variadic_param_type, return_type } => {
+                let mut params = list(param_types);
+                if let Some(variadic) = variadic_param_type {
+                    if !params.is_empty() {
+                        params.push(' ');
+                    }
+                    params.push_str(&format!("& {}", variadic));
+                }
+                write!(f, "[:=> [{}] {}]", params, return_type)
+            }
+            IrType::Union(types) => write!(f, "[:union {}]", list(types)),
+            IrType::Intersection(types) => write!(f, "[:and {}]", list(types)),
+            IrType::Refined { base, predicates } => {
+                write!(f, "[:and {}", base)?;
+                for predicate in predicates {
+                    write!(f, " {}", crate::parser::printer::print_type_predicate(predicate))?;
+                }
+                write!(f, "]")
+            }
+            IrType::Resource(name) => write!(f, "[:resource {}]", name),
+            IrType::LiteralValue(literal) => {
+                write!(f, "[:val {}]", crate::parser::printer::print_literal(literal))
+            }
+            IrType::TypeRef(name) | IrType::TypeVar(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl IrType {
+    /// The type expression this type was converted from, for checking values against it
+    /// at runtime. Lists become vectors and unresolved type references become aliases.
+    pub fn to_type_expr(&self) -> TypeExpr {
+        let boxed = |t: &IrType| Box::new(t.to_type_expr());
+        let all = |types: &[IrType]| types.iter().map(IrType::to_type_expr).collect();
+        match self {
+            IrType::Int => TypeExpr::Primitive(PrimitiveType::Int),
+            IrType::Float => TypeExpr::Primitive(PrimitiveType::Float),
+            IrType::String => TypeExpr::Primitive(PrimitiveType::String),
+            IrType::Bool => TypeExpr::Primitive(PrimitiveType::Bool),
+            IrType::Nil => TypeExpr::Primitive(PrimitiveType::Nil),
+            IrType::Keyword => TypeExpr::Primitive(PrimitiveType::Keyword),
+            IrType::Symbol => TypeExpr::Primitive(PrimitiveType::Symbol),
+            IrType::Any => TypeExpr::Any,
+            IrType::Never => TypeExpr::Never,
+            IrType::Vector(element) | IrType::List(element) => TypeExpr::Vector(boxed(element)),
+            IrType::Tuple(types) => TypeExpr::Tuple(all(types)),
+            IrType::Array { element_type, shape } => TypeExpr::Array { element_type: boxed(element_type), shape: shape.clone() },
+            IrType::Map { entries, wildcard } => TypeExpr::Map {
+                entries: entries
+                    .iter()
+                    .map(|entry| MapTypeEntry {
+                        key: entry.key.clone(),
+                        value_type: boxed(&entry.value_type),
+                        optional: entry.optional,
+                    })
+                    .collect(),
+                wildcard: wildcard.as_deref().map(boxed),
+            },
+            IrType::Function { param_types, variadic_param_type, return_type } => TypeExpr::Function {
+                param_types: param_types.iter().map(|t| ParamType::Simple(boxed(t))).collect(),
+                variadic_param_type: variadic_param_type.as_deref().map(boxed),
+                return_type: boxed(return_type),
+            },
+            IrType::Union(types) => TypeExpr::Union(all(types)),
+            IrType::Intersection(types) => TypeExpr::Intersection(all(types)),
+            IrType::Refined { base, predicates } => TypeExpr::Refined { base: boxed(base), predicates: predicates.clone() },
+            IrType::Resource(name) => TypeExpr::Resource(Symbol(name.clone())),
+            IrType::LiteralValue(literal) => TypeExpr::Literal(literal.clone()),
+            IrType::TypeRef(name) => TypeExpr::Alias(Symbol(name.clone())),
+            IrType::TypeVar(name) => TypeExpr::TypeVar(Symbol(name.clone())),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -82,6 +225,21 @@ pub enum IrNode {
         source_location: Option<SourceLocation>,
     },
     
+    // Collection constructors
+    Vector {
+        id: NodeId,
+        elements: Vec<IrNode>,
+        ir_type: IrType,
+        source_location: Option<SourceLocation>,
+    },
+
+    Map {
+        id: NodeId,
+        entries: Vec<IrMapEntry>,
+        ir_type: IrType,
+        source_location: Option<SourceLocation>,
+    },
+    
     // Variable operations
     VariableRef {
         id: NodeId,
@@ -97,6 +255,14 @@ pub enum IrNode {
         ir_type: IrType,
         source_location: Option<SourceLocation>,
     },
+
+    Destructure {
+        id: NodeId,
+        pattern: IrPattern, // Binds parts of the value; absent parts bind nil
+        defaults: Vec<IrPatternDefault>, // `:or` values for absent map keys
+        ir_type: IrType,
+        source_location: Option<SourceLocation>,
+    },
     
     // Function operations
     Apply {
@@ -107,11 +273,20 @@ pub enum IrNode {
         source_location: Option<SourceLocation>,
     },
     
+    // Call of builtin `+`, `-` or `*` on operands of known numeric type (see ir_specialize)
+    Arithmetic {
+        id: NodeId,
+        op: ArithmeticOp,
+        operands: Vec<IrNode>,
+        ir_type: IrType, // Int or Float; int operands of a float node are widened
+        source_location: Option<SourceLocation>,
+    },
+    
     Lambda {
         id: NodeId,
-        params: Vec<IrNode>, // IrParam nodes
+        params: Rc<[IrNode]>, // IrParam nodes, shared with the closures created from this lambda
         variadic_param: Option<Box<IrNode>>,
-        body: Vec<IrNode>,
+        body: Rc<[IrNode]>,
         captures: Vec<IrCapture>,
         ir_type: IrType,
         source_location: Option<SourceLocation>,
@@ -150,6 +325,33 @@ pub enum IrNode {
         source_location: Option<SourceLocation>,
     },
     
+    // Iteration
+    Loop {
+        id: NodeId,
+        bindings: Vec<IrLetBinding>,
+        body: Vec<IrNode>,
+        ir_type: IrType,
+        source_location: Option<SourceLocation>,
+    },
+    
+    Recur {
+        id: NodeId,
+        loop_id: NodeId, // The Loop whose bindings are rebound
+        arguments: Vec<IrNode>,
+        ir_type: IrType, // Never: control continues at the top of the loop
+        source_location: Option<SourceLocation>,
+    },
+    
+    // `for`, `doseq` and `dotimes`
+    Iterate {
+        id: NodeId,
+        clauses: Vec<IrIterationClause>,
+        body: Vec<IrNode>,
+        collect: bool, // Whether the body values are collected into a vector, as `for` does
+        ir_type: IrType,
+        source_location: Option<SourceLocation>,
+    },
+    
     // Pattern matching
     Match {
         id: NodeId,
@@ -220,6 +422,13 @@ pub enum IrNode {
         source_location: Option<SourceLocation>,
     },
     
+    TypeDef {
+        id: NodeId,
+        name: String,
+        definition: IrType, // The named type, with any aliases it mentions already resolved
+        source_location: Option<SourceLocation>,
+    },
+    
     Import {
         id: NodeId,
         module_name: String,
@@ -257,6 +466,66 @@ pub struct IrCapture {
     pub ir_type: IrType,
 }
 
+/// Operator of an `Arithmetic` node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+}
+
+impl ArithmeticOp {
+    /// Operator implementing the builtin of this name
+    pub fn from_builtin(name: &str) -> Option<Self> {
+        match name {
+            "+" => Some(ArithmeticOp::Add),
+            "-" => Some(ArithmeticOp::Subtract),
+            "*" => Some(ArithmeticOp::Multiply),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArithmeticOp::Add => "+",
+            ArithmeticOp::Subtract => "-",
+            ArithmeticOp::Multiply => "*",
+        }
+    }
+
+    /// Combine integer operands as the builtin does; `None` on overflow.
+    /// A single operand of `-` is negated.
+    pub fn apply_int(self, first: i64, rest: &[i64]) -> Option<i64> {
+        if rest.is_empty() && self == ArithmeticOp::Subtract {
+            return first.checked_neg();
+        }
+        rest.iter().try_fold(first, |acc, &value| match self {
+            ArithmeticOp::Add => acc.checked_add(value),
+            ArithmeticOp::Subtract => acc.checked_sub(value),
+            ArithmeticOp::Multiply => acc.checked_mul(value),
+        })
+    }
+
+    /// Combine float operands as the builtin does. A single operand of `-` is negated.
+    pub fn apply_float(self, first: f64, rest: &[f64]) -> f64 {
+        if rest.is_empty() && self == ArithmeticOp::Subtract {
+            return -first;
+        }
+        rest.iter().fold(first, |acc, &value| match self {
+            ArithmeticOp::Add => acc + value,
+            ArithmeticOp::Subtract => acc - value,
+            ArithmeticOp::Multiply => acc * value,
+        })
+    }
+}
+
+/// Entry of a map constructor
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrMapEntry {
+    pub key: MapKey,
+    pub value: IrNode,
+}
+
 /// Let binding in IR
 #[derive(Debug, Clone, PartialEq)]
 pub struct IrLetBinding {
@@ -265,6 +534,17 @@ pub struct IrLetBinding {
     pub init_expr: IrNode,
 }
 
+/// Clause of an `Iterate` node; later clauses run once per item of earlier ones
+#[derive(Debug, Clone, PartialEq)]
+pub enum IrIterationClause {
+    /// Bind each item of a vector, or each `[key value]` entry of a map
+    Binding { pattern: IrNode, collection: IrNode },
+    /// Bind the integers from 0 up to `count`, as `dotimes` does
+    Range { binding: IrNode, count: IrNode },
+    /// Skip the items for which the test is falsy
+    When(IrNode),
+}
+
 /// Match clause in IR
 #[derive(Debug, Clone, PartialEq)]
 pub struct IrMatchClause {
@@ -277,17 +557,25 @@ pub struct IrMatchClause {
 #[derive(Debug, Clone, PartialEq)]
 pub enum IrPattern {
     Literal(Literal),
-    Variable(String),
+    Variable {
+        name: String,
+        binding_id: NodeId, // Referenced by VariableRef nodes in the clause body
+    },
     Wildcard,
     Vector {
         elements: Vec<IrPattern>,
-        rest: Option<String>,
+        rest: Option<Box<IrPattern>>, // Variable pattern bound to the remaining elements
     },
     Map {
         entries: Vec<IrMapPatternEntry>,
-        rest: Option<String>,
+        rest: Option<Box<IrPattern>>, // Variable pattern bound to the unmatched entries
     },
     Type(IrType),
+    As {
+        name: String,
+        binding_id: NodeId,
+        pattern: Box<IrPattern>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -296,11 +584,18 @@ pub struct IrMapPatternEntry {
     pub pattern: IrPattern,
 }
 
+/// `:or` default of a variable bound by a map destructuring pattern, used when its key is absent
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrPatternDefault {
+    pub binding_id: NodeId,
+    pub value: IrNode,
+}
+
 /// Catch clause in try-catch
 #[derive(Debug, Clone, PartialEq)]
 pub struct IrCatchClause {
     pub error_pattern: IrPattern,
-    pub binding: Option<String>,
+    pub binding: Option<IrNode>, // VariableBinding for the caught error value
     pub body: Vec<IrNode>,
 }
 
@@ -317,14 +612,21 @@ impl IrNode {
         match self {
             IrNode::Program { id, .. } => *id,
             IrNode::Literal { id, .. } => *id,
+            IrNode::Vector { id, .. } => *id,
+            IrNode::Map { id, .. } => *id,
             IrNode::VariableRef { id, .. } => *id,
             IrNode::VariableBinding { id, .. } => *id,
+            IrNode::Destructure { id, .. } => *id,
             IrNode::Apply { id, .. } => *id,
+            IrNode::Arithmetic { id, .. } => *id,
             IrNode::Lambda { id, .. } => *id,
             IrNode::Param { id, .. } => *id,
             IrNode::If { id, .. } => *id,
             IrNode::Let { id, .. } => *id,
             IrNode::Do { id, .. } => *id,
+            IrNode::Loop { id, .. } => *id,
+            IrNode::Recur { id, .. } => *id,
+            IrNode::Iterate { id, .. } => *id,
             IrNode::Match { id, .. } => *id,
             IrNode::TryCatch { id, .. } => *id,
             IrNode::Parallel { id, .. } => *id,
@@ -333,6 +635,7 @@ impl IrNode {
             IrNode::Module { id, .. } => *id,
             IrNode::FunctionDef { id, .. } => *id,
             IrNode::VariableDef { id, .. } => *id,
+            IrNode::TypeDef { id, .. } => *id,
             IrNode::Import { id, .. } => *id,
             IrNode::Task { id, .. } => *id,
             IrNode::TaskContextAccess { id, .. } => *id,
@@ -343,14 +646,21 @@ impl IrNode {
     pub fn ir_type(&self) -> Option<&IrType> {
         match self {
             IrNode::Literal { ir_type, .. } => Some(ir_type),
+            IrNode::Vector { ir_type, .. } => Some(ir_type),
+            IrNode::Map { ir_type, .. } => Some(ir_type),
             IrNode::VariableRef { ir_type, .. } => Some(ir_type),
             IrNode::VariableBinding { ir_type, .. } => Some(ir_type),
+            IrNode::Destructure { ir_type, .. } => Some(ir_type),
             IrNode::Apply { ir_type, .. } => Some(ir_type),
+            IrNode::Arithmetic { ir_type, .. } => Some(ir_type),
             IrNode::Lambda { ir_type, .. } => Some(ir_type),
             IrNode::Param { ir_type, .. } => Some(ir_type),
             IrNode::If { ir_type, .. } => Some(ir_type),
             IrNode::Let { ir_type, .. } => Some(ir_type),
             IrNode::Do { ir_type, .. } => Some(ir_type),
+            IrNode::Loop { ir_type, .. } => Some(ir_type),
+            IrNode::Recur { ir_type, .. } => Some(ir_type),
+            IrNode::Iterate { ir_type, .. } => Some(ir_type),
             IrNode::Match { ir_type, .. } => Some(ir_type),
             IrNode::TryCatch { ir_type, .. } => Some(ir_type),
             IrNode::Parallel { ir_type, .. } => Some(ir_type),
@@ -369,14 +679,21 @@ impl IrNode {
         match self {
             IrNode::Program { source_location, .. } => source_location.as_ref(),
             IrNode::Literal { source_location, .. } => source_location.as_ref(),
+            IrNode::Vector { source_location, .. } => source_location.as_ref(),
+            IrNode::Map { source_location, .. } => source_location.as_ref(),
             IrNode::VariableRef { source_location, .. } => source_location.as_ref(),
             IrNode::VariableBinding { source_location, .. } => source_location.as_ref(),
+            IrNode::Destructure { source_location, .. } => source_location.as_ref(),
             IrNode::Apply { source_location, .. } => source_location.as_ref(),
+            IrNode::Arithmetic { source_location, .. } => source_location.as_ref(),
             IrNode::Lambda { source_location, .. } => source_location.as_ref(),
             IrNode::Param { source_location, .. } => source_location.as_ref(),
             IrNode::If { source_location, .. } => source_location.as_ref(),
             IrNode::Let { source_location, .. } => source_location.as_ref(),
             IrNode::Do { source_location, .. } => source_location.as_ref(),
+            IrNode::Loop { source_location, .. } => source_location.as_ref(),
+            IrNode::Recur { source_location, .. } => source_location.as_ref(),
+            IrNode::Iterate { source_location, .. } => source_location.as_ref(),
             IrNode::Match { source_location, .. } => source_location.as_ref(),
             IrNode::TryCatch { source_location, .. } => source_location.as_ref(),
             IrNode::Parallel { source_location, .. } => source_location.as_ref(),
@@ -385,9 +702,117 @@ impl IrNode {
             IrNode::Module { source_location, .. } => source_location.as_ref(),
             IrNode::FunctionDef { source_location, .. } => source_location.as_ref(),
             IrNode::VariableDef { source_location, .. } => source_location.as_ref(),
+            IrNode::TypeDef { source_location, .. } => source_location.as_ref(),
             IrNode::Import { source_location, .. } => source_location.as_ref(),
             IrNode::Task { source_location, .. } => source_location.as_ref(),
             IrNode::TaskContextAccess { source_location, .. } => source_location.as_ref(),
         }
     }
+
+    /// Direct child nodes, in evaluation order. Patterns are not nodes and are not included.
+    pub fn children(&self) -> Vec<&IrNode> {
+        match self {
+            IrNode::Program { forms, .. } => forms.iter().collect(),
+            IrNode::Vector { elements, .. } => elements.iter().collect(),
+            IrNode::Map { entries, .. } => entries.iter().map(|entry| &entry.value).collect(),
+            IrNode::Apply { function, arguments, .. } => {
+                std::iter::once(function.as_ref()).chain(arguments).collect()
+            }
+            IrNode::Arithmetic { operands, .. } => operands.iter().collect(),
+            IrNode::Lambda { params, variadic_param, body, .. } => {
+                params.iter().chain(variadic_param.as_deref()).chain(body.iter()).collect()
+            }
+            IrNode::Param { binding, .. } => vec![binding.as_ref()],
+            IrNode::Destructure { defaults, .. } => defaults.iter().map(|default| &default.value).collect(),
+            IrNode::If { condition, then_branch, else_branch, .. } => {
+                vec![condition.as_ref(), then_branch.as_ref()].into_iter().chain(else_branch.as_deref()).collect()
+            }
+            IrNode::Let { bindings, body, .. } => bindings
+                .iter()
+                .flat_map(|binding| [&binding.pattern, &binding.init_expr])
+                .chain(body)
+                .collect(),
+            IrNode::Do { expressions, .. } => expressions.iter().collect(),
+            IrNode::Loop { bindings, body, .. } => bindings
+                .iter()
+                .flat_map(|binding| [&binding.pattern, &binding.init_expr])
+                .chain(body)
+                .collect(),
+            IrNode::Recur { arguments, .. } => arguments.iter().collect(),
+            IrNode::Iterate { clauses, body, .. } => clauses
+                .iter()
+                .flat_map(|clause| match clause {
+                    IrIterationClause::Binding { pattern, collection } => vec![collection, pattern],
+                    IrIterationClause::Range { binding, count } => vec![count, binding],
+                    IrIterationClause::When(test) => vec![test],
+                })
+                .chain(body)
+                .collect(),
+            IrNode::Match { expression, clauses, .. } => std::iter::once(expression.as_ref())
+                .chain(clauses.iter().flat_map(|clause| clause.guard.iter().chain(std::iter::once(&clause.body))))
+                .collect(),
+            IrNode::TryCatch { try_body, catch_clauses, finally_body, .. } => try_body
+                .iter()
+                .chain(catch_clauses.iter().flat_map(|clause| clause.binding.iter().chain(&clause.body)))
+                .chain(finally_body.iter().flatten())
+                .collect(),
+            IrNode::Parallel { bindings, .. } => bindings
+                .iter()
+                .flat_map(|binding| [&binding.binding, &binding.init_expr])
+                .collect(),
+            IrNode::WithResource { binding, init_expr, body, .. } => {
+                vec![binding.as_ref(), init_expr.as_ref()].into_iter().chain(body).collect()
+            }
+            IrNode::LogStep { values, .. } => values.iter().collect(),
+            IrNode::Module { definitions, .. } => definitions.iter().collect(),
+            IrNode::FunctionDef { lambda, .. } => vec![lambda.as_ref()],
+            IrNode::VariableDef { init_expr, .. } => vec![init_expr.as_ref()],
+            IrNode::Task { intent, contracts, plan, .. } => vec![intent.as_ref(), contracts.as_ref(), plan.as_ref()],
+            IrNode::Literal { .. }
+            | IrNode::VariableRef { .. }
+            | IrNode::VariableBinding { .. }
+            | IrNode::TypeDef { .. }
+            | IrNode::Import { .. }
+            | IrNode::TaskContextAccess { .. } => Vec::new(),
+        }
+    }
+
+    /// Set the source location of this node
+    pub fn set_source_location(&mut self, location: Option<SourceLocation>) {
+        *self.source_location_slot_mut() = location;
+    }
+
+    fn source_location_slot_mut(&mut self) -> &mut Option<SourceLocation> {
+        match self {
+            IrNode::Program { source_location, .. } => source_location,
+            IrNode::Literal { source_location, .. } => source_location,
+            IrNode::Vector { source_location, .. } => source_location,
+            IrNode::Map { source_location, .. } => source_location,
+            IrNode::VariableRef { source_location, .. } => source_location,
+            IrNode::VariableBinding { source_location, .. } => source_location,
+            IrNode::Destructure { source_location, .. } => source_location,
+            IrNode::Apply { source_location, .. } => source_location,
+            IrNode::Arithmetic { source_location, .. } => source_location,
+            IrNode::Lambda { source_location, .. } => source_location,
+            IrNode::Param { source_location, .. } => source_location,
+            IrNode::If { source_location, .. } => source_location,
+            IrNode::Let { source_location, .. } => source_location,
+            IrNode::Do { source_location, .. } => source_location,
+            IrNode::Loop { source_location, .. } => source_location,
+            IrNode::Recur { source_location, .. } => source_location,
+            IrNode::Iterate { source_location, .. } => source_location,
+            IrNode::Match { source_location, .. } => source_location,
+            IrNode::TryCatch { source_location, .. } => source_location,
+            IrNode::Parallel { source_location, .. } => source_location,
+            IrNode::WithResource { source_location, .. } => source_location,
+            IrNode::LogStep { source_location, .. } => source_location,
+            IrNode::Module { source_location, .. } => source_location,
+            IrNode::FunctionDef { source_location, .. } => source_location,
+            IrNode::VariableDef { source_location, .. } => source_location,
+            IrNode::TypeDef { source_location, .. } => source_location,
+            IrNode::Import { source_location, .. } => source_location,
+            IrNode::Task { source_location, .. } => source_location,
+            IrNode::TaskContextAccess { source_location, .. } => source_location,
+        }
+    }
 }
@@ -1,10 +1,11 @@
 // Complete AST to IR Converter Implementation
 // Provides full conversion from parsed AST to optimized IR
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use crate::ast::*;
 use crate::ir::*;
+use crate::runtime::module_runtime::ExportType;
 
 /// Error types for IR conversion
 #[derive(Debug, Clone, PartialEq)]
@@ -14,8 +15,8 @@ pub enum IrConversionError {
         location: Option<SourceLocation>,
     },
     TypeMismatch {
-        expected: IrType,
-        found: IrType,
+        expected: Box<IrType>,
+        found: Box<IrType>,
         location: Option<SourceLocation>,
     },
     InvalidPattern {
@@ -26,6 +27,15 @@ pub enum IrConversionError {
         message: String,
         location: Option<SourceLocation>,
     },
+    ResourceMisuse {
+        resource_type: String,
+        message: String,
+        location: Option<SourceLocation>,
+    },
+    InvalidSpecialForm {
+        message: String,
+        location: Option<SourceLocation>,
+    },
     InternalError {
         message: String,
     },
@@ -33,6 +43,33 @@ pub enum IrConversionError {
 
 pub type IrConversionResult<T> = Result<T, IrConversionError>;
 
+impl IrConversionError {
+    /// Fill in the source location if the error does not already carry one
+    pub fn with_location(self, location: SourceLocation) -> Self {
+        match self {
+            IrConversionError::UndefinedSymbol { symbol, location: None } => {
+                IrConversionError::UndefinedSymbol { symbol, location: Some(location) }
+            }
+            IrConversionError::TypeMismatch { expected, found, location: None } => {
+                IrConversionError::TypeMismatch { expected, found, location: Some(location) }
+            }
+            IrConversionError::InvalidPattern { message, location: None } => {
+                IrConversionError::InvalidPattern { message, location: Some(location) }
+            }
+            IrConversionError::InvalidTypeAnnotation { message, location: None } => {
+                IrConversionError::InvalidTypeAnnotation { message, location: Some(location) }
+            }
+            IrConversionError::ResourceMisuse { resource_type, message, location: None } => {
+                IrConversionError::ResourceMisuse { resource_type, message, location: Some(location) }
+            }
+            IrConversionError::InvalidSpecialForm { message, location: None } => {
+                IrConversionError::InvalidSpecialForm { message, location: Some(location) }
+            }
+            error => error,
+        }
+    }
+}
+
 /// Information about a binding in the current scope
 #[derive(Debug, Clone)]
 pub struct BindingInfo {
@@ -44,6 +81,7 @@ pub struct BindingInfo {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum BindingKind {
+    Builtin,
     Variable,
     Function,
     Parameter,
@@ -103,6 +141,13 @@ pub struct IrConverter {
     scope_stack: Vec<HashMap<String, BindingInfo>>,
     type_context: TypeContext,
     capture_analysis: HashMap<NodeId, Vec<IrCapture>>,
+    /// Lambdas being converted, innermost last, with the scope depth of their parameters
+    function_scopes: Vec<(NodeId, usize)>,
+    /// Loops being converted, innermost last, with the number of enclosing lambdas
+    /// and of bindings of each; `recur` rebinds the innermost one
+    loop_scopes: Vec<(NodeId, usize, usize)>,
+    /// Slots of the definitions (`def`, `defn`, `letfn`) converted or declared so far
+    definitions: HashSet<NodeId>,
     /// Optional module registry for resolving qualified symbols during conversion
     module_registry: Option<*const crate::runtime::module_runtime::ModuleRegistry>,
 }
@@ -117,6 +162,9 @@ impl IrConverter {
                 constraints: Vec::new(),
             },
             capture_analysis: HashMap::new(),
+            function_scopes: Vec::new(),
+            loop_scopes: Vec::new(),
+            definitions: HashSet::new(),
             module_registry: None,
         };
         
@@ -133,50 +181,14 @@ impl IrConverter {
     
     /// Add built-in functions to global scope
     fn add_builtin_functions(&mut self) {
-        let builtins = [
-            ("+", IrType::Function {
-                param_types: vec![IrType::Int, IrType::Int],
-                variadic_param_type: Some(Box::new(IrType::Int)),
-                return_type: Box::new(IrType::Int),
-            }),
-            ("-", IrType::Function {
-                param_types: vec![IrType::Int, IrType::Int],
-                variadic_param_type: None,
-                return_type: Box::new(IrType::Int),
-            }),
-            ("*", IrType::Function {
-                param_types: vec![IrType::Int, IrType::Int],
-                variadic_param_type: Some(Box::new(IrType::Int)),
-                return_type: Box::new(IrType::Int),
-            }),
-            ("/", IrType::Function {
-                param_types: vec![IrType::Int, IrType::Int],
-                variadic_param_type: None,
-                return_type: Box::new(IrType::Int),
-            }),
-            ("=", IrType::Function {
-                param_types: vec![IrType::Any, IrType::Any],
-                variadic_param_type: None,
-                return_type: Box::new(IrType::Bool),
-            }),
-            (">", IrType::Function {
-                param_types: vec![IrType::Any, IrType::Any],
-                variadic_param_type: None,
-                return_type: Box::new(IrType::Bool),
-            }),
-            ("<", IrType::Function {
-                param_types: vec![IrType::Any, IrType::Any],
-                variadic_param_type: None,
-                return_type: Box::new(IrType::Bool),
-            }),
-        ];
+        let builtins = crate::ir_typecheck::builtin_signatures();
         
         for (name, func_type) in builtins {
             let binding_info = BindingInfo {
                 name: name.to_string(),
                 binding_id: self.next_id(),
                 ir_type: func_type,
-                kind: BindingKind::Function,
+                kind: BindingKind::Builtin,
             };
             self.scope_stack[0].insert(name.to_string(), binding_info);
         }
@@ -199,26 +211,54 @@ impl IrConverter {
         }
     }
     
-    /// Look up a symbol in the scope stack
-    fn lookup_symbol(&self, name: &str) -> Option<&BindingInfo> {
-        for scope in self.scope_stack.iter().rev() {
+    /// Look up a symbol in the scope stack, along with the depth of the scope that defines it
+    fn lookup_symbol_with_depth(&self, name: &str) -> Option<(&BindingInfo, usize)> {
+        for (depth, scope) in self.scope_stack.iter().enumerate().rev() {
             if let Some(binding) = scope.get(name) {
-                return Some(binding);
+                return Some((binding, depth));
             }
         }
         None
     }
     
+    /// Record a reference to a binding defined at `depth` as a capture of every
+    /// enclosing lambda whose parameters live in a deeper scope
+    fn record_capture(&mut self, binding: &BindingInfo, depth: usize) {
+        if binding.kind == BindingKind::Builtin {
+            return; // Builtins are resolved from the global environment
+        }
+        for (lambda_id, function_depth) in &self.function_scopes {
+            if depth >= *function_depth {
+                continue;
+            }
+            let captures = self.capture_analysis.entry(*lambda_id).or_default();
+            if !captures.iter().any(|c| c.binding_id == binding.binding_id) {
+                captures.push(IrCapture {
+                    name: binding.name.clone(),
+                    binding_id: binding.binding_id,
+                    ir_type: binding.ir_type.clone(),
+                });
+            }
+        }
+    }
+    
     /// Convert a simple expression (main entry point)
     pub fn convert_expression(&mut self, expr: Expression) -> IrConversionResult<IrNode> {
         match expr {
             Expression::Literal(lit) => self.convert_literal(lit),
             Expression::Symbol(sym) => self.convert_symbol_ref(sym),
+            Expression::TaskContextAccess(field_name) => Ok(IrNode::TaskContextAccess {
+                id: self.next_id(),
+                field_name,
+                ir_type: IrType::Any,
+                source_location: None,
+            }),
             Expression::FunctionCall { callee, arguments } => {
                 self.convert_function_call(*callee, arguments)
             }
             Expression::If(if_expr) => self.convert_if(if_expr),
             Expression::Let(let_expr) => self.convert_let(let_expr),
+            Expression::LetFn(letfn) => self.convert_letfn(letfn),
             Expression::Do(do_expr) => self.convert_do(do_expr),
             Expression::Fn(fn_expr) => self.convert_fn(fn_expr),
             Expression::Match(match_expr) => self.convert_match(*match_expr),
@@ -231,12 +271,29 @@ impl IrConverter {
             Expression::LogStep(log_expr) => self.convert_log_step(*log_expr),
             Expression::Def(def_expr) => self.convert_def(*def_expr),
             Expression::Defn(defn_expr) => self.convert_defn(*defn_expr),
+            Expression::DefType(deftype) => self.convert_deftype(*deftype),
+            Expression::Loop(loop_expr) => self.convert_loop(loop_expr),
+            Expression::Recur(arguments) => self.convert_recur(arguments),
+            Expression::For(for_expr) => self.convert_iteration(*for_expr, true),
+            Expression::DoSeq(for_expr) => self.convert_iteration(*for_expr, false),
+            Expression::DoTimes(dotimes) => self.convert_dotimes(*dotimes),
+            Expression::Sugar(sugar) => self.convert_expression(sugar.expansion().clone()),
+            Expression::Spanned(inner, span) => {
+                let location = SourceLocation::from(span.as_ref());
+                let mut node = self
+                    .convert_expression(*inner)
+                    .map_err(|e| e.with_location(location.clone()))?;
+                node.set_source_location(Some(location));
+                Ok(node)
+            }
         }
     }
     
-    /// High-level conversion method (entry point)
+    /// High-level conversion method (entry point); the result has its types inferred
     pub fn convert(&mut self, expr: &Expression) -> IrConversionResult<IrNode> {
-        self.convert_expression(expr.clone())
+        let mut node = self.convert_expression(expr.clone())?;
+        crate::ir_inference::infer_types(&mut node);
+        Ok(node)
     }
     
     /// Convert a literal value
@@ -288,8 +345,9 @@ impl IrConverter {
         }
         
         // Look up the symbol in current scope
-        match self.lookup_symbol(&name) {
-            Some(binding_info) => {
+        match self.lookup_symbol_with_depth(&name).map(|(info, depth)| (info.clone(), depth)) {
+            Some((binding_info, depth)) => {
+                self.record_capture(&binding_info, depth);
                 Ok(IrNode::VariableRef {
                     id,
                     name,
@@ -368,40 +426,14 @@ impl IrConverter {
         
         // Enter new scope for let bindings
         self.enter_scope();
-          // Process bindings in order
+        // Process bindings in order
         for binding in let_expr.bindings {
-            let binding_id = self.next_id();
-            let init_expr = self.convert_expression(*binding.value)?;
-            let binding_type = init_expr.ir_type().unwrap_or(&IrType::Any);
-            
-            // Convert pattern to binding - store pattern first to avoid borrow issues
-            let pattern_clone = binding.pattern.clone();
-            let pattern_node = self.convert_pattern(binding.pattern, binding_id, binding_type.clone())?;
-            
-            // Add binding to current scope
-            if let Pattern::Symbol(sym) = &pattern_clone {
-                let binding_info = BindingInfo {
-                    name: sym.0.clone(),
-                    binding_id,
-                    ir_type: binding_type.clone(),
-                    kind: BindingKind::Variable,
-                };
-                self.define_binding(sym.0.clone(), binding_info);
-            }
-            
-            bindings.push(IrLetBinding {
-                pattern: pattern_node,
-                type_annotation: binding.type_annotation.map(|t| self.convert_type_annotation(t)).transpose()?,
-                init_expr,
-            });
+            bindings.push(self.convert_let_binding(binding, false)?);
         }
         
         // Convert body expressions in the new scope
-        let mut body_exprs = Vec::new();
-        for body_expr in let_expr.body {
-            body_exprs.push(self.convert_expression(body_expr)?);
-        }
-          // Exit scope        
+        let body_exprs = self.convert_body(let_expr.body)?;
+        // Exit scope
         self.exit_scope();
         
         // Infer result type from last body expression
@@ -418,9 +450,209 @@ impl IrConverter {
             source_location: None,
         })
     }
+    
+    /// Convert a `let` or `loop` binding and define its variables in the current scope.
+    /// Loop bindings are `rebound` by `recur`, so only an annotation fixes their type.
+    fn convert_let_binding(&mut self, binding: LetBinding, rebound: bool) -> IrConversionResult<IrLetBinding> {
+        let binding_id = self.next_id();
+        let init_expr = self.convert_expression(*binding.value)?;
+        let type_annotation = binding.type_annotation.map(|t| self.convert_type_annotation(t)).transpose()?;
+        // References see the declared type; the checker verifies the initializer against it
+        let inferred_type = if rebound { None } else { init_expr.ir_type() };
+        let binding_type = type_annotation.as_ref().or(inferred_type).unwrap_or(&IrType::Any).clone();
+        let pattern = self.define_pattern(binding.pattern, binding_id, binding_type)?;
+        Ok(IrLetBinding {
+            pattern,
+            type_annotation,
+            init_expr,
+        })
+    }
+    
+    /// Convert a binding pattern and define the variables it binds in the current scope
+    fn define_pattern(&mut self, pattern: Pattern, binding_id: NodeId, ir_type: IrType) -> IrConversionResult<IrNode> {
+        if let Pattern::Symbol(sym) = pattern.unspanned() {
+            let binding_info = BindingInfo {
+                name: sym.0.clone(),
+                binding_id,
+                ir_type: ir_type.clone(),
+                kind: BindingKind::Variable,
+            };
+            self.define_binding(sym.0.clone(), binding_info);
+        }
+        self.convert_pattern(pattern, binding_id, ir_type)
+    }
+    
+    fn convert_loop(&mut self, loop_expr: LoopExpr) -> IrConversionResult<IrNode> {
+        let id = self.next_id();
+        self.enter_scope();
+        let mut bindings = Vec::new();
+        for binding in loop_expr.bindings {
+            bindings.push(self.convert_let_binding(binding, true)?);
+        }
+        
+        self.loop_scopes.push((id, self.function_scopes.len(), bindings.len()));
+        let body = self.convert_body(loop_expr.body);
+        self.loop_scopes.pop();
+        self.exit_scope();
+        let body = body?;
+        
+        let result_type = body.last()
+            .and_then(|expr| expr.ir_type())
+            .cloned()
+            .unwrap_or(IrType::Nil);
+        Ok(IrNode::Loop {
+            id,
+            bindings,
+            body,
+            ir_type: result_type,
+            source_location: None,
+        })
+    }
+    
+    /// Convert a `recur`, which must be inside a loop of the current function.
+    /// The parser also checks that it is in tail position.
+    fn convert_recur(&mut self, arguments: Vec<Expression>) -> IrConversionResult<IrNode> {
+        let id = self.next_id();
+        let (loop_id, arity) = match self.loop_scopes.last() {
+            Some((loop_id, function_depth, arity)) if *function_depth == self.function_scopes.len() => {
+                (*loop_id, *arity)
+            }
+            _ => {
+                return Err(IrConversionError::InvalidSpecialForm {
+                    message: "recur outside of a loop".to_string(),
+                    location: None,
+                })
+            }
+        };
+        if arguments.len() != arity {
+            return Err(IrConversionError::InvalidSpecialForm {
+                message: format!("recur expects {} arguments, got {}", arity, arguments.len()),
+                location: None,
+            });
+        }
+        
+        Ok(IrNode::Recur {
+            id,
+            loop_id,
+            arguments: self.convert_body(arguments)?,
+            ir_type: IrType::Never,
+            source_location: None,
+        })
+    }
+    
+    /// Convert `for` (which collects the body values) or `doseq` (which does not)
+    fn convert_iteration(&mut self, for_expr: ForExpr, collect: bool) -> IrConversionResult<IrNode> {
+        let id = self.next_id();
+        self.enter_scope();
+        let mut clauses = Vec::new();
+        for clause in for_expr.clauses {
+            clauses.push(match clause {
+                IterationClause::Binding { pattern, collection } => {
+                    let collection = self.convert_expression(*collection)?;
+                    let item_type = match collection.ir_type() {
+                        Some(IrType::Vector(element_type)) => element_type.as_ref().clone(),
+                        _ => IrType::Any,
+                    };
+                    let binding_id = self.next_id();
+                    IrIterationClause::Binding {
+                        pattern: self.define_pattern(pattern, binding_id, item_type)?,
+                        collection,
+                    }
+                }
+                IterationClause::When(test) => IrIterationClause::When(self.convert_expression(*test)?),
+            });
+        }
+        let body = self.convert_body(for_expr.body);
+        self.exit_scope();
+        let body = body?;
+        
+        let ir_type = if collect {
+            IrType::Vector(Box::new(body.last().and_then(|expr| expr.ir_type()).cloned().unwrap_or(IrType::Nil)))
+        } else {
+            IrType::Nil
+        };
+        Ok(IrNode::Iterate {
+            id,
+            clauses,
+            body,
+            collect,
+            ir_type,
+            source_location: None,
+        })
+    }
+    
+    fn convert_dotimes(&mut self, dotimes: DoTimesExpr) -> IrConversionResult<IrNode> {
+        let id = self.next_id();
+        let count = self.convert_expression(*dotimes.count)?;
+        self.enter_scope();
+        let binding_id = self.next_id();
+        let binding = self.define_pattern(Pattern::Symbol(dotimes.symbol), binding_id, IrType::Int)?;
+        let body = self.convert_body(dotimes.body);
+        self.exit_scope();
+        let body = body?;
+        
+        Ok(IrNode::Iterate {
+            id,
+            clauses: vec![IrIterationClause::Range { binding, count }],
+            body,
+            collect: false,
+            ir_type: IrType::Nil,
+            source_location: None,
+        })
+    }
+    
+    fn convert_body(&mut self, exprs: Vec<Expression>) -> IrConversionResult<Vec<IrNode>> {
+        self.declare_functions(&exprs);
+        exprs.into_iter().map(|expr| self.convert_expression(expr)).collect()
+    }
+    
+    /// Declare the functions that a body defines with `defn` in the current scope
+    /// before converting it, so that each definition can call the ones after it
+    pub fn declare_functions(&mut self, exprs: &[Expression]) {
+        for expr in exprs {
+            if let Expression::Defn(defn_expr) = expr.unspanned() {
+                self.function_slot(&defn_expr.name.0);
+            }
+        }
+    }
+    
+    /// The slot of an earlier definition of `name` in the current scope. Defining the
+    /// name again fills the same slot, so that the functions defined in between see
+    /// the new value.
+    fn definition_slot(&self, name: &str) -> Option<NodeId> {
+        self.scope_stack
+            .last()
+            .and_then(|scope| scope.get(name))
+            .map(|info| info.binding_id)
+            .filter(|binding_id| self.definitions.contains(binding_id))
+    }
+    
+    /// The slot a function definition fills, bound in the current scope ahead of
+    /// converting the function; references to it are untyped until its definition
+    /// has been converted
+    fn function_slot(&mut self, name: &str) -> NodeId {
+        if let Some(binding_id) = self.definition_slot(name) {
+            return binding_id;
+        }
+        let binding_id = self.next_id();
+        self.definitions.insert(binding_id);
+        self.define_binding(name.to_string(), BindingInfo {
+            name: name.to_string(),
+            binding_id,
+            ir_type: IrType::Any,
+            kind: BindingKind::Function,
+        });
+        binding_id
+    }
+    
       /// Convert pattern to IR node
     fn convert_pattern(&mut self, pattern: Pattern, binding_id: NodeId, ir_type: IrType) -> IrConversionResult<IrNode> {
         match pattern {
+            Pattern::Spanned(inner, span) => {
+                let mut node = self.convert_pattern(*inner, binding_id, ir_type)?;
+                node.set_source_location(Some(SourceLocation::from(span.as_ref())));
+                Ok(node)
+            }
             Pattern::Symbol(sym) => {
                 Ok(IrNode::VariableBinding {
                     id: binding_id,
@@ -437,42 +669,121 @@ impl IrConverter {
                     source_location: None,
                 })
             }
-            Pattern::VectorDestructuring { elements: _, rest: _, as_symbol: _ } => {
-                // Create a destructuring pattern (simplified)
-                Ok(IrNode::VariableBinding {
+            Pattern::VectorDestructuring { .. } | Pattern::MapDestructuring { .. } => {
+                let mut defaults = Vec::new();
+                let pattern = self.convert_destructuring_pattern(pattern, &mut defaults)?;
+                Ok(IrNode::Destructure {
                     id: binding_id,
-                    name: format!("__vector_destructure_{}", binding_id),
+                    pattern,
+                    defaults,
                     ir_type,
                     source_location: None,
                 })
             }
-            Pattern::MapDestructuring { entries: _, rest: _, as_symbol: _ } => {
-                // Similar to vector - simplified destructuring
-                Ok(IrNode::VariableBinding {
-                    id: binding_id,
-                    name: format!("__map_destructure_{}", binding_id),
-                    ir_type,
-                    source_location: None,
+        }
+    }
+    
+    /// Convert a destructuring pattern, defining the variables it binds in the current scope.
+    /// `:or` defaults are converted before the pattern's own variables are in scope.
+    fn convert_destructuring_pattern(
+        &mut self,
+        pattern: Pattern,
+        defaults: &mut Vec<IrPatternDefault>,
+    ) -> IrConversionResult<IrPattern> {
+        match pattern {
+            Pattern::Spanned(inner, span) => self
+                .convert_destructuring_pattern(*inner, defaults)
+                .map_err(|e| e.with_location(SourceLocation::from(span.as_ref()))),
+            Pattern::Symbol(sym) => Ok(self.convert_pattern_variable(sym)),
+            Pattern::Wildcard => Ok(IrPattern::Wildcard),
+            Pattern::VectorDestructuring { elements, rest, as_symbol } => {
+                let mut ir_elements = Vec::new();
+                for element in elements {
+                    ir_elements.push(self.convert_destructuring_pattern(element, defaults)?);
+                }
+                let vector_pattern = IrPattern::Vector {
+                    elements: ir_elements,
+                    rest: rest.map(|s| Box::new(self.convert_pattern_variable(s))),
+                };
+                Ok(match as_symbol {
+                    Some(sym) => self.convert_as_pattern(sym, vector_pattern),
+                    None => vector_pattern,
+                })
+            }
+            Pattern::MapDestructuring { entries, rest, as_symbol } => {
+                let mut default_values = HashMap::new();
+                for entry in &entries {
+                    if let MapDestructuringEntry::Defaults(entry_defaults) = entry {
+                        for (sym, value) in entry_defaults {
+                            default_values.insert(sym.0.clone(), self.convert_expression(value.clone())?);
+                        }
+                    }
+                }
+                
+                let mut ir_entries = Vec::new();
+                let mut bind_key = |converter: &mut Self, key: MapKey, pattern: Pattern| -> IrConversionResult<()> {
+                    let target = match pattern.unspanned() {
+                        Pattern::Symbol(sym) => Some(sym.0.clone()),
+                        _ => None,
+                    };
+                    let pattern = converter.convert_destructuring_pattern(pattern, defaults)?;
+                    if let (Some(value), IrPattern::Variable { binding_id, .. }) =
+                        (target.and_then(|name| default_values.remove(&name)), &pattern)
+                    {
+                        defaults.push(IrPatternDefault { binding_id: *binding_id, value });
+                    }
+                    ir_entries.push(IrMapPatternEntry { key, pattern });
+                    Ok(())
+                };
+                for entry in entries {
+                    match entry {
+                        MapDestructuringEntry::KeyBinding { key, pattern } => bind_key(self, key, *pattern)?,
+                        MapDestructuringEntry::Keys(symbols) => {
+                            for sym in symbols {
+                                let key = MapKey::Keyword(Keyword(sym.0.clone()));
+                                bind_key(self, key, Pattern::Symbol(sym))?;
+                            }
+                        }
+                        MapDestructuringEntry::Defaults(_) => {}
+                    }
+                }
+                
+                let map_pattern = IrPattern::Map {
+                    entries: ir_entries,
+                    rest: rest.map(|s| Box::new(self.convert_pattern_variable(s))),
+                };
+                Ok(match as_symbol {
+                    Some(sym) => self.convert_as_pattern(sym, map_pattern),
+                    None => map_pattern,
                 })
             }
         }
     }
     
     /// Convert type annotation to IR type
-    fn convert_type_annotation(&mut self, type_expr: TypeExpr) -> IrConversionResult<IrType> {
+    pub fn convert_type_annotation(&mut self, type_expr: TypeExpr) -> IrConversionResult<IrType> {
         match type_expr {
+            TypeExpr::Spanned(inner, span) => self
+                .convert_type_annotation(*inner)
+                .map_err(|e| e.with_location(SourceLocation::from(span.as_ref()))),
             TypeExpr::Primitive(PrimitiveType::Int) => Ok(IrType::Int),
             TypeExpr::Primitive(PrimitiveType::Float) => Ok(IrType::Float),
             TypeExpr::Primitive(PrimitiveType::String) => Ok(IrType::String),
             TypeExpr::Primitive(PrimitiveType::Bool) => Ok(IrType::Bool),
             TypeExpr::Primitive(PrimitiveType::Keyword) => Ok(IrType::Keyword),
             TypeExpr::Primitive(PrimitiveType::Symbol) => Ok(IrType::Symbol),
+            TypeExpr::Primitive(PrimitiveType::Nil) => Ok(IrType::Nil),
+            TypeExpr::Primitive(PrimitiveType::Custom(keyword)) => Ok(IrType::TypeRef(keyword.0)),
             TypeExpr::Any => Ok(IrType::Any),
             TypeExpr::Never => Ok(IrType::Never),
             TypeExpr::Vector(element_type) => {
                 let ir_element_type = self.convert_type_annotation(*element_type)?;
                 Ok(IrType::Vector(Box::new(ir_element_type)))
             }
+            TypeExpr::Array { element_type, shape } => Ok(IrType::Array {
+                element_type: Box::new(self.convert_type_annotation(*element_type)?),
+                shape,
+            }),
             TypeExpr::Union(types) => {
                 let mut ir_types = Vec::new();
                 for t in types {
@@ -480,19 +791,77 @@ impl IrConverter {
                 }
                 Ok(IrType::Union(ir_types))
             }
+            TypeExpr::Intersection(types) => {
+                let mut ir_types = Vec::new();
+                for t in types {
+                    ir_types.push(self.convert_type_annotation(t)?);
+                }
+                Ok(IrType::Intersection(ir_types))
+            }
+            TypeExpr::Refined { base, predicates } => Ok(IrType::Refined {
+                base: Box::new(self.convert_type_annotation(*base)?),
+                predicates,
+            }),
+            TypeExpr::Tuple(types) => {
+                let mut ir_types = Vec::new();
+                for t in types {
+                    ir_types.push(self.convert_type_annotation(t)?);
+                }
+                Ok(IrType::Tuple(ir_types))
+            }
+            TypeExpr::Map { entries, wildcard } => {
+                let mut ir_entries = Vec::new();
+                for entry in entries {
+                    ir_entries.push(IrMapTypeEntry {
+                        key: entry.key,
+                        value_type: self.convert_type_annotation(*entry.value_type)?,
+                        optional: entry.optional,
+                    });
+                }
+                let wildcard = match wildcard {
+                    Some(t) => Some(Box::new(self.convert_type_annotation(*t)?)),
+                    None => None,
+                };
+                Ok(IrType::Map { entries: ir_entries, wildcard })
+            }
+            TypeExpr::Function { param_types, variadic_param_type, return_type } => {
+                let mut ir_params = Vec::new();
+                for ParamType::Simple(t) in param_types {
+                    ir_params.push(self.convert_type_annotation(*t)?);
+                }
+                let variadic_param_type = match variadic_param_type {
+                    Some(t) => Some(Box::new(self.convert_type_annotation(*t)?)),
+                    None => None,
+                };
+                Ok(IrType::Function {
+                    param_types: ir_params,
+                    variadic_param_type,
+                    return_type: Box::new(self.convert_type_annotation(*return_type)?),
+                })
+            }
+            TypeExpr::Resource(sym) => Ok(IrType::Resource(sym.0)),
             TypeExpr::Literal(lit) => Ok(IrType::LiteralValue(lit)),
-            TypeExpr::Alias(sym) => Ok(IrType::TypeRef(sym.0)),
-            _ => Ok(IrType::Any), // TODO: Implement remaining type conversions
+            // The parser reads primitive type names as aliases
+            TypeExpr::Alias(sym) => Ok(match sym.0.as_str() {
+                "int" => IrType::Int,
+                "float" => IrType::Float,
+                "string" => IrType::String,
+                "bool" => IrType::Bool,
+                "nil" => IrType::Nil,
+                "keyword" => IrType::Keyword,
+                "symbol" => IrType::Symbol,
+                "any" => IrType::Any,
+                "never" => IrType::Never,
+                _ => self.resolve_type_name(sym.0),
+            }),
+            TypeExpr::TypeVar(sym) => Ok(IrType::TypeVar(sym.0)),
         }
     }
     
     // Placeholder implementations for other expression types
     fn convert_do(&mut self, do_expr: DoExpr) -> IrConversionResult<IrNode> {
         let id = self.next_id();
-        let mut expressions = Vec::new();
-          for expr in do_expr.expressions {
-            expressions.push(self.convert_expression(expr)?);
-        }
+        let expressions = self.convert_body(do_expr.expressions)?;
         
         let result_type = expressions.last()
             .and_then(|expr| expr.ir_type())
@@ -511,6 +880,7 @@ impl IrConverter {
         
         // Enter new scope for function body
         self.enter_scope();
+        self.function_scopes.push((id, self.scope_stack.len() - 1));
         
         // Convert parameters
         let mut params = Vec::new();
@@ -526,7 +896,7 @@ impl IrConverter {
             let binding_node = self.convert_pattern(param_def.pattern.clone(), param_id, param_type.clone())?;
             
             // Add parameter to scope
-            if let Pattern::Symbol(sym) = &param_def.pattern {
+            if let Pattern::Symbol(sym) = param_def.pattern.unspanned() {
                 let binding_info = BindingInfo {
                     name: sym.0.clone(),
                     binding_id: param_id,
@@ -556,7 +926,7 @@ impl IrConverter {
             
             let binding_node = self.convert_pattern(variadic_def.pattern.clone(), param_id, param_type.clone())?;
             
-            if let Pattern::Symbol(sym) = &variadic_def.pattern {
+            if let Pattern::Symbol(sym) = variadic_def.pattern.unspanned() {
                 let binding_info = BindingInfo {
                     name: sym.0.clone(),
                     binding_id: param_id,
@@ -578,22 +948,18 @@ impl IrConverter {
         };
         
         // Convert body expressions
-        let mut body_exprs = Vec::new();
-        for body_expr in fn_expr.body {
-            body_exprs.push(self.convert_expression(body_expr)?);
-        }
+        let body_exprs = self.convert_body(fn_expr.body)?;
         
         // Exit function scope
         self.exit_scope();
-        
-        // Determine return type
-        let return_type = if let Some(ret_type) = fn_expr.return_type {
-            self.convert_type_annotation(ret_type)?
-        } else {
-            body_exprs.last()
-                .and_then(|expr| expr.ir_type())
-                .cloned()
-                .unwrap_or(IrType::Any)
+        self.function_scopes.pop();
+        let captures = self.capture_analysis.remove(&id).unwrap_or_default();
+        
+        // Determine return type. Without an annotation it is left to inference, which
+        // narrows it to the type of the body once that is known.
+        let return_type = match fn_expr.return_type {
+            Some(ret_type) => self.convert_type_annotation(ret_type)?,
+            None => IrType::Any,
         };
         
         // Build function type
@@ -602,9 +968,14 @@ impl IrConverter {
             .cloned()
             .collect();
         
+        // Function types give the type of each extra argument, as the builtin
+        // signatures do, while the rest parameter itself is bound to a vector of them
         let variadic_param_type = variadic_param.as_ref()
             .and_then(|p| p.ir_type())
-            .map(|t| Box::new(t.clone()));
+            .map(|t| match t {
+                IrType::Vector(element) => element.clone(),
+                _ => Box::new(IrType::Any),
+            });
         
         let function_type = IrType::Function {
             param_types,
@@ -612,14 +983,11 @@ impl IrConverter {
             return_type: Box::new(return_type),
         };
         
-        // TODO: Implement capture analysis for closures
-        let captures = Vec::new();
-        
         Ok(IrNode::Lambda {
             id,
-            params,
+            params: params.into(),
             variadic_param,
-            body: body_exprs,
+            body: body_exprs.into(),
             captures,
             ir_type: function_type,
             source_location: None,
@@ -693,29 +1061,24 @@ impl IrConverter {
     
     /// Convert AST pattern to IR pattern
     fn convert_pattern_to_ir_pattern(&mut self, pattern: MatchPattern) -> IrConversionResult<IrPattern> {        match pattern {
-            MatchPattern::Symbol(sym) => {
-                // Add to current scope for pattern binding
-                let binding_info = BindingInfo {
-                    name: sym.0.clone(),
-                    binding_id: self.next_id(),
-                    ir_type: IrType::Any, // Will be refined during type inference
-                    kind: BindingKind::Variable,
-                };
-                self.define_binding(sym.0.clone(), binding_info);
-                Ok(IrPattern::Variable(sym.0))
-            }
+            MatchPattern::Spanned(inner, span) => self
+                .convert_pattern_to_ir_pattern(*inner)
+                .map_err(|e| e.with_location(SourceLocation::from(span.as_ref()))),
+            MatchPattern::Symbol(sym) => Ok(self.convert_pattern_variable(sym)),
             MatchPattern::Wildcard => Ok(IrPattern::Wildcard),
-            MatchPattern::Literal(lit) => Ok(IrPattern::Literal(lit)),            MatchPattern::Vector { elements, rest } => {
+            MatchPattern::Literal(lit) => Ok(IrPattern::Literal(lit)),
+            MatchPattern::Vector { elements, rest } => {
                 let mut ir_patterns = Vec::new();
                 
                 for pat in elements {
                     ir_patterns.push(self.convert_pattern_to_ir_pattern(pat)?);
                 }
-                  Ok(IrPattern::Vector {
+                Ok(IrPattern::Vector {
                     elements: ir_patterns,
-                    rest: rest.map(|s| s.0),
+                    rest: rest.map(|s| Box::new(self.convert_pattern_variable(s))),
                 })
-            }            MatchPattern::Map { entries, rest } => {
+            }
+            MatchPattern::Map { entries, rest } => {
                 let mut ir_entries = Vec::new();
                 
                 for entry in entries {
@@ -728,42 +1091,53 @@ impl IrConverter {
                 
                 Ok(IrPattern::Map {
                     entries: ir_entries,
-                    rest: rest.map(|s| s.0),
+                    rest: rest.map(|s| Box::new(self.convert_pattern_variable(s))),
                 })
             }
             MatchPattern::Keyword(kw) => {
                 Ok(IrPattern::Literal(Literal::Keyword(kw)))
-            }            MatchPattern::Type(_type_expr, binding) => {
-                // For now, treat type patterns as wildcards with optional binding
-                // TODO: Use _type_expr for proper type checking
-                if let Some(sym) = binding {
-                    let binding_info = BindingInfo {
-                        name: sym.0.clone(),
-                        binding_id: self.next_id(),
-                        ir_type: IrType::Any, // Would need type resolution here
-                        kind: BindingKind::Variable,
-                    };
-                    self.define_binding(sym.0.clone(), binding_info);
-                    Ok(IrPattern::Variable(sym.0))
-                } else {
-                    Ok(IrPattern::Wildcard)
+            }
+            MatchPattern::Type(type_expr, binding) => {
+                let type_pattern = IrPattern::Type(self.convert_type_annotation(type_expr)?);
+                match binding {
+                    Some(sym) => Ok(self.convert_as_pattern(sym, type_pattern)),
+                    None => Ok(type_pattern),
                 }
-            }            MatchPattern::As(symbol, inner_pattern) => {
-                // Convert inner pattern and bind the result to symbol
-                let _inner_ir_pattern = self.convert_pattern_to_ir_pattern(*inner_pattern)?;
-                // TODO: Properly handle the inner pattern structure
-                let binding_info = BindingInfo {
-                    name: symbol.0.clone(),
-                    binding_id: self.next_id(),
-                    ir_type: IrType::Any,
-                    kind: BindingKind::Variable,
-                };
-                self.define_binding(symbol.0.clone(), binding_info);
-                // For simplicity, return the variable pattern (more complex logic would wrap both)
-                Ok(IrPattern::Variable(symbol.0))
+            }
+            MatchPattern::As(symbol, inner_pattern) => {
+                let inner_ir_pattern = self.convert_pattern_to_ir_pattern(*inner_pattern)?;
+                Ok(self.convert_as_pattern(symbol, inner_ir_pattern))
             }
         }
     }
+    
+    /// Bind a pattern variable in the current scope
+    fn convert_pattern_variable(&mut self, sym: Symbol) -> IrPattern {
+        let binding_id = self.define_pattern_binding(&sym);
+        IrPattern::Variable { name: sym.0, binding_id }
+    }
+    
+    /// Bind `sym` to the whole value matched by `pattern`
+    fn convert_as_pattern(&mut self, sym: Symbol, pattern: IrPattern) -> IrPattern {
+        let binding_id = self.define_pattern_binding(&sym);
+        IrPattern::As {
+            name: sym.0,
+            binding_id,
+            pattern: Box::new(pattern),
+        }
+    }
+    
+    fn define_pattern_binding(&mut self, sym: &Symbol) -> NodeId {
+        let binding_id = self.next_id();
+        let binding_info = BindingInfo {
+            name: sym.0.clone(),
+            binding_id,
+            ir_type: IrType::Any, // Will be refined during type inference
+            kind: BindingKind::Variable,
+        };
+        self.define_binding(sym.0.clone(), binding_info);
+        binding_id
+    }
       fn convert_vector(&mut self, exprs: Vec<Expression>) -> IrConversionResult<IrNode> {
         let id = self.next_id();
         let mut elements = Vec::new();
@@ -792,10 +1166,9 @@ impl IrConverter {
             }
         };
         
-        // Create a literal vector node (representing a vector literal)
-        Ok(IrNode::Literal {
+        Ok(IrNode::Vector {
             id,
-            value: Literal::Nil, // TODO: Create proper vector literal representation
+            elements,
             ir_type: IrType::Vector(Box::new(element_type)),
             source_location: None,
         })
@@ -820,7 +1193,7 @@ impl IrConverter {
                 }
             }
             
-            converted_entries.push((key, value));
+            converted_entries.push(IrMapEntry { key, value });
         }
         
         let map_type = IrType::Map {
@@ -828,10 +1201,9 @@ impl IrConverter {
             wildcard: None,
         };
         
-        // Create a literal map node (representing a map literal)
-        Ok(IrNode::Literal {
+        Ok(IrNode::Map {
             id,
-            value: Literal::Nil, // TODO: Create proper map literal representation
+            entries: converted_entries,
             ir_type: map_type,
             source_location: None,
         })
@@ -839,9 +1211,10 @@ impl IrConverter {
     
     fn convert_list_as_application(&mut self, exprs: Vec<Expression>) -> IrConversionResult<IrNode> {
         if exprs.is_empty() {
-            return Ok(IrNode::Literal {
+            // `()` evaluates to an empty collection, as in the AST evaluator
+            return Ok(IrNode::Vector {
                 id: self.next_id(),
-                value: Literal::Nil,
+                elements: Vec::new(),
                 ir_type: IrType::Vector(Box::new(IrType::Never)),
                 source_location: None,
             });
@@ -898,14 +1271,20 @@ impl IrConverter {
             };
             
             // Handle error binding (use the binding field from CatchClause)
+            let binding_id = self.next_id();
             let binding_info = BindingInfo {
                 name: catch_clause.binding.0.clone(),
-                binding_id: self.next_id(),
+                binding_id,
                 ir_type: IrType::Any, // Error type
                 kind: BindingKind::Variable,
             };
             self.define_binding(catch_clause.binding.0.clone(), binding_info);
-            let binding = Some(catch_clause.binding.0);
+            let binding = Some(IrNode::VariableBinding {
+                id: binding_id,
+                name: catch_clause.binding.0,
+                ir_type: IrType::Any,
+                source_location: None,
+            });
             
             // Convert catch body
             let mut catch_body = Vec::new();
@@ -951,6 +1330,7 @@ impl IrConverter {
       fn convert_parallel(&mut self, parallel_expr: ParallelExpr) -> IrConversionResult<IrNode> {
         let id = self.next_id();
         let mut bindings = Vec::new();
+        let mut type_entries = Vec::new();
         
         for parallel_binding in parallel_expr.bindings {
             // Convert the initialization expression
@@ -971,17 +1351,25 @@ impl IrConverter {
                 source_location: None,
             };
             
+            type_entries.push(IrMapTypeEntry {
+                key: Keyword(parallel_binding.symbol.0),
+                value_type: binding_type,
+                optional: false,
+            });
             bindings.push(IrParallelBinding {
                 binding: binding_node,
                 init_expr,
             });
         }
         
-        // Parallel expressions don't have a specific return type - they're about concurrency
+        // The result maps each binding name (as a keyword) to its value
         Ok(IrNode::Parallel {
             id,
             bindings,
-            ir_type: IrType::Nil,
+            ir_type: IrType::Map {
+                entries: type_entries,
+                wildcard: None,
+            },
             source_location: None,
         })
     }
@@ -1043,18 +1431,30 @@ impl IrConverter {
         for value_expr in log_expr.values {
             values.push(self.convert_expression(value_expr)?);
         }
+        
+        // A log step returns the last value it logged
+        let result_type = values.last()
+            .and_then(|value| value.ir_type())
+            .cloned()
+            .unwrap_or(IrType::Nil);
           Ok(IrNode::LogStep {
             id,
             level: log_expr.level.unwrap_or(Keyword("info".to_string())),
             values,
             location: log_expr.location,
-            ir_type: IrType::Nil, // Log steps don't return values
+            ir_type: result_type,
             source_location: None,
         })
     }
     
     fn convert_def(&mut self, def_expr: DefExpr) -> IrConversionResult<IrNode> {
-        let id = self.next_id();
+        // The value is converted before the name is bound, so it still sees the
+        // binding (or earlier definition) the name had
+        let id = match self.definition_slot(&def_expr.symbol.0) {
+            Some(binding_id) => binding_id,
+            None => self.next_id(),
+        };
+        self.definitions.insert(id);
         
         // Convert the initialization expression
         let init_expr = Box::new(self.convert_expression(*def_expr.value)?);
@@ -1092,7 +1492,9 @@ impl IrConverter {
     }
     
     fn convert_defn(&mut self, defn_expr: DefnExpr) -> IrConversionResult<IrNode> {
-        let id = self.next_id();
+        // Use the slot the enclosing body declared, or declare it now so that the
+        // function can call itself
+        let id = self.function_slot(&defn_expr.name.0);
         
         // Convert the function parameters and body using the existing fn converter
         let fn_expr = FnExpr {
@@ -1123,6 +1525,100 @@ impl IrConverter {
         })
     }
     
+    /// Convert `letfn` to a `Let` whose body defines its functions first, all declared
+    /// before any of them is converted so that each body can call every one of them
+    fn convert_letfn(&mut self, letfn: LetFnExpr) -> IrConversionResult<IrNode> {
+        let id = self.next_id();
+        self.enter_scope();
+        let binding_ids: Vec<NodeId> = letfn
+            .functions
+            .iter()
+            .map(|function| self.function_slot(&function.name.0))
+            .collect();
+        
+        let mut body = Vec::new();
+        for (function, binding_id) in letfn.functions.into_iter().zip(binding_ids) {
+            let lambda = self.convert_fn(FnExpr {
+                params: function.params,
+                variadic_param: function.variadic_param,
+                return_type: function.return_type,
+                body: function.body,
+            })?;
+            let function_type = lambda.ir_type().cloned().unwrap_or(IrType::Any);
+            let name = function.name.0;
+            self.define_binding(name.clone(), BindingInfo {
+                name: name.clone(),
+                binding_id,
+                ir_type: function_type.clone(),
+                kind: BindingKind::Function,
+            });
+            body.push(IrNode::FunctionDef {
+                id: binding_id,
+                name,
+                lambda: Box::new(lambda),
+                ir_type: function_type,
+                source_location: None,
+            });
+        }
+        
+        body.extend(self.convert_body(letfn.body)?);
+        self.exit_scope();
+        
+        let result_type = body.last()
+            .and_then(|expr| expr.ir_type())
+            .cloned()
+            .unwrap_or(IrType::Nil);
+        Ok(IrNode::Let {
+            id,
+            bindings: Vec::new(),
+            body,
+            ir_type: result_type,
+            source_location: None,
+        })
+    }
+    
+    /// Convert a `deftype`, making its name stand for the type in the annotations that follow
+    fn convert_deftype(&mut self, deftype: DefTypeExpr) -> IrConversionResult<IrNode> {
+        let name = deftype.name.0;
+        if DefTypeExpr::is_reserved(&name) {
+            return Err(IrConversionError::InvalidTypeAnnotation {
+                message: format!("deftype cannot define {}, a builtin type or type variable name", name),
+                location: None,
+            });
+        }
+        // Types are named at module or top level, not inside functions or let bodies
+        if self.scope_stack.len() > 1 {
+            return Err(IrConversionError::InvalidTypeAnnotation {
+                message: format!("deftype {} must appear at module or top level", name),
+                location: None,
+            });
+        }
+        let definition = self.convert_type_annotation(deftype.type_expr)?;
+        self.type_context.type_aliases.insert(name.clone(), definition.clone());
+        
+        Ok(IrNode::TypeDef {
+            id: self.next_id(),
+            name,
+            definition,
+            source_location: None,
+        })
+    }
+    
+    /// Resolve a type name: one defined by `deftype`, a type exported by a loaded module
+    /// (`module/Name`), or otherwise a reference to a type that is not known yet
+    fn resolve_type_name(&self, name: String) -> IrType {
+        if let Some(definition) = self.type_context.type_aliases.get(&name) {
+            return definition.clone();
+        }
+        if let (Some(registry), Some((module_name, type_name))) = (self.get_module_registry(), name.split_once('/')) {
+            let export = registry.get_module(module_name).and_then(|module| module.exports.get(type_name).cloned());
+            if let Some(export) = export.filter(|export| export.export_type == ExportType::Type) {
+                return export.ir_type;
+            }
+        }
+        IrType::TypeRef(name)
+    }
+    
     /// Set the module registry for qualified symbol resolution
     pub fn set_module_registry(&mut self, registry: &crate::runtime::module_runtime::ModuleRegistry) {
         self.module_registry = Some(registry as *const _);
@@ -460,10 +460,10 @@ fn collect_stats(node: &IrNode, stats: &mut ConversionStats) {
         }
         IrNode::Lambda { params, body, .. } => {
             stats.lambdas += 1;
-            for param in params {
+            for param in params.iter() {
                 collect_stats(param, stats);
             }
-            for expr in body {
+            for expr in body.iter() {
                 collect_stats(expr, stats);
             }
         }
@@ -526,10 +526,10 @@ fn collect_types(node: &IrNode, types: &mut std::collections::HashMap<String, us
             }
         }
         IrNode::Lambda { params, body, .. } => {
-            for param in params {
+            for param in params.iter() {
                 collect_types(param, types);
             }
-            for expr in body {
+            for expr in body.iter() {
                 collect_types(expr, types);
             }
         }
@@ -617,7 +617,7 @@ fn find_optimization_opportunities(node: &IrNode, opportunities: &mut Vec<String
                     opportunities.push("Tail call optimization: single function call in lambda body".to_string());
                 }
             }
-            for expr in body {
+            for expr in body.iter() {
                 find_optimization_opportunities(expr, opportunities, depth + 1);
             }
         }
@@ -748,10 +748,10 @@ fn count_ir_node_types_recursive(node: &IrNode, counts: &mut HashMap<String, usi
             }
         }
         IrNode::Lambda { params, body, .. } => {
-            for param in params {
+            for param in params.iter() {
                 count_ir_node_types_recursive(param, counts);
             }
-            for expr in body {
+            for expr in body.iter() {
                 count_ir_node_types_recursive(expr, counts);
             }
         }
@@ -0,0 +1,622 @@
+// IR Type Inference
+// Computes a type for every IR node from its children and writes it back into the tree
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::ast::MapKey;
+use crate::ir::*;
+use crate::ir_typecheck::{builtin_signatures, instantiate, is_subtype, literal_type};
+
+/// Infer the type of every node of an IR tree, in place
+pub fn infer_types(node: &mut IrNode) {
+    TypeInference::new().infer(node);
+}
+
+/// Least type covering both `a` and `b`: `a` itself if they agree, otherwise their union
+pub fn join(a: &IrType, b: &IrType) -> IrType {
+    match (a, b) {
+        (IrType::Any, _) | (_, IrType::Any) => IrType::Any,
+        (IrType::Never, other) | (other, IrType::Never) => other.clone(),
+        _ if a == b => a.clone(),
+        _ => {
+            let mut members: Vec<IrType> = Vec::new();
+            for t in [a, b] {
+                let flattened = match t {
+                    IrType::Union(types) => types.clone(),
+                    other => vec![other.clone()],
+                };
+                for member in flattened {
+                    if !members.contains(&member) {
+                        members.push(member);
+                    }
+                }
+            }
+            IrType::Union(members)
+        }
+    }
+}
+
+/// Join a sequence of types; `Never` when there are none
+fn join_all<'a>(types: impl IntoIterator<Item = &'a IrType>) -> IrType {
+    types.into_iter().fold(IrType::Never, |acc, t| join(&acc, t))
+}
+
+/// Whether a type is fully known, i.e. mentions neither `Any` nor an unresolved alias
+fn is_concrete(t: &IrType) -> bool {
+    match t {
+        IrType::Any | IrType::TypeRef(_) => false,
+        IrType::Vector(element) | IrType::List(element) => is_concrete(element),
+        IrType::Array { element_type, .. } => is_concrete(element_type),
+        IrType::Tuple(types) | IrType::Union(types) | IrType::Intersection(types) => types.iter().all(is_concrete),
+        IrType::Refined { base, .. } => is_concrete(base),
+        IrType::Map { entries, wildcard } => {
+            entries.iter().all(|entry| is_concrete(&entry.value_type))
+                && wildcard.as_deref().is_none_or(is_concrete)
+        }
+        IrType::Function { param_types, variadic_param_type, return_type } => {
+            param_types.iter().all(is_concrete)
+                && variadic_param_type.as_deref().is_none_or(is_concrete)
+                && is_concrete(return_type)
+        }
+        _ => true,
+    }
+}
+
+/// Use `inferred` instead of `declared` when it is a fully known refinement of it.
+/// Declared predicates are kept: the inferred type cannot prove them.
+fn narrow(declared: &IrType, inferred: IrType) -> IrType {
+    if is_concrete(&inferred) && is_subtype(&inferred, declared) && !matches!(declared, IrType::Refined { .. }) {
+        inferred
+    } else {
+        declared.clone()
+    }
+}
+
+/// Local, bottom-up type inference over the IR.
+///
+/// - bindings take their annotation, or else the type of their initializer;
+///   `recur` may rebind loop bindings, so those are `any` unless annotated
+/// - references take the type of the binding they point to
+/// - `if`, `match` and `try` join their branches into a union
+/// - a branch of an `if` that tests a binding for nil, as `(if x ...)` or
+///   `(if (nil? x) ...)`, takes that binding to be non-nil where it cannot be nil,
+///   and so do the `match` clauses after a `nil` pattern
+/// - `+`, `-` and `*` are typed from their arguments (`int` when all are ints,
+///   `any` when one is untyped)
+/// - other calls take the return type of the callee, instantiated for generic callees
+/// - lambdas narrow their return type to the type of their body
+pub struct TypeInference {
+    bindings: HashMap<NodeId, IrType>,
+    builtins: HashMap<&'static str, IrType>,
+}
+
+impl TypeInference {
+    pub fn new() -> Self {
+        TypeInference {
+            bindings: HashMap::new(),
+            builtins: builtin_signatures().into_iter().collect(),
+        }
+    }
+
+    fn infer_body(&mut self, body: &mut [IrNode]) -> IrType {
+        let mut last = IrType::Nil;
+        for node in body {
+            last = self.infer(node);
+        }
+        last
+    }
+
+    /// Record the type of a binding node and of everything that refers to it
+    fn bind(&mut self, binding: &mut IrNode, binding_type: IrType) {
+        match binding {
+            IrNode::VariableBinding { id, ir_type, .. } => {
+                *ir_type = binding_type.clone();
+                self.bindings.insert(*id, binding_type);
+            }
+            IrNode::Destructure { pattern, defaults, ir_type, .. } => {
+                // Destructuring binds nil for absent parts instead of failing
+                self.bind_pattern(pattern, &binding_type, &IrType::Nil);
+                *ir_type = binding_type;
+                for default in defaults.iter_mut() {
+                    let default_type = self.infer(&mut default.value);
+                    let bound_type = self.bindings.get(&default.binding_id).cloned().unwrap_or(IrType::Any);
+                    self.bindings.insert(default.binding_id, join(&bound_type, &default_type));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Infer a node's type, store it in the node and return it
+    pub fn infer(&mut self, node: &mut IrNode) -> IrType {
+        match node {
+            IrNode::Program { forms, .. } => self.infer_body(forms),
+            IrNode::Module { definitions, .. } => {
+                self.infer_body(definitions);
+                IrType::Nil
+            }
+            IrNode::Import { .. } | IrNode::TypeDef { .. } => IrType::Nil,
+
+            IrNode::Literal { value, ir_type, .. } => {
+                *ir_type = literal_type(value);
+                ir_type.clone()
+            }
+            IrNode::Vector { elements, ir_type, .. } => {
+                let element_types: Vec<IrType> = elements.iter_mut().map(|e| self.infer(e)).collect();
+                // A literal has a known length, so it also fits tuple types
+                *ir_type = IrType::Tuple(element_types);
+                ir_type.clone()
+            }
+            IrNode::Map { entries, ir_type, .. } => {
+                let mut type_entries = Vec::new();
+                for entry in entries.iter_mut() {
+                    let value_type = self.infer(&mut entry.value);
+                    if let MapKey::Keyword(keyword) = &entry.key {
+                        type_entries.push(IrMapTypeEntry {
+                            key: keyword.clone(),
+                            value_type,
+                            optional: false,
+                        });
+                    }
+                }
+                *ir_type = IrType::Map { entries: type_entries, wildcard: None };
+                ir_type.clone()
+            }
+
+            IrNode::VariableRef { binding_id, ir_type, .. } => {
+                if let Some(binding_type) = self.bindings.get(binding_id) {
+                    *ir_type = binding_type.clone();
+                }
+                ir_type.clone()
+            }
+            IrNode::VariableBinding { ir_type, .. }
+            | IrNode::Destructure { ir_type, .. }
+            | IrNode::Param { ir_type, .. }
+            | IrNode::TaskContextAccess { ir_type, .. } => ir_type.clone(),
+
+            IrNode::Apply { function, arguments, ir_type, .. } => {
+                let function_type = self.infer(function);
+                let argument_types: Vec<IrType> = arguments.iter_mut().map(|a| self.infer(a)).collect();
+                let return_type = match instantiate(&function_type, &argument_types) {
+                    IrType::Function { return_type, .. } => *return_type,
+                    _ => IrType::Any,
+                };
+                *ir_type = self.arithmetic_type(function, &argument_types).unwrap_or(return_type);
+                ir_type.clone()
+            }
+            // Already specialized: the type was fixed when the operands were known
+            IrNode::Arithmetic { operands, ir_type, .. } => {
+                for operand in operands.iter_mut() {
+                    self.infer(operand);
+                }
+                ir_type.clone()
+            }
+
+            IrNode::Lambda { params, variadic_param, body, captures, ir_type, .. } => {
+                for param in Rc::make_mut(params).iter_mut().chain(variadic_param.as_deref_mut()) {
+                    if let IrNode::Param { binding, ir_type: param_type, .. } = param {
+                        let param_type = param_type.clone();
+                        self.bind(binding, param_type);
+                    }
+                }
+                let body_type = self.infer_body(Rc::make_mut(body));
+                for capture in captures.iter_mut() {
+                    if let Some(binding_type) = self.bindings.get(&capture.binding_id) {
+                        capture.ir_type = binding_type.clone();
+                    }
+                }
+                if let IrType::Function { return_type, .. } = ir_type {
+                    **return_type = narrow(return_type, body_type);
+                }
+                ir_type.clone()
+            }
+
+            IrNode::If { condition, then_branch, else_branch, ir_type, .. } => {
+                self.infer(condition);
+                let (non_nil_in_then, non_nil_in_else) = match self.nil_test(condition) {
+                    Some((binding_id, true)) => (None, Some(binding_id)),
+                    Some((binding_id, false)) => (Some(binding_id), None),
+                    None => (None, None),
+                };
+                let then_type = self.infer_non_nil(then_branch, non_nil_in_then);
+                let else_type = match else_branch {
+                    Some(else_branch) => self.infer_non_nil(else_branch, non_nil_in_else),
+                    None => IrType::Nil,
+                };
+                *ir_type = join(&then_type, &else_type);
+                ir_type.clone()
+            }
+
+            IrNode::Let { bindings, body, ir_type, .. } => {
+                for binding in bindings.iter_mut() {
+                    let init_type = self.infer(&mut binding.init_expr);
+                    let binding_type = binding.type_annotation.clone().unwrap_or(init_type);
+                    self.bind(&mut binding.pattern, binding_type);
+                }
+                *ir_type = self.infer_body(body);
+                ir_type.clone()
+            }
+
+            IrNode::Do { expressions, ir_type, .. } => {
+                *ir_type = self.infer_body(expressions);
+                ir_type.clone()
+            }
+
+            IrNode::Loop { bindings, body, ir_type, .. } => {
+                for binding in bindings.iter_mut() {
+                    self.infer(&mut binding.init_expr);
+                    let binding_type = binding.type_annotation.clone().unwrap_or(IrType::Any);
+                    self.bind(&mut binding.pattern, binding_type);
+                }
+                *ir_type = self.infer_body(body);
+                ir_type.clone()
+            }
+
+            IrNode::Recur { arguments, ir_type, .. } => {
+                self.infer_body(arguments);
+                *ir_type = IrType::Never;
+                IrType::Never
+            }
+
+            IrNode::Iterate { clauses, body, collect, ir_type, .. } => {
+                for clause in clauses.iter_mut() {
+                    match clause {
+                        IrIterationClause::Binding { pattern, collection } => {
+                            let item_type = match self.infer(collection) {
+                                IrType::Vector(element_type) => *element_type,
+                                IrType::Tuple(element_types) => join_all(&element_types),
+                                _ => IrType::Any,
+                            };
+                            self.bind(pattern, item_type);
+                        }
+                        IrIterationClause::Range { binding, count } => {
+                            self.infer(count);
+                            self.bind(binding, IrType::Int);
+                        }
+                        IrIterationClause::When(test) => {
+                            self.infer(test);
+                        }
+                    }
+                }
+                let body_type = self.infer_body(body);
+                *ir_type = if *collect { IrType::Vector(Box::new(body_type)) } else { IrType::Nil };
+                ir_type.clone()
+            }
+
+            IrNode::Match { expression, clauses, ir_type, .. } => {
+                let mut scrutinee_type = self.infer(expression);
+                let mut clause_types = Vec::new();
+                for clause in clauses.iter_mut() {
+                    self.bind_pattern(&clause.pattern, &scrutinee_type, &IrType::Never);
+                    if let Some(guard) = &mut clause.guard {
+                        self.infer(guard);
+                    }
+                    clause_types.push(self.infer(&mut clause.body));
+                    // The clauses after an unguarded `nil` pattern never see nil
+                    if clause.guard.is_none() && clause.pattern == IrPattern::Literal(crate::ast::Literal::Nil) {
+                        scrutinee_type = without_nil(&scrutinee_type);
+                    }
+                }
+                *ir_type = join_all(&clause_types);
+                ir_type.clone()
+            }
+
+            IrNode::TryCatch { try_body, catch_clauses, finally_body, ir_type, .. } => {
+                let mut branch_types = vec![self.infer_body(try_body)];
+                for clause in catch_clauses.iter_mut() {
+                    if let Some(binding) = &mut clause.binding {
+                        self.bind(binding, IrType::Any);
+                    }
+                    branch_types.push(self.infer_body(&mut clause.body));
+                }
+                if let Some(finally_body) = finally_body {
+                    self.infer_body(finally_body);
+                }
+                *ir_type = join_all(&branch_types);
+                ir_type.clone()
+            }
+
+            IrNode::Parallel { bindings, ir_type, .. } => {
+                let mut type_entries = Vec::new();
+                for binding in bindings.iter_mut() {
+                    let init_type = self.infer(&mut binding.init_expr);
+                    if let IrNode::VariableBinding { name, ir_type: declared, .. } = &binding.binding {
+                        let binding_type = narrow(declared, init_type);
+                        type_entries.push(IrMapTypeEntry {
+                            key: crate::ast::Keyword(name.clone()),
+                            value_type: binding_type.clone(),
+                            optional: false,
+                        });
+                        self.bind(&mut binding.binding, binding_type);
+                    }
+                }
+                *ir_type = IrType::Map { entries: type_entries, wildcard: None };
+                ir_type.clone()
+            }
+
+            IrNode::WithResource { binding, init_expr, body, ir_type, .. } => {
+                self.infer(init_expr);
+                if let Some(resource_type) = binding.ir_type().cloned() {
+                    self.bind(binding, resource_type);
+                }
+                *ir_type = self.infer_body(body);
+                ir_type.clone()
+            }
+
+            IrNode::LogStep { values, ir_type, .. } => {
+                *ir_type = self.infer_body(values);
+                ir_type.clone()
+            }
+
+            IrNode::FunctionDef { id, lambda, ir_type, .. } => {
+                *ir_type = self.infer(lambda);
+                self.bindings.insert(*id, ir_type.clone());
+                ir_type.clone()
+            }
+            IrNode::VariableDef { id, type_annotation, init_expr, ir_type, .. } => {
+                let init_type = self.infer(init_expr);
+                *ir_type = type_annotation.clone().unwrap_or(init_type);
+                self.bindings.insert(*id, ir_type.clone());
+                ir_type.clone()
+            }
+
+            IrNode::Task { intent, contracts, plan, ir_type, .. } => {
+                self.infer(intent);
+                self.infer(contracts);
+                self.infer(plan);
+                ir_type.clone()
+            }
+        }
+    }
+
+    /// The binding an `if` condition tests for nil, and whether it is `(nil? x)`
+    /// (true) rather than `x` itself (false)
+    fn nil_test(&self, condition: &IrNode) -> Option<(NodeId, bool)> {
+        match condition {
+            IrNode::VariableRef { binding_id, .. } => Some((*binding_id, false)),
+            IrNode::Apply { function, arguments, .. } => match (function.as_ref(), arguments.as_slice()) {
+                // Unless a user binding shadows the builtin
+                (IrNode::VariableRef { name, ir_type, .. }, [IrNode::VariableRef { binding_id, .. }])
+                    if name == "nil?" && self.builtins.get("nil?") == Some(ir_type) =>
+                {
+                    Some((*binding_id, true))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Infer `node` knowing that the binding `non_nil` (if any) is not nil there
+    fn infer_non_nil(&mut self, node: &mut IrNode, non_nil: Option<NodeId>) -> IrType {
+        let Some(binding_id) = non_nil else {
+            return self.infer(node);
+        };
+        let Some(binding_type) = self.bindings.get(&binding_id).cloned() else {
+            return self.infer(node);
+        };
+        self.bindings.insert(binding_id, without_nil(&binding_type));
+        let node_type = self.infer(node);
+        self.bindings.insert(binding_id, binding_type);
+        node_type
+    }
+
+    /// Type of a call to builtin `+`, `-` or `*`, when all argument types are known numbers
+    fn arithmetic_type(&self, function: &IrNode, argument_types: &[IrType]) -> Option<IrType> {
+        let (name, function_type) = match function {
+            IrNode::VariableRef { name, ir_type, .. } => (name.as_str(), ir_type),
+            _ => return None,
+        };
+        // A user binding that shadows the builtin has a different type
+        if !matches!(name, "+" | "-" | "*") || self.builtins.get(name) != Some(function_type) {
+            return None;
+        }
+        if argument_types.is_empty() {
+            return None;
+        }
+        // Untyped arguments give an untyped result, rather than any number
+        if argument_types.contains(&IrType::Any) {
+            Some(IrType::Any)
+        } else if argument_types.iter().all(|t| *t == IrType::Int) {
+            Some(IrType::Int)
+        } else if argument_types.iter().all(|t| matches!(t, IrType::Int | IrType::Float)) {
+            Some(IrType::Float)
+        } else {
+            None
+        }
+    }
+
+    /// Give the variables a pattern binds the type of the part of the value they match;
+    /// `missing` is the type of tuple elements the value does not have
+    fn bind_pattern(&mut self, pattern: &IrPattern, value_type: &IrType, missing: &IrType) {
+        match pattern {
+            IrPattern::Variable { binding_id, .. } => {
+                self.bindings.insert(*binding_id, value_type.clone());
+            }
+            IrPattern::As { binding_id, pattern, .. } => {
+                // `(:as n :int)` only matches ints, so `n` is an int
+                let bound_type = match pattern.as_ref() {
+                    IrPattern::Type(pattern_type) => pattern_type.clone(),
+                    _ => value_type.clone(),
+                };
+                self.bindings.insert(*binding_id, bound_type);
+                self.bind_pattern(pattern, value_type, missing);
+            }
+            IrPattern::Vector { elements, rest } => {
+                for (i, element) in elements.iter().enumerate() {
+                    let element_type = match value_type {
+                        IrType::Vector(element_type) => (**element_type).clone(),
+                        IrType::Tuple(types) => types.get(i).cloned().unwrap_or_else(|| missing.clone()),
+                        _ => IrType::Any,
+                    };
+                    self.bind_pattern(element, &element_type, missing);
+                }
+                if let Some(rest) = rest {
+                    let rest_type = match value_type {
+                        IrType::Vector(_) => value_type.clone(),
+                        IrType::Tuple(types) => {
+                            IrType::Tuple(types.iter().skip(elements.len()).cloned().collect())
+                        }
+                        _ => IrType::Vector(Box::new(IrType::Any)),
+                    };
+                    self.bind_pattern(rest, &rest_type, missing);
+                }
+            }
+            IrPattern::Map { entries, rest } => {
+                for entry in entries {
+                    let entry_type = match (value_type, &entry.key) {
+                        (IrType::Map { entries: type_entries, wildcard }, MapKey::Keyword(keyword)) => type_entries
+                            .iter()
+                            .find(|t| t.key == *keyword)
+                            .map(|t| t.value_type.clone())
+                            .or(wildcard.as_deref().cloned())
+                            .unwrap_or(IrType::Any),
+                        _ => IrType::Any,
+                    };
+                    self.bind_pattern(&entry.pattern, &entry_type, missing);
+                }
+                if let Some(rest) = rest {
+                    let any_map = IrType::Map { entries: Vec::new(), wildcard: Some(Box::new(IrType::Any)) };
+                    self.bind_pattern(rest, &any_map, missing);
+                }
+            }
+            IrPattern::Literal(_) | IrPattern::Wildcard | IrPattern::Type(_) => {}
+        }
+    }
+}
+
+/// A type without its `nil` member. Where a value could only be nil, no value is left
+/// (`Never`): code that sees it is unreachable, so it is not checked against nil.
+fn without_nil(t: &IrType) -> IrType {
+    match t {
+        IrType::Nil => IrType::Never,
+        IrType::Union(members) => {
+            let mut members: Vec<IrType> = members.iter().filter(|m| **m != IrType::Nil).cloned().collect();
+            match members.len() {
+                0 => IrType::Never,
+                1 => members.remove(0),
+                _ => IrType::Union(members),
+            }
+        }
+        other => other.clone(),
+    }
+}
+
+impl Default for TypeInference {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_converter::IrConverter;
+    use crate::parser::parse_expression;
+
+    fn infer_source(source: &str) -> IrNode {
+        IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap()
+    }
+
+    fn type_of(source: &str) -> IrType {
+        infer_source(source).ir_type().cloned().unwrap()
+    }
+
+    #[test]
+    fn test_infers_literals_and_arithmetic() {
+        assert_eq!(type_of("(+ 1 2)"), IrType::Int);
+        assert_eq!(type_of("(* 2 1.5)"), IrType::Float);
+        assert_eq!(type_of("(/ 4 2)"), IrType::Float);
+        assert_eq!(type_of("(let [x 1 y (- x 3)] (* x y))"), IrType::Int);
+        assert_eq!(type_of("(> 2 1)"), IrType::Bool);
+        assert_eq!(type_of("(str 1)"), IrType::String);
+        assert_eq!(type_of("[1 (+ 1 1)]"), IrType::Tuple(vec![IrType::Int, IrType::Int]));
+        assert_eq!(type_of("(for [x [1 2.5]] x)"), IrType::Vector(Box::new(IrType::Union(vec![IrType::Int, IrType::Float]))));
+    }
+
+    #[test]
+    fn test_joins_branches_into_unions() {
+        assert_eq!(type_of("(if true 1 \"one\")"), IrType::Union(vec![IrType::Int, IrType::String]));
+        assert_eq!(type_of("(if true 1)"), IrType::Union(vec![IrType::Int, IrType::Nil]));
+        assert_eq!(type_of("(if true 1 2)"), IrType::Int);
+        assert_eq!(
+            type_of("(match 3 1 :one 2 \"two\" n (+ n 1))"),
+            IrType::Union(vec![IrType::Keyword, IrType::String, IrType::Int])
+        );
+    }
+
+    #[test]
+    fn test_nil_tests_narrow_the_branches() {
+        let then_type = |source: &str| match infer_source(source) {
+            IrNode::Let { body, .. } => match &body[0] {
+                IrNode::If { then_branch, .. } => then_branch.ir_type().cloned().unwrap(),
+                other => panic!("expected an if, got {:?}", other),
+            },
+            other => panic!("expected a let, got {:?}", other),
+        };
+        assert_eq!(then_type("(let [x (if true 1 nil)] (if x x 0))"), IrType::Int);
+        assert_eq!(then_type("(let [x (if true 1 nil)] (if (nil? x) x 0))"), IrType::Union(vec![IrType::Int, IrType::Nil]));
+        assert_eq!(type_of("(let [x (if true 1 nil)] (if (nil? x) 0 x))"), IrType::Int);
+        assert_eq!(then_type("(let [x nil] (if x x 0))"), IrType::Never);
+        // The narrowing ends with the branch
+        assert_eq!(type_of("(let [x (if true 1 nil)] (if x x 0) x)"), IrType::Union(vec![IrType::Int, IrType::Nil]));
+    }
+
+    #[test]
+    fn test_infers_lambda_types() {
+        let expected = IrType::Function {
+            param_types: vec![IrType::Int],
+            variadic_param_type: None,
+            return_type: Box::new(IrType::Int),
+        };
+        assert_eq!(type_of("(fn [x :int] (+ x 1))"), expected);
+        assert_eq!(type_of("(let [inc (fn [x :int] (+ x 1))] (inc 2))"), IrType::Int);
+        // Annotations win over less precise bodies
+        assert_eq!(
+            type_of("(fn [x] :int x)"),
+            IrType::Function {
+                param_types: vec![IrType::Any],
+                variadic_param_type: None,
+                return_type: Box::new(IrType::Int),
+            }
+        );
+    }
+
+    #[test]
+    fn test_instantiates_generic_calls() {
+        assert_eq!(type_of("(get [1 2] 0)"), IrType::Int);
+        assert_eq!(type_of("(get {:a 1} :a)"), IrType::Any);
+        assert_eq!(type_of("(vector 1 2)"), IrType::Vector(Box::new(IrType::Int)));
+        assert_eq!(type_of("(map-fn (fn [x :int] (str x)) [1 2])"), IrType::Vector(Box::new(IrType::String)));
+        assert_eq!(
+            type_of("(let [first-of (fn [xs :[:vector T]] :T (get xs 0))] (first-of [\"a\"]))"),
+            IrType::String
+        );
+        assert_eq!(
+            type_of("(fn [xs :[:vector T]] (get xs 0))"),
+            IrType::Function {
+                param_types: vec![IrType::Vector(Box::new(IrType::TypeVar("T".to_string())))],
+                variadic_param_type: None,
+                return_type: Box::new(IrType::TypeVar("T".to_string())),
+            }
+        );
+    }
+
+    #[test]
+    fn test_every_reference_is_typed() {
+        fn collect_refs(node: &IrNode, out: &mut Vec<IrType>) {
+            if let IrNode::VariableRef { name, ir_type, .. } = node {
+                if name == "total" {
+                    out.push(ir_type.clone());
+                }
+            }
+            for child in node.children() {
+                collect_refs(child, out);
+            }
+        }
+        let ir = infer_source("(let [total (+ 1 2)] (do (> total 0) [total {:sum total}]))");
+        let mut types = Vec::new();
+        collect_refs(&ir, &mut types);
+        assert_eq!(types, vec![IrType::Int; 3]);
+    }
+}
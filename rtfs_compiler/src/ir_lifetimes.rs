@@ -0,0 +1,348 @@
+// IR Resource Lifetime Checker
+// Rejects resource handles that outlive their `with-resource` scope or are used after being consumed
+
+use std::collections::{HashMap, HashSet};
+use crate::ir::*;
+use crate::ir_converter::IrConversionError;
+
+/// Tools that release the handle passed as their first argument
+const CONSUMING_TOOLS: [&str; 1] = ["tool:close-file"];
+
+/// Builtins whose result holds on to their arguments
+const COLLECTION_BUILDERS: [&str; 4] = ["vector", "map", "assoc", "conj"];
+
+/// How a value comes to hold on to a handle
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Holder {
+    /// The handle itself
+    Handle,
+    /// A closure capturing the handle
+    Closure,
+    /// A collection containing the handle
+    Collection,
+}
+
+impl Holder {
+    fn describe(self) -> &'static str {
+        match self {
+            Holder::Handle => "returned",
+            Holder::Closure => "captured by a closure",
+            Holder::Collection => "stored in a collection",
+        }
+    }
+}
+
+/// Check an IR tree for resource handles that escape their `with-resource` scope
+/// (returned, captured by a closure, stored in a collection or a definition) and for
+/// handles used after a consuming tool such as `tool:close-file`.
+pub fn check_lifetimes(node: &IrNode) -> Result<(), Vec<IrConversionError>> {
+    let mut checker = LifetimeChecker::default();
+    checker.check_escapes(node);
+    checker.check_uses(node, &mut HashMap::new());
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+#[derive(Default)]
+struct LifetimeChecker {
+    /// Binding ids of `with-resource` handles and other bindings typed as resources
+    resources: HashSet<NodeId>,
+    errors: Vec<IrConversionError>,
+}
+
+impl LifetimeChecker {
+    fn error(&mut self, resource_type: &IrType, message: String, location: Option<&SourceLocation>) {
+        self.errors.push(IrConversionError::ResourceMisuse {
+            resource_type: resource_type.to_string(),
+            message,
+            location: location.cloned(),
+        });
+    }
+
+    /// Check every `with-resource` block in the tree for handles leaving it
+    fn check_escapes(&mut self, node: &IrNode) {
+        if let IrNode::WithResource { binding, body, source_location, .. } = node {
+            if let IrNode::VariableBinding { id, name, ir_type, .. } = binding.as_ref() {
+                self.resources.insert(*id);
+                let mut holders = HashMap::from([(*id, Holder::Handle)]);
+                for expr in body {
+                    self.track_holders(expr, &mut holders, name, ir_type);
+                }
+                if let Some(holder) = body.last().and_then(|last| holds(last, &holders)) {
+                    let location = body.last().and_then(IrNode::source_location).or(source_location.as_ref());
+                    self.error(
+                        ir_type,
+                        format!("handle `{}` escapes its with-resource scope: {}", name, holder.describe()),
+                        location,
+                    );
+                }
+            }
+        }
+        for child in node.children() {
+            self.check_escapes(child);
+        }
+    }
+
+    /// Record the let bindings inside a `with-resource` body that hold on to its handle,
+    /// reporting definitions that would keep the handle alive
+    fn track_holders(&mut self, node: &IrNode, holders: &mut HashMap<NodeId, Holder>, name: &str, resource_type: &IrType) {
+        match node {
+            IrNode::Let { bindings, body, .. } => {
+                for binding in bindings {
+                    self.track_holders(&binding.init_expr, holders, name, resource_type);
+                    if let (IrNode::VariableBinding { id, .. }, Some(holder)) =
+                        (&binding.pattern, holds(&binding.init_expr, holders))
+                    {
+                        holders.insert(*id, holder);
+                    }
+                }
+                for expr in body {
+                    self.track_holders(expr, holders, name, resource_type);
+                }
+            }
+            IrNode::VariableDef { init_expr, source_location, .. } => {
+                self.track_holders(init_expr, holders, name, resource_type);
+                if let Some(holder) = holds(init_expr, holders) {
+                    self.error(
+                        resource_type,
+                        format!(
+                            "handle `{}` escapes its with-resource scope: {} and defined globally",
+                            name,
+                            holder.describe()
+                        ),
+                        source_location.as_ref(),
+                    );
+                }
+            }
+            IrNode::FunctionDef { lambda, source_location, .. } => {
+                if let Some(holder) = holds(lambda, holders) {
+                    self.error(
+                        resource_type,
+                        format!("handle `{}` escapes its with-resource scope: {} and defined globally", name, holder.describe()),
+                        source_location.as_ref(),
+                    );
+                }
+                self.track_holders(lambda, holders, name, resource_type);
+            }
+            other => {
+                for child in other.children() {
+                    self.track_holders(child, holders, name, resource_type);
+                }
+            }
+        }
+    }
+
+    /// Walk the tree in evaluation order, reporting uses of handles after a consuming tool
+    /// released them. `consumed` maps handles to the tool that released them.
+    fn check_uses(&mut self, node: &IrNode, consumed: &mut HashMap<NodeId, String>) {
+        match node {
+            IrNode::VariableRef { binding_id, name, ir_type, source_location, .. } => {
+                if let Some(tool) = consumed.get(binding_id) {
+                    let message = format!("handle `{}` is used after {} released it", name, tool);
+                    self.error(ir_type, message, source_location.as_ref());
+                }
+            }
+            IrNode::Apply { function, arguments, .. } => {
+                self.check_uses(function, consumed);
+                for argument in arguments {
+                    self.check_uses(argument, consumed);
+                }
+                if let (IrNode::VariableRef { name: tool, .. }, Some(IrNode::VariableRef { binding_id, ir_type, .. })) =
+                    (function.as_ref(), arguments.first())
+                {
+                    let is_resource = self.resources.contains(binding_id) || matches!(ir_type, IrType::Resource(_));
+                    if is_resource && CONSUMING_TOOLS.contains(&tool.as_str()) {
+                        consumed.insert(*binding_id, tool.clone());
+                    }
+                }
+            }
+            IrNode::Let { bindings, body, .. } => {
+                for binding in bindings {
+                    self.check_uses(&binding.init_expr, consumed);
+                    if let IrNode::VariableBinding { id, ir_type: IrType::Resource(_), .. } = &binding.pattern {
+                        self.resources.insert(*id);
+                    }
+                }
+                for expr in body {
+                    self.check_uses(expr, consumed);
+                }
+            }
+            IrNode::Param { binding, ir_type, .. } => {
+                if let (IrNode::VariableBinding { id, .. }, IrType::Resource(_)) = (binding.as_ref(), ir_type) {
+                    self.resources.insert(*id);
+                }
+            }
+            IrNode::WithResource { binding, init_expr, body, .. } => {
+                self.check_uses(init_expr, consumed);
+                self.resources.insert(binding.id());
+                for expr in body {
+                    self.check_uses(expr, consumed);
+                }
+            }
+            // Branches run one or the other: a handle released in either may be released after
+            IrNode::If { condition, then_branch, else_branch, .. } => {
+                self.check_uses(condition, consumed);
+                let branches = std::iter::once(then_branch.as_ref()).chain(else_branch.as_deref());
+                self.check_branches(branches.collect(), consumed);
+            }
+            IrNode::Match { expression, clauses, .. } => {
+                self.check_uses(expression, consumed);
+                let mut after = consumed.clone();
+                for clause in clauses {
+                    let mut state = consumed.clone();
+                    if let Some(guard) = &clause.guard {
+                        self.check_uses(guard, &mut state);
+                    }
+                    self.check_uses(&clause.body, &mut state);
+                    after.extend(state);
+                }
+                *consumed = after;
+            }
+            IrNode::TryCatch { try_body, catch_clauses, finally_body, .. } => {
+                let mut after = consumed.clone();
+                for expr in try_body {
+                    self.check_uses(expr, &mut after);
+                }
+                // A catch clause may run after any part of the try body
+                let before_catch = after.clone();
+                for clause in catch_clauses {
+                    let mut state = before_catch.clone();
+                    for expr in &clause.body {
+                        self.check_uses(expr, &mut state);
+                    }
+                    after.extend(state);
+                }
+                for expr in finally_body.iter().flatten() {
+                    self.check_uses(expr, &mut after);
+                }
+                *consumed = after;
+            }
+            // A closure body runs later, so it sees what is released when it is created
+            // but releases nothing in the enclosing code
+            IrNode::Lambda { params, variadic_param, body, .. } => {
+                let mut state = consumed.clone();
+                for param in params.iter().chain(variadic_param.as_deref()) {
+                    self.check_uses(param, &mut state);
+                }
+                for expr in body.iter() {
+                    self.check_uses(expr, &mut state);
+                }
+            }
+            other => {
+                for child in other.children() {
+                    self.check_uses(child, consumed);
+                }
+            }
+        }
+    }
+
+    fn check_branches(&mut self, branches: Vec<&IrNode>, consumed: &mut HashMap<NodeId, String>) {
+        let mut after = consumed.clone();
+        for branch in branches {
+            let mut state = consumed.clone();
+            self.check_uses(branch, &mut state);
+            after.extend(state);
+        }
+        *consumed = after;
+    }
+}
+
+/// Whether the value of `node` holds on to a tracked handle, and how
+fn holds(node: &IrNode, holders: &HashMap<NodeId, Holder>) -> Option<Holder> {
+    let any = |nodes: Vec<&IrNode>| nodes.into_iter().find_map(|n| holds(n, holders));
+    match node {
+        IrNode::VariableRef { binding_id, .. } => holders.get(binding_id).copied(),
+        IrNode::Lambda { captures, .. } => captures
+            .iter()
+            .any(|capture| holders.contains_key(&capture.binding_id))
+            .then_some(Holder::Closure),
+        IrNode::Vector { elements, .. } => any(elements.iter().collect()).map(|_| Holder::Collection),
+        IrNode::Map { entries, .. } => any(entries.iter().map(|entry| &entry.value).collect()).map(|_| Holder::Collection),
+        IrNode::Apply { function, arguments, .. } => match function.as_ref() {
+            IrNode::VariableRef { name, .. } if COLLECTION_BUILDERS.contains(&name.as_str()) => {
+                any(arguments.iter().collect()).map(|_| Holder::Collection)
+            }
+            _ => None,
+        },
+        // Compound forms produce the value of their last expression
+        IrNode::Do { expressions: body, .. } | IrNode::Let { body, .. } | IrNode::WithResource { body, .. } => {
+            body.last().and_then(|last| holds(last, holders))
+        }
+        IrNode::If { then_branch, else_branch, .. } => {
+            any(std::iter::once(then_branch.as_ref()).chain(else_branch.as_deref()).collect())
+        }
+        IrNode::Match { clauses, .. } => any(clauses.iter().map(|clause| &clause.body).collect()),
+        IrNode::TryCatch { try_body, catch_clauses, .. } => {
+            any(try_body.last().into_iter().chain(catch_clauses.iter().filter_map(|clause| clause.body.last())).collect())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_converter::IrConverter;
+    use crate::parser::parse_expression;
+
+    fn check_source(source: &str) -> Result<(), Vec<String>> {
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        check_lifetimes(&ir).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|error| match error {
+                    IrConversionError::ResourceMisuse { message, .. } => message,
+                    other => panic!("expected a resource error, got {:?}", other),
+                })
+                .collect()
+        })
+    }
+
+    const OPEN: &str = "(tool:open-file \"data.txt\")";
+
+    #[test]
+    fn test_handles_used_inside_their_scope_are_accepted() {
+        assert!(check_source(&format!("(with-resource [f FileHandle {}] (tool:read-line f))", OPEN)).is_ok());
+        assert!(check_source(&format!(
+            "(with-resource [f FileHandle {}] (let [line (tool:read-line f) g (fn [] (tool:read-line f))] (g) line))",
+            OPEN
+        ))
+        .is_ok());
+        // Nested blocks may return the outer handle to the outer body
+        assert!(check_source(&format!(
+            "(with-resource [a FileHandle {}] (tool:read-line (with-resource [b FileHandle {}] a)))",
+            OPEN, OPEN
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_escaping_handles_are_rejected() {
+        let escapes = |body: &str| check_source(&format!("(with-resource [f FileHandle {}] {})", OPEN, body)).unwrap_err();
+        assert_eq!(escapes("f"), vec!["handle `f` escapes its with-resource scope: returned"]);
+        assert_eq!(escapes("(if (tool:read-line f) f nil)"), vec!["handle `f` escapes its with-resource scope: returned"]);
+        assert_eq!(escapes("(fn [] (tool:read-line f))"), vec!["handle `f` escapes its with-resource scope: captured by a closure"]);
+        assert_eq!(escapes("{:file f}"), vec!["handle `f` escapes its with-resource scope: stored in a collection"]);
+        assert_eq!(escapes("(let [fs [f]] fs)"), vec!["handle `f` escapes its with-resource scope: stored in a collection"]);
+        assert_eq!(escapes("(assoc {} :file f)"), vec!["handle `f` escapes its with-resource scope: stored in a collection"]);
+        assert_eq!(
+            escapes("(do (def reader (fn [] (tool:read-line f))) nil)"),
+            vec!["handle `f` escapes its with-resource scope: captured by a closure and defined globally"]
+        );
+    }
+
+    #[test]
+    fn test_use_after_close_is_rejected() {
+        let after_close = format!("(with-resource [f FileHandle {}] (tool:close-file f) (tool:read-line f))", OPEN);
+        assert_eq!(check_source(&after_close).unwrap_err(), vec!["handle `f` is used after tool:close-file released it"]);
+        let let_bound = format!("(let [f {}] (tool:close-file f) (tool:read-line f))", OPEN);
+        assert!(check_source(&let_bound).is_err());
+        // Closing in one branch may leave the handle released afterwards
+        let branch = format!("(let [f {}] (if (tool:read-line f) (tool:close-file f) nil) (tool:read-line f))", OPEN);
+        assert!(check_source(&branch).is_err());
+        assert!(check_source(&format!("(let [f {}] (tool:read-line f) (tool:close-file f))", OPEN)).is_ok());
+    }
+}
@@ -4,12 +4,14 @@
 use std::collections::HashSet;
 use crate::ir::*;
 use crate::ast::Literal;
+use crate::ir_specialize::ArithmeticSpecializer;
 
 /// Enhanced IR Optimizer with advanced optimization strategies
 pub struct EnhancedIrOptimizer {
     optimization_level: OptimizationLevel,
     inline_threshold: usize,
     max_inline_depth: usize,
+    arithmetic: ArithmeticSpecializer,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,6 +27,7 @@ impl EnhancedIrOptimizer {
             optimization_level: OptimizationLevel::Aggressive,
             inline_threshold: 10,
             max_inline_depth: 3,
+            arithmetic: ArithmeticSpecializer::new(),
         }
     }
 
@@ -39,6 +42,7 @@ impl EnhancedIrOptimizer {
             optimization_level: level,
             inline_threshold: threshold,
             max_inline_depth: 3,
+            arithmetic: ArithmeticSpecializer::new(),
         }
     }
 
@@ -80,7 +84,7 @@ impl EnhancedIrOptimizer {
                                 id: 0,
                                 value: Literal::Nil,
                                 ir_type: IrType::Nil,
-                                source_location: None,
+                                source_location,
                             }
                         }
                     },
@@ -161,6 +165,17 @@ impl EnhancedIrOptimizer {
                 }
             },
             
+            IrNode::Apply { id, function, arguments, ir_type, source_location } => {
+                let arguments: Vec<IrNode> = arguments.into_iter()
+                    .map(|arg| self.optimize_control_flow(arg))
+                    .collect();
+                
+                let function = self.optimize_control_flow(*function);
+                
+                // Fold or specialize arithmetic the inference pass has typed as int or float
+                self.arithmetic.specialize(id, function, arguments, ir_type, source_location)
+            },
+            
             // Recursively optimize other node types
             _ => self.optimize_recursive(node)
         }
@@ -236,9 +251,9 @@ impl EnhancedIrOptimizer {
             IrNode::Lambda { id, params, variadic_param, body, captures, ir_type, source_location } => {
                 IrNode::Lambda {
                     id,
-                    params: params.into_iter().map(|p| self.optimize_control_flow(p)).collect(),
+                    params: params.iter().map(|p| self.optimize_control_flow(p.clone())).collect(),
                     variadic_param: variadic_param.map(|vp| Box::new(self.optimize_control_flow(*vp))),
-                    body: body.into_iter().map(|expr| self.optimize_control_flow(expr)).collect(),
+                    body: body.iter().map(|expr| self.optimize_control_flow(expr.clone())).collect(),
                     captures,
                     ir_type,
                     source_location,
@@ -259,6 +274,37 @@ impl EnhancedIrOptimizer {
                 }
             },
             
+            IrNode::Vector { id, elements, ir_type, source_location } => {
+                IrNode::Vector {
+                    id,
+                    elements: elements.into_iter().map(|e| self.optimize_control_flow(e)).collect(),
+                    ir_type,
+                    source_location,
+                }
+            },
+            
+            IrNode::Map { id, entries, ir_type, source_location } => {
+                IrNode::Map {
+                    id,
+                    entries: entries.into_iter().map(|entry| IrMapEntry {
+                        key: entry.key,
+                        value: self.optimize_control_flow(entry.value),
+                    }).collect(),
+                    ir_type,
+                    source_location,
+                }
+            },
+            
+            IrNode::Arithmetic { id, op, operands, ir_type, source_location } => {
+                IrNode::Arithmetic {
+                    id,
+                    op,
+                    operands: operands.into_iter().map(|operand| self.optimize_control_flow(operand)).collect(),
+                    ir_type,
+                    source_location,
+                }
+            },
+            
             // For leaf nodes and other complex nodes, return as-is
             _ => node,
         }
@@ -268,6 +314,8 @@ impl EnhancedIrOptimizer {
         match node {
             IrNode::Literal { .. } => false,
             IrNode::VariableRef { .. } => false,
+            IrNode::Vector { elements, .. } => elements.iter().any(|e| self.has_side_effects(e)),
+            IrNode::Map { entries, .. } => entries.iter().any(|entry| self.has_side_effects(&entry.value)),
             IrNode::Apply { function, arguments, .. } => {
                 // Conservative: assume function calls have side effects unless known pure
                 match function.as_ref() {
@@ -282,6 +330,7 @@ impl EnhancedIrOptimizer {
                     _ => true,
                 }
             },
+            IrNode::Arithmetic { operands, .. } => operands.iter().any(|operand| self.has_side_effects(operand)),
             IrNode::LogStep { .. } => true, // Logging has side effects
             IrNode::TryCatch { .. } => true, // Exception handling has side effects
             IrNode::WithResource { .. } => true, // Resource management has side effects
@@ -300,6 +349,11 @@ impl EnhancedIrOptimizer {
                     self.collect_used_variables(arg, used);
                 }
             },
+            IrNode::Arithmetic { operands, .. } => {
+                for operand in operands {
+                    self.collect_used_variables(operand, used);
+                }
+            },
             IrNode::If { condition, then_branch, else_branch, .. } => {
                 self.collect_used_variables(condition, used);
                 self.collect_used_variables(then_branch, used);
@@ -320,6 +374,16 @@ impl EnhancedIrOptimizer {
                     self.collect_used_variables(expr, used);
                 }
             },
+            IrNode::Vector { elements, .. } => {
+                for element in elements {
+                    self.collect_used_variables(element, used);
+                }
+            },
+            IrNode::Map { entries, .. } => {
+                for entry in entries {
+                    self.collect_used_variables(&entry.value, used);
+                }
+            },
             _ => {}
         }
     }
@@ -342,6 +406,9 @@ impl EnhancedIrOptimizer {
             IrNode::Apply { function, arguments, .. } => {
                 1 + self.estimate_node_size(function) + arguments.iter().map(|arg| self.estimate_node_size(arg)).sum::<usize>()
             },
+            IrNode::Arithmetic { operands, .. } => {
+                1 + operands.iter().map(|operand| self.estimate_node_size(operand)).sum::<usize>()
+            },
             IrNode::If { condition, then_branch, else_branch, .. } => {
                 1 + self.estimate_node_size(condition) + self.estimate_node_size(then_branch) +
                 else_branch.as_ref().map_or(0, |e| self.estimate_node_size(e))
@@ -353,6 +420,12 @@ impl EnhancedIrOptimizer {
             IrNode::Do { expressions, .. } => {
                 1 + expressions.iter().map(|expr| self.estimate_node_size(expr)).sum::<usize>()
             },
+            IrNode::Vector { elements, .. } => {
+                1 + elements.iter().map(|e| self.estimate_node_size(e)).sum::<usize>()
+            },
+            IrNode::Map { entries, .. } => {
+                1 + entries.iter().map(|entry| self.estimate_node_size(&entry.value)).sum::<usize>()
+            },
             _ => 3, // Conservative estimate for complex nodes
         }
     }
@@ -509,4 +582,32 @@ mod tests {
             panic!("Expected dead code eliminated result");
         }
     }
+    
+    #[test]
+    fn test_folds_typed_arithmetic() {
+        use crate::ir_converter::IrConverter;
+        use crate::parser::parse_expression;
+        
+        let optimize = |source: &str| {
+            let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+            EnhancedIrOptimizer::new().optimize_with_control_flow(ir)
+        };
+        
+        assert!(matches!(optimize("(+ 1 (* 2 3))"), IrNode::Literal { value: Literal::Integer(7), .. }));
+        assert!(matches!(optimize("(- 1.5 1)"), IrNode::Literal { value: Literal::Float(f), .. } if f == 0.5));
+        // Typed operands that are not all literals are specialized instead of folded
+        assert!(matches!(
+            optimize("(fn [x :int] (+ x 1))"),
+            IrNode::Lambda { body, .. }
+                if matches!(&body[0], IrNode::Arithmetic { op: ArithmeticOp::Add, ir_type: IrType::Int, operands, .. } if operands.len() == 2)
+        ));
+        assert!(matches!(
+            optimize("(fn [x :int y :float] (* x y))"),
+            IrNode::Lambda { body, .. } if matches!(body[0], IrNode::Arithmetic { op: ArithmeticOp::Multiply, ir_type: IrType::Float, .. })
+        ));
+        // Overflow is left to the runtime; untyped operands and shadowed operators stay calls
+        assert!(matches!(optimize("(* 9223372036854775807 2)"), IrNode::Arithmetic { .. }));
+        assert!(matches!(optimize("(fn [x] (+ x 1))"), IrNode::Lambda { body, .. } if matches!(body[0], IrNode::Apply { .. })));
+        assert!(matches!(optimize("(let [+ (fn [a b] :int 0)] (+ 1 2))"), IrNode::Let { .. }));
+    }
 }
@@ -0,0 +1,95 @@
+// Type-directed specialization of builtin arithmetic, shared by the IR optimizers
+
+use std::collections::HashMap;
+
+use crate::ast::Literal;
+use crate::ir::*;
+use crate::ir_typecheck::builtin_signatures;
+
+/// Rewrites calls of builtin `+`, `-` and `*` that inference has typed as int or float
+pub struct ArithmeticSpecializer {
+    builtins: HashMap<&'static str, IrType>,
+}
+
+impl ArithmeticSpecializer {
+    pub fn new() -> Self {
+        Self {
+            builtins: builtin_signatures().into_iter().collect(),
+        }
+    }
+
+    /// Specialize a call. All-literal operands are folded to a literal; otherwise, when every
+    /// operand is known to be an int (or, for a float result, an int or float), the call becomes
+    /// an `Arithmetic` node, which also leaves an overflowing fold to the runtime to report.
+    /// Anything else stays an `Apply`.
+    pub fn specialize(
+        &self,
+        id: NodeId,
+        function: IrNode,
+        arguments: Vec<IrNode>,
+        ir_type: IrType,
+        source_location: Option<SourceLocation>,
+    ) -> IrNode {
+        let op = match self.builtin_op(&function) {
+            Some(op) if !arguments.is_empty() && self.operands_fit(&arguments, &ir_type) => op,
+            _ => return IrNode::Apply { id, function: Box::new(function), arguments, ir_type, source_location },
+        };
+        if let Some(value) = fold(op, &arguments, &ir_type) {
+            return IrNode::Literal { id, value, ir_type, source_location };
+        }
+        IrNode::Arithmetic { id, op, operands: arguments, ir_type, source_location }
+    }
+
+    /// Operator of a reference to the builtin; a user binding that shadows it has a different type
+    fn builtin_op(&self, function: &IrNode) -> Option<ArithmeticOp> {
+        match function {
+            IrNode::VariableRef { name, ir_type, .. } if self.builtins.get(name.as_str()) == Some(ir_type) => {
+                ArithmeticOp::from_builtin(name)
+            },
+            _ => None,
+        }
+    }
+
+    fn operands_fit(&self, arguments: &[IrNode], ir_type: &IrType) -> bool {
+        arguments.iter().all(|arg| match (ir_type, arg.ir_type()) {
+            (IrType::Int, Some(IrType::Int)) => true,
+            (IrType::Float, Some(IrType::Int | IrType::Float)) => true,
+            _ => false,
+        })
+    }
+}
+
+/// Evaluate operands that are all numeric literals; `None` if one is not, or on integer overflow
+fn fold(op: ArithmeticOp, arguments: &[IrNode], ir_type: &IrType) -> Option<Literal> {
+    let literals: Vec<&Literal> = arguments.iter()
+        .map(|arg| match arg {
+            IrNode::Literal { value, .. } => Some(value),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    match ir_type {
+        IrType::Int => {
+            let values: Vec<i64> = literals.iter()
+                .map(|literal| match literal {
+                    Literal::Integer(i) => Some(*i),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?;
+            let (first, rest) = values.split_first()?;
+            op.apply_int(*first, rest).map(Literal::Integer)
+        },
+        IrType::Float => {
+            let values: Vec<f64> = literals.iter()
+                .map(|literal| match literal {
+                    Literal::Integer(i) => Some(*i as f64),
+                    Literal::Float(f) => Some(*f),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?;
+            let (first, rest) = values.split_first()?;
+            Some(Literal::Float(op.apply_float(*first, rest)))
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,850 @@
+// IR Type Checker
+// Checks an IR tree against its `IrType`s before it runs
+
+use std::collections::HashMap;
+use crate::ast::{Literal, TypeExpr};
+use crate::ir::*;
+use crate::ir_converter::{IrConversionError, IrConverter};
+use crate::ir_inference::join;
+use crate::runtime::schema::{predicate_holds, resolve_type};
+use crate::runtime::Value;
+
+/// `Int` or `Float`
+fn number() -> IrType {
+    IrType::Union(vec![IrType::Int, IrType::Float])
+}
+
+/// Any map, whatever its keys
+fn any_map() -> IrType {
+    IrType::Map {
+        entries: Vec::new(),
+        wildcard: Some(Box::new(IrType::Any)),
+    }
+}
+
+fn type_var(name: &str) -> IrType {
+    IrType::TypeVar(name.to_string())
+}
+
+fn vector_of(element: IrType) -> IrType {
+    IrType::Vector(Box::new(element))
+}
+
+fn function(param_types: Vec<IrType>, variadic_param_type: Option<IrType>, return_type: IrType) -> IrType {
+    IrType::Function {
+        param_types,
+        variadic_param_type: variadic_param_type.map(Box::new),
+        return_type: Box::new(return_type),
+    }
+}
+
+/// Signatures of the standard library builtins defined in `runtime/stdlib.rs`.
+///
+/// The converter puts these in its global scope, so references to a builtin
+/// carry its signature and the checker can check calls against it. Collection
+/// builtins are generic over the element type `T` of the vectors they take.
+pub fn builtin_signatures() -> Vec<(&'static str, IrType)> {
+    let collection = IrType::Union(vec![IrType::Vector(Box::new(IrType::Any)), any_map()]);
+    let generic_collection = IrType::Union(vec![vector_of(type_var("T")), any_map()]);
+    let comparable = IrType::Union(vec![IrType::Int, IrType::Float, IrType::String]);
+    let predicate = function(vec![IrType::Any], None, IrType::Bool);
+    let element_predicate = function(vec![type_var("T")], None, IrType::Any);
+    let array = IrType::Array { element_type: Box::new(IrType::Any), shape: None };
+
+    vec![
+        // Arithmetic
+        ("+", function(vec![number()], Some(number()), number())),
+        ("-", function(vec![number()], Some(number()), number())),
+        ("*", function(vec![number()], Some(number()), number())),
+        ("/", function(vec![number()], Some(number()), IrType::Float)),
+        // Comparison
+        ("=", function(vec![IrType::Any], Some(IrType::Any), IrType::Bool)),
+        ("!=", function(vec![IrType::Any], Some(IrType::Any), IrType::Bool)),
+        (">", function(vec![comparable.clone(), comparable.clone()], None, IrType::Bool)),
+        ("<", function(vec![comparable.clone(), comparable.clone()], None, IrType::Bool)),
+        (">=", function(vec![comparable.clone(), comparable.clone()], None, IrType::Bool)),
+        ("<=", function(vec![comparable.clone(), comparable.clone()], None, IrType::Bool)),
+        // Boolean
+        ("and", function(vec![], Some(IrType::Any), IrType::Any)),
+        ("or", function(vec![], Some(IrType::Any), IrType::Any)),
+        ("not", predicate.clone()),
+        // Strings
+        ("str", function(vec![], Some(IrType::Any), IrType::String)),
+        ("string-length", function(vec![IrType::String], None, IrType::Int)),
+        ("substring", function(vec![IrType::String, IrType::Int, IrType::Int], None, IrType::String)),
+        // Collections
+        ("get", function(vec![generic_collection.clone(), IrType::Any, IrType::Any], None, type_var("T"))),
+        ("assoc", function(vec![collection.clone()], Some(IrType::Any), collection)),
+        ("dissoc", function(vec![any_map()], Some(IrType::Any), any_map())),
+        ("count", function(
+            vec![IrType::Union(vec![IrType::Vector(Box::new(IrType::Any)), any_map(), IrType::String])],
+            None,
+            IrType::Int,
+        )),
+        ("conj", function(vec![generic_collection.clone()], Some(type_var("T")), generic_collection.clone())),
+        ("vector", function(vec![], Some(type_var("T")), vector_of(type_var("T")))),
+        ("map", function(vec![], Some(IrType::Any), any_map())),
+        ("map-fn", function(
+            vec![function(vec![type_var("T")], None, type_var("U")), generic_collection.clone()],
+            None,
+            vector_of(type_var("U")),
+        )),
+        ("filter", function(vec![element_predicate.clone(), generic_collection.clone()], None, vector_of(type_var("T")))),
+        ("reduce", function(vec![IrType::Any, IrType::Any], Some(IrType::Any), IrType::Any)),
+        ("sort-by", function(
+            vec![function(vec![type_var("T")], None, comparable.clone()), generic_collection.clone()],
+            None,
+            vector_of(type_var("T")),
+        )),
+        ("some", function(
+            vec![function(vec![type_var("T")], None, type_var("U")), generic_collection.clone()],
+            None,
+            IrType::Union(vec![type_var("U"), IrType::Nil]),
+        )),
+        ("every?", function(vec![element_predicate, generic_collection], None, IrType::Bool)),
+        ("apply", function(vec![IrType::Any], Some(IrType::Any), IrType::Any)),
+        ("array", function(vec![IrType::Vector(Box::new(IrType::Any))], None, array.clone())),
+        // Type predicates
+        ("int?", predicate.clone()),
+        ("float?", predicate.clone()),
+        ("number?", predicate.clone()),
+        ("string?", predicate.clone()),
+        ("bool?", predicate.clone()),
+        ("nil?", predicate.clone()),
+        ("map?", predicate.clone()),
+        ("vector?", predicate.clone()),
+        ("keyword?", predicate.clone()),
+        ("symbol?", predicate.clone()),
+        ("fn?", predicate),
+        // Tools
+        ("tool:log", function(vec![IrType::Any], None, IrType::Nil)),
+        ("tool:print", function(vec![], Some(IrType::Any), IrType::Nil)),
+        ("tool:current-time", function(vec![], None, IrType::String)),
+        ("tool:parse-json", function(vec![IrType::String], None, IrType::Any)),
+        ("tool:serialize-json", function(vec![IrType::Any], None, IrType::String)),
+        ("tool:open-file", function(
+            vec![IrType::String, IrType::Any, IrType::Any],
+            None,
+            IrType::Resource("FileHandle".to_string()),
+        )),
+        ("tool:read-line", function(vec![IrType::Resource("FileHandle".to_string())], None, IrType::Any)),
+        ("tool:write-line", function(
+            vec![IrType::Resource("FileHandle".to_string()), IrType::String],
+            None,
+            IrType::Any,
+        )),
+        ("tool:close-file", function(vec![IrType::Resource("FileHandle".to_string())], None, IrType::Any)),
+        ("tool:get-env", function(vec![IrType::String, IrType::Any], None, IrType::Any)),
+        ("tool:http-fetch", function(vec![IrType::String, IrType::Any], None, IrType::Any)),
+        ("tool:tensor-elementwise-add", function(vec![array.clone(), array.clone()], None, array.clone())),
+        ("tool:tensor-scalar-multiply", function(vec![array.clone(), number()], None, array.clone())),
+        ("tool:get-tensor-summary", function(vec![array], None, any_map())),
+    ]
+}
+
+/// Type of a literal value
+pub fn literal_type(literal: &Literal) -> IrType {
+    match literal {
+        Literal::Integer(_) => IrType::Int,
+        Literal::Float(_) => IrType::Float,
+        Literal::String(_) => IrType::String,
+        Literal::Boolean(_) => IrType::Bool,
+        Literal::Keyword(_) => IrType::Keyword,
+        Literal::Nil => IrType::Nil,
+    }
+}
+
+/// Whether every value of type `sub` can be used where `sup` is expected.
+///
+/// `Any` is compatible in both directions, so unannotated code always checks.
+/// Named types should be resolved first (see `TypeChecker::resolve`).
+pub fn is_subtype(sub: &IrType, sup: &IrType) -> bool {
+    match (sub, sup) {
+        (_, IrType::Any) | (IrType::Any, _) | (IrType::Never, _) => true,
+        // A name left unresolved names a type this tree does not define, e.g. one from a
+        // module that is not loaded, so nothing is known about it; it is treated like `Any`
+        (IrType::TypeRef(_), _) | (_, IrType::TypeRef(_)) => true,
+
+        (IrType::Union(subs), _) => subs.iter().all(|t| is_subtype(t, sup)),
+        (_, IrType::Intersection(sups)) => sups.iter().all(|t| is_subtype(sub, t)),
+        (_, IrType::Union(sups)) => sups.iter().any(|t| is_subtype(sub, t)),
+        (IrType::Intersection(subs), _) => subs.iter().any(|t| is_subtype(t, sup)),
+
+        // Refinements: predicates are known to hold only for literals and narrower refinements.
+        // Other values are checked against them at runtime, when they are bound.
+        (IrType::Refined { base: sub_base, predicates: sub_predicates }, IrType::Refined { base, predicates }) => {
+            is_subtype(sub_base, base) && predicates.iter().all(|p| sub_predicates.contains(p))
+        }
+        (IrType::LiteralValue(literal), IrType::Refined { base, predicates }) => {
+            let value = Value::from(literal);
+            is_subtype(sub, base) && predicates.iter().all(|p| predicate_holds(p, &value))
+        }
+        (_, IrType::Refined { base, .. }) => is_subtype(sub, base),
+        (IrType::Refined { base, .. }, _) => is_subtype(base, sup),
+
+        (IrType::LiteralValue(a), IrType::LiteralValue(b)) => a == b,
+        (IrType::LiteralValue(literal), _) => is_subtype(&literal_type(literal), sup),
+
+        (IrType::Vector(a), IrType::Vector(b)) | (IrType::List(a), IrType::List(b)) => is_subtype(a, b),
+        (IrType::Tuple(a), IrType::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| is_subtype(x, y))
+        }
+        (IrType::Tuple(elements), IrType::Vector(b)) => elements.iter().all(|t| is_subtype(t, b)),
+        (
+            IrType::Array { element_type: sub_element, shape: sub_shape },
+            IrType::Array { element_type, shape },
+        ) => is_subtype(sub_element, element_type) && shape_fits(sub_shape.as_deref(), shape.as_deref()),
+
+        (
+            IrType::Map { entries: sub_entries, wildcard: sub_wildcard },
+            IrType::Map { entries: sup_entries, wildcard: sup_wildcard },
+        ) => {
+            // Every entry the expected type declares must be present (unless optional) and conform
+            let declared = sup_entries.iter().all(|expected| {
+                match sub_entries.iter().find(|entry| entry.key == expected.key) {
+                    Some(entry) => is_subtype(&entry.value_type, &expected.value_type),
+                    None => expected.optional,
+                }
+            });
+            // Any other entry must fit the expected wildcard
+            let extra = match sup_wildcard {
+                Some(wildcard) => {
+                    sub_entries
+                        .iter()
+                        .filter(|entry| sup_entries.iter().all(|e| e.key != entry.key))
+                        .all(|entry| is_subtype(&entry.value_type, wildcard))
+                        && sub_wildcard.as_ref().is_none_or(|w| is_subtype(w, wildcard))
+                }
+                None => true,
+            };
+            declared && extra
+        }
+
+        // A generic function can be used at any instance of its type
+        (IrType::Function { .. }, IrType::Function { .. }) if has_type_variables(sub) => {
+            is_subtype(&substitute(sub, &HashMap::new()), sup)
+        }
+        (
+            IrType::Function { param_types: sub_params, variadic_param_type: sub_variadic, return_type: sub_return },
+            IrType::Function { param_types: sup_params, variadic_param_type: sup_variadic, return_type: sup_return },
+        ) => {
+            // Parameters are contravariant, the return type covariant
+            let params = sup_params.iter().enumerate().all(|(i, expected)| {
+                match sub_params.get(i).or(sub_variadic.as_deref()) {
+                    Some(accepted) => is_subtype(expected, accepted),
+                    None => false,
+                }
+            });
+            let variadic = match (sup_variadic, sub_variadic) {
+                (Some(expected), Some(accepted)) => is_subtype(expected, accepted),
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+            params && variadic && is_subtype(sub_return, sup_return)
+        }
+
+        (IrType::Resource(a), IrType::Resource(b)) => a == b,
+        _ => sub == sup,
+    }
+}
+
+/// Whether an array of shape `sub` may have shape `sup`. Like `Any`, an unknown shape or
+/// dimension is compatible either way; the runtime checks it when the array is bound.
+fn shape_fits(sub: Option<&[Option<usize>]>, sup: Option<&[Option<usize>]>) -> bool {
+    match (sub, sup) {
+        (Some(sub), Some(sup)) => {
+            sub.len() == sup.len()
+                && sub.iter().zip(sup).all(|(a, b)| a.is_none() || b.is_none() || a == b)
+        }
+        _ => true,
+    }
+}
+
+/// Whether a type mentions a type variable
+pub fn has_type_variables(t: &IrType) -> bool {
+    mentions(t, &|part| matches!(part, IrType::TypeVar(_)))
+}
+
+/// Whether `t` or any type it is built from is one `is_part` accepts
+fn mentions(t: &IrType, is_part: &dyn Fn(&IrType) -> bool) -> bool {
+    if is_part(t) {
+        return true;
+    }
+    match t {
+        IrType::Vector(element) | IrType::List(element) => mentions(element, is_part),
+        IrType::Array { element_type, .. } => mentions(element_type, is_part),
+        IrType::Tuple(types) | IrType::Union(types) | IrType::Intersection(types) => {
+            types.iter().any(|t| mentions(t, is_part))
+        }
+        IrType::Refined { base, .. } => mentions(base, is_part),
+        IrType::Map { entries, wildcard } => {
+            entries.iter().any(|entry| mentions(&entry.value_type, is_part))
+                || wildcard.as_deref().is_some_and(|t| mentions(t, is_part))
+        }
+        IrType::Function { param_types, variadic_param_type, return_type } => {
+            param_types.iter().any(|t| mentions(t, is_part))
+                || variadic_param_type.as_deref().is_some_and(|t| mentions(t, is_part))
+                || mentions(return_type, is_part)
+        }
+        _ => false,
+    }
+}
+
+/// Replace the type variables of `t` by their bindings; unbound variables become `Any`
+pub fn substitute(t: &IrType, bindings: &HashMap<String, IrType>) -> IrType {
+    let sub = |t: &IrType| Box::new(substitute(t, bindings));
+    let sub_all = |types: &[IrType]| types.iter().map(|t| substitute(t, bindings)).collect();
+    match t {
+        IrType::TypeVar(name) => bindings.get(name).cloned().unwrap_or(IrType::Any),
+        IrType::Vector(element) => IrType::Vector(sub(element)),
+        IrType::List(element) => IrType::List(sub(element)),
+        IrType::Array { element_type, shape } => IrType::Array { element_type: sub(element_type), shape: shape.clone() },
+        IrType::Tuple(types) => IrType::Tuple(sub_all(types)),
+        IrType::Union(types) => IrType::Union(sub_all(types)),
+        IrType::Intersection(types) => IrType::Intersection(sub_all(types)),
+        IrType::Refined { base, predicates } => IrType::Refined { base: sub(base), predicates: predicates.clone() },
+        IrType::Map { entries, wildcard } => IrType::Map {
+            entries: entries
+                .iter()
+                .map(|entry| IrMapTypeEntry { value_type: substitute(&entry.value_type, bindings), ..entry.clone() })
+                .collect(),
+            wildcard: wildcard.as_deref().map(sub),
+        },
+        IrType::Function { param_types, variadic_param_type, return_type } => IrType::Function {
+            param_types: sub_all(param_types),
+            variadic_param_type: variadic_param_type.as_deref().map(sub),
+            return_type: sub(return_type),
+        },
+        other => other.clone(),
+    }
+}
+
+/// Bind the type variables of `param` to the parts of `arg` they stand for.
+///
+/// A variable that meets several types is bound to their join, so
+/// `(conj [1] "a")` is a `[:vector [:union int string]]`. Conflicts show up
+/// afterwards, when the arguments are checked against the instantiated parameters.
+fn unify(param: &IrType, arg: &IrType, bindings: &mut HashMap<String, IrType>) {
+    match (param, arg) {
+        (IrType::TypeVar(name), _) => {
+            let bound = match bindings.get(name) {
+                Some(previous) => join(previous, arg),
+                None => arg.clone(),
+            };
+            bindings.insert(name.clone(), bound);
+        }
+        // A generic function argument is used at an instance where its own variables are `Any`
+        (_, IrType::Function { .. }) if has_type_variables(arg) => {
+            unify(param, &substitute(arg, &HashMap::new()), bindings)
+        }
+        (_, IrType::Union(members)) if !matches!(param, IrType::Union(_)) => {
+            for member in members {
+                unify(param, member, bindings);
+            }
+        }
+        // Use the first alternative the argument fits, whatever its variables stand for
+        (IrType::Union(alternatives), _) => {
+            let erased = HashMap::new();
+            if let Some(alternative) = alternatives.iter().find(|alt| is_subtype(arg, &substitute(alt, &erased))) {
+                unify(alternative, arg, bindings);
+            }
+        }
+        (IrType::Refined { base, .. }, _) => unify(base, arg, bindings),
+        (_, IrType::Refined { base, .. }) => unify(param, base, bindings),
+        (_, IrType::LiteralValue(literal)) => unify(param, &literal_type(literal), bindings),
+        (IrType::Vector(p), IrType::Vector(a)) | (IrType::List(p), IrType::List(a)) => unify(p, a, bindings),
+        (IrType::Vector(p), IrType::Tuple(elements)) => {
+            for element in elements {
+                unify(p, element, bindings);
+            }
+        }
+        (IrType::Tuple(ps), IrType::Tuple(args)) => {
+            for (p, a) in ps.iter().zip(args) {
+                unify(p, a, bindings);
+            }
+        }
+        (IrType::Array { element_type: p, .. }, IrType::Array { element_type: a, .. }) => unify(p, a, bindings),
+        (
+            IrType::Map { entries: param_entries, wildcard: param_wildcard },
+            IrType::Map { entries: arg_entries, wildcard: arg_wildcard },
+        ) => {
+            for entry in arg_entries {
+                match param_entries.iter().find(|p| p.key == entry.key) {
+                    Some(p) => unify(&p.value_type, &entry.value_type, bindings),
+                    None => {
+                        if let Some(wildcard) = param_wildcard {
+                            unify(wildcard, &entry.value_type, bindings);
+                        }
+                    }
+                }
+            }
+            if let (Some(p), Some(a)) = (param_wildcard, arg_wildcard) {
+                unify(p, a, bindings);
+            }
+        }
+        (
+            IrType::Function { param_types: ps, variadic_param_type: pv, return_type: pr },
+            IrType::Function { param_types: args, variadic_param_type: av, return_type: ar },
+        ) => {
+            for (p, a) in ps.iter().zip(args) {
+                unify(p, a, bindings);
+            }
+            if let (Some(p), Some(a)) = (pv, av) {
+                unify(p, a, bindings);
+            }
+            unify(pr, ar, bindings);
+        }
+        _ => {}
+    }
+}
+
+/// Instantiate a generic function type for a call with arguments of the given types.
+/// Other types are returned unchanged.
+pub fn instantiate(function_type: &IrType, argument_types: &[IrType]) -> IrType {
+    let IrType::Function { param_types, variadic_param_type, .. } = function_type else {
+        return function_type.clone();
+    };
+    if !has_type_variables(function_type) {
+        return function_type.clone();
+    }
+    let mut bindings = HashMap::new();
+    for (i, argument_type) in argument_types.iter().enumerate() {
+        if let Some(param) = param_types.get(i).or(variadic_param_type.as_deref()) {
+            unify(param, argument_type, &mut bindings);
+        }
+    }
+    substitute(function_type, &bindings)
+}
+
+/// Check an IR tree, collecting every type mismatch it contains. Type names may refer to
+/// `types`, types defined with `deftype` outside the tree; the converter already resolves
+/// the names the tree defines itself.
+pub fn typecheck(node: &IrNode, types: &HashMap<String, TypeExpr>) -> Result<(), Vec<IrConversionError>> {
+    let mut checker = TypeChecker::with_types(types.clone());
+    checker.check(node);
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+/// Walks an IR tree and checks each node against the types of its children.
+///
+/// Calls are checked against the callee's function type, annotated bindings
+/// against their initializers (or the values `recur` passes), and lambdas
+/// against their declared return type.
+pub struct TypeChecker {
+    errors: Vec<IrConversionError>,
+    /// Declared types of the bindings of each loop, by loop id
+    loop_annotations: HashMap<NodeId, Vec<Option<IrType>>>,
+    /// Types defined outside the tree, by name
+    types: HashMap<String, TypeExpr>,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        Self::with_types(HashMap::new())
+    }
+
+    /// A checker for trees whose type names may refer to `types`
+    pub fn with_types(types: HashMap<String, TypeExpr>) -> Self {
+        TypeChecker { errors: Vec::new(), loop_annotations: HashMap::new(), types }
+    }
+
+    /// Replace the type names in `t` by the types they name. Names that are not
+    /// defined stay as they are.
+    fn resolve(&self, t: &IrType) -> IrType {
+        if self.types.is_empty() || !mentions(t, &|part| matches!(part, IrType::TypeRef(_))) {
+            return t.clone();
+        }
+        IrConverter::new()
+            .convert_type_annotation(resolve_type(&t.to_type_expr(), &self.types))
+            .unwrap_or_else(|_| t.clone())
+    }
+
+    fn mismatch(&mut self, expected: &IrType, found: &IrType, at: &IrNode, fallback: &IrNode) {
+        self.errors.push(IrConversionError::TypeMismatch {
+            expected: Box::new(expected.clone()),
+            found: Box::new(found.clone()),
+            location: at.source_location().or(fallback.source_location()).cloned(),
+        });
+    }
+
+    /// Check that `value` conforms to `expected`
+    fn expect_node(&mut self, expected: &IrType, value: &IrNode, fallback: &IrNode) {
+        let expected = &self.resolve(expected);
+        match value {
+            // A literal is checked by value, so refinement predicates can be checked too
+            IrNode::Literal { value: literal, ir_type, .. } => {
+                if !is_subtype(&IrType::LiteralValue(literal.clone()), expected) {
+                    self.mismatch(expected, ir_type, value, fallback);
+                }
+            }
+            _ => {
+                if let Some(found) = value.ir_type() {
+                    if !is_subtype(&self.resolve(found), expected) {
+                        self.mismatch(expected, found, value, fallback);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_all(&mut self, nodes: &[IrNode]) {
+        for node in nodes {
+            self.check(node);
+        }
+    }
+
+    /// Check a node and everything below it
+    pub fn check(&mut self, node: &IrNode) {
+        match node {
+            IrNode::Program { forms, .. } => self.check_all(forms),
+            IrNode::Vector { elements, .. } => self.check_all(elements),
+            IrNode::Map { entries, .. } => {
+                for entry in entries {
+                    self.check(&entry.value);
+                }
+            }
+            IrNode::Apply { function, arguments, .. } => {
+                self.check(function);
+                self.check_all(arguments);
+                let argument_types: Vec<IrType> =
+                    arguments.iter().map(|a| a.ir_type().cloned().unwrap_or(IrType::Any)).collect();
+                let function_type = function.ir_type().map(|t| instantiate(t, &argument_types));
+                if let Some(IrType::Function { param_types, variadic_param_type, .. }) = function_type {
+                    for (i, argument) in arguments.iter().enumerate() {
+                        if let Some(expected) = param_types.get(i).or(variadic_param_type.as_deref()) {
+                            self.expect_node(expected, argument, node);
+                        }
+                    }
+                }
+            }
+            IrNode::Arithmetic { operands, ir_type, .. } => {
+                self.check_all(operands);
+                // Int operands are widened in a float node
+                let expected = if *ir_type == IrType::Float { number() } else { ir_type.clone() };
+                for operand in operands {
+                    self.expect_node(&expected, operand, node);
+                }
+            }
+            IrNode::Lambda { params, variadic_param, body, ir_type, .. } => {
+                self.check_all(params);
+                if let Some(variadic) = variadic_param {
+                    self.check(variadic);
+                }
+                self.check_all(body);
+                if let (IrType::Function { return_type, .. }, Some(last)) = (ir_type, body.last()) {
+                    self.expect_node(return_type, last, node);
+                }
+            }
+            IrNode::If { condition, then_branch, else_branch, .. } => {
+                self.check(condition);
+                self.check(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check(else_branch);
+                }
+            }
+            IrNode::Let { bindings, body, .. } => {
+                for binding in bindings {
+                    self.check(&binding.init_expr);
+                    self.check(&binding.pattern);
+                    if let Some(annotation) = &binding.type_annotation {
+                        self.expect_node(annotation, &binding.init_expr, node);
+                    }
+                }
+                self.check_all(body);
+            }
+            IrNode::Do { expressions, .. } => self.check_all(expressions),
+            IrNode::Loop { id, bindings, body, .. } => {
+                for binding in bindings {
+                    self.check(&binding.init_expr);
+                    self.check(&binding.pattern);
+                    if let Some(annotation) = &binding.type_annotation {
+                        self.expect_node(annotation, &binding.init_expr, node);
+                    }
+                }
+                let annotations = bindings.iter().map(|binding| binding.type_annotation.clone()).collect();
+                self.loop_annotations.insert(*id, annotations);
+                self.check_all(body);
+            }
+            IrNode::Recur { loop_id, arguments, .. } => {
+                self.check_all(arguments);
+                let annotations = self.loop_annotations.get(loop_id).cloned().unwrap_or_default();
+                for (annotation, argument) in annotations.iter().zip(arguments) {
+                    if let Some(annotation) = annotation {
+                        self.expect_node(annotation, argument, node);
+                    }
+                }
+            }
+            IrNode::Iterate { clauses, body, .. } => {
+                for clause in clauses {
+                    match clause {
+                        IrIterationClause::Binding { pattern, collection } => {
+                            self.check(collection);
+                            self.check(pattern);
+                        }
+                        IrIterationClause::Range { count, .. } => {
+                            self.check(count);
+                            self.expect_node(&IrType::Int, count, node);
+                        }
+                        IrIterationClause::When(test) => self.check(test),
+                    }
+                }
+                self.check_all(body);
+            }
+            IrNode::Match { expression, clauses, .. } => {
+                self.check(expression);
+                for clause in clauses {
+                    if let Some(guard) = &clause.guard {
+                        self.check(guard);
+                    }
+                    self.check(&clause.body);
+                }
+            }
+            IrNode::TryCatch { try_body, catch_clauses, finally_body, .. } => {
+                self.check_all(try_body);
+                for clause in catch_clauses {
+                    self.check_all(&clause.body);
+                }
+                if let Some(finally_body) = finally_body {
+                    self.check_all(finally_body);
+                }
+            }
+            IrNode::Parallel { bindings, .. } => {
+                for binding in bindings {
+                    self.check(&binding.init_expr);
+                    if let Some(declared) = binding.binding.ir_type() {
+                        self.expect_node(declared, &binding.init_expr, node);
+                    }
+                }
+            }
+            IrNode::WithResource { init_expr, body, .. } => {
+                self.check(init_expr);
+                self.check_all(body);
+            }
+            IrNode::LogStep { values, .. } => self.check_all(values),
+            IrNode::Module { definitions, .. } => self.check_all(definitions),
+            IrNode::FunctionDef { lambda, .. } => self.check(lambda),
+            IrNode::VariableDef { type_annotation, init_expr, .. } => {
+                self.check(init_expr);
+                if let Some(annotation) = type_annotation {
+                    self.expect_node(annotation, init_expr, node);
+                }
+            }
+            IrNode::Param { binding, .. } => self.check(binding),
+            IrNode::Destructure { defaults, .. } => {
+                for default in defaults {
+                    self.check(&default.value);
+                }
+            }
+            IrNode::Task { intent, contracts, plan, .. } => {
+                self.check(intent);
+                self.check(contracts);
+                self.check(plan);
+            }
+            IrNode::Literal { .. }
+            | IrNode::VariableRef { .. }
+            | IrNode::VariableBinding { .. }
+            | IrNode::TypeDef { .. }
+            | IrNode::Import { .. }
+            | IrNode::TaskContextAccess { .. } => {}
+        }
+    }
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::TypePredicate;
+    use crate::ir_converter::IrConverter;
+    use crate::parser::parse_expression;
+
+    fn check_source(source: &str) -> Result<(), Vec<IrConversionError>> {
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        typecheck(&ir, &HashMap::new())
+    }
+
+    #[test]
+    fn test_type_names_are_resolved() {
+        // Defined in the program: the converter resolves the name
+        let pair = "(deftype Pair [:tuple int string])";
+        assert!(check_source(&format!("(do {} ((fn [p :Pair] p) [1 \"a\"]))", pair)).is_ok());
+        assert!(check_source(&format!("(do {} ((fn [p :Pair] p) [1 2]))", pair)).is_err());
+
+        // Defined outside it: the checker resolves the name
+        let mut types = HashMap::new();
+        let positive = TypeExpr::Refined {
+            base: Box::new(TypeExpr::Primitive(crate::ast::PrimitiveType::Int)),
+            predicates: vec![TypePredicate::GreaterThan(Literal::Integer(0))],
+        };
+        types.insert("Positive".to_string(), positive);
+        let check = |source: &str| {
+            let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+            typecheck(&ir, &types)
+        };
+        assert!(check("((fn [n :Positive] n) 5)").is_ok());
+        assert!(check("((fn [n :Positive] n) -5)").is_err());
+        assert!(check("((fn [n :Positive] n) \"5\")").is_err());
+        // A name nothing defines is not known to be wrong
+        assert!(check("((fn [n :Unknown] n) 5)").is_ok());
+    }
+
+    #[test]
+    fn test_subtyping() {
+        let int_or_string = IrType::Union(vec![IrType::Int, IrType::String]);
+        assert!(is_subtype(&IrType::Int, &int_or_string));
+        assert!(!is_subtype(&int_or_string, &IrType::Int));
+        assert!(is_subtype(&IrType::LiteralValue(Literal::Integer(1)), &number()));
+        let positive = IrType::Refined {
+            base: Box::new(IrType::Int),
+            predicates: vec![TypePredicate::GreaterThan(Literal::Integer(0))],
+        };
+        assert!(is_subtype(&IrType::Intersection(vec![IrType::Int, positive]), &IrType::Int));
+        assert!(!is_subtype(&IrType::Int, &IrType::Intersection(vec![IrType::Int, IrType::String])));
+        assert!(is_subtype(&IrType::Vector(Box::new(IrType::Never)), &IrType::Vector(Box::new(IrType::String))));
+
+        let user = |entries: Vec<(&str, IrType, bool)>| IrType::Map {
+            entries: entries
+                .into_iter()
+                .map(|(key, value_type, optional)| IrMapTypeEntry {
+                    key: crate::ast::Keyword(key.to_string()),
+                    value_type,
+                    optional,
+                })
+                .collect(),
+            wildcard: None,
+        };
+        let expected = user(vec![("id", IrType::Int, false), ("name", IrType::String, true)]);
+        assert!(is_subtype(&user(vec![("id", IrType::Int, false), ("age", IrType::Int, false)]), &expected));
+        assert!(!is_subtype(&user(vec![("name", IrType::String, false)]), &expected));
+        assert!(!is_subtype(&user(vec![("id", IrType::String, false)]), &expected));
+    }
+
+    #[test]
+    fn test_builtin_calls_are_checked() {
+        assert!(check_source("(+ 1 2.5)").is_ok());
+        assert!(check_source("(str \"a\" 1 :b)").is_ok());
+        assert!(check_source("(count [1 2])").is_ok());
+
+        let errors = check_source("(do 1 (+ \"a\" 1))").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            IrConversionError::TypeMismatch { expected, found, location } => {
+                assert_eq!(**expected, number());
+                assert_eq!(**found, IrType::String);
+                let location = location.as_ref().expect("mismatch should carry a location");
+                assert_eq!((location.line, location.column), (1, 10));
+            }
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+        assert!(check_source("(string-length 5)").is_err());
+    }
+
+    #[test]
+    fn test_annotations_are_checked() {
+        assert!(check_source("((fn [x :int] (+ x 1)) 2)").is_ok());
+        assert!(check_source("((fn [x :int] x) \"two\")").is_err());
+        assert!(check_source("(fn [x] :int \"not an int\")").is_err());
+        assert!(check_source("(parallel [a :string (+ 1 2)])").is_err());
+        assert!(check_source("(def limit :int \"ten\")").is_err());
+        // Unannotated code is left alone
+        assert!(check_source("(let [f (fn [x] x)] (f \"a\"))").is_ok());
+        // Vector literals fit tuple types of their length as well as vector types
+        assert!(check_source("((fn [p :[:tuple int string]] p) [1 \"a\"])").is_ok());
+        assert!(check_source("((fn [p :[:tuple int string]] p) [1 2])").is_err());
+        assert!(check_source("((fn [p :[:tuple int string]] p) [1])").is_err());
+        assert!(check_source("((fn [xs :[:vector int]] xs) [1 2])").is_ok());
+    }
+
+    #[test]
+    fn test_refinements_are_checked_on_literals() {
+        let positive = "(fn [n :[:and int [:> 0]]] n)";
+        assert!(check_source(&format!("({} 5)", positive)).is_ok());
+        assert!(check_source(&format!("({} -5)", positive)).is_err());
+        assert!(check_source(&format!("({} \"5\")", positive)).is_err());
+        // Computed values are checked when they are bound
+        assert!(check_source(&format!("({} (- 0 5))", positive)).is_ok());
+        assert!(check_source("(def name :[:and string [:min-length 1]] \"\")").is_err());
+
+        let refined = |predicates| IrType::Refined { base: Box::new(IrType::Int), predicates };
+        let positive = refined(vec![TypePredicate::GreaterThan(Literal::Integer(0))]);
+        let small_positive = refined(vec![
+            TypePredicate::GreaterThan(Literal::Integer(0)),
+            TypePredicate::LessThan(Literal::Integer(10)),
+        ]);
+        assert!(is_subtype(&small_positive, &positive));
+        assert!(!is_subtype(&positive, &small_positive));
+        assert!(is_subtype(&positive, &IrType::Int));
+    }
+
+    #[test]
+    fn test_named_types_are_resolved() {
+        let positive = "(deftype Positive [:and int [:> 0]])";
+        assert!(check_source(&format!("(do {} ((fn [n :Positive] n) 5))", positive)).is_ok());
+        assert!(check_source(&format!("(do {} ((fn [n :Positive] n) -5))", positive)).is_err());
+
+        let user = "(deftype User [:map [:id string] [:name string]])";
+        assert!(check_source(&format!("(do {} (def ada :User {{:id \"u-1\" :name \"Ada\"}}))", user)).is_ok());
+        assert!(check_source(&format!("(do {} (def ada :User {{:id 1 :name \"Ada\"}}))", user)).is_err());
+        // Named types can be built from other named types
+        let team = "(deftype Team [:vector User])";
+        assert!(check_source(&format!("(do {} {} (def team :Team [{{:id \"u-1\"}}]))", user, team)).is_err());
+
+        let convert = |source: &str| IrConverter::new().convert(&parse_expression(source).unwrap());
+        assert!(convert("(fn [x] (deftype Local int) x)").is_err());
+        assert!(convert("(deftype int string)").is_err());
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let first_of = "(fn [xs :[:vector T]] :T (get xs 0))";
+        assert!(check_source(&format!("(+ ({} [1 2]) 1)", first_of)).is_ok());
+        assert!(check_source(&format!("(+ ({} [\"a\"]) 1)", first_of)).is_err());
+        // Inside a generic function its type variables stand for one unknown type
+        assert!(check_source("(fn [x :T] :T 1)").is_err());
+        assert!(check_source("(fn [x :T] (+ x 1))").is_err());
+
+        assert!(check_source("(+ (get [1 2] 0) 1)").is_ok());
+        assert!(check_source("(+ (get [\"a\"] 0) 1)").is_err());
+        assert!(check_source("(map-fn (fn [x :int] (+ x 1)) [1 2])").is_ok());
+        assert!(check_source("(map-fn (fn [x :int] (+ x 1)) [\"a\"])").is_err());
+        assert!(check_source("(map-fn (fn [x :T] x) [1 2])").is_ok());
+        assert!(check_source("(filter (fn [x :int] (> x 1)) [1 2])").is_ok());
+        assert!(check_source("(filter (fn [x :int] (> x 1)) [\"a\"])").is_err());
+        assert!(check_source("(+ (reduce + 0 [1 2]) 1)").is_ok());
+        assert!(check_source("(sort-by (fn [s :string] (string-length s)) [\"ab\" \"c\"])").is_ok());
+
+        let identity = function(vec![type_var("T")], None, type_var("T"));
+        assert!(is_subtype(&identity, &function(vec![IrType::Int], None, IrType::Int)));
+        assert_eq!(
+            instantiate(&identity, &[IrType::String]),
+            function(vec![IrType::String], None, IrType::String)
+        );
+        let convert = |source: &str| IrConverter::new().convert(&parse_expression(source).unwrap());
+        assert!(convert("(deftype T int)").is_err());
+    }
+
+    #[test]
+    fn test_array_shapes() {
+        let array = |element: IrType, shape: Option<Vec<Option<usize>>>| IrType::Array { element_type: Box::new(element), shape };
+        let matrix = array(IrType::Float, Some(vec![Some(2), Some(3)]));
+        assert!(is_subtype(&matrix, &array(IrType::Float, Some(vec![None, Some(3)]))));
+        assert!(is_subtype(&matrix, &array(IrType::Float, None)));
+        assert!(!is_subtype(&matrix, &array(IrType::Float, Some(vec![Some(3), Some(2)]))));
+        assert!(!is_subtype(&matrix, &array(IrType::Float, Some(vec![Some(2)]))));
+        assert!(!is_subtype(&matrix, &array(IrType::Int, None)));
+        // Unknown shapes are checked when the array is bound
+        assert!(is_subtype(&array(IrType::Float, None), &matrix));
+
+        assert!(check_source("(def m :[:array float [2 ?]] (array [[1.0] [2.0]]))").is_ok());
+        assert!(check_source("(tool:tensor-scalar-multiply (array [1 2]) \"x\")").is_err());
+    }
+}
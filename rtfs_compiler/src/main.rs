@@ -1,8 +1,14 @@
 mod ast; // Declare the ast module
+mod desugar; // Expansion of the conditional and threading forms
 pub mod parser; // Declare the parser module (now a directory)
 pub mod runtime; // Declare the runtime module
 mod ir; // Declare the IR module
 mod ir_converter; // Declare the IR converter module
+mod ir_typecheck; // Static type checker over the IR
+mod ir_inference; // Local type inference over the IR
+mod ir_lifetimes; // Static resource-lifetime checker over the IR
+mod match_analysis; // Exhaustiveness and reachability checks for match expressions
+mod ir_specialize; // Type-directed specialization of builtin arithmetic
 mod ir_optimizer; // Declare the IR optimizer module
 mod enhanced_ir_optimizer; // Enhanced IR optimizer with advanced passes (Step 2)
 mod enhanced_ir_demo; // Enhanced IR optimizer demonstration (Step 2)
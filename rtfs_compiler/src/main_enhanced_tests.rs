@@ -1,9 +1,15 @@
 // Enhanced integration test runner demonstrating Step 1 completion
 mod ast; 
+mod desugar; 
 pub mod parser; 
 pub mod runtime; 
 mod ir; 
 mod ir_converter; 
+mod ir_typecheck; 
+mod ir_inference; 
+mod ir_lifetimes; 
+mod match_analysis; 
+mod ir_specialize; 
 mod ir_optimizer; 
 mod integration_tests; 
 
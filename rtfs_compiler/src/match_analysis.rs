@@ -0,0 +1,356 @@
+// Match Analysis
+// Warns about non-exhaustive matches and unreachable match clauses before a plan runs
+
+use std::fmt;
+use crate::ast::{Expression, Keyword, Literal, MatchExpr, MatchPattern, TypePredicate};
+use crate::ir::{IrNode, IrPattern, IrType, SourceLocation};
+use crate::ir_converter::IrConverter;
+use crate::ir_typecheck::is_subtype;
+use crate::parser::printer::print_literal;
+
+/// What a match warning is about
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchWarningKind {
+    /// No clause handles these cases, written as patterns, e.g. `[:error _]`
+    NonExhaustive { missing: Vec<String> },
+    /// A clause (counted from 0) that can never be selected, because an earlier
+    /// clause without a guard matches every value
+    Unreachable { clause: usize, shadowed_by: usize },
+}
+
+/// A problem with a `match` found before it runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchWarning {
+    pub kind: MatchWarningKind,
+    pub location: Option<SourceLocation>,
+}
+
+impl fmt::Display for MatchWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            MatchWarningKind::NonExhaustive { missing } => {
+                write!(f, "non-exhaustive match, no clause handles {}", missing.join(", "))?
+            }
+            MatchWarningKind::Unreachable { clause, shadowed_by } => write!(
+                f,
+                "unreachable match clause {}: clause {} matches every value",
+                clause + 1,
+                shadowed_by + 1
+            )?,
+        }
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check every `match` in a plan, with scrutinee types when the plan converts to IR
+pub fn check_plan(plan: &Expression) -> Vec<MatchWarning> {
+    match IrConverter::new().convert(plan) {
+        Ok(ir) => check_ir(&ir),
+        Err(_) => check_expression(plan),
+    }
+}
+
+/// Check every `match` in an expression.
+///
+/// Scrutinee types are not known before IR conversion, so only matches on
+/// `[:ok v]`/`[:error e]` results and on literals are checked for exhaustiveness.
+pub fn check_expression(expr: &Expression) -> Vec<MatchWarning> {
+    let mut warnings = Vec::new();
+    walk_expression(expr, None, &mut warnings);
+    warnings
+}
+
+/// Check every `match` in an IR tree, using the inferred type of each scrutinee
+pub fn check_ir(node: &IrNode) -> Vec<MatchWarning> {
+    let mut warnings = Vec::new();
+    walk_ir(node, &mut warnings);
+    warnings
+}
+
+/// Check the clauses of one match against the type of the value it matches on
+pub fn check_match_expr(match_expr: &MatchExpr, scrutinee_type: &IrType, location: Option<SourceLocation>) -> Vec<MatchWarning> {
+    let clauses: Vec<(Pat, bool)> = match_expr
+        .clauses
+        .iter()
+        .map(|clause| (Pat::from_match_pattern(&clause.pattern), clause.guard.is_some()))
+        .collect();
+    let clause_locations: Vec<Option<SourceLocation>> = match_expr
+        .clauses
+        .iter()
+        .map(|clause| clause.pattern.span().map(SourceLocation::from))
+        .collect();
+    let mut warnings = check_clauses(&clauses, scrutinee_type, location.clone());
+    // Point unreachable clauses at their own pattern when it is known
+    for warning in &mut warnings {
+        if let MatchWarningKind::Unreachable { clause, .. } = warning.kind {
+            warning.location = clause_locations[clause].clone().or(location.clone());
+        }
+    }
+    warnings
+}
+
+fn walk_expression(expr: &Expression, location: Option<SourceLocation>, out: &mut Vec<MatchWarning>) {
+    if let Expression::Spanned(inner, span) = expr {
+        return walk_expression(inner, Some(SourceLocation::from(span.as_ref())), out);
+    }
+    if let Expression::Match(match_expr) = expr {
+        let scrutinee_type = match match_expr.expression.unspanned() {
+            Expression::Literal(literal) => IrType::LiteralValue(literal.clone()),
+            _ => IrType::Any,
+        };
+        out.extend(check_match_expr(match_expr, &scrutinee_type, location));
+    }
+    for child in expr.children() {
+        walk_expression(child, None, out);
+    }
+}
+
+fn walk_ir(node: &IrNode, out: &mut Vec<MatchWarning>) {
+    if let IrNode::Match { expression, clauses, source_location, .. } = node {
+        let clauses: Vec<(Pat, bool)> = clauses
+            .iter()
+            .map(|clause| (Pat::from_ir_pattern(&clause.pattern), clause.guard.is_some()))
+            .collect();
+        let scrutinee_type = expression.ir_type().cloned().unwrap_or(IrType::Any);
+        out.extend(check_clauses(&clauses, &scrutinee_type, source_location.clone()));
+    }
+    for child in node.children() {
+        walk_ir(child, out);
+    }
+}
+
+/// A pattern reduced to what matters for coverage
+#[derive(Debug, Clone, PartialEq)]
+enum Pat {
+    /// Matches every value: `_`, a symbol, or a type pattern for `any`
+    Any,
+    Literal(Literal),
+    Vector { elements: Vec<Pat>, rest: bool },
+    Type(IrType),
+    /// A pattern whose coverage is not tracked, e.g. a map pattern
+    Other,
+}
+
+impl Pat {
+    fn from_match_pattern(pattern: &MatchPattern) -> Pat {
+        match pattern {
+            MatchPattern::Spanned(inner, _) => Pat::from_match_pattern(inner),
+            MatchPattern::Wildcard | MatchPattern::Symbol(_) => Pat::Any,
+            MatchPattern::As(_, inner) => Pat::from_match_pattern(inner),
+            MatchPattern::Literal(literal) => Pat::Literal(literal.clone()),
+            MatchPattern::Keyword(keyword) => Pat::Literal(Literal::Keyword(keyword.clone())),
+            MatchPattern::Vector { elements, rest } => Pat::Vector {
+                elements: elements.iter().map(Pat::from_match_pattern).collect(),
+                rest: rest.is_some(),
+            },
+            MatchPattern::Type(type_expr, _) => match IrConverter::new().convert_type_annotation(type_expr.clone()) {
+                Ok(IrType::Any) => Pat::Any,
+                Ok(ir_type) => Pat::Type(ir_type),
+                Err(_) => Pat::Other,
+            },
+            MatchPattern::Map { .. } => Pat::Other,
+        }
+    }
+
+    fn from_ir_pattern(pattern: &IrPattern) -> Pat {
+        match pattern {
+            IrPattern::Wildcard | IrPattern::Variable { .. } | IrPattern::Type(IrType::Any) => Pat::Any,
+            IrPattern::As { pattern, .. } => Pat::from_ir_pattern(pattern),
+            IrPattern::Literal(literal) => Pat::Literal(literal.clone()),
+            IrPattern::Vector { elements, rest } => Pat::Vector {
+                elements: elements.iter().map(Pat::from_ir_pattern).collect(),
+                rest: rest.is_some(),
+            },
+            IrPattern::Type(ir_type) => Pat::Type(ir_type.clone()),
+            IrPattern::Map { .. } => Pat::Other,
+        }
+    }
+
+    /// Whether this pattern matches every value of type `t`
+    fn covers(&self, t: &IrType) -> bool {
+        match (self, t) {
+            (Pat::Any, _) => true,
+            // Unknown types are not known to fit a type pattern
+            (Pat::Type(_), IrType::Any | IrType::TypeRef(_)) | (Pat::Type(IrType::TypeRef(_)), _) => false,
+            (Pat::Type(pattern_type), _) => is_subtype(t, pattern_type),
+            (Pat::Literal(literal), IrType::LiteralValue(value)) => literal == value,
+            (Pat::Literal(Literal::Nil), IrType::Nil) => true,
+            (Pat::Vector { elements, rest }, IrType::Tuple(types)) => {
+                let arity_fits = if *rest { elements.len() <= types.len() } else { elements.len() == types.len() };
+                arity_fits && elements.iter().zip(types).all(|(element, t)| element.covers(t))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this pattern destructures a tool result such as `[:ok v]` or `[:error e]`
+    fn is_result_shaped(&self) -> bool {
+        matches!(self, Pat::Vector { elements, .. }
+            if matches!(elements.first(), Some(Pat::Literal(Literal::Keyword(Keyword(tag)))) if tag == "ok" || tag == "error"))
+    }
+}
+
+/// The type of a tool result: `[:ok value]` or `[:error error]`
+fn result_type() -> IrType {
+    let tagged = |tag: &str| IrType::Tuple(vec![IrType::LiteralValue(Literal::Keyword(Keyword(tag.to_string()))), IrType::Any]);
+    IrType::Union(vec![tagged("ok"), tagged("error")])
+}
+
+/// The finitely many cases that make up a type, if it has them
+fn cases(t: &IrType) -> Option<Vec<IrType>> {
+    match t {
+        IrType::Union(types) => Some(types.clone()),
+        IrType::Bool => Some(vec![
+            IrType::LiteralValue(Literal::Boolean(true)),
+            IrType::LiteralValue(Literal::Boolean(false)),
+        ]),
+        IrType::Refined { base, predicates } => predicates
+            .iter()
+            .find_map(|predicate| match predicate {
+                TypePredicate::OneOf(options) => Some(options.iter().cloned().map(IrType::LiteralValue).collect()),
+                _ => None,
+            })
+            .or_else(|| cases(base)),
+        // A tuple splits on the first element that has cases
+        IrType::Tuple(types) => types.iter().enumerate().find_map(|(i, element)| {
+            cases(element).map(|element_cases| {
+                element_cases
+                    .into_iter()
+                    .map(|case| {
+                        let mut split = types.clone();
+                        split[i] = case;
+                        IrType::Tuple(split)
+                    })
+                    .collect()
+            })
+        }),
+        _ => None,
+    }
+}
+
+/// The cases of `t` that no pattern covers
+fn missing_cases(patterns: &[&Pat], t: &IrType) -> Vec<IrType> {
+    if patterns.iter().any(|pattern| pattern.covers(t)) {
+        return Vec::new();
+    }
+    match cases(t) {
+        Some(cases) => cases.iter().flat_map(|case| missing_cases(patterns, case)).collect(),
+        None => vec![t.clone()],
+    }
+}
+
+/// Write a missing case the way a clause handling it would
+fn case_pattern(t: &IrType) -> String {
+    match t {
+        IrType::Any => "_".to_string(),
+        IrType::LiteralValue(literal) => print_literal(literal),
+        IrType::Tuple(types) => format!("[{}]", types.iter().map(case_pattern).collect::<Vec<_>>().join(" ")),
+        other => other.to_string(),
+    }
+}
+
+/// Clauses are patterns paired with whether they have a guard
+fn check_clauses(clauses: &[(Pat, bool)], scrutinee_type: &IrType, location: Option<SourceLocation>) -> Vec<MatchWarning> {
+    let mut warnings = Vec::new();
+
+    let mut catch_all = None;
+    for (i, (pattern, guarded)) in clauses.iter().enumerate() {
+        if let Some(shadowed_by) = catch_all {
+            warnings.push(MatchWarning {
+                kind: MatchWarningKind::Unreachable { clause: i, shadowed_by },
+                location: location.clone(),
+            });
+        } else if !guarded && *pattern == Pat::Any {
+            catch_all = Some(i);
+        }
+    }
+
+    // Only types with finitely many cases can be checked: results, unions, literals and bools
+    let scrutinee_type = match scrutinee_type {
+        IrType::Any | IrType::TypeRef(_) if clauses.iter().any(|(pattern, _)| pattern.is_result_shaped()) => result_type(),
+        other => other.clone(),
+    };
+    let checkable = cases(&scrutinee_type).is_some() || matches!(scrutinee_type, IrType::LiteralValue(_));
+    // Guarded clauses may decline any value, so they cover nothing
+    let unguarded: Vec<&Pat> = clauses.iter().filter(|(_, guarded)| !guarded).map(|(pattern, _)| pattern).collect();
+    let missing = missing_cases(&unguarded, &scrutinee_type);
+    if checkable && !missing.is_empty() {
+        warnings.push(MatchWarning {
+            kind: MatchWarningKind::NonExhaustive { missing: missing.iter().map(case_pattern).collect() },
+            location,
+        });
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    fn ast_warnings(source: &str) -> Vec<String> {
+        check_expression(&parse_expression(source).unwrap()).iter().map(|w| w.to_string()).collect()
+    }
+
+    fn ir_warnings(source: &str) -> Vec<String> {
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        // IR locations cover the whole match, so leave them out
+        check_ir(&ir)
+            .into_iter()
+            .map(|w| MatchWarning { location: None, ..w }.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_result_matches_need_both_branches() {
+        let missing_error = "(fn [f] (match (tool:read-line f) [:ok line] line))";
+        assert_eq!(ast_warnings(missing_error), vec!["non-exhaustive match, no clause handles [:error _] at 1:9"]);
+        assert_eq!(ir_warnings(missing_error), vec!["non-exhaustive match, no clause handles [:error _]"]);
+        assert_eq!(
+            ast_warnings("(match r [:error e] nil)"),
+            vec!["non-exhaustive match, no clause handles [:ok _] at 1:1"]
+        );
+        assert!(ast_warnings("(match r [:ok v] v [:error e] nil)").is_empty());
+        assert!(ast_warnings("(match r [:ok v] v _ nil)").is_empty());
+        // A guarded branch may not handle every error
+        assert_eq!(ast_warnings("(match r [:ok v] v [:error e] when (retry? e) nil)").len(), 1);
+        // Nested matches are checked too
+        assert_eq!(ast_warnings("(do (let [x (match r [:ok v] v)] x))").len(), 1);
+    }
+
+    #[test]
+    fn test_union_and_literal_types_need_every_case() {
+        let level = "(fn [level :[:union [:val :low] [:val :high] int]] (match level :low 0 :high 1))";
+        assert_eq!(ir_warnings(level), vec!["non-exhaustive match, no clause handles int"]);
+        let one_of = "(fn [level :[:and keyword [:one-of :low :mid :high]]] (match level :low 0 :high 1))";
+        assert_eq!(ir_warnings(one_of), vec!["non-exhaustive match, no clause handles :mid"]);
+        assert!(ir_warnings("(fn [n :[:union [:val :a] nil]] (match n :a 0 nil 1))").is_empty());
+        assert_eq!(ir_warnings("(fn [flag :bool] (match flag true 1))"), vec!["non-exhaustive match, no clause handles false"]);
+        assert!(ir_warnings("(fn [flag :bool] (match flag true 1 false 0))").is_empty());
+        // Open types such as int are not checked
+        assert!(ir_warnings("(fn [n :int] (match n 1 :one))").is_empty());
+        // Named types are checked through their definitions
+        let named = "(do (deftype Level [:union [:val :low] [:val :high]]) (fn [level :Level] (match level :low 0)))";
+        assert_eq!(ir_warnings(named), vec!["non-exhaustive match, no clause handles :high"]);
+        // Type patterns cover the members of a union
+        assert!(ir_warnings("(fn [x :[:union int string]] (match x :int 0 :string 1))").is_empty());
+        assert_eq!(
+            ir_warnings("(fn [x :[:union int string]] (match x :int 0))"),
+            vec!["non-exhaustive match, no clause handles string"]
+        );
+    }
+
+    #[test]
+    fn test_clauses_after_a_catch_all_are_unreachable() {
+        assert_eq!(
+            ast_warnings("(match x\n  _ 0\n  1 :one)"),
+            vec!["unreachable match clause 2: clause 1 matches every value at 3:3"]
+        );
+        assert_eq!(ir_warnings("(fn [x] (match x y 0 [a] 1 _ 2))").len(), 2);
+        // A guarded catch-all can decline, so later clauses stay reachable
+        assert!(ast_warnings("(match x y when (> y 0) 0 _ 1)").is_empty());
+    }
+}
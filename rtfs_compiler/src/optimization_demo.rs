@@ -167,10 +167,10 @@ fn analyze_ir_recursive(node: &IrNode, stats: &mut IRComplexityStats) {
         IrNode::Lambda { params, body, .. } => {
             stats.total_operations += params.len() + body.len(); // Function definition cost
             
-            for param in params {
+            for param in params.iter() {
                 analyze_ir_recursive(param, stats);
             }
-            for expr in body {
+            for expr in body.iter() {
                 analyze_ir_recursive(expr, stats);
             }
         }
@@ -1,8 +1,41 @@
 use super::utils::unescape;
 use super::PestParseError; // Added for Result return types
 use super::Rule;
-use crate::ast::{Keyword, Literal, MapDestructuringEntry, MapKey, MapMatchEntry, MatchPattern, Pattern, Symbol}; // Added match-related types
+use super::expressions::build_expression;
+use crate::ast::{DefTypeExpr, Keyword, Literal, MapDestructuringEntry, MapKey, MapMatchEntry, MatchPattern, Pattern, Span, Symbol}; // Added match-related types
 use pest::iterators::Pair;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// --- Source Spans ---
+
+thread_local! {
+    // File name recorded in spans built while a `parse_with_file` call is active
+    static SOURCE_FILE: RefCell<Option<Rc<str>>> = const { RefCell::new(None) };
+}
+
+// Run `f` with `file` as the source file recorded in spans
+pub(super) fn with_source_file<T>(file: &str, f: impl FnOnce() -> T) -> T {
+    let previous = SOURCE_FILE.with(|current| current.replace(Some(Rc::from(file))));
+    let result = f();
+    SOURCE_FILE.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+pub(super) fn build_span(pair: &Pair<Rule>) -> Span {
+    let span = pair.as_span();
+    let (line, column) = span.start_pos().line_col();
+    let (end_line, end_column) = span.end_pos().line_col();
+    Span {
+        file: SOURCE_FILE.with(|current| current.borrow().clone()),
+        start: span.start(),
+        end: span.end(),
+        line,
+        column,
+        end_line,
+        end_column,
+    }
+}
 
 // --- Helper Builders ---
 
@@ -128,6 +161,19 @@ fn build_map_destructuring_parts(
                     }
                     
                     entries.push(MapDestructuringEntry::Keys(symbols));
+                } else if first_token.as_rule() == Rule::or_entry {
+                    // Handle or_entry rule: alternating symbols and default expressions
+                    let or_entry_pair = entry_inner.next().unwrap();
+                    let mut or_inner = or_entry_pair.into_inner();
+                    let mut defaults = Vec::new();
+                    while let Some(symbol_pair) = or_inner.next() {
+                        let value_pair = or_inner.next().ok_or_else(|| {
+                            PestParseError::MissingToken("default value in :or entry".to_string())
+                        })?;
+                        defaults.push((build_symbol(symbol_pair)?, build_expression(value_pair)?));
+                    }
+
+                    entries.push(MapDestructuringEntry::Defaults(defaults));
                 } else {
                     // Regular map_key ~ binding_pattern
                     let key_token_pair = entry_inner.next().ok_or_else(|| {
@@ -300,6 +346,11 @@ fn build_vector_destructuring_parts(
 }
 
 pub(super) fn build_pattern(pair: Pair<Rule>) -> Result<Pattern, PestParseError> {
+    let span = build_span(&pair);
+    build_unspanned_pattern(pair).map(|pattern| pattern.with_span(span))
+}
+
+fn build_unspanned_pattern(pair: Pair<Rule>) -> Result<Pattern, PestParseError> {
     let actual_pair = match pair.as_rule() {
         Rule::binding_pattern | Rule::match_pattern => pair
             .into_inner()
@@ -339,6 +390,20 @@ pub(super) fn build_pattern(pair: Pair<Rule>) -> Result<Pattern, PestParseError>
 
 // Build match pattern for match expressions
 pub(super) fn build_match_pattern(pair: Pair<Rule>) -> Result<MatchPattern, PestParseError> {
+    let span = build_span(&pair);
+    build_unspanned_match_pattern(pair).map(|pattern| pattern.with_span(span))
+}
+
+// The symbol of a `& rest` binding in a vector or map match pattern
+fn build_match_rest(pair: Pair<Rule>) -> Result<Symbol, PestParseError> {
+    let symbol = pair
+        .into_inner()
+        .find(|p| p.as_rule() == Rule::symbol)
+        .ok_or_else(|| PestParseError::MissingToken("symbol in match_rest_binding".to_string()))?;
+    build_symbol(symbol)
+}
+
+fn build_unspanned_match_pattern(pair: Pair<Rule>) -> Result<MatchPattern, PestParseError> {
     let actual_pair = match pair.as_rule() {
         Rule::match_pattern => pair
             .into_inner()
@@ -348,143 +413,80 @@ pub(super) fn build_match_pattern(pair: Pair<Rule>) -> Result<MatchPattern, Pest
     };
 
     match actual_pair.as_rule() {
-        Rule::literal => Ok(MatchPattern::Literal(build_literal(actual_pair)?)),
+        Rule::literal => match build_literal(actual_pair.clone())? {
+            // A keyword naming a builtin type, such as `:int`, matches the values of that type
+            Literal::Keyword(keyword) if DefTypeExpr::RESERVED_NAMES.contains(&keyword.0.as_str()) => {
+                let keyword_pair = actual_pair.into_inner().next().ok_or_else(|| {
+                    PestParseError::MissingToken("keyword in literal".to_string())
+                })?;
+                Ok(MatchPattern::Type(super::types::build_type_expr(keyword_pair)?, None))
+            }
+            literal => Ok(MatchPattern::Literal(literal)),
+        },
         Rule::symbol => Ok(MatchPattern::Symbol(build_symbol(actual_pair)?)),
         Rule::keyword => Ok(MatchPattern::Keyword(build_keyword(actual_pair)?)),
         Rule::wildcard => Ok(MatchPattern::Wildcard),
-        Rule::type_expr => {
-            // For type expressions, we don't bind to a symbol in basic matching
-            // More complex type matching with binding would need different syntax
-            Ok(MatchPattern::Type(super::types::build_type_expr(actual_pair)?, None))
-        }
+        // `type_expr` is silent, so type patterns arrive as the rule of the type itself.
+        // A type pattern binds nothing; `(:as name type)` binds the matched value.
+        Rule::vector_type
+        | Rule::map_type
+        | Rule::tuple_type
+        | Rule::function_type
+        | Rule::resource_type
+        | Rule::union_type
+        | Rule::intersection_type
+        | Rule::literal_type
+        | Rule::array_type => Ok(MatchPattern::Type(super::types::build_type_expr(actual_pair)?, None)),
         Rule::vector_match_pattern => {
             let mut elements = Vec::new();
             let mut rest: Option<Symbol> = None;
-            let mut inner_pairs = actual_pair.into_inner().peekable();
-            
-            while let Some(p_peek) = inner_pairs.peek() {
-                // Skip whitespace and comments
-                if p_peek.as_rule() == Rule::WHITESPACE || p_peek.as_rule() == Rule::COMMENT {
-                    inner_pairs.next();
-                    continue;
-                }
-                
-                // Check for rest pattern "&"
-                if p_peek.as_str() == "&" {
-                    inner_pairs.next(); // Consume "&"
-                    
-                    // Skip whitespace after "&"
-                    while let Some(ws_peek) = inner_pairs.peek() {
-                        if ws_peek.as_rule() == Rule::WHITESPACE || ws_peek.as_rule() == Rule::COMMENT {
-                            inner_pairs.next();
-                        } else {
-                            break;
-                        }
-                    }
-                    
-                    let sym_pair = inner_pairs.next().ok_or_else(|| {
-                        PestParseError::InvalidInput(
-                            "Expected symbol after & in vector match pattern".to_string(),
-                        )
-                    })?;
-                    
-                    if sym_pair.as_rule() != Rule::symbol {
-                        return Err(PestParseError::UnexpectedRule {
-                            expected: "symbol".to_string(),
-                            found: format!("{:?}", sym_pair.as_rule()),
-                            rule_text: sym_pair.as_str().to_string(),
-                        });
-                    }
-                    
-                    rest = Some(build_symbol(sym_pair)?);
-                    break;
+            for p in actual_pair.into_inner() {
+                match p.as_rule() {
+                    Rule::WHITESPACE | Rule::COMMENT => {}
+                    Rule::match_rest_binding => rest = Some(build_match_rest(p)?),
+                    _ => elements.push(build_match_pattern(p)?),
                 }
-                
-                // Otherwise, it's an element pattern
-                let p = inner_pairs.next().unwrap();
-                elements.push(build_match_pattern(p)?);
             }
-            
             Ok(MatchPattern::Vector { elements, rest })
         }
         Rule::map_match_pattern => {
             let mut entries = Vec::new();
             let mut rest: Option<Symbol> = None;
-            let mut inner_pairs = actual_pair.into_inner().peekable();
-            
-            while let Some(p_peek) = inner_pairs.peek() {
-                // Skip whitespace and comments
-                if p_peek.as_rule() == Rule::WHITESPACE || p_peek.as_rule() == Rule::COMMENT {
-                    inner_pairs.next();
-                    continue;
-                }
-                
-                // Check for rest pattern "&"
-                if p_peek.as_str() == "&" {
-                    inner_pairs.next(); // Consume "&"
-                    
-                    // Skip whitespace after "&"
-                    while let Some(ws_peek) = inner_pairs.peek() {
-                        if ws_peek.as_rule() == Rule::WHITESPACE || ws_peek.as_rule() == Rule::COMMENT {
-                            inner_pairs.next();
-                        } else {
-                            break;
-                        }
+            for p in actual_pair.into_inner() {
+                match p.as_rule() {
+                    Rule::WHITESPACE | Rule::COMMENT => {}
+                    Rule::match_rest_binding => rest = Some(build_match_rest(p)?),
+                    Rule::map_match_pattern_entry => {
+                        let mut entry_inner = p.into_inner();
+
+                        let key_pair = entry_inner.next().ok_or_else(|| {
+                            PestParseError::MissingToken("map_key in map_match_pattern_entry".to_string())
+                        })?;
+
+                        let value_pattern_pair = entry_inner.next().ok_or_else(|| {
+                            PestParseError::MissingToken("match_pattern in map_match_pattern_entry".to_string())
+                        })?;
+
+                        entries.push(MapMatchEntry {
+                            key: build_map_key(key_pair)?,
+                            pattern: Box::new(build_match_pattern(value_pattern_pair)?),
+                        });
                     }
-                    
-                    let sym_pair = inner_pairs.next().ok_or_else(|| {
-                        PestParseError::InvalidInput(
-                            "Expected symbol after & in map match pattern".to_string(),
-                        )
-                    })?;
-                    
-                    if sym_pair.as_rule() != Rule::symbol {
+                    other => {
                         return Err(PestParseError::UnexpectedRule {
-                            expected: "symbol".to_string(),
-                            found: format!("{:?}", sym_pair.as_rule()),
-                            rule_text: sym_pair.as_str().to_string(),
-                        });
+                            expected: "map_match_pattern_entry or match_rest_binding".to_string(),
+                            found: format!("{:?}", other),
+                            rule_text: p.as_str().to_string(),
+                        })
                     }
-                    
-                    rest = Some(build_symbol(sym_pair)?);
-                    break;
-                }
-                
-                // Otherwise, it should be a map_match_pattern_entry
-                if p_peek.as_rule() == Rule::map_match_pattern_entry {
-                    let entry_pair = inner_pairs.next().unwrap();
-                    let mut entry_inner = entry_pair.into_inner();
-                    
-                    let key_pair = entry_inner.next().ok_or_else(|| {
-                        PestParseError::MissingToken("map_key in map_match_pattern_entry".to_string())
-                    })?;
-                    
-                    let value_pattern_pair = entry_inner.next().ok_or_else(|| {
-                        PestParseError::MissingToken("match_pattern in map_match_pattern_entry".to_string())
-                    })?;
-                    
-                    entries.push(MapMatchEntry {
-                        key: build_map_key(key_pair)?,
-                        pattern: Box::new(build_match_pattern(value_pattern_pair)?),
-                    });
-                } else {
-                    return Err(PestParseError::UnexpectedRule {
-                        expected: "map_match_pattern_entry or & rest".to_string(),
-                        found: format!("{:?}", p_peek.as_rule()),
-                        rule_text: p_peek.as_str().to_string(),
-                    });
                 }
             }
-            
             Ok(MatchPattern::Map { entries, rest })
         }
         Rule::as_match_pattern => {
-            // Parse (:as symbol pattern)
+            // Parse (:as symbol pattern); ":as" is matched literally and yields no pair
             let mut inner_pairs = actual_pair.into_inner();
             
-            // Skip :as keyword
-            let _as_keyword = inner_pairs.next(); // Should be ":as"
-            
             // Skip whitespace
             while let Some(ws_peek) = inner_pairs.peek() {
                 if ws_peek.as_rule() == Rule::WHITESPACE || ws_peek.as_rule() == Rule::COMMENT {
@@ -0,0 +1,398 @@
+// Lossless concrete syntax tree
+//
+// The grammar treats WHITESPACE and COMMENT as silent, so pest pairs only cover
+// significant rules: delimiters and keywords matched by string literals, and all
+// trivia, fall into the gaps between pairs. The CST fills those gaps with tokens
+// so that concatenating every token reproduces the input byte for byte. Each node
+// keeps its pest pair, and lowering hands that pair to the existing `build_*`
+// functions, so the CST and the AST can never disagree about what a form means.
+
+use super::common::build_span;
+use super::expressions::build_expression;
+use super::{build_ast, PestParseError, RTFSParser, Rule};
+use crate::ast::{Expression, Span, TopLevel};
+use pest::iterators::Pair;
+use pest::Parser;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    Comment,   // `;` up to (not including) the end of the line
+    Delimiter, // ( ) [ ] { }
+    String,    // A complete string literal, quotes included
+    Text,      // Any other significant text: symbols, keywords, numbers, `&`, ...
+}
+
+impl TokenKind {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstToken<'i> {
+    pub kind: TokenKind,
+    pub text: &'i str,
+    pub start: usize, // Byte offset in the input
+}
+
+impl CstToken<'_> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CstElement<'i> {
+    Node(CstNode<'i>),
+    Token(CstToken<'i>),
+}
+
+#[derive(Clone)]
+pub struct CstNode<'i> {
+    pair: Pair<'i, Rule>,
+    children: Vec<CstElement<'i>>,
+}
+
+// A replacement of the byte range `start..end` of a source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub new_text: String,
+}
+
+// Parse a program into a lossless syntax tree rooted at the `program` rule
+pub fn parse_cst(input: &str) -> Result<CstNode<'_>, PestParseError> {
+    let pair = RTFSParser::parse(Rule::program, input)
+        .map_err(PestParseError::from)?
+        .next()
+        .ok_or_else(|| PestParseError::MissingToken("program".to_string()))?;
+    Ok(CstNode::from_pair(pair))
+}
+
+// Apply non-overlapping edits to `source`, in any order
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| edit.start);
+    let mut out = String::with_capacity(source.len());
+    let mut cursor = 0;
+    for edit in sorted {
+        out.push_str(&source[cursor..edit.start]);
+        out.push_str(&edit.new_text);
+        cursor = edit.end;
+    }
+    out.push_str(&source[cursor..]);
+    out
+}
+
+impl<'i> CstNode<'i> {
+    fn from_pair(pair: Pair<'i, Rule>) -> CstNode<'i> {
+        let input = pair.get_input();
+        let (start, end) = (pair.as_span().start(), pair.as_span().end());
+        let mut children = Vec::new();
+
+        if pair.as_rule() == Rule::string {
+            // Leaf: string contents are not lexed (they may contain `;` or delimiters)
+            children.push(CstElement::Token(CstToken {
+                kind: TokenKind::String,
+                text: pair.as_str(),
+                start,
+            }));
+        } else {
+            let mut cursor = start;
+            for inner in pair.clone().into_inner() {
+                let span = inner.as_span();
+                // Zero-width markers such as EOI carry no text
+                if span.start() == span.end() {
+                    continue;
+                }
+                lex_gap(input, cursor, span.start(), &mut children);
+                cursor = span.end();
+                children.push(CstElement::Node(CstNode::from_pair(inner)));
+            }
+            lex_gap(input, cursor, end, &mut children);
+        }
+
+        CstNode { pair, children }
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.pair.as_rule()
+    }
+
+    pub fn text(&self) -> &'i str {
+        self.pair.as_str()
+    }
+
+    pub fn start(&self) -> usize {
+        self.pair.as_span().start()
+    }
+
+    pub fn end(&self) -> usize {
+        self.pair.as_span().end()
+    }
+
+    pub fn span(&self) -> Span {
+        build_span(&self.pair)
+    }
+
+    pub fn children(&self) -> &[CstElement<'i>] {
+        &self.children
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &CstNode<'i>> {
+        self.children.iter().filter_map(|child| match child {
+            CstElement::Node(node) => Some(node),
+            CstElement::Token(_) => None,
+        })
+    }
+
+    // This node and all nodes below it, in source order
+    pub fn descendants(&self) -> Vec<&CstNode<'i>> {
+        let mut nodes = vec![self];
+        for child in self.child_nodes() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    // Every token below this node, in source order
+    pub fn tokens(&self) -> Vec<&CstToken<'i>> {
+        let mut tokens = Vec::new();
+        for child in &self.children {
+            match child {
+                CstElement::Node(node) => tokens.extend(node.tokens()),
+                CstElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    pub fn comments(&self) -> Vec<&CstToken<'i>> {
+        self.tokens()
+            .into_iter()
+            .filter(|token| token.kind == TokenKind::Comment)
+            .collect()
+    }
+
+    // The innermost node whose text contains the byte `offset`
+    pub fn node_at_offset(&self, offset: usize) -> Option<&CstNode<'i>> {
+        if offset < self.start() || offset >= self.end() {
+            return None;
+        }
+        self.child_nodes()
+            .find_map(|child| child.node_at_offset(offset))
+            .or(Some(self))
+    }
+
+    // The source text of this node, rebuilt from its tokens
+    pub fn to_source(&self) -> String {
+        self.tokens().iter().map(|token| token.text).collect()
+    }
+
+    pub fn replace_with(&self, new_text: impl Into<String>) -> TextEdit {
+        TextEdit {
+            start: self.start(),
+            end: self.end(),
+            new_text: new_text.into(),
+        }
+    }
+
+    // --- Lowering ---
+
+    pub fn lower_program(&self) -> Result<Vec<TopLevel>, PestParseError> {
+        if self.rule() != Rule::program {
+            return Err(PestParseError::UnexpectedRule {
+                expected: "program".to_string(),
+                found: format!("{:?}", self.rule()),
+                rule_text: self.text().to_string(),
+            });
+        }
+        self.child_nodes()
+            .map(|node| build_ast(node.pair.clone()))
+            .collect()
+    }
+
+    pub fn lower_top_level(&self) -> Result<TopLevel, PestParseError> {
+        build_ast(self.pair.clone())
+    }
+
+    pub fn lower_expression(&self) -> Result<Expression, PestParseError> {
+        build_expression(self.pair.clone())
+    }
+}
+
+impl fmt::Debug for CstNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{}..{} ", self.rule(), self.start(), self.end())?;
+        f.debug_list().entries(&self.children).finish()
+    }
+}
+
+// Split the text between two pairs into trivia and literal tokens
+fn lex_gap<'i>(input: &'i str, start: usize, end: usize, out: &mut Vec<CstElement<'i>>) {
+    let bytes = input.as_bytes();
+    let mut i = start;
+    while i < end {
+        let token_start = i;
+        let kind = match bytes[i] {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                while i < end && matches!(bytes[i], b' ' | b'\t' | b'\r' | b'\n') {
+                    i += 1;
+                }
+                TokenKind::Whitespace
+            }
+            b';' => {
+                while i < end && bytes[i] != b'\n' && bytes[i] != b'\r' {
+                    i += 1;
+                }
+                TokenKind::Comment
+            }
+            b'(' | b')' | b'[' | b']' | b'{' | b'}' => {
+                i += 1;
+                TokenKind::Delimiter
+            }
+            _ => {
+                while i < end && !b" \t\r\n;()[]{}".contains(&bytes[i]) {
+                    i += 1;
+                }
+                TokenKind::Text
+            }
+        };
+        out.push(CstElement::Token(CstToken {
+            kind,
+            text: &input[token_start..i],
+            start: token_start,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, parse_expression};
+
+    const SOURCE: &str = "; Plan for the weekly report\n(module reports ; inline note\n  (:exports [summarize])\n  ;; helper\n  (defn summarize [xs]\n    (let [n (count xs)] ; count first\n      (str \"total; (\" n \")\"))))\n\n(task :id \"t-1\"\n      :plan (do\n              (log-step :id \"s1\" (summarize [1 2]))\n              {:a 1   :b [2 3]}))   \n";
+
+    #[test]
+    fn test_cst_is_lossless() {
+        let cst = parse_cst(SOURCE).unwrap();
+        assert_eq!(cst.to_source(), SOURCE);
+
+        let mut offset = 0;
+        for token in cst.tokens() {
+            assert_eq!(token.start, offset, "gap or overlap before {:?}", token);
+            offset = token.end();
+        }
+        assert_eq!(offset, SOURCE.len());
+    }
+
+    #[test]
+    fn test_cst_keeps_comments_and_strings() {
+        let cst = parse_cst(SOURCE).unwrap();
+        let comments: Vec<&str> = cst.comments().iter().map(|token| token.text).collect();
+        assert_eq!(
+            comments,
+            vec![
+                "; Plan for the weekly report",
+                "; inline note",
+                ";; helper",
+                "; count first"
+            ]
+        );
+
+        let strings: Vec<&str> = cst
+            .tokens()
+            .into_iter()
+            .filter(|token| token.kind == TokenKind::String)
+            .map(|token| token.text)
+            .collect();
+        assert_eq!(strings, vec!["\"total; (\"", "\")\"", "\"t-1\"", "\"s1\""]);
+    }
+
+    #[test]
+    fn test_lowering_matches_parser() {
+        let cst = parse_cst(SOURCE).unwrap();
+        assert_eq!(cst.lower_program().unwrap(), parse(SOURCE).unwrap());
+
+        let offset = SOURCE.find("(let").unwrap();
+        let node = cst.node_at_offset(offset).unwrap();
+        assert_eq!(node.rule(), Rule::let_expr);
+        assert_eq!((node.span().line, node.span().column), (6, 5));
+        assert_eq!(
+            node.lower_expression().unwrap(),
+            parse_expression(node.text()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_edits_preserve_comments() {
+        let cst = parse_cst(SOURCE).unwrap();
+        let edits: Vec<TextEdit> = cst
+            .descendants()
+            .into_iter()
+            .filter(|node| node.rule() == Rule::symbol && node.text() == "summarize")
+            .map(|node| node.replace_with("summarise"))
+            .collect();
+        assert_eq!(edits.len(), 3);
+
+        let edited = apply_edits(SOURCE, &edits);
+        assert_eq!(edited, SOURCE.replace("summarize", "summarise"));
+        let comments: Vec<String> = parse_cst(&edited)
+            .unwrap()
+            .comments()
+            .iter()
+            .map(|token| token.text.to_string())
+            .collect();
+        assert_eq!(comments.len(), 4);
+    }
+
+    #[test]
+    fn test_token_kinds() {
+        let cst = parse_cst("(let [x 1] ; c\n x)").unwrap();
+        let tokens: Vec<(TokenKind, &str)> = cst
+            .tokens()
+            .into_iter()
+            .map(|token| (token.kind, token.text))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Delimiter, "("),
+                (TokenKind::Text, "let"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Delimiter, "["),
+                (TokenKind::Text, "x"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Text, "1"),
+                (TokenKind::Delimiter, "]"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Comment, "; c"),
+                (TokenKind::Whitespace, "\n "),
+                (TokenKind::Text, "x"),
+                (TokenKind::Delimiter, ")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repository_sources_are_lossless() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut pending = vec![root.join("test_modules"), root.join("examples")];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "rtfs") {
+                    let source = std::fs::read_to_string(&path).unwrap();
+                    if let Ok(cst) = parse_cst(&source) {
+                        assert_eq!(cst.to_source(), source, "{}", path.display());
+                    }
+                }
+            }
+        }
+    }
+}
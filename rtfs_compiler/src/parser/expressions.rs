@@ -1,15 +1,22 @@
-use super::common::{build_literal, build_map_key, build_symbol}; // Removed build_keyword
+use super::common::{build_literal, build_map_key, build_span, build_symbol}; // Removed build_keyword
 use super::special_forms::{
-    build_def_expr, build_defn_expr, build_do_expr, build_fn_expr, build_if_expr, build_let_expr,
-    build_log_step_expr, build_match_expr, build_parallel_expr, build_try_catch_expr,
-    build_with_resource_expr,
+    build_def_expr, build_defn_expr, build_deftype_expr, build_do_expr, build_dotimes_expr, build_fn_expr,
+    build_for_expr, build_if_expr, build_let_expr, build_letfn_expr, build_log_step_expr, build_loop_expr, build_match_expr,
+    build_parallel_expr, build_recur_expr, build_try_catch_expr, build_with_resource_expr,
+    build_case_expr, build_cond_expr, build_if_let_expr, build_thread_expr, build_when_expr, build_when_let_expr,
 };
 use super::{PestParseError, Rule}; // Added PestParseError
-use crate::ast::{Expression, MapKey};
+use crate::ast::{Expression, Keyword, MapKey, SugarForm};
 use pest::iterators::Pair;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-pub(super) fn build_expression(mut pair: Pair<Rule>) -> Result<Expression, PestParseError> {
+pub(super) fn build_expression(pair: Pair<Rule>) -> Result<Expression, PestParseError> {
+    let span = build_span(&pair);
+    build_unspanned_expression(pair).map(|expr| expr.with_span(span))
+}
+
+fn build_unspanned_expression(mut pair: Pair<Rule>) -> Result<Expression, PestParseError> {
     // Drill down through silent rules like \\\'expression\\\' or \\\'special_form\\\'
     loop {
         let rule = pair.as_rule();
@@ -30,6 +37,7 @@ pub(super) fn build_expression(mut pair: Pair<Rule>) -> Result<Expression, PestP
     match pair.as_rule() {
         Rule::literal => Ok(Expression::Literal(build_literal(pair)?)),
         Rule::symbol => Ok(Expression::Symbol(build_symbol(pair)?)),
+        Rule::task_context_access => build_task_context_access(pair),
         Rule::vector => Ok(Expression::Vector(
             pair.into_inner()
                 .map(build_expression)
@@ -46,6 +54,9 @@ pub(super) fn build_expression(mut pair: Pair<Rule>) -> Result<Expression, PestP
         Rule::defn_expr => Ok(Expression::Defn(Box::new(build_defn_expr(
             pair.into_inner(),
         )?))),
+        Rule::deftype_expr => Ok(Expression::DefType(Box::new(build_deftype_expr(
+            pair.into_inner(),
+        )?))),
         Rule::parallel_expr => Ok(Expression::Parallel(build_parallel_expr(
             pair.into_inner(),
         )?)),
@@ -58,6 +69,18 @@ pub(super) fn build_expression(mut pair: Pair<Rule>) -> Result<Expression, PestP
         Rule::match_expr => Ok(Expression::Match(Box::new(build_match_expr(
             pair.into_inner(),
         )?))),
+        Rule::letfn_expr => Ok(Expression::LetFn(build_letfn_expr(pair.into_inner())?)),
+        Rule::loop_expr => Ok(Expression::Loop(build_loop_expr(pair.into_inner())?)),
+        Rule::recur_expr => Ok(Expression::Recur(build_recur_expr(pair.into_inner())?)),
+        Rule::for_expr => Ok(Expression::For(Box::new(build_for_expr(pair.into_inner())?))),
+        Rule::doseq_expr => Ok(Expression::DoSeq(Box::new(build_for_expr(pair.into_inner())?))),
+        Rule::dotimes_expr => Ok(Expression::DoTimes(Box::new(build_dotimes_expr(pair.into_inner())?))),
+        Rule::cond_expr => Ok(Expression::Sugar(Rc::new(SugarForm::new(build_cond_expr(pair.into_inner())?)))),
+        Rule::when_expr => Ok(Expression::Sugar(Rc::new(SugarForm::new(build_when_expr(pair.into_inner())?)))),
+        Rule::if_let_expr => Ok(Expression::Sugar(Rc::new(SugarForm::new(build_if_let_expr(pair.into_inner())?)))),
+        Rule::when_let_expr => Ok(Expression::Sugar(Rc::new(SugarForm::new(build_when_let_expr(pair.into_inner())?)))),
+        Rule::case_expr => Ok(Expression::Sugar(Rc::new(SugarForm::new(build_case_expr(pair.into_inner())?)))),
+        Rule::thread_expr => Ok(Expression::Sugar(Rc::new(SugarForm::new(build_thread_expr(pair.into_inner())?)))),
         Rule::log_step_expr => Ok(Expression::LogStep(Box::new(build_log_step_expr(
             pair.into_inner(),
         )?))),
@@ -77,7 +100,7 @@ pub(super) fn build_expression(mut pair: Pair<Rule>) -> Result<Expression, PestP
 
                 // Heuristic: if the first element is a Symbol, or an Fn expression,
                 // or another FunctionCall, treat it as a function call.
-                match callee_ast {
+                match callee_ast.unspanned() {
                     Expression::Symbol(_) | Expression::Fn(_) | Expression::FunctionCall { .. } => {
                         // It's likely a function call. Parse remaining as arguments.
                         let arguments = inner_pairs
@@ -112,6 +135,16 @@ pub(super) fn build_expression(mut pair: Pair<Rule>) -> Result<Expression, PestP
     }
 }
 
+// task_context_access = { "@" ~ (identifier | keyword) }
+// `@field` and `@:field` both access the field named `field`
+fn build_task_context_access(pair: Pair<Rule>) -> Result<Expression, PestParseError> {
+    let field_pair = pair.into_inner().next().ok_or_else(|| {
+        PestParseError::MissingToken("expected field name after '@'".to_string())
+    })?;
+    let field = field_pair.as_str().trim_start_matches(':');
+    Ok(Expression::TaskContextAccess(Keyword(field.to_string())))
+}
+
 pub(super) fn build_map(pair: Pair<Rule>) -> Result<HashMap<MapKey, Expression>, PestParseError> {
     if pair.as_rule() != Rule::map {
         return Err(PestParseError::InvalidInput(format!(
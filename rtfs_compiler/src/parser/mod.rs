@@ -29,8 +29,11 @@ impl From<pest::error::Error<Rule>> for PestParseError {
 
 // Declare submodules
 pub mod common;
+pub mod cst;
 pub mod expressions;
 pub mod special_forms;
+pub mod printer;
+pub mod recovery;
 pub mod types;
 pub mod utils;
 
@@ -49,9 +52,16 @@ use crate::ast::{
 // Removed unused build_keyword, build_literal, build_map_key
 use common::build_symbol;
 use expressions::{build_expression, build_map}; // Added build_map
-use special_forms::{build_def_expr, build_defn_expr};
+use special_forms::{build_def_expr, build_defn_expr, build_deftype_expr};
 use utils::unescape; // Added def/defn builders
 
+pub use cst::{apply_edits, parse_cst, CstElement, CstNode, CstToken, TextEdit, TokenKind};
+pub use printer::{
+    format_source, print_expression, print_module_definition, print_program, print_task_definition,
+    print_top_level,
+};
+pub use recovery::{parse_with_recovery, Diagnostic, DiagnosticCode, RecoveredParse};
+
 // Define the parser struct using the grammar file
 #[derive(pest_derive::Parser)]
 #[grammar = "rtfs.pest"] // Path relative to src/
@@ -66,24 +76,13 @@ fn next_significant<'a>(pairs: &mut Pairs<'a, Rule>) -> Option<Pair<'a, Rule>> {
 
 // Parse a full RTFS program (potentially multiple top-level items)
 pub fn parse(input: &str) -> Result<Vec<TopLevel>, PestParseError> {
-    // MODIFIED error type
-    let pairs = RTFSParser::parse(Rule::program, input).map_err(PestParseError::from)?; // MODIFIED to map error
-                                                                                        // Program contains SOI ~ (task_definition | module_definition | expression)* ~ EOI
-                                                                                        // The `pairs` variable is an iterator over the content matched by Rule::program.
-                                                                                        // We need its single inner item (which should be the sequence inside program)
-                                                                                        // then iterate over *that* sequence's inner items.
-    let program_content = pairs
-        .peek()
-        .expect("Parse should have yielded one program rule");
-    let top_level_pairs = program_content.into_inner().filter(|p| {
-        p.as_rule() != Rule::WHITESPACE && p.as_rule() != Rule::COMMENT && p.as_rule() != Rule::EOI
-    });
-    // Keep EOI filter as it's implicitly added by Pest
-
-    // Ok(top_level_pairs.map(build_ast).collect()) // OLD
-    top_level_pairs
-        .map(build_ast)
-        .collect::<Result<Vec<_>, _>>() // NEW
+    // The AST is lowered from the lossless syntax tree, see `cst`
+    parse_cst(input)?.lower_program()
+}
+
+// Parse a program read from `file`, recording the file name in every span
+pub fn parse_with_file(input: &str, file: &str) -> Result<Vec<TopLevel>, PestParseError> {
+    common::with_source_file(file, || parse(input))
 }
 
 // Parse a single expression (useful for REPL or simple evaluation)
@@ -110,16 +109,29 @@ fn build_ast(pair: Pair<Rule>) -> Result<TopLevel, PestParseError> {
         | Rule::vector
         | Rule::map
         | Rule::let_expr
+        | Rule::letfn_expr
         | Rule::if_expr
         | Rule::do_expr
         | Rule::fn_expr
         | Rule::def_expr // def/defn can appear outside modules (though maybe discouraged)
         | Rule::defn_expr
+        | Rule::deftype_expr
         | Rule::parallel_expr
         | Rule::with_resource_expr
         | Rule::try_catch_expr
         | Rule::match_expr
         | Rule::log_step_expr
+        | Rule::loop_expr
+        | Rule::recur_expr
+        | Rule::for_expr
+        | Rule::doseq_expr
+        | Rule::dotimes_expr
+        | Rule::cond_expr
+        | Rule::when_expr
+        | Rule::if_let_expr
+        | Rule::when_let_expr
+        | Rule::case_expr
+        | Rule::thread_expr
         | Rule::identifier // Allow standalone identifiers? Maybe error later.
         // | Rule::namespaced_identifier => Ok(TopLevel::Expression(build_expression(pair?))), // MODIFIED OLD
         | Rule::namespaced_identifier => build_expression(pair).map(TopLevel::Expression), // MODIFIED NEW
@@ -171,66 +183,50 @@ fn build_task_definition(pair: Pair<Rule>) -> Result<TaskDefinition, PestParseEr
     let _task_keyword = next_significant(&mut inner);
 
     while let Some(prop_pair) = next_significant(&mut inner) {
-        eprintln!(
-            "[build_task_definition] prop_pair: rule={:?}, str='{}'",
-            prop_pair.as_rule(),
-            prop_pair.as_str()
-        );
         let prop_str = prop_pair.as_str().to_string();
         let mut prop_inner = prop_pair.into_inner();
-        let value_pair = next_significant(&mut prop_inner).expect("Task property needs value");
-        eprintln!(
-            "[build_task_definition] value_pair: rule={:?}, str='{}'",
-            value_pair.as_rule(),
-            value_pair.as_str()
-        );
+        let value_pair = next_significant(&mut prop_inner).ok_or_else(|| {
+            PestParseError::MissingToken(format!("Task property needs value: {}", prop_str))
+        })?;
         match prop_str.trim_start() {
             s if s.starts_with(":id") => {
-                assert_eq!(value_pair.as_rule(), Rule::string);
-                let raw_str = value_pair.as_str();
-                let content = &raw_str[1..raw_str.len() - 1];
-                id = Some(unescape(content).expect("Invalid escape sequence in task id"));
+                id = Some(build_task_string(value_pair, ":id")?);
             }
             s if s.starts_with(":source") => {
-                assert_eq!(value_pair.as_rule(), Rule::string);
-                let raw_str = value_pair.as_str();
-                let content = &raw_str[1..raw_str.len() - 1];
-                source = Some(unescape(content).expect("Invalid escape sequence in task source"));
+                source = Some(build_task_string(value_pair, ":source")?);
             }
             s if s.starts_with(":timestamp") => {
-                assert_eq!(value_pair.as_rule(), Rule::string);
-                let raw_str = value_pair.as_str();
-                let content = &raw_str[1..raw_str.len() - 1];
-                timestamp =
-                    Some(unescape(content).expect("Invalid escape sequence in task timestamp"));
+                timestamp = Some(build_task_string(value_pair, ":timestamp")?);
             }
             s if s.starts_with(":metadata") => {
-                assert_eq!(value_pair.as_rule(), Rule::map);
+                expect_task_value_rule(&value_pair, Rule::map, ":metadata")?;
                 metadata = Some(Expression::Map(build_map(value_pair)?));
             }
             s if s.starts_with(":intent") => {
                 intent = Some(build_expression(value_pair)?);
             }
             s if s.starts_with(":contracts") => {
-                assert_eq!(value_pair.as_rule(), Rule::map);
+                expect_task_value_rule(&value_pair, Rule::map, ":contracts")?;
                 contracts = Some(Expression::Map(build_map(value_pair)?));
             }
             s if s.starts_with(":plan") => {
                 plan = Some(build_expression(value_pair)?);
             }
             s if s.starts_with(":execution-trace") => {
-                assert_eq!(value_pair.as_rule(), Rule::vector);
-                // execution_trace = Some(Expression::Vector( // OLD
-                //     value_pair.into_inner().map(build_expression).collect(),
-                // ));
-                let exprs = value_pair // NEW
+                expect_task_value_rule(&value_pair, Rule::vector, ":execution-trace")?;
+                let exprs = value_pair
                     .into_inner()
                     .filter(|p| p.as_rule() != Rule::WHITESPACE && p.as_rule() != Rule::COMMENT)
                     .map(build_expression)
                     .collect::<Result<Vec<_>, PestParseError>>()?;
-                execution_trace = Some(Expression::Vector(exprs)); // NEW
+                execution_trace = Some(Expression::Vector(exprs));
+            }
+            _ => {
+                return Err(PestParseError::CustomError(format!(
+                    "Unknown task property: {}",
+                    prop_str
+                )))
             }
-            _ => panic!("Unknown task property: {}", prop_str),
         }
     }
 
@@ -246,6 +242,25 @@ fn build_task_definition(pair: Pair<Rule>) -> Result<TaskDefinition, PestParseEr
     })
 }
 
+// Check that a task property value was parsed with the expected rule
+fn expect_task_value_rule(pair: &Pair<Rule>, rule: Rule, property: &str) -> Result<(), PestParseError> {
+    if pair.as_rule() != rule {
+        return Err(PestParseError::UnexpectedRule {
+            expected: format!("{:?} for task property {}", rule, property),
+            found: format!("{:?}", pair.as_rule()),
+            rule_text: pair.as_str().to_string(),
+        });
+    }
+    Ok(())
+}
+
+// Build the string value of a task property such as :id or :source
+fn build_task_string(pair: Pair<Rule>, property: &str) -> Result<String, PestParseError> {
+    expect_task_value_rule(&pair, Rule::string, property)?;
+    let raw_str = pair.as_str();
+    unescape(&raw_str[1..raw_str.len() - 1])
+}
+
 // Helper function to build export options
 // Expected pairs: inner of export_option rule (exports_keyword, export_symbols_vec)
 fn build_export_option(mut pairs: Pairs<Rule>) -> Result<Vec<Symbol>, PestParseError> {
@@ -328,7 +343,7 @@ fn build_module_definition(pair: Pair<Rule>) -> Result<ModuleDefinition, PestPar
         }
     }
 
-    // 4. Definitions (def_expr | defn_expr | import_definition)*
+    // 4. Definitions (deftype_expr | def_expr | defn_expr | import_definition)*
     for def_candidate_pair in remaining_module_parts {
         // Skip whitespace and comments if they are passed (though next_significant in a loop would be better if they could be mixed)
         // The current loop structure assumes `remaining_module_parts` only yields significant tokens.
@@ -340,22 +355,21 @@ fn build_module_definition(pair: Pair<Rule>) -> Result<ModuleDefinition, PestPar
                 definitions.push(ModuleLevelDefinition::Def(def_node));
             }
             Rule::defn_expr => {
-                eprintln!(
-                    "[build_module_definition] Calling build_defn_expr for: rule={:?}, str='{}'",
-                    def_candidate_pair.as_rule(),
-                    def_candidate_pair.as_str()
-                );
                 // let defn_node = build_defn_expr(def_candidate_pair.into_inner()); // OLD
                 let defn_node = build_defn_expr(def_candidate_pair.into_inner())?; // NEW
                 definitions.push(ModuleLevelDefinition::Defn(defn_node));
             }
+            Rule::deftype_expr => {
+                let deftype_node = build_deftype_expr(def_candidate_pair.into_inner())?;
+                definitions.push(ModuleLevelDefinition::DefType(deftype_node));
+            }
             Rule::import_definition => {
                 let import_node = build_import_definition(def_candidate_pair.into_inner())?;
                 definitions.push(ModuleLevelDefinition::Import(import_node));
             }
             rule => {
                 return Err(PestParseError::UnexpectedRule {
-                    expected: "def_expr, defn_expr, or import_definition".to_string(),
+                    expected: "deftype_expr, def_expr, defn_expr, or import_definition".to_string(),
                     found: format!("{:?}", rule),
                     rule_text: def_candidate_pair.as_str().to_string(),
                 });
@@ -485,12 +499,18 @@ mod tests {
         CatchClause,
         CatchPattern,
         DefExpr,
+        DefTypeExpr,
         DefnExpr,
         DoExpr,
         Expression,
+        ForExpr,
+        IfExpr,
         ImportDefinition,
+        IterationClause,
         Keyword,
         LetBinding,        LetExpr,
+        LetFnExpr,
+        LoopExpr,
         Literal,        MapKey,
         MapMatchEntry,
         MatchClause,
@@ -502,11 +522,14 @@ mod tests {
         ParallelExpr,
         ParamDef,
         Pattern,
+        SugarExpr,
         Symbol,
         TaskDefinition,
+        ThreadKind,
         TopLevel,
         TryCatchExpr,
         TypeExpr,
+        TypePredicate,
         WithResourceExpr,
     };
     // use crate::parser::types::build_type_expr; // Removed unused import
@@ -595,6 +618,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_task_context_access() {
+        assert_expr_parses_to!(
+            "@intent",
+            Expression::TaskContextAccess(Keyword("intent".to_string()))
+        );
+        assert_expr_parses_to!(
+            "@:user-id",
+            Expression::TaskContextAccess(Keyword("user-id".to_string()))
+        );
+    }
+
     #[test]
     fn test_parse_collections() {
         // Vector
@@ -683,6 +718,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_deftype() {
+        assert_expr_parses_to!(
+            "(deftype Count [:and int [:>= 0]])",
+            Expression::DefType(Box::new(DefTypeExpr {
+                name: Symbol("Count".to_string()),
+                type_expr: TypeExpr::Refined {
+                    base: Box::new(TypeExpr::Alias(Symbol("int".to_string()))),
+                    predicates: vec![TypePredicate::GreaterOrEqual(Literal::Integer(0))],
+                },
+            }))
+        );
+        let program = parse("(module shared (:exports [Id]) (deftype Id string))").unwrap();
+        let TopLevel::Module(module) = &program[0] else {
+            panic!("Expected module, got {:?}", program[0]);
+        };
+        assert_eq!(
+            module.definitions,
+            vec![ModuleLevelDefinition::DefType(DefTypeExpr {
+                name: Symbol("Id".to_string()),
+                type_expr: TypeExpr::Alias(Symbol("string".to_string())),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_parse_type_variables() {
+        let type_var = |name: &str| TypeExpr::TypeVar(Symbol(name.to_string()));
+        assert_expr_parses_to!(
+            "(def first-of :[:=> [[:vector T]] T] f)",
+            Expression::Def(Box::new(DefExpr {
+                symbol: Symbol("first-of".to_string()),
+                type_annotation: Some(TypeExpr::Function {
+                    param_types: vec![crate::ast::ParamType::Simple(Box::new(TypeExpr::Vector(Box::new(type_var("T")))))],
+                    variadic_param_type: None,
+                    return_type: Box::new(type_var("T")),
+                }),
+                value: Box::new(Expression::Symbol(Symbol("f".to_string()))),
+            }))
+        );
+        // Longer names are type aliases
+        assert_expr_parses_to!(
+            "(def t :Tree f)",
+            Expression::Def(Box::new(DefExpr {
+                symbol: Symbol("t".to_string()),
+                type_annotation: Some(TypeExpr::Alias(Symbol("Tree".to_string()))),
+                value: Box::new(Expression::Symbol(Symbol("f".to_string()))),
+            }))
+        );
+    }
+
     #[test]
     fn test_parse_let() {
         // Simple let
@@ -1221,4 +1307,181 @@ mod tests {
             }))
         );
     }
+
+    #[test]
+    fn test_parse_loop_and_iteration() {
+        let symbol = |name: &str| Expression::Symbol(Symbol(name.to_string()));
+        assert_expr_parses_to!(
+            "(loop [i 0] (if (< i 3) (recur (inc i)) i))",
+            Expression::Loop(LoopExpr {
+                bindings: vec![LetBinding {
+                    pattern: Pattern::Symbol(Symbol("i".to_string())),
+                    type_annotation: None,
+                    value: Box::new(Expression::Literal(Literal::Integer(0))),
+                }],
+                body: vec![Expression::If(IfExpr {
+                    condition: Box::new(Expression::FunctionCall {
+                        callee: Box::new(symbol("<")),
+                        arguments: vec![symbol("i"), Expression::Literal(Literal::Integer(3))],
+                    }),
+                    then_branch: Box::new(Expression::Recur(vec![Expression::FunctionCall {
+                        callee: Box::new(symbol("inc")),
+                        arguments: vec![symbol("i")],
+                    }])),
+                    else_branch: Some(Box::new(symbol("i"))),
+                })],
+            })
+        );
+        assert_expr_parses_to!(
+            "(for [x xs :when (odd? x)] x)",
+            Expression::For(Box::new(ForExpr {
+                clauses: vec![
+                    IterationClause::Binding {
+                        pattern: Pattern::Symbol(Symbol("x".to_string())),
+                        collection: Box::new(symbol("xs")),
+                    },
+                    IterationClause::When(Box::new(Expression::FunctionCall {
+                        callee: Box::new(symbol("odd?")),
+                        arguments: vec![symbol("x")],
+                    })),
+                ],
+                body: vec![symbol("x")],
+            }))
+        );
+
+        // recur must be in tail position of its loop, with one value per binding
+        let error = |source: &str| match parse_expression(source) {
+            Err(PestParseError::InvalidInput(message)) => message,
+            other => panic!("expected an invalid input error for {}, got {:?}", source, other),
+        };
+        assert_eq!(
+            error("(loop [i 0]
+  (+ 1 (recur i)))"),
+            "recur must be in tail position of a loop at 2:8"
+        );
+        assert!(error("(loop [i 0 j 0] (recur 1))").starts_with("recur expects 2 arguments"));
+        assert!(error("(loop [i 0] (fn [] (recur 1)))").starts_with("recur must be in tail position"));
+        assert!(error("(defn f [x] (recur x))").starts_with("recur must be in tail position"));
+        assert!(parse_expression("(loop [a 1] (let [b a] (match b 1 (recur 2) _ (do b (loop [] b)))))").is_ok());
+    }
+
+    #[test]
+    fn test_parse_letfn() {
+        let symbol = |name: &str| Expression::Symbol(Symbol(name.to_string()));
+        let function = |name: &str, other: &str| DefnExpr {
+            name: Symbol(name.to_string()),
+            params: vec![ParamDef {
+                pattern: Pattern::Symbol(Symbol("n".to_string())),
+                type_annotation: None,
+            }],
+            variadic_param: None,
+            return_type: None,
+            body: vec![Expression::FunctionCall {
+                callee: Box::new(symbol(other)),
+                arguments: vec![symbol("n")],
+            }],
+        };
+        assert_expr_parses_to!(
+            "(letfn [(f [n] (g n)) (g [n] (f n))] (f 1))",
+            Expression::LetFn(LetFnExpr {
+                functions: vec![function("f", "g"), function("g", "f")],
+                body: vec![Expression::FunctionCall {
+                    callee: Box::new(symbol("f")),
+                    arguments: vec![Expression::Literal(Literal::Integer(1))],
+                }],
+            })
+        );
+        // A symbol that merely starts with the keyword is still a symbol
+        assert_expr_parses_to!("(letfns 1)", Expression::FunctionCall {
+            callee: Box::new(symbol("letfns")),
+            arguments: vec![Expression::Literal(Literal::Integer(1))],
+        });
+        // The body of letfn is in tail position of an enclosing loop
+        assert!(parse_expression("(loop [i 0] (letfn [(f [x] x)] (recur (f i))))").is_ok());
+    }
+
+    #[test]
+    fn test_parse_conditional_and_threading_forms() {
+        let sugar = |source: &str| match parse_expression(source).unwrap().into_unspanned() {
+            Expression::Sugar(sugar) => sugar.form.clone(),
+            other => panic!("Expected a sugar form for {}, got {:?}", source, other),
+        };
+        assert!(matches!(sugar("(when-not ok (a))"), SugarExpr::When { negated: true, .. }));
+        assert!(matches!(sugar("(if-let [x (f)] x)"), SugarExpr::IfLet { else_branch: None, .. }));
+        assert!(matches!(sugar("(some-> m :a)"), SugarExpr::Thread { kind: ThreadKind::SomeFirst, .. }));
+        assert!(matches!(sugar("(->> xs (f))"), SugarExpr::Thread { kind: ThreadKind::Last, .. }));
+        match sugar("(case k 1 :one (2 3) :few :many)") {
+            SugarExpr::Case { clauses, default, .. } => {
+                assert_eq!(clauses[1].keys, vec![Literal::Integer(2), Literal::Integer(3)]);
+                assert_eq!(default.as_deref(), Some(&Expression::Literal(Literal::Keyword(Keyword("many".to_string())))));
+            }
+            other => panic!("Expected case, got {:?}", other),
+        }
+
+        // Symbols that merely start with a keyword are still symbols
+        for source in ["(when-ready 1)", "(->x 1)", "(cases 1)", "(conds 1)"] {
+            assert!(
+                matches!(parse_expression(source).unwrap().unspanned(), Expression::FunctionCall { .. }),
+                "{}",
+                source
+            );
+        }
+        assert!(parse_expression("(cond (a) 1 (b))").is_err());
+        // Special forms have no argument to thread into
+        for source in ["(-> x (if a b))", "(->> x (let [y 1] y))", "(some-> x (fn [y] y))", "(-> x (when a))"] {
+            assert!(
+                matches!(parse_expression(source), Err(PestParseError::InvalidInput(message)) if message.contains("special form")),
+                "{}",
+                source
+            );
+        }
+        assert!(parse_expression("(-> x ((fn [y] y)) f [1] :k)").is_ok());
+
+        // recur is allowed in the tail positions of the expansion only
+        assert!(parse_expression("(loop [i 0] (when (< i 3) (recur (+ i 1))))").is_ok());
+        assert!(parse_expression("(loop [i 0] (cond (> i 3) i :else (recur (+ i 1))))").is_ok());
+        assert!(parse_expression("(loop [i 0] (cond (recur i) 1))").is_err());
+        assert!(parse_expression("(loop [i 0] (-> i (recur)))").is_err());
+    }
+
+    #[test]
+    fn test_parse_records_spans() {
+        let expr = parse_expression("(let [x 1]\n  (+ x y))").unwrap();
+        let span = expr.span().expect("outer form should carry a span");
+        assert_eq!((span.line, span.column), (1, 1));
+        assert_eq!((span.start, span.end), (0, 21));
+        assert_eq!((span.end_line, span.end_column), (2, 11));
+
+        let let_expr = match expr.unspanned() {
+            Expression::Let(let_expr) => let_expr.clone(),
+            other => panic!("Expected let expression, got {:?}", other),
+        };
+        let binding_span = let_expr.bindings[0].pattern.span().unwrap();
+        assert_eq!((binding_span.line, binding_span.column), (1, 7));
+        let body_span = let_expr.body[0].span().unwrap();
+        assert_eq!((body_span.line, body_span.column, body_span.start), (2, 3, 13));
+
+        // Spans never take part in structural equality
+        assert_eq!(
+            parse_expression("(+ 1 2)").unwrap(),
+            parse_expression("(+ 1\n 2)").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_with_file_records_file_name() {
+        let program = parse_with_file("(def x :int 1)", "plans/example.rtfs").unwrap();
+        let TopLevel::Expression(expr) = &program[0] else {
+            panic!("Expected expression, got {:?}", program[0]);
+        };
+        let Expression::Def(def_expr) = expr.unspanned() else {
+            panic!("Expected def, got {:?}", expr);
+        };
+        assert_eq!(
+            def_expr.type_annotation.as_ref().unwrap().span().unwrap().file.as_deref(),
+            Some("plans/example.rtfs")
+        );
+        // The file name does not leak into later parses
+        assert_eq!(parse_expression("x").unwrap().span().unwrap().file, None);
+    }
 }
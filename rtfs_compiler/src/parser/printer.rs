@@ -0,0 +1,1172 @@
+// Canonical pretty-printer for RTFS source
+//
+// Turns AST nodes back into RTFS text. The output is canonical (map entries are
+// sorted by key, spacing and indentation follow fixed rules) and re-parseable:
+// for any AST produced by `parse`, parsing the printed text yields an equal AST.
+//
+// Layout rules: a form is printed on one line when it fits in `MAX_WIDTH`
+// columns. Block forms (`do`, `defn`, `letfn`, `match`, `try`, `with-resource`,
+// `parallel`, tasks and modules) always break, with their bodies indented by
+// `INDENT` from the opening parenthesis. `let`/`loop` bindings and `for`
+// clauses are aligned after the opening bracket, a broken `cond` or `case` puts
+// each clause on its own line, and a broken call puts each argument on its own line.
+
+use super::{parse, PestParseError};
+use crate::ast::{
+    CatchPattern, DefExpr, DefTypeExpr, DefnExpr, Expression, ForExpr, ImportDefinition,
+    IterationClause, Keyword, LetBinding, Literal, MapDestructuringEntry, MapKey, MatchClause, MatchPattern, ModuleDefinition,
+    ModuleLevelDefinition, ParamDef, ParamType, Pattern, PrimitiveType, SugarExpr, TaskDefinition,
+    TopLevel, TypeExpr, TypePredicate,
+};
+use std::collections::HashMap;
+
+const MAX_WIDTH: usize = 80;
+const INDENT: usize = 2;
+
+// --- Public Entry Points ---
+
+// Parse `input` and print it back in canonical form
+pub fn format_source(input: &str) -> Result<String, PestParseError> {
+    Ok(print_program(&parse(input)?))
+}
+
+// Print a whole program: top-level items separated by a blank line
+pub fn print_program(items: &[TopLevel]) -> String {
+    let mut out = items
+        .iter()
+        .map(print_top_level)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+pub fn print_top_level(item: &TopLevel) -> String {
+    match item {
+        TopLevel::Task(task) => print_task_definition(task),
+        TopLevel::Module(module) => print_module_definition(module),
+        TopLevel::Expression(expr) => print_expression(expr),
+    }
+}
+
+pub fn print_expression(expr: &Expression) -> String {
+    let mut printer = Printer::default();
+    printer.expression(expr);
+    printer.out
+}
+
+pub fn print_task_definition(task: &TaskDefinition) -> String {
+    let mut printer = Printer::default();
+    printer.task_definition(task);
+    printer.out
+}
+
+pub fn print_module_definition(module: &ModuleDefinition) -> String {
+    let mut printer = Printer::default();
+    printer.module_definition(module);
+    printer.out
+}
+
+pub fn print_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Symbol(symbol) => symbol.0.clone(),
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::VectorDestructuring {
+            elements,
+            rest,
+            as_symbol,
+        } => {
+            let mut parts: Vec<String> = elements.iter().map(print_pattern).collect();
+            if let Some(rest) = rest {
+                parts.push(format!("& {}", rest.0));
+            }
+            if let Some(as_symbol) = as_symbol {
+                parts.push(format!(":as {}", as_symbol.0));
+            }
+            format!("[{}]", parts.join(" "))
+        }
+        Pattern::MapDestructuring {
+            entries,
+            rest,
+            as_symbol,
+        } => {
+            let mut parts: Vec<String> = entries
+                .iter()
+                .map(|entry| match entry {
+                    MapDestructuringEntry::KeyBinding { key, pattern } => {
+                        format!("{} {}", print_map_key(key), print_pattern(pattern))
+                    }
+                    MapDestructuringEntry::Keys(symbols) => format!(
+                        ":keys [{}]",
+                        symbols
+                            .iter()
+                            .map(|s| s.0.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ),
+                    MapDestructuringEntry::Defaults(defaults) => format!(
+                        ":or {{{}}}",
+                        defaults
+                            .iter()
+                            .map(|(symbol, value)| format!("{} {}", symbol.0, print_expression(value)))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ),
+                })
+                .collect();
+            if let Some(rest) = rest {
+                parts.push(format!("& {}", rest.0));
+            }
+            if let Some(as_symbol) = as_symbol {
+                parts.push(format!(":as {}", as_symbol.0));
+            }
+            format!("{{{}}}", parts.join(" "))
+        }
+        Pattern::Spanned(inner, _) => print_pattern(inner),
+    }
+}
+
+pub fn print_match_pattern(pattern: &MatchPattern) -> String {
+    match pattern {
+        MatchPattern::Literal(literal) => print_literal(literal),
+        MatchPattern::Symbol(symbol) => symbol.0.clone(),
+        MatchPattern::Keyword(keyword) => print_keyword(keyword),
+        MatchPattern::Wildcard => "_".to_string(),
+        MatchPattern::Type(type_expr, None) => print_type_pattern(type_expr),
+        // There is no surface syntax for a binding type pattern; `:as` is equivalent
+        MatchPattern::Type(type_expr, Some(symbol)) => {
+            format!("(:as {} {})", symbol.0, print_type_pattern(type_expr))
+        }
+        MatchPattern::Vector { elements, rest } => {
+            let mut parts: Vec<String> = elements.iter().map(print_match_pattern).collect();
+            if let Some(rest) = rest {
+                parts.push(format!("& {}", rest.0));
+            }
+            format!("[{}]", parts.join(" "))
+        }
+        MatchPattern::Map { entries, rest } => {
+            let mut parts: Vec<String> = entries
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "{} {}",
+                        print_map_key(&entry.key),
+                        print_match_pattern(&entry.pattern)
+                    )
+                })
+                .collect();
+            if let Some(rest) = rest {
+                parts.push(format!("& {}", rest.0));
+            }
+            format!("{{{}}}", parts.join(" "))
+        }
+        MatchPattern::As(symbol, inner) => {
+            format!("(:as {} {})", symbol.0, print_match_pattern(inner))
+        }
+        MatchPattern::Spanned(inner, _) => print_match_pattern(inner),
+    }
+}
+
+/// A type used as a match pattern. Builtin types are written as keywords, since a bare
+/// name such as `int` would be read back as a variable.
+fn print_type_pattern(type_expr: &TypeExpr) -> String {
+    match type_expr.unspanned() {
+        TypeExpr::Primitive(_) | TypeExpr::Any | TypeExpr::Never => format!(":{}", print_type_expr(type_expr)),
+        _ => print_type_expr(type_expr),
+    }
+}
+
+pub fn print_type_expr(type_expr: &TypeExpr) -> String {
+    match type_expr {
+        TypeExpr::Primitive(primitive) => match primitive {
+            PrimitiveType::Int => "int".to_string(),
+            PrimitiveType::Float => "float".to_string(),
+            PrimitiveType::String => "string".to_string(),
+            PrimitiveType::Bool => "bool".to_string(),
+            PrimitiveType::Nil => "nil".to_string(),
+            PrimitiveType::Keyword => "keyword".to_string(),
+            PrimitiveType::Symbol => "symbol".to_string(),
+            PrimitiveType::Custom(keyword) => keyword.0.clone(),
+        },
+        TypeExpr::Alias(symbol) | TypeExpr::TypeVar(symbol) => symbol.0.clone(),
+        TypeExpr::Vector(element) => format!("[:vector {}]", print_type_expr(element)),
+        TypeExpr::Tuple(elements) => format!("[:tuple {}]", print_type_list(elements)),
+        TypeExpr::Array { element_type, shape } => match shape {
+            Some(shape) => format!("[:array {} {}]", print_type_expr(element_type), print_array_shape(shape)),
+            None => format!("[:array {}]", print_type_expr(element_type)),
+        },
+        TypeExpr::Map { entries, wildcard } => {
+            let mut parts: Vec<String> = entries
+                .iter()
+                .map(|entry| {
+                    let optional = if entry.optional { " ?" } else { "" };
+                    format!(
+                        "[{} {}{}]",
+                        print_keyword(&entry.key),
+                        print_type_expr(&entry.value_type),
+                        optional
+                    )
+                })
+                .collect();
+            if let Some(wildcard) = wildcard {
+                parts.push(format!("[:* {}]", print_type_expr(wildcard)));
+            }
+            if parts.is_empty() {
+                "[:map]".to_string()
+            } else {
+                format!("[:map {}]", parts.join(" "))
+            }
+        }
+        TypeExpr::Function {
+            param_types,
+            variadic_param_type,
+            return_type,
+        } => {
+            let mut params: Vec<String> = param_types
+                .iter()
+                .map(|ParamType::Simple(param)| print_type_expr(param))
+                .collect();
+            if let Some(variadic) = variadic_param_type {
+                params.push(format!("& {}", print_type_expr(variadic)));
+            }
+            format!(
+                "[:=> [{}] {}]",
+                params.join(" "),
+                print_type_expr(return_type)
+            )
+        }
+        TypeExpr::Resource(symbol) => format!("[:resource {}]", symbol.0),
+        TypeExpr::Union(types) => format!("[:union {}]", print_type_list(types)),
+        TypeExpr::Intersection(types) => format!("[:and {}]", print_type_list(types)),
+        TypeExpr::Refined { base, predicates } => {
+            let mut parts = vec![print_type_expr(base)];
+            parts.extend(predicates.iter().map(print_type_predicate));
+            format!("[:and {}]", parts.join(" "))
+        }
+        TypeExpr::Literal(literal) => format!("[:val {}]", print_literal(literal)),
+        TypeExpr::Any => "any".to_string(),
+        TypeExpr::Never => "never".to_string(),
+        TypeExpr::Spanned(inner, _) => print_type_expr(inner),
+    }
+}
+
+/// An array shape such as `[2 ? 3]`, where `?` is a dimension of unknown size
+pub fn print_array_shape(shape: &[Option<usize>]) -> String {
+    let dimensions: Vec<String> = shape
+        .iter()
+        .map(|d| d.map_or("?".to_string(), |size| size.to_string()))
+        .collect();
+    format!("[{}]", dimensions.join(" "))
+}
+
+pub fn print_type_predicate(predicate: &TypePredicate) -> String {
+    let mut parts = vec![format!(":{}", predicate.name())];
+    parts.extend(predicate.args().iter().map(print_literal));
+    format!("[{}]", parts.join(" "))
+}
+
+// --- Atoms ---
+
+fn print_type_list(types: &[TypeExpr]) -> String {
+    types
+        .iter()
+        .map(print_type_expr)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn print_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::Integer(value) => value.to_string(),
+        Literal::Float(value) => print_float(*value),
+        Literal::String(value) => print_string(value),
+        Literal::Boolean(value) => value.to_string(),
+        Literal::Keyword(keyword) => print_keyword(keyword),
+        Literal::Nil => "nil".to_string(),
+    }
+}
+
+// The float rule needs digits on both sides of the point, also before an
+// exponent. `{:?}` gives the shortest text that reads back to the same value.
+fn print_float(value: f64) -> String {
+    let text = format!("{:?}", value);
+    match text.find('e') {
+        Some(exponent) if !text[..exponent].contains('.') => {
+            format!("{}.0{}", &text[..exponent], &text[exponent..])
+        }
+        _ => text,
+    }
+}
+
+fn print_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn print_keyword(keyword: &Keyword) -> String {
+    format!(":{}", keyword.0)
+}
+
+fn print_map_key(key: &MapKey) -> String {
+    match key {
+        MapKey::Keyword(keyword) => print_keyword(keyword),
+        MapKey::String(value) => print_string(value),
+        MapKey::Integer(value) => value.to_string(),
+    }
+}
+
+fn print_catch_pattern(pattern: &CatchPattern) -> String {
+    match pattern {
+        CatchPattern::Keyword(keyword) => print_keyword(keyword),
+        CatchPattern::Type(type_expr) => print_type_expr(type_expr),
+        CatchPattern::Symbol(symbol) => symbol.0.clone(),
+    }
+}
+
+fn print_param(param: &ParamDef) -> String {
+    match &param.type_annotation {
+        Some(type_expr) => format!(
+            "{} :{}",
+            print_pattern(&param.pattern),
+            print_type_expr(type_expr)
+        ),
+        None => print_pattern(&param.pattern),
+    }
+}
+
+// `[a :int & rest] :ret` for fn and defn
+fn print_signature(
+    params: &[ParamDef],
+    variadic_param: &Option<ParamDef>,
+    return_type: &Option<TypeExpr>,
+) -> String {
+    let mut parts: Vec<String> = params.iter().map(print_param).collect();
+    if let Some(variadic) = variadic_param {
+        parts.push(format!("& {}", print_param(variadic)));
+    }
+    let mut signature = format!("[{}]", parts.join(" "));
+    if let Some(return_type) = return_type {
+        signature.push_str(&format!(" :{}", print_type_expr(return_type)));
+    }
+    signature
+}
+
+// Type annotation suffix for def and parallel bindings
+fn print_annotation(type_annotation: &Option<TypeExpr>) -> String {
+    match type_annotation {
+        Some(type_expr) => format!(" :{}", print_type_expr(type_expr)),
+        None => String::new(),
+    }
+}
+
+// Map entries in canonical (key-sorted) order
+fn sorted_entries(map: &HashMap<MapKey, Expression>) -> Vec<(String, &Expression)> {
+    let mut entries: Vec<(String, &Expression)> = map
+        .iter()
+        .map(|(key, value)| (print_map_key(key), value))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+// --- Single-Line Rendering ---
+
+// Render a form on one line, or None if it is (or contains) a block form
+fn flat(expr: &Expression) -> Option<String> {
+    let text = match expr {
+        Expression::Spanned(inner, _) => return flat(inner),
+        Expression::Literal(literal) => print_literal(literal),
+        Expression::Symbol(symbol) => symbol.0.clone(),
+        Expression::TaskContextAccess(field) => format!("@{}", field.0),
+        Expression::List(items) => format!("({})", flat_all(items)?),
+        Expression::Vector(items) => format!("[{}]", flat_all(items)?),
+        Expression::Map(map) => {
+            let entries = sorted_entries(map)
+                .into_iter()
+                .map(|(key, value)| Some(format!("{} {}", key, flat(value)?)))
+                .collect::<Option<Vec<_>>>()?;
+            format!("{{{}}}", entries.join(" "))
+        }
+        Expression::FunctionCall { callee, arguments } => {
+            let callee = flat(callee)?;
+            if arguments.is_empty() {
+                format!("({})", callee)
+            } else {
+                format!("({} {})", callee, flat_all(arguments)?)
+            }
+        }
+        Expression::If(if_expr) => {
+            let mut parts = vec![
+                flat(&if_expr.condition)?,
+                flat(&if_expr.then_branch)?,
+            ];
+            if let Some(else_branch) = &if_expr.else_branch {
+                parts.push(flat(else_branch)?);
+            }
+            format!("(if {})", parts.join(" "))
+        }
+        Expression::Let(let_expr) => format!(
+            "(let [{}] {})",
+            flat_bindings(&let_expr.bindings)?,
+            flat_all(&let_expr.body)?
+        ),
+        Expression::Loop(loop_expr) => format!(
+            "(loop [{}] {})",
+            flat_bindings(&loop_expr.bindings)?,
+            flat_all(&loop_expr.body)?
+        ),
+        Expression::Recur(arguments) if arguments.is_empty() => "(recur)".to_string(),
+        Expression::Recur(arguments) => format!("(recur {})", flat_all(arguments)?),
+        Expression::For(for_expr) => flat_iteration("for", for_expr)?,
+        Expression::DoSeq(for_expr) => flat_iteration("doseq", for_expr)?,
+        Expression::DoTimes(dotimes) => format!(
+            "(dotimes [{} {}] {})",
+            dotimes.symbol.0,
+            flat(&dotimes.count)?,
+            flat_all(&dotimes.body)?
+        ),
+        Expression::Fn(fn_expr) => format!(
+            "(fn {} {})",
+            print_signature(&fn_expr.params, &fn_expr.variadic_param, &fn_expr.return_type),
+            flat_all(&fn_expr.body)?
+        ),
+        Expression::Def(def_expr) => flat_def(def_expr)?,
+        Expression::DefType(deftype) => print_deftype(deftype),
+        Expression::LogStep(log_step) => {
+            let mut parts = vec![format!(
+                ":id {}",
+                print_string(log_step.location.as_deref().unwrap_or(""))
+            )];
+            for value in &log_step.values {
+                parts.push(flat(value)?);
+            }
+            format!("(log-step {})", parts.join(" "))
+        }
+        Expression::Sugar(sugar) => flat_sugar(&sugar.form)?,
+        Expression::Do(_)
+        | Expression::Defn(_)
+        | Expression::LetFn(_)
+        | Expression::Match(_)
+        | Expression::TryCatch(_)
+        | Expression::WithResource(_)
+        | Expression::Parallel(_) => return None,
+    };
+    Some(text)
+}
+
+fn flat_all(exprs: &[Expression]) -> Option<String> {
+    Some(
+        exprs
+            .iter()
+            .map(flat)
+            .collect::<Option<Vec<_>>>()?
+            .join(" "),
+    )
+}
+
+fn flat_bindings(bindings: &[LetBinding]) -> Option<String> {
+    let bindings = bindings
+        .iter()
+        .map(|binding| Some(format!("{} {}", print_pattern(&binding.pattern), flat(&binding.value)?)))
+        .collect::<Option<Vec<_>>>()?;
+    Some(bindings.join(" "))
+}
+
+// `(for [...] body)` or `(doseq [...] body)` on one line
+fn flat_iteration(head: &str, for_expr: &ForExpr) -> Option<String> {
+    let clauses = for_expr
+        .clauses
+        .iter()
+        .map(|clause| match clause {
+            IterationClause::Binding { pattern, collection } => {
+                Some(format!("{} {}", print_pattern(pattern), flat(collection)?))
+            }
+            IterationClause::When(test) => Some(format!(":when {}", flat(test)?)),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("({} [{}] {})", head, clauses.join(" "), flat_all(&for_expr.body)?))
+}
+
+// `cond`, `when`, `if-let`, `case` and the threading forms on one line
+fn flat_sugar(sugar: &SugarExpr) -> Option<String> {
+    let mut parts = vec![sugar_head(sugar).to_string()];
+    match sugar {
+        SugarExpr::Cond(clauses) => {
+            for (test, expr) in clauses {
+                parts.push(flat(test)?);
+                parts.push(flat(expr)?);
+            }
+        }
+        SugarExpr::When { test, body, .. } => {
+            parts.push(flat(test)?);
+            parts.extend(body.iter().map(flat).collect::<Option<Vec<_>>>()?);
+        }
+        SugarExpr::IfLet { binding, then_branch, else_branch } => {
+            parts.push(format!("[{}]", flat_bindings(std::slice::from_ref(binding))?));
+            parts.push(flat(then_branch)?);
+            if let Some(else_branch) = else_branch {
+                parts.push(flat(else_branch)?);
+            }
+        }
+        SugarExpr::WhenLet { binding, body } => {
+            parts.push(format!("[{}]", flat_bindings(std::slice::from_ref(binding))?));
+            parts.extend(body.iter().map(flat).collect::<Option<Vec<_>>>()?);
+        }
+        SugarExpr::Case { value, clauses, default } => {
+            parts.push(flat(value)?);
+            for clause in clauses {
+                parts.push(print_case_keys(&clause.keys));
+                parts.push(flat(&clause.body)?);
+            }
+            if let Some(default) = default {
+                parts.push(flat(default)?);
+            }
+        }
+        SugarExpr::Thread { value, forms, .. } => {
+            parts.push(flat(value)?);
+            parts.extend(forms.iter().map(flat).collect::<Option<Vec<_>>>()?);
+        }
+    }
+    Some(format!("({})", parts.join(" ")))
+}
+
+fn sugar_head(sugar: &SugarExpr) -> &'static str {
+    match sugar {
+        SugarExpr::Cond(_) => "cond",
+        SugarExpr::When { negated: false, .. } => "when",
+        SugarExpr::When { negated: true, .. } => "when-not",
+        SugarExpr::IfLet { .. } => "if-let",
+        SugarExpr::WhenLet { .. } => "when-let",
+        SugarExpr::Case { .. } => "case",
+        SugarExpr::Thread { kind, .. } => kind.keyword(),
+    }
+}
+
+// A single `case` key as is, several as a list
+fn print_case_keys(keys: &[Literal]) -> String {
+    match keys {
+        [key] => print_literal(key),
+        _ => format!("({})", keys.iter().map(print_literal).collect::<Vec<_>>().join(" ")),
+    }
+}
+
+fn flat_def(def_expr: &DefExpr) -> Option<String> {
+    Some(format!(
+        "(def {}{} {})",
+        def_expr.symbol.0,
+        print_annotation(&def_expr.type_annotation),
+        flat(&def_expr.value)?
+    ))
+}
+
+fn print_deftype(deftype: &DefTypeExpr) -> String {
+    format!("(deftype {} {})", deftype.name.0, print_type_expr(&deftype.type_expr))
+}
+
+// --- Multi-Line Layout ---
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    column: usize,
+}
+
+impl Printer {
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+        match text.rfind('\n') {
+            Some(index) => self.column = text[index + 1..].chars().count(),
+            None => self.column += text.chars().count(),
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(indent));
+        self.column = indent;
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.column + text.chars().count() <= MAX_WIDTH
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        if let Some(text) = flat(expr) {
+            if self.fits(&text) {
+                self.write(&text);
+                return;
+            }
+        }
+
+        let start = self.column;
+        match expr {
+            Expression::Spanned(inner, _) => self.expression(inner),
+            Expression::Literal(literal) => self.write(&print_literal(literal)),
+            Expression::Symbol(symbol) => self.write(&symbol.0),
+            Expression::TaskContextAccess(field) => self.write(&format!("@{}", field.0)),
+            Expression::List(items) => self.sequence("(", items, ")"),
+            Expression::Vector(items) => self.sequence("[", items, "]"),
+            Expression::Map(map) => {
+                self.write("{");
+                for (index, (key, value)) in sorted_entries(map).into_iter().enumerate() {
+                    if index > 0 {
+                        self.newline(start + 1);
+                    }
+                    self.write(&key);
+                    self.write(" ");
+                    self.expression(value);
+                }
+                self.write("}");
+            }
+            Expression::FunctionCall { callee, arguments } => {
+                self.write("(");
+                self.expression(callee);
+                self.body(arguments, start);
+                self.write(")");
+            }
+            Expression::If(if_expr) => {
+                self.write("(if ");
+                self.expression(&if_expr.condition);
+                self.newline(start + INDENT);
+                self.expression(&if_expr.then_branch);
+                if let Some(else_branch) = &if_expr.else_branch {
+                    self.newline(start + INDENT);
+                    self.expression(else_branch);
+                }
+                self.write(")");
+            }
+            Expression::Let(let_expr) => self.binding_form("let", &let_expr.bindings, &let_expr.body, start),
+            Expression::LetFn(letfn) => {
+                self.write("(letfn [");
+                let functions_column = self.column;
+                for (index, function) in letfn.functions.iter().enumerate() {
+                    if index > 0 {
+                        self.newline(functions_column);
+                    }
+                    let function_start = self.column;
+                    self.write(&format!(
+                        "({} {}",
+                        function.name.0,
+                        print_signature(&function.params, &function.variadic_param, &function.return_type)
+                    ));
+                    self.body(&function.body, function_start);
+                    self.write(")");
+                }
+                self.write("]");
+                self.body(&letfn.body, start);
+                self.write(")");
+            }
+            Expression::Loop(loop_expr) => self.binding_form("loop", &loop_expr.bindings, &loop_expr.body, start),
+            Expression::Recur(arguments) => {
+                self.write("(recur");
+                for argument in arguments {
+                    self.nested(argument, start);
+                }
+                self.write(")");
+            }
+            Expression::For(for_expr) => self.iteration("for", for_expr, start),
+            Expression::DoSeq(for_expr) => self.iteration("doseq", for_expr, start),
+            Expression::DoTimes(dotimes) => {
+                self.write(&format!("(dotimes [{} ", dotimes.symbol.0));
+                self.expression(&dotimes.count);
+                self.write("]");
+                self.body(&dotimes.body, start);
+                self.write(")");
+            }
+            Expression::Do(do_expr) => {
+                self.write("(do");
+                self.body(&do_expr.expressions, start);
+                self.write(")");
+            }
+            Expression::Fn(fn_expr) => {
+                self.write("(fn ");
+                self.write(&print_signature(
+                    &fn_expr.params,
+                    &fn_expr.variadic_param,
+                    &fn_expr.return_type,
+                ));
+                self.body(&fn_expr.body, start);
+                self.write(")");
+            }
+            Expression::Def(def_expr) => self.def_expr(def_expr),
+            Expression::Defn(defn_expr) => self.defn_expr(defn_expr),
+            Expression::DefType(deftype) => self.write(&print_deftype(deftype)),
+            Expression::Match(match_expr) => {
+                self.write("(match ");
+                self.expression(&match_expr.expression);
+                for clause in &match_expr.clauses {
+                    self.newline(start + INDENT);
+                    self.match_clause(clause);
+                }
+                self.write(")");
+            }
+            Expression::TryCatch(try_expr) => {
+                self.write("(try");
+                self.body(&try_expr.try_body, start);
+                for clause in &try_expr.catch_clauses {
+                    self.newline(start + INDENT);
+                    self.write(&format!(
+                        "(catch {} {}",
+                        print_catch_pattern(&clause.pattern),
+                        clause.binding.0
+                    ));
+                    self.body(&clause.body, start + INDENT);
+                    self.write(")");
+                }
+                if let Some(finally_body) = &try_expr.finally_body {
+                    self.newline(start + INDENT);
+                    self.write("(finally");
+                    self.body(finally_body, start + INDENT);
+                    self.write(")");
+                }
+                self.write(")");
+            }
+            Expression::WithResource(with_expr) => {
+                self.write(&format!(
+                    "(with-resource [{} {} ",
+                    with_expr.resource_symbol.0,
+                    print_type_expr(&with_expr.resource_type)
+                ));
+                self.expression(&with_expr.resource_init);
+                self.write("]");
+                self.body(&with_expr.body, start);
+                self.write(")");
+            }
+            Expression::Parallel(parallel_expr) => {
+                self.write("(parallel");
+                for binding in &parallel_expr.bindings {
+                    self.newline(start + INDENT);
+                    self.write(&format!(
+                        "[{}{} ",
+                        binding.symbol.0,
+                        print_annotation(&binding.type_annotation)
+                    ));
+                    self.expression(&binding.expression);
+                    self.write("]");
+                }
+                self.write(")");
+            }
+            Expression::Sugar(sugar) => self.sugar(&sugar.form, start),
+            Expression::LogStep(log_step) => {
+                self.write(&format!(
+                    "(log-step :id {}",
+                    print_string(log_step.location.as_deref().unwrap_or(""))
+                ));
+                for value in &log_step.values {
+                    self.nested(value, start);
+                }
+                self.write(")");
+            }
+        }
+    }
+
+    // `(let [...] body)` or `(loop [...] body)`, one binding per line
+    fn binding_form(&mut self, head: &str, bindings: &[LetBinding], body: &[Expression], start: usize) {
+        self.write(&format!("({} [", head));
+        let bindings_column = self.column;
+        for (index, binding) in bindings.iter().enumerate() {
+            if index > 0 {
+                self.newline(bindings_column);
+            }
+            self.write(&print_pattern(&binding.pattern));
+            self.write(" ");
+            self.expression(&binding.value);
+        }
+        self.write("]");
+        self.body(body, start);
+        self.write(")");
+    }
+
+    // `cond` and `case` with one clause per line; the other forms keep their
+    // test, binding or threaded value on the first line
+    fn sugar(&mut self, sugar: &SugarExpr, start: usize) {
+        self.write(&format!("({}", sugar_head(sugar)));
+        match sugar {
+            SugarExpr::Cond(clauses) => {
+                for (test, expr) in clauses {
+                    self.newline(start + INDENT);
+                    let clause_start = self.column;
+                    self.expression(test);
+                    self.nested(expr, clause_start);
+                }
+            }
+            SugarExpr::When { test, body, .. } => {
+                self.write(" ");
+                self.expression(test);
+                self.body(body, start);
+            }
+            SugarExpr::IfLet { binding, then_branch, else_branch } => {
+                self.conditional_binding(binding);
+                self.body(std::slice::from_ref(then_branch.as_ref()), start);
+                if let Some(else_branch) = else_branch {
+                    self.body(std::slice::from_ref(else_branch.as_ref()), start);
+                }
+            }
+            SugarExpr::WhenLet { binding, body } => {
+                self.conditional_binding(binding);
+                self.body(body, start);
+            }
+            SugarExpr::Case { value, clauses, default } => {
+                self.write(" ");
+                self.expression(value);
+                for clause in clauses {
+                    self.newline(start + INDENT);
+                    let clause_start = self.column;
+                    self.write(&print_case_keys(&clause.keys));
+                    self.nested(&clause.body, clause_start);
+                }
+                if let Some(default) = default {
+                    self.body(std::slice::from_ref(default.as_ref()), start);
+                }
+            }
+            SugarExpr::Thread { value, forms, .. } => {
+                self.write(" ");
+                self.expression(value);
+                self.body(forms, start);
+            }
+        }
+        self.write(")");
+    }
+
+    // ` [pattern value]` of `if-let` and `when-let`
+    fn conditional_binding(&mut self, binding: &LetBinding) {
+        self.write(&format!(" [{} ", print_pattern(&binding.pattern)));
+        self.expression(&binding.value);
+        self.write("]");
+    }
+
+    // `(for [...] body)` or `(doseq [...] body)`, one clause per line
+    fn iteration(&mut self, head: &str, for_expr: &ForExpr, start: usize) {
+        self.write(&format!("({} [", head));
+        let clauses_column = self.column;
+        for (index, clause) in for_expr.clauses.iter().enumerate() {
+            if index > 0 {
+                self.newline(clauses_column);
+            }
+            let (prefix, expr) = match clause {
+                IterationClause::Binding { pattern, collection } => (print_pattern(pattern), collection),
+                IterationClause::When(test) => (":when".to_string(), test),
+            };
+            self.write(&prefix);
+            self.write(" ");
+            self.expression(expr);
+        }
+        self.write("]");
+        self.body(&for_expr.body, start);
+        self.write(")");
+    }
+
+    // Each expression on its own line, indented from the form starting at `start`
+    fn body(&mut self, exprs: &[Expression], start: usize) {
+        for expr in exprs {
+            self.newline(start + INDENT);
+            self.expression(expr);
+        }
+    }
+
+    // Elements of a list or vector, one per line, aligned after the opener
+    fn sequence(&mut self, open: &str, items: &[Expression], close: &str) {
+        let start = self.column;
+        self.write(open);
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                self.newline(start + open.len());
+            }
+            self.expression(item);
+        }
+        self.write(close);
+    }
+
+    // Continue on the current line if `expr` fits there, else on a new indented line
+    fn nested(&mut self, expr: &Expression, start: usize) {
+        if let Some(text) = flat(expr) {
+            if self.column + 1 + text.chars().count() <= MAX_WIDTH {
+                self.write(" ");
+                self.write(&text);
+                return;
+            }
+        }
+        self.newline(start + INDENT);
+        self.expression(expr);
+    }
+
+    fn match_clause(&mut self, clause: &MatchClause) {
+        let start = self.column;
+        self.write(&print_match_pattern(&clause.pattern));
+        if let Some(guard) = &clause.guard {
+            self.write(" when ");
+            self.expression(guard);
+        }
+        self.nested(&clause.body, start);
+    }
+
+    fn def_expr(&mut self, def_expr: &DefExpr) {
+        if let Some(text) = flat_def(def_expr) {
+            if self.fits(&text) {
+                self.write(&text);
+                return;
+            }
+        }
+        let start = self.column;
+        self.write(&format!(
+            "(def {}{}",
+            def_expr.symbol.0,
+            print_annotation(&def_expr.type_annotation)
+        ));
+        self.nested(&def_expr.value, start);
+        self.write(")");
+    }
+
+    fn defn_expr(&mut self, defn_expr: &DefnExpr) {
+        let start = self.column;
+        self.write(&format!(
+            "(defn {} {}",
+            defn_expr.name.0,
+            print_signature(
+                &defn_expr.params,
+                &defn_expr.variadic_param,
+                &defn_expr.return_type
+            )
+        ));
+        self.body(&defn_expr.body, start);
+        self.write(")");
+    }
+
+    fn task_definition(&mut self, task: &TaskDefinition) {
+        let start = self.column;
+        self.write("(task");
+        let strings = [
+            (":id", &task.id),
+            (":source", &task.source),
+            (":timestamp", &task.timestamp),
+        ];
+        for (key, value) in strings {
+            if let Some(value) = value {
+                self.newline(start + INDENT);
+                self.write(&format!("{} {}", key, print_string(value)));
+            }
+        }
+        let properties = [
+            (":intent", &task.intent),
+            (":contracts", &task.contracts),
+            (":plan", &task.plan),
+            (":execution-trace", &task.execution_trace),
+            (":metadata", &task.metadata),
+        ];
+        for (key, value) in properties {
+            if let Some(value) = value {
+                self.newline(start + INDENT);
+                self.write(key);
+                self.nested(value, start + INDENT);
+            }
+        }
+        self.write(")");
+    }
+
+    fn module_definition(&mut self, module: &ModuleDefinition) {
+        let start = self.column;
+        self.write(&format!("(module {}", module.name.0));
+        if let Some(exports) = &module.exports {
+            self.newline(start + INDENT);
+            let names: Vec<&str> = exports.iter().map(|s| s.0.as_str()).collect();
+            self.write(&format!("(:exports [{}])", names.join(" ")));
+        }
+        for definition in &module.definitions {
+            self.newline(start + INDENT);
+            match definition {
+                ModuleLevelDefinition::Def(def_expr) => self.def_expr(def_expr),
+                ModuleLevelDefinition::Defn(defn_expr) => self.defn_expr(defn_expr),
+                ModuleLevelDefinition::DefType(deftype) => self.write(&print_deftype(deftype)),
+                ModuleLevelDefinition::Import(import) => self.write(&print_import(import)),
+            }
+        }
+        self.write(")");
+    }
+}
+
+fn print_import(import: &ImportDefinition) -> String {
+    let mut text = format!("(import {}", import.module_name.0);
+    if let Some(alias) = &import.alias {
+        text.push_str(&format!(" :as {}", alias.0));
+    }
+    if let Some(only) = &import.only {
+        let names: Vec<&str> = only.iter().map(|s| s.0.as_str()).collect();
+        text.push_str(&format!(" :only [{}]", names.join(" ")));
+    }
+    text.push(')');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    // Printing a parsed program and parsing it again must give the same AST
+    fn assert_round_trip(source: &str) -> String {
+        let ast = parse(source).unwrap_or_else(|e| panic!("failed to parse {:?}: {:?}", source, e));
+        let printed = print_program(&ast);
+        let reparsed = parse(&printed)
+            .unwrap_or_else(|e| panic!("failed to reparse printed source:\n{}\n{:?}", printed, e));
+        assert_eq!(reparsed, ast, "round trip changed the AST of:\n{}", printed);
+        assert_eq!(print_program(&reparsed), printed, "printing is not idempotent");
+        printed
+    }
+
+    #[test]
+    fn test_round_trip_expressions() {
+        let sources = [
+            "42",
+            "-3.5",
+            "1.0e20",
+            "\"quote \\\" backslash \\\\ newline \\n tab \\t\"",
+            "[1 :two \"three\" nil true false]",
+            "{:b 2 :a 1 \"s\" [3] 7 {}}",
+            "(+ 1 (* 2 3))",
+            "((fn [x] x) 1)",
+            "(1 2 3)",
+            "()",
+            "(if (> x 0) :pos :neg)",
+            "(if ready (go))",
+            "(let [x 1 [a b & rest :as all] (range) {:keys [k] :v v & more :as m} cfg _ 0] (+ x a k v))",
+            "(fn [{:keys [host port] :or {port 8080 host (env \"HOST\")} & opts}] [host port opts])",
+            "(do (tool:log \"a\") (tool:log \"b\"))",
+            "(fn [a :int [b c] & rest :[:vector int]] :int (+ a b c))",
+            "(def x :[:map [:a int] [:b string ?] [:* any]] {:a 1})",
+            "(defn add [a b] (+ a b))",
+            "(match x 1 :one [a & r] when (> a 0) (f a r) {:k v} v {:k v & more} more _ (do (log) nil))",
+            "(match x :int (inc x) (:as s :string) s [:vector int] :ints :ok :ok _ nil)",
+            "(try (risky) (catch :error/network e (retry e)) (catch :error e nil) (finally (cleanup) (done)))",
+            "(with-resource [f FileHandle (tool:open-file \"data.csv\")] (read f) (tool:close-file f))",
+            "(parallel [a (fetch 1)] [b :[:union int string] (fetch 2)])",
+            "(log-step :id \"step-1\" (compute))",
+            "(def f :[:=> [int & string] [:tuple int bool]] g)",
+            "(def r :[:resource my.pkg/Handle] h)",
+            "(def v :[:val \"x\"] \"x\")",
+            "(def i :[:and Named] thing)",
+            "(def n :[:and int [:> 0] [:<= 100]] 5)",
+            "(def s :[:and string [:min-length 1] [:matches-regex \"^[a-z]+$\"]] \"a\")",
+            "(def l :[:and keyword [:one-of :low :high]] :low)",
+            "(def m :[:array float [2 ? 3]] (load))",
+            "(def v :[:array int] (load))",
+            "(deftype User [:map [:id UserId] [:email string ?]])",
+            "(def first-of :[:=> [[:vector T]] T] (load))",
+            "(loop [page 1 [item & more] items] (if (done? page) item (recur (inc page) more)))",
+            "(for [x xs :when (odd? x) [k v] (entries x)] [k v])",
+            "(doseq [{:keys [id]} users] (tool:log id))",
+            "(dotimes [i 3] (tool:log i))",
+            "(letfn [(ev? [n] (if (= n 0) true (od? (- n 1)))) (od? [n :int] :bool (if (= n 0) false (ev? (- n 1))))] (ev? 4))",
+            "(cond (< x 0) :neg (= x 0) :zero :else :pos)",
+            "(when (ready?) (tool:log \"go\") (go))",
+            "(when-not done (retry))",
+            "(if-let [{:keys [id]} (find-user)] id :anonymous)",
+            "(when-let [[first-item] (items)] (tool:log first-item))",
+            "(case status :ok 1 (:error :failed) 2 nil 3 0)",
+            "(-> cfg :db (get :host) (str \":5432\"))",
+            "(->> xs (filter odd?) (reduce +))",
+            "(some-> (find-user) :address :city)",
+        ];
+        for source in sources {
+            assert_round_trip(source);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_task_and_module() {
+        let source = r#"
+            (module my.app.core
+              (:exports [run helper])
+              (import my.lib :as lib :only [a b])
+              (deftype Limit [:and int [:> 0]])
+              (def limit :Limit 10)
+              (defn helper [x] (lib/a x)))
+            (task :id "t-1" :source "planner" :timestamp "2024-01-01T00:00:00Z"
+              :metadata {:owner "ops"}
+              :intent (analyze "report")
+              :contracts {:input-schema [:map [:path string]] :output-schema string}
+              :plan (do (step-one) (step-two))
+              :execution-trace [{:step 1}])
+        "#;
+        assert_round_trip(source);
+    }
+
+    #[test]
+    fn test_block_forms_use_stable_indentation() {
+        let expr = parse_expression(
+            "(let [x 1 y (match x 1 :one _ :other)] (try (use x y) (catch :error/io e (log e)) (finally (close))))",
+        )
+        .unwrap();
+        assert_eq!(
+            print_expression(&expr),
+            "(let [x 1\n      y (match x\n          1 :one\n          _ :other)]\n  (try\n    (use x y)\n    (catch :error/io e\n      (log e))\n    (finally\n      (close))))"
+        );
+
+        let expr = parse_expression(
+            "(with-resource [h Handle (open)] (parallel [a (f h)] [b :int (g h)]))",
+        )
+        .unwrap();
+        assert_eq!(
+            print_expression(&expr),
+            "(with-resource [h Handle (open)]\n  (parallel\n    [a (f h)]\n    [b :int (g h)]))"
+        );
+    }
+
+    #[test]
+    fn test_short_forms_stay_on_one_line_and_long_calls_break() {
+        let expr = parse_expression("(  let [ x   1 ]\n  (+ x   1))").unwrap();
+        assert_eq!(print_expression(&expr), "(let [x 1] (+ x 1))");
+
+        let long_arg = "a".repeat(40);
+        let expr = parse_expression(&format!("(str \"{0}\" \"{0}\")", long_arg)).unwrap();
+        assert_eq!(
+            print_expression(&expr),
+            format!("(str\n  \"{0}\"\n  \"{0}\")", long_arg)
+        );
+        assert_round_trip(&format!("(str \"{0}\" \"{0}\")", long_arg));
+    }
+
+    #[test]
+    fn test_format_source_normalizes_layout() {
+        let formatted = format_source("(def  m {:z 1 :a   2}) ; comment\n\n\n(task :plan (do (a)) :id \"x\")").unwrap();
+        assert_eq!(
+            formatted,
+            "(def m {:a 2 :z 1})\n\n(task\n  :id \"x\"\n  :plan\n    (do\n      (a)))\n"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_repository_sources() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut pending = vec![root.join("test_modules"), root.join("examples")];
+        let mut checked = 0;
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "rtfs") {
+                    let source = std::fs::read_to_string(&path).unwrap();
+                    if parse(&source).is_ok() {
+                        assert_round_trip(&source);
+                        checked += 1;
+                    }
+                }
+            }
+        }
+        assert!(checked > 0);
+    }
+}
@@ -0,0 +1,626 @@
+// Error-recovering parser
+//
+// `parse` stops at the first error. This module splits the input into top-level
+// forms by scanning balanced delimiters, parses each form on its own and keeps
+// going after failures, so one pass reports every problem in a file together
+// with whatever forms did parse.
+
+use super::{parse, PestParseError, Rule};
+use crate::ast::{Span, TopLevel};
+use pest::error::{ErrorVariant, InputLocation};
+use pest::Position;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    UnclosedDelimiter,
+    UnexpectedClosingDelimiter,
+    MismatchedDelimiter,
+    UnterminatedString,
+    SyntaxError,
+    InvalidForm,
+}
+
+impl DiagnosticCode {
+    // Stable identifier for tooling (e.g. a generate-and-repair loop)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticCode::UnclosedDelimiter => "P001",
+            DiagnosticCode::UnexpectedClosingDelimiter => "P002",
+            DiagnosticCode::MismatchedDelimiter => "P003",
+            DiagnosticCode::UnterminatedString => "P004",
+            DiagnosticCode::SyntaxError => "P005",
+            DiagnosticCode::InvalidForm => "P006",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: DiagnosticCode,
+    pub span: Span,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error[{}] at {}:{}: {}",
+            self.code.as_str(),
+            self.span.line,
+            self.span.column,
+            self.message
+        )?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n  hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+// Result of a recovering parse: every top-level item that parsed, in source
+// order, plus the diagnostics for everything that did not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredParse {
+    pub items: Vec<TopLevel>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl RecoveredParse {
+    pub fn has_errors(&self) -> bool {
+        !self.diagnostics.is_empty()
+    }
+}
+
+// Parse a program, resynchronizing at balanced delimiters and top-level
+// `task`/`module` forms instead of stopping at the first error.
+pub fn parse_with_recovery(input: &str) -> RecoveredParse {
+    if let Ok(items) = parse(input) {
+        return RecoveredParse {
+            items,
+            diagnostics: Vec::new(),
+        };
+    }
+
+    let blank: String = input
+        .chars()
+        .map(|c| if c == '\n' { c.to_string() } else { " ".repeat(c.len_utf8()) })
+        .collect();
+    let mut recovery = Recovery {
+        input,
+        buffer: blank.clone(),
+        blank,
+        items: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    let forms = recovery.scan();
+    for form in &forms {
+        recovery.recover_top_level(form);
+    }
+
+    let mut diagnostics = recovery.diagnostics;
+    diagnostics.sort_by_key(|d| d.span.start);
+    RecoveredParse {
+        items: recovery.items,
+        diagnostics,
+    }
+}
+
+// --- Delimiter Scanning ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormKind {
+    List,
+    Vector,
+    Map,
+    Atom,
+}
+
+// A top-level form or a delimited group nested in one
+#[derive(Debug)]
+struct Form {
+    kind: FormKind,
+    start: usize,
+    end: usize,
+    closed: bool, // false if the closing delimiter is missing
+    children: Vec<Form>,
+}
+
+fn closing_delimiter(kind: FormKind) -> char {
+    match kind {
+        FormKind::List => ')',
+        FormKind::Vector => ']',
+        FormKind::Map => '}',
+        FormKind::Atom => ' ',
+    }
+}
+
+fn opening_delimiter(kind: FormKind) -> char {
+    match kind {
+        FormKind::List => '(',
+        FormKind::Vector => '[',
+        FormKind::Map => '{',
+        FormKind::Atom => ' ',
+    }
+}
+
+fn is_atom_end(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || b"()[]{}\";".contains(&byte)
+}
+
+struct Recovery<'a> {
+    input: &'a str,
+    blank: String,  // `input` with everything but newlines blanked out
+    buffer: String, // `blank` with the form being parsed copied back in
+    items: Vec<TopLevel>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Recovery<'a> {
+    // Split the input into top-level forms, reporting delimiter problems
+    fn scan(&mut self) -> Vec<Form> {
+        let bytes = self.input.as_bytes();
+        let mut top = Vec::new();
+        let mut stack: Vec<Form> = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b';' => {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                }
+                b'"' => {
+                    let start = i;
+                    i += 1;
+                    while i < bytes.len() && bytes[i] != b'"' {
+                        if bytes[i] == b'\\' {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    i = i.min(bytes.len());
+                    let closed = i < bytes.len();
+                    if !closed {
+                        self.report(
+                            DiagnosticCode::UnterminatedString,
+                            start,
+                            start + 1,
+                            "unterminated string literal".to_string(),
+                            Some("add a closing '\"'".to_string()),
+                        );
+                    } else {
+                        i += 1;
+                    }
+                    if stack.is_empty() {
+                        top.push(Form {
+                            kind: FormKind::Atom,
+                            start,
+                            end: i,
+                            closed,
+                            children: Vec::new(),
+                        });
+                    }
+                }
+                byte @ (b'(' | b'[' | b'{') => {
+                    if byte == b'(' && !stack.is_empty() && self.starts_top_level_form(i) {
+                        // A `(task` or `(module` at the start of a line begins a new
+                        // top-level form: whatever is still open was never closed.
+                        self.close_unclosed(&mut stack, &mut top, i);
+                    }
+                    let kind = match byte {
+                        b'(' => FormKind::List,
+                        b'[' => FormKind::Vector,
+                        _ => FormKind::Map,
+                    };
+                    stack.push(Form {
+                        kind,
+                        start: i,
+                        end: i + 1,
+                        closed: false,
+                        children: Vec::new(),
+                    });
+                    i += 1;
+                }
+                byte @ (b')' | b']' | b'}') => {
+                    let found = byte as char;
+                    match stack.iter().rposition(|f| closing_delimiter(f.kind) == found) {
+                        Some(index) if index + 1 == stack.len() => {
+                            let mut form = stack.pop().unwrap();
+                            form.end = i + 1;
+                            form.closed = true;
+                            push_form(&mut stack, &mut top, form);
+                        }
+                        Some(index) => {
+                            // Forms opened after the matching one are missing their closers
+                            while stack.len() > index + 1 {
+                                let mut form = stack.pop().unwrap();
+                                form.end = i;
+                                self.report_unclosed(&form, Some(found));
+                                push_form(&mut stack, &mut top, form);
+                            }
+                            let mut form = stack.pop().unwrap();
+                            form.end = i + 1;
+                            form.closed = true;
+                            push_form(&mut stack, &mut top, form);
+                        }
+                        None => match stack.pop() {
+                            Some(mut open) => {
+                                let expected = closing_delimiter(open.kind);
+                                let (line, column) = self.line_col(open.start);
+                                self.report(
+                                    DiagnosticCode::MismatchedDelimiter,
+                                    i,
+                                    i + 1,
+                                    format!("mismatched closing delimiter '{}'", found),
+                                    Some(format!(
+                                        "expected '{}' to close the '{}' opened at {}:{}",
+                                        expected,
+                                        opening_delimiter(open.kind),
+                                        line,
+                                        column
+                                    )),
+                                );
+                                // Treat the wrong closer as the end of the innermost form
+                                open.end = i + 1;
+                                push_form(&mut stack, &mut top, open);
+                            }
+                            None => {
+                                self.report(
+                                    DiagnosticCode::UnexpectedClosingDelimiter,
+                                    i,
+                                    i + 1,
+                                    format!("unexpected closing delimiter '{}'", found),
+                                    Some("remove it or add the matching opening delimiter".to_string()),
+                                );
+                            }
+                        },
+                    }
+                    i += 1;
+                }
+                byte if byte.is_ascii_whitespace() => i += 1,
+                _ => {
+                    let start = i;
+                    while i < bytes.len() && !is_atom_end(bytes[i]) {
+                        i += 1;
+                    }
+                    if stack.is_empty() {
+                        top.push(Form {
+                            kind: FormKind::Atom,
+                            start,
+                            end: i,
+                            closed: true,
+                            children: Vec::new(),
+                        });
+                    }
+                }
+            }
+        }
+
+        self.close_unclosed(&mut stack, &mut top, bytes.len());
+        top
+    }
+
+    fn starts_top_level_form(&self, offset: usize) -> bool {
+        let at_line_start = offset == 0 || self.input.as_bytes()[offset - 1] == b'\n';
+        let rest = &self.input[offset + 1..];
+        at_line_start
+            && ["task", "module"].iter().any(|keyword| {
+                rest.starts_with(keyword)
+                    && rest[keyword.len()..]
+                        .bytes()
+                        .next()
+                        .is_none_or(is_atom_end)
+            })
+    }
+
+    fn close_unclosed(&mut self, stack: &mut Vec<Form>, top: &mut Vec<Form>, end: usize) {
+        while let Some(mut form) = stack.pop() {
+            form.end = end;
+            self.report_unclosed(&form, None);
+            push_form(stack, top, form);
+        }
+    }
+
+    fn report_unclosed(&mut self, form: &Form, found: Option<char>) {
+        let open = opening_delimiter(form.kind);
+        let close = closing_delimiter(form.kind);
+        let message = match found {
+            Some(found) => format!("unclosed '{}' before '{}'", open, found),
+            None => format!("unclosed '{}'", open),
+        };
+        self.report(
+            DiagnosticCode::UnclosedDelimiter,
+            form.start,
+            form.start + 1,
+            message,
+            Some(format!("add a matching '{}'", close)),
+        );
+    }
+
+    // --- Form Recovery ---
+
+    fn recover_top_level(&mut self, form: &Form) {
+        if form.closed {
+            match self.parse_isolated(form) {
+                Ok(items) => {
+                    self.items.extend(items);
+                    return;
+                }
+                Err(error) => {
+                    if !self.recover_children(form) {
+                        self.report_parse_error(error, form);
+                    }
+                    return;
+                }
+            }
+        }
+        // Delimiter problems were already reported; look for more inside
+        self.recover_children(form);
+    }
+
+    // Check the nested lists of a form that failed to parse. Returns true if a
+    // problem was found (and reported) below this form.
+    fn recover_children(&mut self, form: &Form) -> bool {
+        let mut found = false;
+        for child in &form.children {
+            if !child.closed {
+                found = true;
+                self.recover_children(child);
+            } else if child.kind == FormKind::List {
+                if let Err(error) = self.parse_isolated(child) {
+                    found = true;
+                    if !self.recover_children(child) {
+                        self.report_parse_error(error, child);
+                    }
+                }
+            } else {
+                found |= self.recover_children_quietly(child);
+            }
+        }
+        found
+    }
+
+    // Vectors and maps are not parsed on their own (they may be patterns or
+    // types), but lists nested in them still are.
+    fn recover_children_quietly(&mut self, form: &Form) -> bool {
+        let mut found = false;
+        for child in &form.children {
+            if child.kind == FormKind::List && child.closed {
+                if let Err(error) = self.parse_isolated(child) {
+                    found = true;
+                    if !self.recover_children(child) {
+                        self.report_parse_error(error, child);
+                    }
+                }
+            } else {
+                found |= self.recover_children_quietly(child);
+            }
+        }
+        found
+    }
+
+    // Parse `form` with everything else blanked out, so pest positions stay absolute.
+    // The blanked input is built once; only the form's own text is copied in and out.
+    fn parse_isolated(&mut self, form: &Form) -> Result<Vec<TopLevel>, PestParseError> {
+        let range = form.start..form.end;
+        self.buffer.replace_range(range.clone(), &self.input[range.clone()]);
+        let result = parse(&self.buffer);
+        self.buffer.replace_range(range.clone(), &self.blank[range]);
+        result
+    }
+
+    fn report_parse_error(&mut self, error: PestParseError, form: &Form) {
+        match error {
+            PestParseError::PestError(error) => {
+                let (start, end) = match error.location {
+                    InputLocation::Pos(pos) => (pos, pos),
+                    InputLocation::Span(span) => span,
+                };
+                let found = self.input[start..]
+                    .split(|c: char| c.is_whitespace() || "()[]{}".contains(c))
+                    .next()
+                    .filter(|token| !token.is_empty())
+                    .map(|token| token.to_string())
+                    .or_else(|| self.input[start..].chars().next().map(|c| c.to_string()));
+                let end = end.max(start + found.as_ref().map_or(0, |t| t.len()));
+                let message = match &found {
+                    Some(token) => format!("unexpected '{}'", token),
+                    None => "unexpected end of input".to_string(),
+                };
+                self.report(
+                    DiagnosticCode::SyntaxError,
+                    start,
+                    end,
+                    message,
+                    expected_hint(&error.variant),
+                );
+            }
+            other => {
+                self.report(
+                    DiagnosticCode::InvalidForm,
+                    form.start,
+                    form.end,
+                    describe_build_error(&other),
+                    None,
+                );
+            }
+        }
+    }
+
+    fn report(
+        &mut self,
+        code: DiagnosticCode,
+        start: usize,
+        end: usize,
+        message: String,
+        hint: Option<String>,
+    ) {
+        let span = self.span(start, end);
+        self.diagnostics.push(Diagnostic {
+            code,
+            span,
+            message,
+            hint,
+        });
+    }
+
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        Position::new(self.input, offset)
+            .map(|pos| pos.line_col())
+            .unwrap_or((1, 1))
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        let end = end.min(self.input.len()).max(start);
+        let (line, column) = self.line_col(start);
+        let (end_line, end_column) = self.line_col(end);
+        Span {
+            file: None,
+            start,
+            end,
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+}
+
+fn push_form(stack: &mut [Form], top: &mut Vec<Form>, form: Form) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(form),
+        None => top.push(form),
+    }
+}
+
+fn expected_hint(variant: &ErrorVariant<Rule>) -> Option<String> {
+    match variant {
+        ErrorVariant::ParsingError { positives, .. } if !positives.is_empty() => {
+            let expected: Vec<String> = positives.iter().map(|rule| format!("{:?}", rule)).collect();
+            Some(format!("expected one of: {}", expected.join(", ")))
+        }
+        ErrorVariant::CustomError { message } => Some(message.clone()),
+        _ => None,
+    }
+}
+
+fn describe_build_error(error: &PestParseError) -> String {
+    match error {
+        PestParseError::UnexpectedRule {
+            expected,
+            found,
+            rule_text,
+        } => format!("expected {}, found {} '{}'", expected, found, rule_text),
+        PestParseError::MissingToken(msg)
+        | PestParseError::InvalidInput(msg)
+        | PestParseError::UnsupportedRule(msg)
+        | PestParseError::InvalidLiteral(msg)
+        | PestParseError::InvalidEscapeSequence(msg)
+        | PestParseError::CustomError(msg) => msg.clone(),
+        PestParseError::PestError(error) => error.variant.message().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Expression;
+
+    fn codes(result: &RecoveredParse) -> Vec<&'static str> {
+        result.diagnostics.iter().map(|d| d.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_valid_input_has_no_diagnostics() {
+        let result = parse_with_recovery("(def x 1)\n(+ x 2)");
+        assert!(!result.has_errors());
+        assert_eq!(result.items.len(), 2);
+    }
+
+    #[test]
+    fn test_reports_every_broken_form_and_keeps_the_rest() {
+        let input = "(def a 1)\n(foo #bad)\n(def b 2)\n(def x ~)\n(def c 3)";
+        let result = parse_with_recovery(input);
+
+        assert_eq!(result.items.len(), 3);
+        assert_eq!(codes(&result), vec!["P005", "P005"]);
+        assert_eq!((result.diagnostics[0].span.line, result.diagnostics[0].span.column), (2, 6));
+        assert_eq!(result.diagnostics[0].message, "unexpected '#bad'");
+        assert_eq!((result.diagnostics[1].span.line, result.diagnostics[1].span.column), (4, 8));
+        assert!(result.diagnostics[0].hint.is_some());
+    }
+
+    #[test]
+    fn test_resynchronizes_at_top_level_task_after_unclosed_form() {
+        let input = "(module my.mod\n  (defn f [x] (+ x 1)\n(task :id \"t1\" :plan (f 1))";
+        let result = parse_with_recovery(input);
+
+        assert_eq!(codes(&result), vec!["P001", "P001"]);
+        assert_eq!((result.diagnostics[0].span.line, result.diagnostics[0].span.column), (1, 1));
+        assert_eq!((result.diagnostics[1].span.line, result.diagnostics[1].span.column), (2, 3));
+        assert_eq!(result.items.len(), 1);
+        assert!(matches!(&result.items[0], TopLevel::Task(task) if task.id.as_deref() == Some("t1")));
+    }
+
+    #[test]
+    fn test_reports_errors_nested_inside_a_form() {
+        let input = "(do\n  (let [x 1] (foo #bad))\n  (bar ~))";
+        let result = parse_with_recovery(input);
+
+        assert!(result.items.is_empty());
+        assert_eq!(codes(&result), vec!["P005", "P005"]);
+        assert_eq!(result.diagnostics[0].span.line, 2);
+        assert_eq!(result.diagnostics[1].span.line, 3);
+    }
+
+    #[test]
+    fn test_forms_are_parsed_in_isolation() {
+        // Each form sees only its own text, including multi-byte characters around it
+        let input = "(def s \"é\")\n(do (foo \"ü\" #bad) (bar ~))\n(def t \"ö\")";
+        let result = parse_with_recovery(input);
+
+        assert_eq!(result.items.len(), 2);
+        assert_eq!(codes(&result), vec!["P005", "P005"]);
+        assert_eq!((result.diagnostics[0].span.line, result.diagnostics[0].span.column), (2, 14));
+        assert_eq!(result.diagnostics[0].message, "unexpected '#bad'");
+        assert_eq!((result.diagnostics[1].span.line, result.diagnostics[1].span.column), (2, 25));
+    }
+
+    #[test]
+    fn test_delimiter_diagnostics() {
+        let result = parse_with_recovery(")\n(def x [1 2)\n\"open");
+        assert_eq!(codes(&result), vec!["P002", "P001", "P004"]);
+        assert_eq!(result.diagnostics[1].message, "unclosed '[' before ')'");
+
+        let result = parse_with_recovery("(def x [1 2})");
+        assert_eq!(codes(&result), vec!["P003"]);
+        assert_eq!(
+            result.diagnostics[0].hint.as_deref(),
+            Some("expected ']' to close the '[' opened at 1:8")
+        );
+    }
+
+    #[test]
+    fn test_delimiters_in_strings_and_comments_are_ignored() {
+        let input = "(def s \")(\") ; ]]\n(foo))\n(def t 1)";
+        let result = parse_with_recovery(input);
+
+        assert_eq!(codes(&result), vec!["P002"]);
+        assert_eq!(result.diagnostics[0].span.line, 2);
+        assert_eq!(result.items.len(), 3);
+        assert!(matches!(&result.items[1], TopLevel::Expression(e) if matches!(e.unspanned(), Expression::FunctionCall { .. })));
+    }
+
+    #[test]
+    fn test_diagnostic_display() {
+        let result = parse_with_recovery("(def x [1 2})");
+        assert_eq!(
+            result.diagnostics[0].to_string(),
+            "error[P003] at 1:12: mismatched closing delimiter '}'\n  hint: expected ']' to close the '[' opened at 1:8"
+        );
+    }
+}
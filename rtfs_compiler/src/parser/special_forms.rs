@@ -3,22 +3,31 @@ use pest::iterators::{Pair, Pairs};
 
 // AST Node Imports - Ensure all used AST nodes are listed here
 use crate::ast::{
+    CaseClause,
     CatchClause,
     CatchPattern,
     DefExpr,
+    DefTypeExpr,
     DefnExpr,
     DoExpr,
     Expression, // Ensure this is correctly in scope
+    DoTimesExpr,
     FnExpr,    IfExpr,
+    ForExpr,
+    IterationClause,
     LetBinding,
     LetExpr,
+    LetFnExpr,
     LogStepExpr,
+    LoopExpr,
     MatchClause,
     MatchExpr,
     ParallelBinding,
     ParallelExpr,
     ParamDef,
     Pattern,
+    SugarExpr,
+    ThreadKind,
     TryCatchExpr,
     TypeExpr,
     WithResourceExpr,
@@ -26,7 +35,7 @@ use crate::ast::{
 
 // Builder function imports from sibling modules
 // CORRECTED IMPORT: build_keyword_from_pair -> build_keyword
-use super::common::{build_keyword, build_pattern, build_symbol};
+use super::common::{build_keyword, build_literal, build_pattern, build_symbol};
 use super::expressions::build_expression;
 use super::types::build_type_expr; // For type annotations
 
@@ -369,7 +378,8 @@ pub(super) fn build_fn_expr(mut pairs: Pairs<Rule>) -> Result<FnExpr, PestParseE
         return Err(PestParseError::InvalidInput(
             "fn requires at least one body expression".to_string(),
         ));
-    }    Ok(FnExpr {
+    }
+    check_recur_body(&body, false, 0)?;    Ok(FnExpr {
         params,
         variadic_param,
         body,
@@ -435,6 +445,32 @@ pub(super) fn build_def_expr(mut pairs: Pairs<Rule>) -> Result<DefExpr, PestPars
     })
 }
 
+pub(super) fn build_deftype_expr(mut pairs: Pairs<Rule>) -> Result<DefTypeExpr, PestParseError> {
+    let mut significant = pairs.by_ref().filter(|p| {
+        !matches!(p.as_rule(), Rule::deftype_keyword | Rule::WHITESPACE | Rule::COMMENT)
+    });
+
+    let name_pair = significant
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("deftype requires a name".to_string()))?;
+    if name_pair.as_rule() != Rule::symbol {
+        return Err(PestParseError::InvalidInput(format!(
+            "Expected symbol for deftype, found {:?}",
+            name_pair.as_rule()
+        )));
+    }
+    let name = build_symbol(name_pair)?;
+
+    let type_pair = significant
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("deftype requires a type".to_string()))?;
+
+    Ok(DefTypeExpr {
+        name,
+        type_expr: build_type_expr(type_pair)?,
+    })
+}
+
 pub(super) fn build_defn_expr(mut pairs: Pairs<Rule>) -> Result<DefnExpr, PestParseError> {
     // Consume defn_keyword if present
     if let Some(p) = pairs.peek() {
@@ -592,6 +628,7 @@ pub(super) fn build_defn_expr(mut pairs: Pairs<Rule>) -> Result<DefnExpr, PestPa
             "defn requires at least one body expression".to_string(),
         ));
     }
+    check_recur_body(&body, false, 0)?;
 
     Ok(DefnExpr {
         name,
@@ -932,9 +969,20 @@ pub(super) fn build_try_catch_expr(mut pairs: Pairs<Rule>) -> Result<TryCatchExp
 // AST: enum CatchPattern { Keyword(Keyword), Type(TypeExpr), Symbol(Symbol) }
 fn build_catch_pattern(pair: Pair<Rule>) -> Result<CatchPattern, PestParseError> {
     match pair.as_rule() {
-        Rule::type_expr => Ok(CatchPattern::Type(build_type_expr(pair)?)),
         Rule::keyword => Ok(CatchPattern::Keyword(build_keyword(pair)?)),
         Rule::symbol => Ok(CatchPattern::Symbol(build_symbol(pair)?)),
+        // `type_expr` is silent, so types arrive as the rule of the type itself;
+        // a bare name such as `Exception` is a `primitive_type`
+        Rule::primitive_type
+        | Rule::vector_type
+        | Rule::map_type
+        | Rule::tuple_type
+        | Rule::function_type
+        | Rule::resource_type
+        | Rule::union_type
+        | Rule::intersection_type
+        | Rule::literal_type
+        | Rule::array_type => Ok(CatchPattern::Type(build_type_expr(pair)?)),
         unknown_rule => Err(PestParseError::InvalidInput(format!(
             "Invalid rule for catch_pattern: {:?}, content: '{}'",
             unknown_rule,
@@ -1147,3 +1195,330 @@ pub(super) fn build_log_step_expr(mut pairs: Pairs<Rule>) -> Result<LogStepExpr,
         location: Some(label),
     })
 }
+
+/// Build `letfn`: each function has the shape of a `defn` without the keyword
+pub(super) fn build_letfn_expr(pairs: Pairs<Rule>) -> Result<LetFnExpr, PestParseError> {
+    let mut functions = Vec::new();
+    let mut body = Vec::new();
+    for pair in pairs.filter(|p| !matches!(p.as_rule(), Rule::letfn_keyword | Rule::WHITESPACE | Rule::COMMENT)) {
+        match pair.as_rule() {
+            Rule::letfn_function => functions.push(build_defn_expr(pair.into_inner())?),
+            _ => body.push(build_expression(pair)?),
+        }
+    }
+    Ok(LetFnExpr { functions, body })
+}
+
+pub(super) fn build_loop_expr(pairs: Pairs<Rule>) -> Result<LoopExpr, PestParseError> {
+    let mut significant = pairs.filter(|p| {
+        !matches!(p.as_rule(), Rule::loop_keyword | Rule::WHITESPACE | Rule::COMMENT)
+    });
+
+    let bindings_pair = significant
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("loop requires a bindings vector".to_string()))?;
+    let mut bindings = Vec::new();
+    let mut binding_pairs = bindings_pair.into_inner();
+    while let Some(pattern_pair) = binding_pairs.next() {
+        let value_pair = binding_pairs.next().ok_or_else(|| {
+            PestParseError::InvalidInput("loop binding requires a value".to_string())
+        })?;
+        bindings.push(LetBinding {
+            pattern: build_pattern(pattern_pair)?,
+            type_annotation: None,
+            value: Box::new(build_expression(value_pair)?),
+        });
+    }
+
+    let body = significant.map(build_expression).collect::<Result<Vec<_>, _>>()?;
+    check_recur_body(&body, true, bindings.len())?;
+    Ok(LoopExpr { bindings, body })
+}
+
+pub(super) fn build_recur_expr(pairs: Pairs<Rule>) -> Result<Vec<Expression>, PestParseError> {
+    pairs
+        .filter(|p| !matches!(p.as_rule(), Rule::recur_keyword | Rule::WHITESPACE | Rule::COMMENT))
+        .map(build_expression)
+        .collect()
+}
+
+/// Build the bindings vector and body shared by `for` and `doseq`
+pub(super) fn build_for_expr(pairs: Pairs<Rule>) -> Result<ForExpr, PestParseError> {
+    let mut significant = pairs.filter(|p| {
+        !matches!(
+            p.as_rule(),
+            Rule::for_keyword | Rule::doseq_keyword | Rule::WHITESPACE | Rule::COMMENT
+        )
+    });
+
+    let bindings_pair = significant
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("expected a bindings vector".to_string()))?;
+    let mut clauses = Vec::new();
+    let mut clause_pairs = bindings_pair.into_inner();
+    while let Some(pair) = clause_pairs.next() {
+        if pair.as_rule() == Rule::iteration_when {
+            let test_pair = pair.into_inner().next().ok_or_else(|| {
+                PestParseError::InvalidInput(":when requires a test expression".to_string())
+            })?;
+            clauses.push(IterationClause::When(Box::new(build_expression(test_pair)?)));
+            continue;
+        }
+        let collection_pair = clause_pairs.next().ok_or_else(|| {
+            PestParseError::InvalidInput("iteration binding requires a collection".to_string())
+        })?;
+        clauses.push(IterationClause::Binding {
+            pattern: build_pattern(pair)?,
+            collection: Box::new(build_expression(collection_pair)?),
+        });
+    }
+    if !matches!(clauses.first(), Some(IterationClause::Binding { .. })) {
+        return Err(PestParseError::InvalidInput(
+            "iteration bindings must start with a binding".to_string(),
+        ));
+    }
+
+    let body = significant.map(build_expression).collect::<Result<Vec<_>, _>>()?;
+    Ok(ForExpr { clauses, body })
+}
+
+pub(super) fn build_dotimes_expr(pairs: Pairs<Rule>) -> Result<DoTimesExpr, PestParseError> {
+    let mut significant = pairs.filter(|p| {
+        !matches!(p.as_rule(), Rule::dotimes_keyword | Rule::WHITESPACE | Rule::COMMENT)
+    });
+
+    let symbol_pair = significant
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("dotimes requires a symbol".to_string()))?;
+    let count_pair = significant
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("dotimes requires a count".to_string()))?;
+
+    Ok(DoTimesExpr {
+        symbol: build_symbol(symbol_pair)?,
+        count: Box::new(build_expression(count_pair)?),
+        body: significant.map(build_expression).collect::<Result<Vec<_>, _>>()?,
+    })
+}
+
+// The pairs of a sugar form after its keyword
+fn sugar_operands(pairs: Pairs<Rule>) -> impl Iterator<Item = Pair<Rule>> {
+    pairs.filter(|p| {
+        !matches!(
+            p.as_rule(),
+            Rule::cond_keyword
+                | Rule::when_keyword
+                | Rule::when_not_keyword
+                | Rule::if_let_keyword
+                | Rule::when_let_keyword
+                | Rule::case_keyword
+                | Rule::thread_keyword
+                | Rule::WHITESPACE
+                | Rule::COMMENT
+        )
+    })
+}
+
+pub(super) fn build_cond_expr(pairs: Pairs<Rule>) -> Result<SugarExpr, PestParseError> {
+    let forms = sugar_operands(pairs).map(build_expression).collect::<Result<Vec<_>, _>>()?;
+    if forms.len() % 2 != 0 {
+        return Err(PestParseError::InvalidInput(
+            "cond requires an even number of forms, a test and an expression per clause".to_string(),
+        ));
+    }
+    let mut forms = forms.into_iter();
+    let mut clauses = Vec::new();
+    while let (Some(test), Some(expr)) = (forms.next(), forms.next()) {
+        clauses.push((test, expr));
+    }
+    Ok(SugarExpr::Cond(clauses))
+}
+
+/// Build `when` or `when-not`, told apart by the keyword
+pub(super) fn build_when_expr(pairs: Pairs<Rule>) -> Result<SugarExpr, PestParseError> {
+    let negated = pairs.clone().any(|p| p.as_rule() == Rule::when_not_keyword);
+    let mut forms = sugar_operands(pairs).map(build_expression);
+    let test = forms
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("when requires a test".to_string()))??;
+    Ok(SugarExpr::When {
+        negated,
+        test: Box::new(test),
+        body: forms.collect::<Result<Vec<_>, _>>()?,
+    })
+}
+
+// The `[pattern value]` of `if-let` and `when-let`, then the remaining forms
+fn build_conditional_binding(
+    pairs: Pairs<Rule>,
+    form: &str,
+) -> Result<(LetBinding, Vec<Expression>), PestParseError> {
+    let mut operands = sugar_operands(pairs);
+    let mut next = |what: &str| {
+        operands
+            .next()
+            .ok_or_else(|| PestParseError::InvalidInput(format!("{} requires {}", form, what)))
+    };
+    let binding = LetBinding {
+        pattern: build_pattern(next("a binding pattern")?)?,
+        type_annotation: None,
+        value: Box::new(build_expression(next("a binding value")?)?),
+    };
+    let forms = operands.map(build_expression).collect::<Result<Vec<_>, _>>()?;
+    Ok((binding, forms))
+}
+
+pub(super) fn build_if_let_expr(pairs: Pairs<Rule>) -> Result<SugarExpr, PestParseError> {
+    let (binding, mut branches) = build_conditional_binding(pairs, "if-let")?;
+    let else_branch = if branches.len() > 1 { branches.pop().map(Box::new) } else { None };
+    let then_branch = branches
+        .pop()
+        .ok_or_else(|| PestParseError::InvalidInput("if-let requires a then branch".to_string()))?;
+    Ok(SugarExpr::IfLet { binding, then_branch: Box::new(then_branch), else_branch })
+}
+
+pub(super) fn build_when_let_expr(pairs: Pairs<Rule>) -> Result<SugarExpr, PestParseError> {
+    let (binding, body) = build_conditional_binding(pairs, "when-let")?;
+    Ok(SugarExpr::WhenLet { binding, body })
+}
+
+/// Build `case`: `key expr` clauses, where a key is a literal or a list of literals,
+/// and an optional trailing default
+pub(super) fn build_case_expr(pairs: Pairs<Rule>) -> Result<SugarExpr, PestParseError> {
+    let mut operands = sugar_operands(pairs);
+    let value = operands
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput("case requires a value".to_string()))?;
+    let mut clauses = Vec::new();
+    let mut default = None;
+    for pair in operands {
+        if pair.as_rule() != Rule::case_clause {
+            default = Some(Box::new(build_expression(pair)?));
+            continue;
+        }
+        let mut inner = pair.into_inner();
+        let keys_pair = inner
+            .next()
+            .ok_or_else(|| PestParseError::MissingToken("case key".to_string()))?;
+        let keys = match keys_pair.as_rule() {
+            Rule::case_keys => keys_pair.into_inner().map(build_literal).collect::<Result<Vec<_>, _>>()?,
+            _ => vec![build_literal(keys_pair)?],
+        };
+        let body_pair = inner
+            .next()
+            .ok_or_else(|| PestParseError::MissingToken("case clause expression".to_string()))?;
+        clauses.push(CaseClause { keys, body: build_expression(body_pair)? });
+    }
+    Ok(SugarExpr::Case { value: Box::new(build_expression(value)?), clauses, default })
+}
+
+/// Build `->`, `->>` or `some->`
+pub(super) fn build_thread_expr(pairs: Pairs<Rule>) -> Result<SugarExpr, PestParseError> {
+    let keyword = pairs
+        .clone()
+        .find(|p| p.as_rule() == Rule::thread_keyword)
+        .ok_or_else(|| PestParseError::MissingToken("threading keyword".to_string()))?;
+    let kind = match keyword.as_str().trim_end() {
+        "->" => ThreadKind::First,
+        "->>" => ThreadKind::Last,
+        _ => ThreadKind::SomeFirst,
+    };
+    let mut forms = sugar_operands(pairs).map(build_expression);
+    let value = forms
+        .next()
+        .ok_or_else(|| PestParseError::InvalidInput(format!("{} requires a value", kind.keyword())))??;
+    let forms = forms.collect::<Result<Vec<_>, _>>()?;
+    // A step is called with the value, so a special form has no place to take it
+    if let Some(form) = forms.iter().find(|form| !is_thread_step(form)) {
+        let location = form
+            .span()
+            .map(|span| format!(" at {}:{}", span.line, span.column))
+            .unwrap_or_default();
+        return Err(PestParseError::InvalidInput(format!(
+            "{} cannot thread into a special form such as if, let or fn{}; call a function instead, e.g. ((fn [x] ...))",
+            kind.keyword(),
+            location
+        )));
+    }
+    Ok(SugarExpr::Thread { kind, value: Box::new(value), forms })
+}
+
+/// Whether a form can be a threading step: a call, a keyword lookup, or a value to call
+fn is_thread_step(form: &Expression) -> bool {
+    matches!(
+        form.unspanned(),
+        Expression::FunctionCall { .. }
+            | Expression::Literal(_)
+            | Expression::Symbol(_)
+            | Expression::List(_)
+            | Expression::Vector(_)
+            | Expression::Map(_)
+            | Expression::TaskContextAccess(_)
+    )
+}
+
+/// Check that every `recur` reachable from a loop body is in tail position and
+/// passes one value per loop binding. The bodies of nested loops are skipped, as
+/// they were checked when built; function bodies are walked as non-tail, so a
+/// `recur` cannot jump out of a function into an enclosing loop.
+fn check_recur(expr: &Expression, tail: bool, arity: usize) -> Result<(), PestParseError> {
+    let error = |message: String| {
+        let location = expr
+            .span()
+            .map(|span| format!(" at {}:{}", span.line, span.column))
+            .unwrap_or_default();
+        Err(PestParseError::InvalidInput(format!("{}{}", message, location)))
+    };
+
+    match expr.unspanned() {
+        Expression::Recur(arguments) => {
+            if !tail {
+                return error("recur must be in tail position of a loop".to_string());
+            }
+            if arguments.len() != arity {
+                return error(format!(
+                    "recur expects {} arguments, one per loop binding, got {}",
+                    arity,
+                    arguments.len()
+                ));
+            }
+            arguments.iter().try_for_each(|argument| check_recur(argument, false, arity))
+        }
+        Expression::If(if_expr) => {
+            check_recur(&if_expr.condition, false, arity)?;
+            check_recur(&if_expr.then_branch, tail, arity)?;
+            if_expr.else_branch.iter().try_for_each(|branch| check_recur(branch, tail, arity))
+        }
+        Expression::Do(do_expr) => check_recur_body(&do_expr.expressions, tail, arity),
+        Expression::Let(let_expr) => {
+            for binding in &let_expr.bindings {
+                check_recur(&binding.value, false, arity)?;
+            }
+            check_recur_body(&let_expr.body, tail, arity)
+        }
+        // The function bodies were checked on their own when they were built
+        Expression::LetFn(letfn) => check_recur_body(&letfn.body, tail, arity),
+        Expression::Match(match_expr) => {
+            check_recur(&match_expr.expression, false, arity)?;
+            for clause in &match_expr.clauses {
+                clause.guard.iter().try_for_each(|guard| check_recur(guard, false, arity))?;
+                check_recur(&clause.body, tail, arity)?;
+            }
+            Ok(())
+        }
+        Expression::Loop(loop_expr) => loop_expr
+            .bindings
+            .iter()
+            .try_for_each(|binding| check_recur(&binding.value, false, arity)),
+        // Tail positions are those of the expansion
+        Expression::Sugar(sugar) => check_recur(sugar.expansion(), tail, arity),
+        other => other.children().into_iter().try_for_each(|child| check_recur(child, false, arity)),
+    }
+}
+
+fn check_recur_body(body: &[Expression], tail: bool, arity: usize) -> Result<(), PestParseError> {
+    let last = body.len().saturating_sub(1);
+    body.iter()
+        .enumerate()
+        .try_for_each(|(index, expr)| check_recur(expr, tail && index == last, arity))
+}
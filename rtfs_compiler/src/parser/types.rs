@@ -1,12 +1,26 @@
 use super::{PestParseError, Rule};
-use crate::ast::{MapTypeEntry, ParamType, PrimitiveType, Symbol, TypeExpr}; // Added Symbol
+use crate::ast::{MapTypeEntry, ParamType, PrimitiveType, Symbol, TypeExpr, TypePredicate}; // Added Symbol
 use pest::iterators::Pair;
 
 // Helper function imports from sibling modules
-use super::common::{build_keyword, build_symbol};
+use super::common::{build_keyword, build_literal, build_span, build_symbol};
+
+// A type written as a bare name: a type variable such as `T`, or else a type alias
+fn named_type(symbol: Symbol) -> TypeExpr {
+    if TypeExpr::is_type_variable_name(&symbol.0) {
+        TypeExpr::TypeVar(symbol)
+    } else {
+        TypeExpr::Alias(symbol)
+    }
+}
 
 // Build type expression from a parsed pair
 pub fn build_type_expr(pair: Pair<Rule>) -> Result<TypeExpr, PestParseError> {
+    let span = build_span(&pair);
+    build_unspanned_type_expr(pair).map(|type_expr| type_expr.with_span(span))
+}
+
+fn build_unspanned_type_expr(pair: Pair<Rule>) -> Result<TypeExpr, PestParseError> {
     // Get the actual type pair, handling wrapper rules
     let actual_type_pair = match pair.as_rule() {
         Rule::type_expr => pair
@@ -39,9 +53,9 @@ pub fn build_type_expr(pair: Pair<Rule>) -> Result<TypeExpr, PestParseError> {
             let symbol_pair = actual_type_pair.into_inner().next().ok_or_else(|| {
                 PestParseError::MissingToken("expected symbol in primitive_type".to_string())
             })?;
-            Ok(TypeExpr::Alias(build_symbol(symbol_pair)?))
+            Ok(named_type(build_symbol(symbol_pair)?))
         }
-        Rule::symbol => Ok(TypeExpr::Alias(build_symbol(actual_type_pair)?)),Rule::vector_type => {
+        Rule::symbol => Ok(named_type(build_symbol(actual_type_pair)?)),Rule::vector_type => {
             let inner_type_pair = actual_type_pair.into_inner().next().ok_or_else(|| {
                 PestParseError::MissingToken("expected inner type for vector".to_string())
             })?;
@@ -172,22 +186,49 @@ pub fn build_type_expr(pair: Pair<Rule>) -> Result<TypeExpr, PestParseError> {
             Ok(TypeExpr::Union(type_pairs?))
         }
         Rule::intersection_type => {
-            let type_pairs: Result<Vec<TypeExpr>, PestParseError> = actual_type_pair
+            // [:and base-type predicate*]
+            let mut inner = actual_type_pair
                 .into_inner()
-                .filter(|p| p.as_rule() != Rule::WHITESPACE && p.as_rule() != Rule::COMMENT)
-                .map(build_type_expr)
-                .collect();
-            Ok(TypeExpr::Intersection(type_pairs?))
-        }        Rule::literal_type => {
+                .filter(|p| p.as_rule() != Rule::WHITESPACE && p.as_rule() != Rule::COMMENT);
+            let base_pair = inner.next().ok_or_else(|| {
+                PestParseError::MissingToken("expected base type in intersection type".to_string())
+            })?;
+            let base = build_type_expr(base_pair)?;
+            let predicates = inner.map(build_type_predicate).collect::<Result<Vec<_>, _>>()?;
+            Ok(TypeExpr::Refined { base: Box::new(base), predicates })
+        }        Rule::array_type => {
+            // [:array element-type shape?], where each dimension is a size or ?
+            let mut inner = actual_type_pair
+                .into_inner()
+                .filter(|p| p.as_rule() != Rule::WHITESPACE && p.as_rule() != Rule::COMMENT);
+            let element_pair = inner.next().ok_or_else(|| {
+                PestParseError::MissingToken("expected element type in array type".to_string())
+            })?;
+            let element_type = build_type_expr(element_pair)?;
+            let shape = inner
+                .next()
+                .map(|shape_pair| {
+                    shape_pair
+                        .into_inner()
+                        .map(|dimension| match dimension.as_str() {
+                            "?" => Ok(None),
+                            size => size.parse::<usize>().map(Some).map_err(|_| {
+                                PestParseError::InvalidInput(format!("invalid array dimension: {}", size))
+                            }),
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()?;
+            Ok(TypeExpr::Array { element_type: Box::new(element_type), shape })
+        }
+        Rule::literal_type => {
             let literal_pair = actual_type_pair.into_inner().next().ok_or_else(|| {
                 PestParseError::MissingToken("expected literal in literal type".to_string())
             })?;
-            use super::common::build_literal;
             Ok(TypeExpr::Literal(build_literal(literal_pair)?))
         }
         Rule::literal => {
             // Handle the case where a keyword is parsed as a literal
-            use super::common::build_literal;
             let literal = build_literal(actual_type_pair.clone())?;
             match literal {
                 crate::ast::Literal::Keyword(keyword) => {
@@ -204,3 +245,20 @@ pub fn build_type_expr(pair: Pair<Rule>) -> Result<TypeExpr, PestParseError> {
         }),
     }
 }
+
+// Build a refinement predicate such as [:> 0] or [:matches-regex "^a"]
+fn build_type_predicate(pair: Pair<Rule>) -> Result<TypePredicate, PestParseError> {
+    let rule_text = pair.as_str().to_string();
+    let mut inner = pair.into_inner();
+    let name_pair = inner
+        .next()
+        .and_then(|p| p.into_inner().next())
+        .ok_or_else(|| PestParseError::MissingToken("expected predicate name".to_string()))?;
+    let name = match name_pair.as_rule() {
+        Rule::keyword => build_keyword(name_pair)?.0,
+        _ => build_symbol(name_pair)?.0,
+    };
+    let args = inner.map(build_literal).collect::<Result<Vec<_>, _>>()?;
+    TypePredicate::from_parts(&name, args)
+        .map_err(|reason| PestParseError::InvalidInput(format!("{} in {}", reason, rule_text)))
+}
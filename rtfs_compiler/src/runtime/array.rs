@@ -0,0 +1,204 @@
+// Dense n-dimensional arrays for RTFS
+// Runtime representation of `[:array T shape]` values and the local tensor operations on them
+
+use crate::runtime::Value;
+
+/// Element storage of an array, flattened in row-major order
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayData {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+}
+
+/// A dense n-dimensional array of ints or floats
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayValue {
+    shape: Vec<usize>,
+    data: ArrayData,
+}
+
+impl ArrayValue {
+    /// Create an array from its shape and row-major data
+    pub fn new(shape: Vec<usize>, data: ArrayData) -> Result<Self, String> {
+        let size: usize = shape.iter().product();
+        let len = match &data {
+            ArrayData::Int(items) => items.len(),
+            ArrayData::Float(items) => items.len(),
+        };
+        if size != len {
+            return Err(format!("shape {:?} needs {} elements, got {}", shape, size, len));
+        }
+        Ok(ArrayValue { shape, data })
+    }
+
+    /// Build an array from nested vectors of numbers, e.g. `[[1 2] [3 4]]`.
+    /// Ints are widened to floats when both occur.
+    pub fn from_nested(value: &Value) -> Result<Self, String> {
+        let mut shape = Vec::new();
+        let mut level = value;
+        while let Value::Vector(items) = level {
+            shape.push(items.len());
+            match items.first() {
+                Some(first) => level = first,
+                None => break,
+            }
+        }
+        if shape.is_empty() {
+            return Err(format!("expected nested vectors of numbers, got {}", value.type_name()));
+        }
+
+        let mut elements = Vec::new();
+        collect_elements(value, &shape, &mut elements)?;
+        let data = if elements.iter().all(|e| matches!(e, Value::Integer(_))) {
+            ArrayData::Int(elements.iter().map(|e| match e {
+                Value::Integer(i) => *i,
+                _ => unreachable!(),
+            }).collect())
+        } else {
+            ArrayData::Float(elements.iter().map(|e| as_float(e).unwrap()).collect())
+        };
+        ArrayValue::new(shape, data)
+    }
+
+    /// Convert back into nested vectors
+    pub fn to_nested(&self) -> Value {
+        fn build(shape: &[usize], elements: &mut dyn Iterator<Item = Value>) -> Value {
+            match shape.split_first() {
+                Some((len, rest)) => Value::Vector((0..*len).map(|_| build(rest, elements)).collect()),
+                None => elements.next().unwrap_or(Value::Nil),
+            }
+        }
+        build(&self.shape, &mut self.values())
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &ArrayData {
+        &self.data
+    }
+
+    /// Number of elements
+    pub fn size(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    /// `int` or `float`
+    pub fn element_type_name(&self) -> &'static str {
+        match self.data {
+            ArrayData::Int(_) => "int",
+            ArrayData::Float(_) => "float",
+        }
+    }
+
+    /// The elements as values, in row-major order
+    pub fn values(&self) -> Box<dyn Iterator<Item = Value> + '_> {
+        match &self.data {
+            ArrayData::Int(items) => Box::new(items.iter().map(|i| Value::Integer(*i))),
+            ArrayData::Float(items) => Box::new(items.iter().map(|f| Value::Float(*f))),
+        }
+    }
+
+    fn floats(&self) -> Vec<f64> {
+        match &self.data {
+            ArrayData::Int(items) => items.iter().map(|i| *i as f64).collect(),
+            ArrayData::Float(items) => items.clone(),
+        }
+    }
+
+    /// Add two arrays of the same shape element by element
+    pub fn elementwise_add(&self, other: &ArrayValue) -> Result<ArrayValue, String> {
+        if self.shape != other.shape {
+            return Err(format!("shape mismatch: {:?} and {:?}", self.shape, other.shape));
+        }
+        let data = match (&self.data, &other.data) {
+            (ArrayData::Int(a), ArrayData::Int(b)) => ArrayData::Int(
+                a.iter()
+                    .zip(b)
+                    .map(|(x, y)| x.checked_add(*y).ok_or_else(|| "integer overflow".to_string()))
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => ArrayData::Float(self.floats().iter().zip(other.floats()).map(|(x, y)| x + y).collect()),
+        };
+        ArrayValue::new(self.shape.clone(), data)
+    }
+
+    /// Multiply every element by a number; an int array stays int only for an int scalar
+    pub fn scalar_multiply(&self, scalar: &Value) -> Result<ArrayValue, String> {
+        let data = match (&self.data, scalar) {
+            (ArrayData::Int(items), Value::Integer(k)) => ArrayData::Int(
+                items.iter()
+                    .map(|x| x.checked_mul(*k).ok_or_else(|| "integer overflow".to_string()))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (_, Value::Integer(_) | Value::Float(_)) => {
+                let k = as_float(scalar).unwrap();
+                ArrayData::Float(self.floats().iter().map(|x| x * k).collect())
+            }
+            (_, other) => return Err(format!("scalar must be a number, got {}", other.type_name())),
+        };
+        ArrayValue::new(self.shape.clone(), data)
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// Flatten nested vectors into `out`, checking that they are rectangular with the given shape
+fn collect_elements(value: &Value, shape: &[usize], out: &mut Vec<Value>) -> Result<(), String> {
+    match (shape.split_first(), value) {
+        (Some((len, rest)), Value::Vector(items)) if items.len() == *len => {
+            items.iter().try_for_each(|item| collect_elements(item, rest, out))
+        }
+        (Some(_), _) => Err("array rows must all have the same shape".to_string()),
+        (None, Value::Integer(_) | Value::Float(_)) => {
+            out.push(value.clone());
+            Ok(())
+        }
+        (None, other) => Err(format!("array elements must be numbers, got {}", other.type_name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(items: &[i64]) -> Value {
+        Value::Vector(items.iter().map(|i| Value::Integer(*i)).collect())
+    }
+
+    #[test]
+    fn test_from_nested() {
+        let matrix = ArrayValue::from_nested(&Value::Vector(vec![ints(&[1, 2, 3]), ints(&[4, 5, 6])])).unwrap();
+        assert_eq!(matrix.shape(), &[2, 3]);
+        assert_eq!(matrix.data(), &ArrayData::Int(vec![1, 2, 3, 4, 5, 6]));
+        assert_eq!(matrix.to_nested(), Value::Vector(vec![ints(&[1, 2, 3]), ints(&[4, 5, 6])]));
+
+        let mixed = ArrayValue::from_nested(&Value::Vector(vec![Value::Integer(1), Value::Float(0.5)])).unwrap();
+        assert_eq!(mixed.data(), &ArrayData::Float(vec![1.0, 0.5]));
+
+        assert!(ArrayValue::from_nested(&Value::Vector(vec![ints(&[1, 2]), ints(&[3])])).is_err());
+        assert!(ArrayValue::from_nested(&Value::Vector(vec![Value::String("a".to_string())])).is_err());
+        assert!(ArrayValue::from_nested(&Value::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn test_operations() {
+        let a = ArrayValue::new(vec![2], ArrayData::Int(vec![1, 2])).unwrap();
+        let b = ArrayValue::new(vec![2], ArrayData::Float(vec![0.5, 0.5])).unwrap();
+        assert_eq!(a.elementwise_add(&a).unwrap().data(), &ArrayData::Int(vec![2, 4]));
+        assert_eq!(a.elementwise_add(&b).unwrap().data(), &ArrayData::Float(vec![1.5, 2.5]));
+        assert!(a.elementwise_add(&ArrayValue::new(vec![1, 2], ArrayData::Int(vec![1, 2])).unwrap()).is_err());
+
+        assert_eq!(a.scalar_multiply(&Value::Integer(3)).unwrap().data(), &ArrayData::Int(vec![3, 6]));
+        assert_eq!(a.scalar_multiply(&Value::Float(0.5)).unwrap().data(), &ArrayData::Float(vec![0.5, 1.0]));
+        assert!(a.scalar_multiply(&Value::Nil).is_err());
+        assert!(ArrayValue::new(vec![2, 2], ArrayData::Int(vec![1])).is_err());
+    }
+}
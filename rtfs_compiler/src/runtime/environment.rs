@@ -1,76 +1,119 @@
 // Environment for variable bindings and scope management
 
+use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
-use crate::ast::Symbol;
+use crate::ast::{Expression, ParamDef, Symbol};
 use crate::runtime::{Value, RuntimeError, RuntimeResult};
+use crate::runtime::values::Function;
 
 /// Environment for variable bindings
-/// Supports lexical scoping with parent environments
-#[derive(Debug, Clone, PartialEq)]
+/// Supports lexical scoping with parent environments.
+///
+/// An environment is a handle to a shared frame: clones see the same bindings, so a
+/// closure that captures its defining environment also sees the names defined there
+/// after it, which is what makes recursive and mutually recursive `defn` work.
+#[derive(Clone)]
 pub struct Environment {
+    frame: Rc<Frame>,
+}
+
+struct Frame {
     /// Current scope bindings
-    bindings: HashMap<String, Value>,
+    bindings: RefCell<HashMap<String, Value>>,
+    /// Functions defined in this frame that close over it. They are kept without
+    /// their closure, which looking them up restores, so that the frame and its
+    /// functions do not keep each other alive.
+    functions: RefCell<HashMap<String, OwnFunction>>,
     /// Parent environment for lexical scoping
-    parent: Option<Rc<Environment>>,
+    parent: Option<Environment>,
+}
+
+struct OwnFunction {
+    params: Vec<ParamDef>,
+    variadic_param: Option<ParamDef>,
+    body: Vec<Expression>,
 }
 
 impl Environment {
     /// Create a new empty environment
     pub fn new() -> Self {
         Environment {
-            bindings: HashMap::new(),
-            parent: None,
+            frame: Rc::new(Frame {
+                bindings: RefCell::new(HashMap::new()),
+                functions: RefCell::new(HashMap::new()),
+                parent: None,
+            }),
         }
     }
-    
+
     /// Create a new environment with a parent
-    pub fn with_parent(parent: Rc<Environment>) -> Self {
+    pub fn with_parent(parent: &Environment) -> Self {
         Environment {
-            bindings: HashMap::new(),
-            parent: Some(parent),
+            frame: Rc::new(Frame {
+                bindings: RefCell::new(HashMap::new()),
+                functions: RefCell::new(HashMap::new()),
+                parent: Some(parent.clone()),
+            }),
         }
     }
-    
+
     /// Define a new binding in the current scope
-    pub fn define(&mut self, symbol: &Symbol, value: Value) {
-        self.bindings.insert(symbol.0.clone(), value);
+    pub fn define(&self, symbol: &Symbol, value: Value) {
+        match value {
+            Value::Function(Function::UserDefined { params, variadic_param, body, closure })
+                if Rc::ptr_eq(&closure.frame, &self.frame) =>
+            {
+                self.frame.bindings.borrow_mut().remove(&symbol.0);
+                self.frame.functions.borrow_mut().insert(symbol.0.clone(), OwnFunction { params, variadic_param, body });
+            }
+            value => {
+                self.frame.functions.borrow_mut().remove(&symbol.0);
+                self.frame.bindings.borrow_mut().insert(symbol.0.clone(), value);
+            }
+        }
     }
-    
+
     /// Look up a symbol in this environment or parent environments
     pub fn lookup(&self, symbol: &Symbol) -> RuntimeResult<Value> {
-        if let Some(value) = self.bindings.get(&symbol.0) {
+        if let Some(value) = self.frame.bindings.borrow().get(&symbol.0) {
             Ok(value.clone())
-        } else if let Some(parent) = &self.parent {
+        } else if let Some(function) = self.frame.functions.borrow().get(&symbol.0) {
+            Ok(Value::Function(Function::UserDefined {
+                params: function.params.clone(),
+                variadic_param: function.variadic_param.clone(),
+                body: function.body.clone(),
+                closure: self.clone(),
+            }))
+        } else if let Some(parent) = &self.frame.parent {
             parent.lookup(symbol)
         } else {
             Err(RuntimeError::UndefinedSymbol(symbol.clone()))
         }
     }
-    
+
     /// Check if a symbol is defined in this environment (not parent environments)
     pub fn contains(&self, symbol: &Symbol) -> bool {
-        self.bindings.contains_key(&symbol.0)
+        self.frame.bindings.borrow().contains_key(&symbol.0) || self.frame.functions.borrow().contains_key(&symbol.0)
     }
-      /// Update an existing binding (searches up the scope chain)
-    pub fn set(&mut self, symbol: &Symbol, value: Value) -> RuntimeResult<()> {
-        if self.bindings.contains_key(&symbol.0) {
-            self.bindings.insert(symbol.0.clone(), value);
-            Ok(())
-        } else if let Some(_parent) = &self.parent {
-            // We can't modify the parent since it's behind an Rc
-            // For now, just create a new binding in the current scope
-            // In a more sophisticated implementation, we might use RefCell or other interior mutability
-            self.bindings.insert(symbol.0.clone(), value);
+
+    /// Update an existing binding in the scope that defines it
+    pub fn set(&self, symbol: &Symbol, value: Value) -> RuntimeResult<()> {
+        if self.contains(symbol) {
+            self.define(symbol, value);
             Ok(())
+        } else if let Some(parent) = &self.frame.parent {
+            parent.set(symbol, value)
         } else {
             Err(RuntimeError::UndefinedSymbol(symbol.clone()))
         }
     }
-    
-    /// Get all bindings in the current scope (for debugging)
-    pub fn current_bindings(&self) -> &HashMap<String, Value> {
-        &self.bindings
+
+    /// Get the bindings in the current scope, other than the functions that close
+    /// over it (for debugging)
+    pub fn current_bindings(&self) -> Ref<'_, HashMap<String, Value>> {
+        self.frame.bindings.borrow()
     }
 }
 
@@ -79,3 +122,62 @@ impl Default for Environment {
         Self::new()
     }
 }
+
+// Lists names only: the values may be closures over this very environment
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<String> = self.frame.bindings.borrow().keys().cloned().collect();
+        names.extend(self.frame.functions.borrow().keys().cloned());
+        names.sort();
+        f.debug_struct("Environment")
+            .field("bindings", &names)
+            .field("parent", &self.frame.parent)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sym(name: &str) -> Symbol {
+        Symbol(name.to_string())
+    }
+
+    #[test]
+    fn test_clones_share_bindings() {
+        let env = Environment::new();
+        let captured = env.clone();
+        env.define(&sym("x"), Value::Integer(1));
+        assert_eq!(captured.lookup(&sym("x")), Ok(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_set_updates_the_defining_scope() {
+        let outer = Environment::new();
+        outer.define(&sym("x"), Value::Integer(1));
+        let inner = Environment::with_parent(&outer);
+        inner.set(&sym("x"), Value::Integer(2)).unwrap();
+        assert!(!inner.contains(&sym("x")));
+        assert_eq!(outer.lookup(&sym("x")), Ok(Value::Integer(2)));
+        assert_eq!(inner.set(&sym("y"), Value::Nil), Err(RuntimeError::UndefinedSymbol(sym("y"))));
+    }
+
+    #[test]
+    fn test_functions_closing_over_their_frame_do_not_keep_it_alive() {
+        let env = Environment::new();
+        env.define(&sym("f"), Value::Function(Function::UserDefined {
+            params: Vec::new(),
+            variadic_param: None,
+            body: Vec::new(),
+            closure: env.clone(),
+        }));
+        match env.lookup(&sym("f")) {
+            Ok(Value::Function(Function::UserDefined { closure, .. })) => assert!(Rc::ptr_eq(&closure.frame, &env.frame)),
+            other => panic!("expected a function, got {:?}", other),
+        }
+        let frame = Rc::downgrade(&env.frame);
+        drop(env);
+        assert!(frame.upgrade().is_none());
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::fmt;
 use crate::ast::{Symbol, Keyword};
+use crate::ir::SourceLocation;
 use crate::runtime::Value;
 
 pub type RuntimeResult<T> = Result<T, RuntimeError>;
@@ -80,6 +81,17 @@ pub enum RuntimeError {
     
     /// Internal runtime errors (should not normally occur)
     InternalError(String),
+    
+    /// Calls nested too deeply for the stack budget
+    StackOverflow {
+        depth: usize,
+    },
+    
+    /// Error annotated with the source location of the form that raised it
+    Located {
+        error: Box<RuntimeError>,
+        location: SourceLocation,
+    },
 }
 
 impl fmt::Display for RuntimeError {
@@ -137,17 +149,57 @@ impl fmt::Display for RuntimeError {
             RuntimeError::InternalError(msg) => {
                 write!(f, "Internal error: {}", msg)
             },
+            RuntimeError::StackOverflow { depth } => {
+                write!(f, "Stack overflow: {} nested calls exceed the stack limit", depth)
+            },
+            RuntimeError::Located { error, location } => {
+                write!(f, "{} (at {})", error, location)
+            },
         }
     }
 }
 
 impl std::error::Error for RuntimeError {}
 
+impl RuntimeError {
+    /// Attach the location of the failing form. Errors that already carry a
+    /// location keep it, so the innermost form wins.
+    pub fn with_location(self, location: SourceLocation) -> Self {
+        match self {
+            RuntimeError::Located { .. } => self,
+            error => RuntimeError::Located {
+                error: Box::new(error),
+                location,
+            },
+        }
+    }
+    
+    /// Source location of the form that raised this error, if known
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            RuntimeError::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+    
+    /// The underlying error without its location annotation
+    pub fn without_location(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::Located { error, .. } => error.without_location(),
+            error => error,
+        }
+    }
+}
+
 /// Convert runtime errors to RTFS error values
 impl RuntimeError {
     pub fn to_value(&self) -> Value {
         use std::collections::HashMap;
         
+        if let RuntimeError::Located { error, .. } = self {
+            return error.to_value();
+        }
+        
         let (error_type, message, data) = match self {
             RuntimeError::TypeError { expected, actual, operation } => (
                 Keyword("error/type".to_string()),
@@ -1,14 +1,29 @@
 // RTFS Evaluator - Executes parsed AST nodes
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use crate::ast::*;
 use crate::runtime::{Value, RuntimeError, RuntimeResult, Environment};
-use crate::runtime::values::{Function, Arity};
+use crate::runtime::values::{Function, FunctionCaller, Arity};
+use crate::runtime::task_context::TaskContext;
+use crate::runtime::stack::StackGuard;
 use crate::runtime::stdlib::StandardLibrary;
+use crate::runtime::schema::{self, resolve_type, value_conforms_to};
+use crate::parser::printer::{print_pattern, print_type_expr};
 
 pub struct Evaluator {
     global_env: Rc<Environment>,
+    /// Context that `@field` resolves against
+    task_context: RefCell<TaskContext>,
+    /// Types named with `deftype`, with the names they mention resolved
+    types: RefCell<HashMap<String, TypeExpr>>,
+    /// Values passed by a `recur`, taken by the enclosing loop once the body returns
+    pending_recur: RefCell<Option<Vec<Value>>>,
+    /// Function and arguments of a call in tail position, made by the caller's trampoline
+    pending_tail_call: RefCell<Option<(Value, Vec<Value>)>>,
+    /// Stack budget for nested calls
+    stack: StackGuard,
 }
 
 impl Evaluator {
@@ -17,68 +32,68 @@ impl Evaluator {
         let global_env = StandardLibrary::create_global_environment();
         Evaluator {
             global_env: Rc::new(global_env),
+            task_context: RefCell::new(TaskContext::new()),
+            types: RefCell::new(HashMap::new()),
+            pending_recur: RefCell::new(None),
+            pending_tail_call: RefCell::new(None),
+            stack: StackGuard::new(),
         }
     }
     
     /// Evaluate an expression in the global environment
     pub fn evaluate(&self, expr: &Expression) -> RuntimeResult<Value> {
-        let mut env = Environment::with_parent(self.global_env.clone());
-        self.eval_expr(expr, &mut env)
+        let mut env = Environment::with_parent(&self.global_env);
+        let result = self.eval_expr(expr, &mut env);
+        self.no_pending_recur(result)
+    }
+    
+    /// Set the task context that `@field` resolves against
+    pub fn set_task_context(&self, context: TaskContext) {
+        *self.task_context.borrow_mut() = context;
+    }
+    
+    /// Take the task context, including step results recorded during evaluation
+    pub fn take_task_context(&self) -> TaskContext {
+        self.task_context.take()
+    }
+    
+    /// Set how many bytes of heap-allocated stack nested calls may use before
+    /// evaluation fails with `RuntimeError::StackOverflow`
+    pub fn set_stack_limit(&self, bytes: usize) {
+        self.stack.set_limit(bytes);
+    }
+    
+    /// The types defined so far with `deftype`, by name
+    pub fn defined_types(&self) -> HashMap<String, TypeExpr> {
+        self.types.borrow().clone()
     }
     
     /// Evaluate an expression in a given environment
     pub fn eval_expr(&self, expr: &Expression, env: &mut Environment) -> RuntimeResult<Value> {
+        self.eval(expr, env, false)
+    }
+    
+    /// Evaluate an expression. In `tail` position of a function body, a call to a
+    /// user-defined function is left pending for the trampoline in `call_user_function`.
+    fn eval(&self, expr: &Expression, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
         match expr {
             Expression::Literal(lit) => self.eval_literal(lit),
             Expression::Symbol(sym) => env.lookup(sym),
-            Expression::List(exprs) => {
+            Expression::TaskContextAccess(field) => self.task_context.borrow().resolve(field),
+            Expression::List(exprs) => match exprs.split_first() {
                 // Empty list evaluates to empty list
-                if exprs.is_empty() {
-                    return Ok(Value::Vector(vec![]));
-                }
-                
+                None => Ok(Value::Vector(vec![])),
                 // First element should be a function
-                let func_expr = &exprs[0];
-                let func_value = self.eval_expr(func_expr, env)?;
-                
-                // Evaluate arguments
-                let args: Result<Vec<Value>, RuntimeError> = exprs[1..]
-                    .iter()
-                    .map(|e| self.eval_expr(e, env))
-                    .collect();
-                let args = args?;
-                
-                self.call_function(func_value, &args, env)
-            },
-            Expression::Vector(exprs) => {
-                let values: Result<Vec<Value>, RuntimeError> = exprs
-                    .iter()
-                    .map(|e| self.eval_expr(e, env))
-                    .collect();
-                Ok(Value::Vector(values?))
-            },
-            Expression::Map(map) => {
-                let mut result = HashMap::new();
-                for (key, value_expr) in map {
-                    let value = self.eval_expr(value_expr, env)?;
-                    result.insert(key.clone(), value);
-                }
-                Ok(Value::Map(result))
-            },
-            Expression::FunctionCall { callee, arguments } => {
-                let func_value = self.eval_expr(callee, env)?;
-                let args: Result<Vec<Value>, RuntimeError> = arguments
-                    .iter()
-                    .map(|e| self.eval_expr(e, env))
-                    .collect();
-                let args = args?;
-                
-                self.call_function(func_value, &args, env)
+                Some((callee, arguments)) => self.eval_call(callee, arguments, env, tail),
             },
-            Expression::If(if_expr) => self.eval_if(if_expr, env),
-            Expression::Let(let_expr) => self.eval_let(let_expr, env),
-            Expression::Do(do_expr) => self.eval_do(do_expr, env),
-            Expression::Match(match_expr) => self.eval_match(match_expr, env),
+            Expression::Vector(exprs) => self.eval_vector(exprs, env),
+            Expression::Map(map) => self.eval_map(map, env),
+            Expression::FunctionCall { callee, arguments } => self.eval_call(callee, arguments, env, tail),
+            Expression::If(if_expr) => self.eval_if(if_expr, env, tail),
+            Expression::Let(let_expr) => self.eval_let(let_expr, env, tail),
+            Expression::LetFn(letfn) => self.eval_letfn(letfn, env, tail),
+            Expression::Do(do_expr) => self.eval_do(do_expr, env, tail),
+            Expression::Match(match_expr) => self.eval_match(match_expr, env, tail),
             Expression::LogStep(log_expr) => self.eval_log_step(log_expr, env),
             Expression::TryCatch(try_expr) => self.eval_try_catch(try_expr, env),
             Expression::Fn(fn_expr) => self.eval_fn(fn_expr, env),
@@ -86,9 +101,60 @@ impl Evaluator {
             Expression::Parallel(parallel_expr) => self.eval_parallel(parallel_expr, env),
             Expression::Def(def_expr) => self.eval_def(def_expr, env),
             Expression::Defn(defn_expr) => self.eval_defn(defn_expr, env),
+            Expression::DefType(deftype) => self.eval_deftype(deftype),
+            Expression::Loop(loop_expr) => self.eval_loop(loop_expr, env, tail),
+            Expression::Recur(arguments) => self.eval_recur(arguments, env),
+            Expression::For(for_expr) => self.eval_for(for_expr, true, env),
+            Expression::DoSeq(for_expr) => self.eval_for(for_expr, false, env),
+            Expression::DoTimes(dotimes) => self.eval_dotimes(dotimes, env),
+            Expression::Sugar(sugar) => self.eval_sugar(sugar, env, tail),
+            Expression::Spanned(inner, span) => self
+                .eval(inner, env, tail)
+                .map_err(|e| e.with_location(span.as_ref().into())),
         }
     }
     
+    /// Evaluate `cond`, `when`, `if-let`, `case`, `->` and the like through their expansion
+    fn eval_sugar(&self, sugar: &SugarForm, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
+        self.eval(sugar.expansion(), env, tail)
+    }
+    
+    /// Evaluate a call: the callee, then the arguments left to right
+    fn eval_call(&self, callee: &Expression, arguments: &[Expression], env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
+        let func_value = self.eval_expr(callee, env)?;
+        let args: Result<Vec<Value>, RuntimeError> = arguments
+            .iter()
+            .map(|e| self.eval_expr(e, env))
+            .collect();
+        let args = args?;
+        
+        self.call_in_position(func_value, args, env, tail)
+    }
+    
+    fn eval_vector(&self, exprs: &[Expression], env: &mut Environment) -> RuntimeResult<Value> {
+        let values: Result<Vec<Value>, RuntimeError> = exprs
+            .iter()
+            .map(|e| self.eval_expr(e, env))
+            .collect();
+        Ok(Value::Vector(values?))
+    }
+    
+    fn eval_map(&self, map: &HashMap<MapKey, Expression>, env: &mut Environment) -> RuntimeResult<Value> {
+        let mut result = HashMap::new();
+        for (key, value_expr) in map {
+            let value = self.eval_expr(value_expr, env)?;
+            result.insert(key.clone(), value);
+        }
+        Ok(Value::Map(result))
+    }
+    
+    /// Evaluate `for`, collecting the body's values, or `doseq`, which returns nil
+    fn eval_for(&self, for_expr: &ForExpr, collect: bool, env: &mut Environment) -> RuntimeResult<Value> {
+        let mut results = if collect { Some(Vec::new()) } else { None };
+        self.eval_iteration(&for_expr.clauses, &for_expr.body, env, &mut results)?;
+        Ok(results.map_or(Value::Nil, Value::Vector))
+    }
+    
     fn eval_literal(&self, lit: &Literal) -> RuntimeResult<Value> {
         match lit {
             Literal::Integer(n) => Ok(Value::Integer(*n)),
@@ -114,41 +180,19 @@ impl Evaluator {
                 
                 func(args)
             },
-            Value::Function(Function::UserDefined { params, variadic_param, body, closure }) => {
-                // Create new environment for function execution
-                let mut func_env = Environment::with_parent(Rc::new(closure.clone()));
-                
-                // Bind parameters
-                let required_params = params.len();
-                let has_variadic = variadic_param.is_some();
-                
-                if !has_variadic && args.len() != required_params {
-                    return Err(RuntimeError::ArityMismatch {
-                        function: "#<user-function>".to_string(),
-                        expected: required_params.to_string(),
-                        actual: args.len(),
-                    });
-                } else if has_variadic && args.len() < required_params {
+            Value::Function(Function::BuiltinWithCallback { name, arity, func }) => {
+                if !self.check_arity(&arity, args.len()) {
                     return Err(RuntimeError::ArityMismatch {
-                        function: "#<user-function>".to_string(),
-                        expected: format!("at least {}", required_params),
+                        function: name,
+                        expected: self.arity_to_string(&arity),
                         actual: args.len(),
                     });
                 }
                 
-                // Bind required parameters
-                for (i, param) in params.iter().enumerate() {
-                    self.bind_pattern(&param.pattern, &args[i], &mut func_env)?;
-                }
-                
-                // Bind variadic parameter if present
-                if let Some(variadic) = &variadic_param {
-                    let variadic_args = args[required_params..].to_vec();
-                    self.bind_pattern(&variadic.pattern, &Value::Vector(variadic_args), &mut func_env)?;
-                }
-                
-                // Execute function body
-                self.eval_do_body(&body, &mut func_env)
+                func(args, &mut EvaluatorCaller { evaluator: self, env })
+            },
+            Value::Function(Function::UserDefined { params, variadic_param, body, closure }) => {
+                self.stack.call(|| self.call_user_function(params, variadic_param, body, closure, args.to_vec()))
             },
             _ => Err(RuntimeError::TypeError {
                 expected: "function".to_string(),
@@ -157,6 +201,78 @@ impl Evaluator {
             }),
         }
     }
+
+    /// Call a user-defined function. Calls in tail position of its body return to
+    /// this loop instead of nesting, so tail recursion runs in constant stack.
+    fn call_user_function(
+        &self,
+        mut params: Vec<ParamDef>,
+        mut variadic_param: Option<ParamDef>,
+        mut body: Vec<Expression>,
+        mut closure: Environment,
+        mut args: Vec<Value>,
+    ) -> RuntimeResult<Value> {
+        loop {
+            // Create new environment for function execution
+            let mut func_env = Environment::with_parent(&closure);
+            
+            // Bind parameters
+            let required_params = params.len();
+            let has_variadic = variadic_param.is_some();
+            
+            if !has_variadic && args.len() != required_params {
+                return Err(RuntimeError::ArityMismatch {
+                    function: "#<user-function>".to_string(),
+                    expected: required_params.to_string(),
+                    actual: args.len(),
+                });
+            } else if has_variadic && args.len() < required_params {
+                return Err(RuntimeError::ArityMismatch {
+                    function: "#<user-function>".to_string(),
+                    expected: format!("at least {}", required_params),
+                    actual: args.len(),
+                });
+            }
+            
+            // Bind required parameters
+            for (i, param) in params.iter().enumerate() {
+                check_binding_type(&self.types.borrow(), &param.type_annotation, &param.pattern, &args[i])?;
+                self.bind_pattern(&param.pattern, &args[i], &mut func_env)?;
+            }
+            
+            // Bind variadic parameter if present
+            if let Some(variadic) = &variadic_param {
+                let variadic_args = Value::Vector(args[required_params..].to_vec());
+                check_binding_type(&self.types.borrow(), &variadic.type_annotation, &variadic.pattern, &variadic_args)?;
+                self.bind_pattern(&variadic.pattern, &variadic_args, &mut func_env)?;
+            }
+            
+            // Execute function body; a call it makes in tail position runs next in this loop
+            let result = self.eval_body(&body, &mut func_env, true);
+            let result = self.no_pending_recur(result)?;
+            match self.pending_tail_call.take() {
+                Some((Value::Function(Function::UserDefined { params: next_params, variadic_param: next_variadic, body: next_body, closure: next_closure }), next_args)) => {
+                    params = next_params;
+                    variadic_param = next_variadic;
+                    body = next_body;
+                    closure = next_closure;
+                    args = next_args;
+                },
+                Some((func_value, args)) => return self.call_function(func_value, &args, &mut func_env),
+                None => return Ok(result),
+            }
+        }
+    }
+    
+    /// Call a function, or in tail position leave a user-defined one pending for
+    /// the trampoline in `call_user_function`
+    fn call_in_position(&self, func_value: Value, args: Vec<Value>, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
+        if tail && matches!(func_value, Value::Function(Function::UserDefined { .. })) {
+            *self.pending_tail_call.borrow_mut() = Some((func_value, args));
+            return Ok(Value::Nil);
+        }
+        self.call_function(func_value, &args, env)
+    }
     
     fn check_arity(&self, arity: &Arity, arg_count: usize) -> bool {
         match arity {
@@ -176,53 +292,165 @@ impl Evaluator {
         }
     }
     
-    fn eval_if(&self, if_expr: &IfExpr, env: &mut Environment) -> RuntimeResult<Value> {
+    fn eval_if(&self, if_expr: &IfExpr, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
         let condition = self.eval_expr(&if_expr.condition, env)?;
         
         if condition.is_truthy() {
-            self.eval_expr(&if_expr.then_branch, env)
+            self.eval(&if_expr.then_branch, env, tail)
         } else if let Some(else_branch) = &if_expr.else_branch {
-            self.eval_expr(else_branch, env)
+            self.eval(else_branch, env, tail)
         } else {
             Ok(Value::Nil)
         }
     }
     
-    fn eval_let(&self, let_expr: &LetExpr, env: &mut Environment) -> RuntimeResult<Value> {
+    fn eval_let(&self, let_expr: &LetExpr, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
         // Create new scope for let bindings
-        let mut let_env = Environment::with_parent(Rc::new(env.clone()));
+        let mut let_env = Environment::with_parent(env);
         
-        // Process bindings sequentially
+        // Process bindings sequentially, each in a scope of its own so that a
+        // closure over an earlier binding keeps seeing it when the name is rebound
         for binding in &let_expr.bindings {
             let value = self.eval_expr(&binding.value, &mut let_env)?;
+            check_binding_type(&self.types.borrow(), &binding.type_annotation, &binding.pattern, &value)?;
+            let_env = Environment::with_parent(&let_env);
             self.bind_pattern(&binding.pattern, &value, &mut let_env)?;
         }
         
         // Evaluate body in the new environment
-        self.eval_do_body(&let_expr.body, &mut let_env)
+        self.eval_body(&let_expr.body, &mut let_env, tail)
+    }
+    
+    /// Define every function of a `letfn` in one new scope that their closures share,
+    /// so each can call the others and itself
+    fn eval_letfn(&self, letfn: &LetFnExpr, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
+        let mut letfn_env = Environment::with_parent(env);
+        for function in &letfn.functions {
+            self.eval_defn(function, &mut letfn_env)?;
+        }
+        self.eval_body(&letfn.body, &mut letfn_env, tail)
     }
     
-    fn eval_do(&self, do_expr: &DoExpr, env: &mut Environment) -> RuntimeResult<Value> {
-        self.eval_do_body(&do_expr.expressions, env)
+    fn eval_do(&self, do_expr: &DoExpr, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
+        self.eval_body(&do_expr.expressions, env, tail)
     }
     
     fn eval_do_body(&self, exprs: &[Expression], env: &mut Environment) -> RuntimeResult<Value> {
-        if exprs.is_empty() {
+        self.eval_body(exprs, env, false)
+    }
+    
+    /// Evaluate a body in order; only its last expression inherits `tail`
+    fn eval_body(&self, exprs: &[Expression], env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
+        let Some((last, init)) = exprs.split_last() else {
             return Ok(Value::Nil);
+        };
+        
+        for expr in init {
+            self.eval_expr(expr, env)?;
+        }
+        self.eval(last, env, tail)
+    }
+    
+    fn eval_loop(&self, loop_expr: &LoopExpr, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
+        let outer = env.clone();
+        let mut loop_env = Environment::with_parent(&outer);
+        for binding in &loop_expr.bindings {
+            let value = self.eval_expr(&binding.value, &mut loop_env)?;
+            self.bind_pattern(&binding.pattern, &value, &mut loop_env)?;
         }
         
-        let mut result = Value::Nil;
-        for expr in exprs {
-            result = self.eval_expr(expr, env)?;
+        loop {
+            let result = self.eval_body(&loop_expr.body, &mut loop_env, tail)?;
+            let values = match self.pending_recur.take() {
+                Some(values) => values,
+                None => return Ok(result),
+            };
+            // Rebind from scratch so that no binding sees the previous iteration's values
+            loop_env = Environment::with_parent(&outer);
+            for (binding, value) in loop_expr.bindings.iter().zip(&values) {
+                self.bind_pattern(&binding.pattern, value, &mut loop_env)?;
+            }
         }
-        Ok(result)
     }
     
-    fn eval_match(&self, match_expr: &MatchExpr, env: &mut Environment) -> RuntimeResult<Value> {
+    fn eval_recur(&self, arguments: &[Expression], env: &mut Environment) -> RuntimeResult<Value> {
+        let values = arguments
+            .iter()
+            .map(|arg| self.eval_expr(arg, env))
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        // `recur` is in tail position, so the enclosing loop sees this as soon as its body returns
+        *self.pending_recur.borrow_mut() = Some(values);
+        Ok(Value::Nil)
+    }
+    
+    /// Fail if a `recur` escaped its loop; the parser rejects these, but hand-built ASTs may not
+    fn no_pending_recur(&self, result: RuntimeResult<Value>) -> RuntimeResult<Value> {
+        if self.pending_recur.take().is_some() {
+            return Err(RuntimeError::InvalidProgram("recur outside of a loop".to_string()));
+        }
+        result
+    }
+    
+    /// Evaluate `body` once per combination of the items bound by `clauses`, the
+    /// first clause outermost, pushing each value to `results` when collecting
+    fn eval_iteration(
+        &self,
+        clauses: &[IterationClause],
+        body: &[Expression],
+        env: &mut Environment,
+        results: &mut Option<Vec<Value>>,
+    ) -> RuntimeResult<()> {
+        match clauses.split_first() {
+            None => {
+                let value = self.eval_do_body(body, env)?;
+                if let Some(results) = results {
+                    results.push(value);
+                }
+                Ok(())
+            }
+            Some((IterationClause::When(test), rest)) => {
+                if self.eval_expr(test, env)?.is_truthy() {
+                    self.eval_iteration(rest, body, env, results)?;
+                }
+                Ok(())
+            }
+            Some((IterationClause::Binding { pattern, collection }, rest)) => {
+                let items = self.eval_expr(collection, env)?.iteration_items()?;
+                let outer = env.clone();
+                for item in items {
+                    let mut item_env = Environment::with_parent(&outer);
+                    self.bind_pattern(pattern, &item, &mut item_env)?;
+                    self.eval_iteration(rest, body, &mut item_env, results)?;
+                }
+                Ok(())
+            }
+        }
+    }
+    
+    fn eval_dotimes(&self, dotimes: &DoTimesExpr, env: &mut Environment) -> RuntimeResult<Value> {
+        let count = match self.eval_expr(&dotimes.count, env)? {
+            Value::Integer(count) => count,
+            other => return Err(RuntimeError::TypeError {
+                expected: "int".to_string(),
+                actual: other.type_name().to_string(),
+                operation: "dotimes".to_string(),
+            }),
+        };
+        
+        let outer = env.clone();
+        for i in 0..count {
+            let mut iteration_env = Environment::with_parent(&outer);
+            iteration_env.define(&dotimes.symbol, Value::Integer(i));
+            self.eval_do_body(&dotimes.body, &mut iteration_env)?;
+        }
+        Ok(Value::Nil)
+    }
+    
+    fn eval_match(&self, match_expr: &MatchExpr, env: &mut Environment, tail: bool) -> RuntimeResult<Value> {
         let value = self.eval_expr(&match_expr.expression, env)?;
         
         for clause in &match_expr.clauses {
-            let mut match_env = Environment::with_parent(Rc::new(env.clone()));
+            let mut match_env = Environment::with_parent(env);
             
             if self.match_pattern(&clause.pattern, &value, &mut match_env)? {
                 // Check guard if present
@@ -234,7 +462,7 @@ impl Evaluator {
                 }
                 
                 // Execute clause body
-                return self.eval_expr(&clause.body, &mut match_env);
+                return self.eval(&clause.body, &mut match_env, tail);
             }
         }
         
@@ -264,7 +492,11 @@ impl Evaluator {
         println!("[{}]{}: {}", level.to_uppercase(), location, message);
         
         // Return the last value or nil
-        Ok(values.last().cloned().unwrap_or(Value::Nil))
+        let result = values.last().cloned().unwrap_or(Value::Nil);
+        if let Some(step_id) = &log_expr.location {
+            self.task_context.borrow_mut().record_step_result(step_id, result.clone());
+        }
+        Ok(result)
     }
     
     fn eval_try_catch(&self, try_expr: &TryCatchExpr, env: &mut Environment) -> RuntimeResult<Value> {
@@ -285,7 +517,7 @@ impl Evaluator {
                 
                 for catch_clause in &try_expr.catch_clauses {
                     if self.match_catch_pattern(&catch_clause.pattern, &error_value)? {
-                        let mut catch_env = Environment::with_parent(Rc::new(env.clone()));
+                        let mut catch_env = Environment::with_parent(env);
                         catch_env.define(&catch_clause.binding, error_value);
                         
                         let result = self.eval_do_body(&catch_clause.body, &mut catch_env);
@@ -328,7 +560,7 @@ impl Evaluator {
             handle.state = crate::runtime::values::ResourceState::Active;
             
             // Create new environment with resource binding
-            let mut resource_env = Environment::with_parent(Rc::new(env.clone()));
+            let mut resource_env = Environment::with_parent(env);
             resource_env.define(&with_expr.resource_symbol, Value::Resource(handle.clone()));
             
             // Execute body and handle cleanup
@@ -366,11 +598,13 @@ impl Evaluator {
     
     fn eval_def(&self, def_expr: &DefExpr, env: &mut Environment) -> RuntimeResult<Value> {
         let value = self.eval_expr(&def_expr.value, env)?;
+        check_binding_type(&self.types.borrow(), &def_expr.type_annotation, &Pattern::Symbol(def_expr.symbol.clone()), &value)?;
         env.define(&def_expr.symbol, value.clone());
         Ok(value)
     }
     
     fn eval_defn(&self, defn_expr: &DefnExpr, env: &mut Environment) -> RuntimeResult<Value> {
+        // The closure shares `env`, so the body sees this name and those defined after it
         let function = Value::Function(Function::UserDefined {
             params: defn_expr.params.clone(),
             variadic_param: defn_expr.variadic_param.clone(),
@@ -382,6 +616,16 @@ impl Evaluator {
         Ok(function)
     }
     
+    fn eval_deftype(&self, deftype: &DefTypeExpr) -> RuntimeResult<Value> {
+        let name = &deftype.name.0;
+        if DefTypeExpr::is_reserved(name) {
+            return Err(RuntimeError::InvalidProgram(format!("deftype cannot define {}, a builtin type or type variable name", name)));
+        }
+        let definition = resolve_type(&deftype.type_expr, &self.types.borrow());
+        self.types.borrow_mut().insert(name.clone(), definition);
+        Ok(Value::Nil)
+    }
+    
     /// Clean up a resource handle by calling its appropriate cleanup function
     fn cleanup_resource(&self, handle: &mut crate::runtime::values::ResourceHandle) -> RuntimeResult<()> {
         // Check if already released
@@ -432,6 +676,9 @@ impl Evaluator {
     // Pattern matching helpers
     fn bind_pattern(&self, pattern: &Pattern, value: &Value, env: &mut Environment) -> RuntimeResult<()> {
         match pattern {
+            Pattern::Spanned(inner, span) => self
+                .bind_pattern(inner, value, env)
+                .map_err(|e| e.with_location(span.as_ref().into())),
             Pattern::Symbol(symbol) => {
                 env.define(symbol, value.clone());
                 Ok(())
@@ -455,7 +702,7 @@ impl Evaluator {
                         
                         // Bind rest if present
                         if let Some(rest_symbol) = rest {
-                            let rest_values = vec[elements.len()..].to_vec();
+                            let rest_values = vec.get(elements.len()..).unwrap_or_default().to_vec();
                             env.define(rest_symbol, Value::Vector(rest_values));
                         }
                         
@@ -475,25 +722,46 @@ impl Evaluator {
                 
                 match value {
                     Value::Map(map) => {
+                        let defaults: Vec<&(Symbol, Expression)> = entries
+                            .iter()
+                            .filter_map(|entry| match entry {
+                                MapDestructuringEntry::Defaults(defaults) => Some(defaults),
+                                _ => None,
+                            })
+                            .flatten()
+                            .collect();
+                        let mut bound_keys = Vec::new();
                         for entry in entries {
                             match entry {
                                 MapDestructuringEntry::KeyBinding { key, pattern } => {
-                                    let entry_value = map.get(key).cloned().unwrap_or(Value::Nil);
+                                    let target = match pattern.unspanned() {
+                                        Pattern::Symbol(symbol) => Some(symbol),
+                                        _ => None,
+                                    };
+                                    let entry_value = self.destructured_entry(map, key, target, &defaults, env)?;
                                     self.bind_pattern(pattern, &entry_value, env)?;
+                                    bound_keys.push(key.clone());
                                 },
                                 MapDestructuringEntry::Keys(symbols) => {
                                     for symbol in symbols {
                                         let key = MapKey::Keyword(Keyword(symbol.0.clone()));
-                                        let entry_value = map.get(&key).cloned().unwrap_or(Value::Nil);
+                                        let entry_value = self.destructured_entry(map, &key, Some(symbol), &defaults, env)?;
                                         env.define(symbol, entry_value);
+                                        bound_keys.push(key);
                                     }
                                 },
+                                MapDestructuringEntry::Defaults(_) => {},
                             }
                         }
                         
-                        // TODO: Handle rest binding
-                        if let Some(_rest_symbol) = rest {
-                            // Implementation needed for rest destructuring
+                        // Bind the entries not named by the pattern
+                        if let Some(rest_symbol) = rest {
+                            let remaining = map
+                                .iter()
+                                .filter(|(key, _)| !bound_keys.contains(key))
+                                .map(|(key, value)| (key.clone(), value.clone()))
+                                .collect();
+                            env.define(rest_symbol, Value::Map(remaining));
                         }
                         
                         Ok(())
@@ -508,8 +776,27 @@ impl Evaluator {
         }
     }
     
+    /// The value under `key`, or the `:or` default of the symbol bound to it when the key is absent
+    fn destructured_entry(
+        &self,
+        map: &HashMap<MapKey, Value>,
+        key: &MapKey,
+        target: Option<&Symbol>,
+        defaults: &[&(Symbol, Expression)],
+        env: &mut Environment,
+    ) -> RuntimeResult<Value> {
+        if let Some(value) = map.get(key) {
+            return Ok(value.clone());
+        }
+        match target.and_then(|symbol| defaults.iter().find(|(name, _)| name == symbol)) {
+            Some((_, default)) => self.eval_expr(default, env),
+            None => Ok(Value::Nil),
+        }
+    }
+    
     fn match_pattern(&self, pattern: &MatchPattern, value: &Value, env: &mut Environment) -> RuntimeResult<bool> {
         match pattern {
+            MatchPattern::Spanned(inner, _) => self.match_pattern(inner, value, env),
             MatchPattern::Literal(lit) => {
                 let lit_value = self.eval_literal(lit)?;
                 Ok(lit_value == *value)
@@ -522,12 +809,14 @@ impl Evaluator {
                 Ok(matches!(value, Value::Keyword(k) if k == keyword))
             },
             MatchPattern::Wildcard => Ok(true),
-            MatchPattern::Type(_type_expr, binding) => {
-                // TODO: Implement proper type matching
+            MatchPattern::Type(type_expr, binding) => {
+                if !value_conforms_to(value, &resolve_type(type_expr, &self.types.borrow())) {
+                    return Ok(false);
+                }
                 if let Some(symbol) = binding {
                     env.define(symbol, value.clone());
                 }
-                Ok(true) // Placeholder - always matches for now
+                Ok(true)
             },
             MatchPattern::Vector { elements, rest } => {
                 match value {
@@ -568,9 +857,14 @@ impl Evaluator {
                             }
                         }
                         
-                        // TODO: Handle rest binding
-                        if let Some(_rest_symbol) = rest {
-                            // Implementation needed
+                        // Bind the entries not named by the pattern
+                        if let Some(rest_symbol) = rest {
+                            let remaining = map
+                                .iter()
+                                .filter(|(key, _)| !entries.iter().any(|entry| &entry.key == *key))
+                                .map(|(key, value)| (key.clone(), value.clone()))
+                                .collect();
+                            env.define(rest_symbol, Value::Map(remaining));
                         }
                         
                         Ok(true)
@@ -593,14 +887,15 @@ impl Evaluator {
         match pattern {
             CatchPattern::Keyword(keyword) => {
                 if let Value::Error(err) = error_value {
-                    Ok(err.error_type == *keyword)
+                    Ok(err.is_kind_of(keyword))
                 } else {
                     Ok(false)
                 }
             },
-            CatchPattern::Type(_type_expr) => {
-                // TODO: Implement proper type matching for catch patterns
-                Ok(true) // Placeholder
+            CatchPattern::Type(type_expr) => match resolve_type(type_expr, &self.types.borrow()) {
+                // A name that is not a defined type, as in `(catch Exception e ...)`, catches every error
+                resolved if matches!(resolved.unspanned(), TypeExpr::Alias(_)) => Ok(true),
+                resolved => Ok(value_conforms_to(error_value, &resolved)),
             },
             CatchPattern::Symbol(_symbol) => {
                 // Symbol patterns match any error (catch-all)
@@ -610,8 +905,36 @@ impl Evaluator {
     }
 }
 
+/// Lets higher-order builtins call function values through the evaluator
+struct EvaluatorCaller<'a> {
+    evaluator: &'a Evaluator,
+    env: &'a mut Environment,
+}
+
+impl FunctionCaller for EvaluatorCaller<'_> {
+    fn call_function(&mut self, function: &Value, args: &[Value]) -> RuntimeResult<Value> {
+        self.evaluator.call_function(function.clone(), args, self.env)
+    }
+}
+
 impl Default for Evaluator {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Check a value bound to a typed pattern against what its type requires at runtime:
+/// the predicates of refined types and the element type and shape of array types,
+/// wherever they occur in it. Type names are looked up in `types`.
+fn check_binding_type(
+    types: &HashMap<String, TypeExpr>,
+    annotation: &Option<TypeExpr>,
+    pattern: &Pattern,
+    value: &Value,
+) -> RuntimeResult<()> {
+    let Some(annotation) = annotation else {
+        return Ok(());
+    };
+    schema::check_binding_type(&resolve_type(annotation, types), value)
+        .map_err(|violation| violation.into_error(&print_type_expr(annotation), &print_pattern(pattern)))
+}
@@ -1,14 +1,18 @@
 // IR Runtime - Efficient execution engine for typed RTFS IR
 // This runtime leverages type information and pre-resolved bindings for performance
 
+use std::cell::{OnceCell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::path::PathBuf;
 use crate::ir::*;
 use crate::runtime::{Value, RuntimeError, RuntimeResult, Environment};
-use crate::runtime::values::{Function, Arity, ResourceHandle, ResourceState, ErrorValue};
+use crate::runtime::values::{Function, FunctionCaller, Arity, ResourceHandle, ResourceState};
 use crate::runtime::stdlib::StandardLibrary;
 use crate::runtime::module_runtime::ModuleRegistry;
+use crate::runtime::task_context::TaskContext;
+use crate::runtime::stack::StackGuard;
+use crate::runtime::schema::{self, value_conforms_to};
 use crate::ast::{Keyword, MapKey};
 
 /// IR-based runtime executor
@@ -17,6 +21,13 @@ pub struct IrRuntime {
     node_cache: HashMap<NodeId, Value>, // Cache for pure expressions
     call_stack: Vec<CallFrame>,
     module_registry: ModuleRegistry,
+    task_context: TaskContext,
+    /// Target loop and values of a `recur`, taken by that loop once its body returns
+    pending_recur: Option<(NodeId, Vec<Value>)>,
+    /// Function and arguments of a call in tail position, made by the caller's trampoline
+    pending_tail_call: Option<(Value, Vec<Value>)>,
+    /// Stack budget for nested calls, shared with the calls it runs as they borrow the runtime
+    stack: Rc<StackGuard>,
 }
 
 /// Call frame for debugging and error reporting
@@ -27,49 +38,208 @@ pub struct CallFrame {
     pub source_location: Option<SourceLocation>,
 }
 
-/// Optimized environment that uses pre-resolved binding IDs
-#[derive(Debug, Clone)]
+/// Optimized environment that uses pre-resolved binding IDs. Like `Environment`, it
+/// is a handle to a shared frame.
+///
+/// Plain bindings (parameters, `let`, `loop`) never change once made, so a closure
+/// copies the ones it captures. The definitions a frame makes (`def`, `defn`,
+/// `letfn`) live in a group that closures share instead, so they see a definition
+/// made after them and a later redefinition of the same name.
+#[derive(Clone)]
 pub struct IrEnvironment {
-    bindings: HashMap<NodeId, Value>, // Keyed by binding node ID, not name
-    parent: Option<Rc<IrEnvironment>>,
+    frame: Rc<IrFrame>,
+}
+
+struct IrFrame {
+    bindings: RefCell<HashMap<NodeId, Value>>, // Keyed by binding node ID, not name
+    /// The definitions of this frame, created with the first one
+    definitions: OnceCell<Rc<Definitions>>,
+    parent: Option<IrEnvironment>,
+}
+
+/// The definition slots of one frame
+#[derive(Default)]
+struct Definitions {
+    slots: RefCell<HashMap<NodeId, Definition>>,
+}
+
+enum Definition {
+    /// Declared ahead of the definition that fills it, so that closures created
+    /// before it can refer to it
+    Declared,
+    Value(Value),
+    /// A function that closes over this group, stored without it so that the two do
+    /// not keep each other alive; reading it closes it over the group again
+    Recursive {
+        params: Rc<[IrNode]>,
+        variadic_param: Option<Box<IrNode>>,
+        body: Rc<[IrNode]>,
+        closure: IrEnvironment,
+    },
+}
+
+/// Where a binding lives
+enum Slot {
+    Binding(Value),
+    Definition(Rc<Definitions>),
 }
 
 impl IrEnvironment {
     pub fn new() -> Self {
         IrEnvironment {
-            bindings: HashMap::new(),
-            parent: None,
+            frame: Rc::new(IrFrame {
+                bindings: RefCell::new(HashMap::new()),
+                definitions: OnceCell::new(),
+                parent: None,
+            }),
+        }
+    }
+    
+    pub fn with_parent(parent: &IrEnvironment) -> Self {
+        IrEnvironment {
+            frame: Rc::new(IrFrame {
+                bindings: RefCell::new(HashMap::new()),
+                definitions: OnceCell::new(),
+                parent: Some(parent.clone()),
+            }),
         }
     }
     
-    pub fn with_parent(parent: Rc<IrEnvironment>) -> Self {
+    /// A frame that only gives access to a group of definitions
+    fn sharing(definitions: Rc<Definitions>, parent: Option<&IrEnvironment>) -> Self {
         IrEnvironment {
-            bindings: HashMap::new(),
-            parent: Some(parent),
+            frame: Rc::new(IrFrame {
+                bindings: RefCell::new(HashMap::new()),
+                definitions: OnceCell::from(definitions),
+                parent: parent.cloned(),
+            }),
+        }
+    }
+    
+    pub fn define(&self, binding_id: NodeId, value: Value) {
+        self.frame.bindings.borrow_mut().insert(binding_id, value);
+    }
+    
+    fn definitions(&self) -> &Rc<Definitions> {
+        self.frame.definitions.get_or_init(Rc::default)
+    }
+    
+    /// Declare the slots of the functions a body defines in this frame, so that the
+    /// closures created before a definition share the slot it fills
+    pub fn declare_functions(&self, expressions: &[IrNode]) {
+        for expression in expressions {
+            if let IrNode::FunctionDef { id, .. } = expression {
+                self.definitions().slots.borrow_mut().entry(*id).or_insert(Definition::Declared);
+            }
         }
     }
     
-    pub fn define(&mut self, binding_id: NodeId, value: Value) {
-        self.bindings.insert(binding_id, value);
+    /// Fill (or refill) a definition slot of this frame
+    pub fn fill(&self, binding_id: NodeId, value: Value) {
+        let definitions = self.definitions();
+        let definition = match &value {
+            Value::Function(Function::IrLambda { params, variadic_param, body, closure }) => {
+                match closure.without_definitions(definitions) {
+                    Some(closure) => Definition::Recursive {
+                        params: params.clone(),
+                        variadic_param: variadic_param.clone(),
+                        body: body.clone(),
+                        closure,
+                    },
+                    None => Definition::Value(value),
+                }
+            }
+            _ => Definition::Value(value),
+        };
+        definitions.slots.borrow_mut().insert(binding_id, definition);
+    }
+    
+    pub fn lookup(&self, binding_id: NodeId) -> Option<Value> {
+        if let Some(value) = self.frame.bindings.borrow().get(&binding_id) {
+            return Some(value.clone());
+        }
+        if let Some(definitions) = self.frame.definitions.get() {
+            match definitions.slots.borrow().get(&binding_id) {
+                Some(Definition::Declared) => return None,
+                Some(Definition::Value(value)) => return Some(value.clone()),
+                Some(Definition::Recursive { params, variadic_param, body, closure }) => {
+                    return Some(Value::Function(Function::IrLambda {
+                        params: params.clone(),
+                        variadic_param: variadic_param.clone(),
+                        body: body.clone(),
+                        closure: IrEnvironment::sharing(definitions.clone(), Some(closure)),
+                    }));
+                }
+                None => {}
+            }
+        }
+        self.frame.parent.as_ref().and_then(|p| p.lookup(binding_id))
     }
     
-    pub fn lookup(&self, binding_id: NodeId) -> Option<&Value> {
-        self.bindings.get(&binding_id).or_else(|| {
-            self.parent.as_ref().and_then(|p| p.lookup(binding_id))
+    /// Find the binding or definition slot a closure captures
+    fn slot(&self, binding_id: NodeId) -> Option<Slot> {
+        if let Some(value) = self.frame.bindings.borrow().get(&binding_id) {
+            return Some(Slot::Binding(value.clone()));
+        }
+        if let Some(definitions) = self.frame.definitions.get() {
+            if definitions.slots.borrow().contains_key(&binding_id) {
+                return Some(Slot::Definition(definitions.clone()));
+            }
+        }
+        self.frame.parent.as_ref().and_then(|p| p.slot(binding_id))
+    }
+    
+    /// This closure without its access to `definitions`, if it has any
+    fn without_definitions(&self, definitions: &Rc<Definitions>) -> Option<IrEnvironment> {
+        if self.frame.definitions.get().is_some_and(|own| Rc::ptr_eq(own, definitions)) {
+            return Some(self.frame.parent.clone().unwrap_or_default());
+        }
+        let parent = self.frame.parent.as_ref()?.without_definitions(definitions)?;
+        Some(IrEnvironment {
+            frame: Rc::new(IrFrame {
+                bindings: RefCell::new(self.frame.bindings.borrow().clone()),
+                definitions: self.frame.definitions.clone(),
+                parent: Some(parent),
+            }),
         })
     }
-      pub fn update(&mut self, binding_id: NodeId, value: Value) -> bool {
-        if self.bindings.contains_key(&binding_id) {
-            self.bindings.insert(binding_id, value);
+    
+    /// Update a binding in the scope that defines it
+    pub fn update(&self, binding_id: NodeId, value: Value) -> bool {
+        let mut bindings = self.frame.bindings.borrow_mut();
+        if let Some(slot) = bindings.get_mut(&binding_id) {
+            *slot = value;
             true
         } else {
-            false
+            drop(bindings);
+            self.frame.parent.as_ref().is_some_and(|p| p.update(binding_id, value))
         }
     }
     
     /// Get the number of bindings in the current scope (used for generating unique IDs)
     pub fn binding_count(&self) -> usize {
-        self.bindings.len()
+        self.frame.bindings.borrow().len()
+    }
+}
+
+impl Default for IrEnvironment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Lists slots only: the values may be closures over this very environment
+impl std::fmt::Debug for IrEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut slots: Vec<NodeId> = self.frame.bindings.borrow().keys().copied().collect();
+        if let Some(definitions) = self.frame.definitions.get() {
+            slots.extend(definitions.slots.borrow().keys().copied());
+        }
+        slots.sort_unstable();
+        f.debug_struct("IrEnvironment")
+            .field("slots", &slots)
+            .field("parent", &self.frame.parent)
+            .finish()
     }
 }
 
@@ -81,6 +251,10 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
             node_cache: HashMap::new(),
             call_stack: Vec::new(),
             module_registry: ModuleRegistry::new(),
+            task_context: TaskContext::new(),
+            pending_recur: None,
+            pending_tail_call: None,
+            stack: Rc::new(StackGuard::new()),
         }
     }
     
@@ -100,6 +274,12 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
     
     /// Execute a single IR node
     pub fn execute_node(&mut self, node: &IrNode, env: &mut IrEnvironment) -> RuntimeResult<Value> {
+        self.execute(node, env, false)
+    }
+    
+    /// Execute a node. In `tail` position of a lambda body, a call to another IR
+    /// lambda is left pending for the trampoline in `call_ir_lambda`.
+    fn execute(&mut self, node: &IrNode, env: &mut IrEnvironment, tail: bool) -> RuntimeResult<Value> {
         // Check cache for pure expressions
         if self.is_pure_expression(node) {
             if let Some(cached_value) = self.node_cache.get(&node.id()) {
@@ -107,7 +287,10 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
             }
         }
         
-        let result = self.execute_node_uncached(node, env)?;
+        let result = self.execute_node_uncached(node, env, tail).map_err(|e| match node.source_location() {
+            Some(location) => e.with_location(location.clone()),
+            None => e,
+        })?;
         
         // Cache pure expressions
         if self.is_pure_expression(node) {
@@ -118,48 +301,51 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
     }
     
     /// Execute node without caching
-    fn execute_node_uncached(&mut self, node: &IrNode, env: &mut IrEnvironment) -> RuntimeResult<Value> {
+    fn execute_node_uncached(&mut self, node: &IrNode, env: &mut IrEnvironment, tail: bool) -> RuntimeResult<Value> {
         match node {
             IrNode::Literal { value, .. } => self.execute_literal(value),
-              IrNode::VariableRef { binding_id, name, .. } => {
-                match env.lookup(*binding_id) {
-                    Some(value) => Ok(value.clone()),
-                    None => {
-                        // Check if it's a qualified symbol (e.g., "module/symbol")
-                        if ModuleRegistry::is_qualified_symbol(name) {
-                            // Resolve through module registry
-                            self.module_registry.resolve_qualified_symbol(name)
-                        } else {
-                            // Fallback to global environment lookup by name
-                            let mut global_env = Environment::with_parent(self.global_env.clone());
-                            global_env.lookup(&crate::ast::Symbol(name.clone()))
-                        }
-                    }
-                }
-            }
+            IrNode::Vector { elements, .. } => self.execute_vector(elements, env),
+            IrNode::Map { entries, .. } => self.execute_map(entries, env),
+            IrNode::VariableRef { binding_id, name, .. } => self.execute_variable_ref(*binding_id, name, env),
             
             IrNode::Apply { function, arguments, .. } => {
-                self.execute_apply(function, arguments, env)
+                self.execute_apply(function, arguments, env, tail)
+            }
+            
+            IrNode::Arithmetic { op, operands, ir_type, .. } => {
+                self.execute_arithmetic(*op, operands, ir_type, env)
             }
             
             IrNode::If { condition, then_branch, else_branch, .. } => {
-                self.execute_if(condition, then_branch, else_branch.as_deref(), env)
+                self.execute_if(condition, then_branch, else_branch.as_deref(), env, tail)
             }
             
             IrNode::Let { bindings, body, .. } => {
-                self.execute_let(bindings, body, env)
+                self.execute_let(bindings, body, env, tail)
             }
             
             IrNode::Do { expressions, .. } => {
-                self.execute_do(expressions, env)
+                self.execute_body(expressions, env, tail)
+            }
+            
+            IrNode::Loop { id, bindings, body, .. } => {
+                self.execute_loop(*id, bindings, body, env, tail)
+            }
+            
+            IrNode::Recur { loop_id, arguments, .. } => {
+                self.execute_recur(*loop_id, arguments, env)
             }
             
-            IrNode::Lambda { params, body, captures, .. } => {
-                self.execute_lambda(params, body, captures, env)
+            IrNode::Iterate { clauses, body, collect, .. } => {
+                self.execute_iterate(clauses, body, *collect, env)
+            }
+            
+            IrNode::Lambda { params, variadic_param, body, captures, .. } => {
+                self.execute_lambda(params, variadic_param.as_deref(), body, captures, env)
             }
             
             IrNode::Match { expression, clauses, .. } => {
-                self.execute_match(expression, clauses, env)
+                self.execute_match(expression, clauses, env, tail)
             }
             
             IrNode::TryCatch { try_body, catch_clauses, finally_body, .. } => {
@@ -182,20 +368,19 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
                 self.execute_task_context_access(field_name)
             }
             
-            IrNode::FunctionDef { name, lambda, .. } => {
-                let function_value = self.execute_node(lambda, env)?;
-                env.define(node.id(), function_value.clone());
-                Ok(function_value)
-            }            IrNode::VariableDef { name, init_expr, .. } => {
-                let value = self.execute_node(init_expr, env)?;
-                env.define(node.id(), value.clone());
-                Ok(value)
+            IrNode::FunctionDef { id, lambda, .. } => {
+                self.execute_definition(*id, None, lambda, env)
+            }            IrNode::VariableDef { id, name, type_annotation, init_expr, .. } => {
+                self.execute_definition(*id, type_annotation.as_ref().map(|t| (name.as_str(), t)), init_expr, env)
             }
             
             IrNode::Module { name, exports, definitions, .. } => {
                 self.execute_module(name, exports, definitions, env)
             }
             
+            // Types are resolved during conversion; naming one has no runtime effect
+            IrNode::TypeDef { .. } => Ok(Value::Nil),
+            
             IrNode::Import { module_name, alias, imports, .. } => {
                 self.execute_import(module_name, alias.as_deref(), imports.as_ref(), env)
             }
@@ -206,6 +391,65 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         }
     }
     
+    /// Look up a variable by binding slot, falling back to module and global names
+    fn execute_variable_ref(&mut self, binding_id: NodeId, name: &str, env: &IrEnvironment) -> RuntimeResult<Value> {
+        match env.lookup(binding_id) {
+            Some(value) => Ok(value),
+            None => {
+                // Check if it's a qualified symbol (e.g., "module/symbol")
+                if ModuleRegistry::is_qualified_symbol(name) {
+                    // Resolve through module registry
+                    self.module_registry.resolve_qualified_symbol(name)
+                } else {
+                    // Fallback to global environment lookup by name
+                    let mut global_env = Environment::with_parent(&self.global_env);
+                    global_env.lookup(&crate::ast::Symbol(name.to_string()))
+                }
+            }
+        }
+    }
+    
+    /// Evaluate the arguments of a `recur` and leave them for its loop
+    fn execute_recur(&mut self, loop_id: NodeId, arguments: &[IrNode], env: &mut IrEnvironment) -> RuntimeResult<Value> {
+        let values = arguments
+            .iter()
+            .map(|argument| self.execute_node(argument, env))
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        // `recur` is in tail position, so its loop sees this as soon as the body returns
+        self.pending_recur = Some((loop_id, values));
+        Ok(Value::Nil)
+    }
+    
+    /// Execute `for`, collecting the body's values, or `doseq`/`dotimes`, which return nil
+    fn execute_iterate(
+        &mut self,
+        clauses: &[IrIterationClause],
+        body: &[IrNode],
+        collect: bool,
+        env: &mut IrEnvironment,
+    ) -> RuntimeResult<Value> {
+        let mut results = if collect { Some(Vec::new()) } else { None };
+        self.execute_iteration(clauses, body, env, &mut results)?;
+        Ok(results.map_or(Value::Nil, Value::Vector))
+    }
+    
+    /// Define the value of a `def` or `defn` in the slot of its node, checking
+    /// it against the type annotation if there is one
+    fn execute_definition(
+        &mut self,
+        id: NodeId,
+        annotation: Option<(&str, &IrType)>,
+        init_expr: &IrNode,
+        env: &mut IrEnvironment,
+    ) -> RuntimeResult<Value> {
+        let value = self.execute_node(init_expr, env)?;
+        if let Some((name, annotation)) = annotation {
+            check_binding_type(annotation, name, &value)?;
+        }
+        env.fill(id, value.clone());
+        Ok(value)
+    }
+    
     /// Execute a literal value
     fn execute_literal(&self, literal: &crate::ast::Literal) -> RuntimeResult<Value> {
         match literal {
@@ -218,8 +462,27 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         }
     }
     
+    /// Build a vector from its element expressions, evaluated left to right
+    fn execute_vector(&mut self, elements: &[IrNode], env: &mut IrEnvironment) -> RuntimeResult<Value> {
+        let values = elements
+            .iter()
+            .map(|element| self.execute_node(element, env))
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        Ok(Value::Vector(values))
+    }
+    
+    /// Build a map from its entries, evaluating each value expression
+    fn execute_map(&mut self, entries: &[IrMapEntry], env: &mut IrEnvironment) -> RuntimeResult<Value> {
+        let mut map = HashMap::new();
+        for entry in entries {
+            let value = self.execute_node(&entry.value, env)?;
+            map.insert(entry.key.clone(), value);
+        }
+        Ok(Value::Map(map))
+    }
+    
     /// Execute function application with optimized dispatch
-    fn execute_apply(&mut self, function: &IrNode, arguments: &[IrNode], env: &mut IrEnvironment) -> RuntimeResult<Value> {
+    fn execute_apply(&mut self, function: &IrNode, arguments: &[IrNode], env: &mut IrEnvironment, tail: bool) -> RuntimeResult<Value> {
         // Add call frame for debugging
         self.call_stack.push(CallFrame {
             node_id: function.id(),
@@ -234,11 +497,54 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
             arg_values.push(self.execute_node(arg, env)?);
         }
         
+        if tail && matches!(func_value, Value::Function(Function::IrLambda { .. })) {
+            self.call_stack.pop();
+            self.pending_tail_call = Some((func_value, arg_values));
+            return Ok(Value::Nil);
+        }
+        
         let result = self.call_function(func_value, &arg_values, env);
         self.call_stack.pop();
         result
     }
     
+    /// Execute arithmetic specialized by the optimizer, without looking up the builtin
+    fn execute_arithmetic(&mut self, op: ArithmeticOp, operands: &[IrNode], ir_type: &IrType, env: &mut IrEnvironment) -> RuntimeResult<Value> {
+        let values = operands
+            .iter()
+            .map(|operand| self.execute_node(operand, env))
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        let type_error = |value: &Value| RuntimeError::TypeError {
+            expected: "number".to_string(),
+            actual: value.type_name().to_string(),
+            operation: op.name().to_string(),
+        };
+        if *ir_type == IrType::Int {
+            let ints = values
+                .iter()
+                .map(|value| match value {
+                    Value::Integer(i) => Ok(*i),
+                    other => Err(type_error(other)),
+                })
+                .collect::<RuntimeResult<Vec<_>>>()?;
+            let (first, rest) = ints.split_first().ok_or_else(|| RuntimeError::InvalidProgram("arithmetic without operands".to_string()))?;
+            op.apply_int(*first, rest)
+                .map(Value::Integer)
+                .ok_or_else(|| RuntimeError::InvalidArgument(format!("integer overflow in {}", op.name())))
+        } else {
+            let floats = values
+                .iter()
+                .map(|value| match value {
+                    Value::Integer(i) => Ok(*i as f64),
+                    Value::Float(f) => Ok(*f),
+                    other => Err(type_error(other)),
+                })
+                .collect::<RuntimeResult<Vec<_>>>()?;
+            let (first, rest) = floats.split_first().ok_or_else(|| RuntimeError::InvalidProgram("arithmetic without operands".to_string()))?;
+            Ok(Value::Float(op.apply_float(*first, rest)))
+        }
+    }
+    
     /// Call a function value (similar to AST runtime but with IR context)
     fn call_function(&mut self, func: Value, args: &[Value], env: &mut IrEnvironment) -> RuntimeResult<Value> {
         match func {
@@ -246,9 +552,17 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
                 self.check_arity(&arity, args.len())?;
                 func(args)
             }
+            Value::Function(Function::BuiltinWithCallback { func, arity, .. }) => {
+                self.check_arity(&arity, args.len())?;
+                func(args, &mut IrCaller { runtime: self, env })
+            }
             Value::Function(Function::UserDefined { params, body, closure, .. }) => {
                 self.call_user_function(params, None, body, closure, args, env)
             }
+            Value::Function(Function::IrLambda { params, variadic_param, body, closure }) => {
+                let stack = self.stack.clone();
+                stack.call(|| self.call_ir_lambda(params, variadic_param, body, closure, args.to_vec()))
+            }
             _ => Err(RuntimeError::NotCallable(format!("{:?}", func))),
         }
     }
@@ -270,7 +584,7 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         for (i, param) in params.iter().enumerate() {
             if let Some(arg_value) = args.get(i) {
                 // For now, only handle simple parameter binding
-                if let crate::ast::Pattern::Symbol(sym) = &param.pattern {
+                if let crate::ast::Pattern::Symbol(sym) = param.pattern.unspanned() {
                     // In full implementation, would use parameter binding ID
                     // For now, using a placeholder approach
                     func_env.define(i as NodeId + 1000, arg_value.clone());
@@ -283,6 +597,66 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         Ok(Value::Nil)
     }
     
+    /// Call a function compiled to IR. The body runs in a fresh environment whose
+    /// parent holds only the slots the lambda captured. Calls in tail position of
+    /// the body return to this loop instead of nesting.
+    fn call_ir_lambda(
+        &mut self,
+        mut params: Rc<[IrNode]>,
+        mut variadic_param: Option<Box<IrNode>>,
+        mut body: Rc<[IrNode]>,
+        mut closure: IrEnvironment,
+        mut args: Vec<Value>,
+    ) -> RuntimeResult<Value> {
+        loop {
+            let arity = match variadic_param {
+                Some(_) => Arity::AtLeast(params.len()),
+                None => Arity::Exact(params.len()),
+            };
+            self.check_arity(&arity, args.len())?;
+            
+            let mut func_env = IrEnvironment::with_parent(&closure);
+            let rest = args.split_off(params.len());
+            for (param, arg) in params.iter().zip(args) {
+                self.bind_param(param, arg, &mut func_env)?;
+            }
+            if let Some(variadic) = &variadic_param {
+                self.bind_param(variadic, Value::Vector(rest), &mut func_env)?;
+            }
+            
+            let result = self.execute_body(&body, &mut func_env, true)?;
+            match self.pending_tail_call.take() {
+                Some((Value::Function(Function::IrLambda { params: next_params, variadic_param: next_variadic, body: next_body, closure: next_closure }), next_args)) => {
+                    params = next_params;
+                    variadic_param = next_variadic;
+                    body = next_body;
+                    closure = next_closure;
+                    args = next_args;
+                }
+                Some((func, args)) => return self.call_function(func, &args, &mut func_env),
+                None => return Ok(result),
+            }
+        }
+    }
+    
+    /// Bind an argument to a lambda parameter
+    fn bind_param(&mut self, param: &IrNode, value: Value, env: &mut IrEnvironment) -> RuntimeResult<()> {
+        match param {
+            IrNode::Param { binding, ir_type, .. } => match binding.as_ref() {
+                IrNode::VariableBinding { id, name, .. } => {
+                    check_binding_type(ir_type, name, &value)?;
+                    env.define(*id, value);
+                    Ok(())
+                }
+                IrNode::Destructure { pattern, defaults, .. } => self.destructure(pattern, defaults, &value, env),
+                other => Err(RuntimeError::NotImplemented(format!(
+                    "Parameter pattern not supported: {:?}", other
+                ))),
+            },
+            other => Err(RuntimeError::InvalidProgram(format!("Expected Param node, got {:?}", other))),
+        }
+    }
+    
     /// Execute if expression with type-aware short-circuiting
     fn execute_if(
         &mut self,
@@ -290,77 +664,266 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         then_branch: &IrNode,
         else_branch: Option<&IrNode>,
         env: &mut IrEnvironment,
+        tail: bool,
     ) -> RuntimeResult<Value> {
         let condition_value = self.execute_node(condition, env)?;
         
         if condition_value.is_truthy() {
-            self.execute_node(then_branch, env)
+            self.execute(then_branch, env, tail)
         } else if let Some(else_node) = else_branch {
-            self.execute_node(else_node, env)
+            self.execute(else_node, env, tail)
         } else {
             Ok(Value::Nil)
         }
     }
     
     /// Execute let binding with optimized scoping
-    fn execute_let(&mut self, bindings: &[IrLetBinding], body: &[IrNode], env: &mut IrEnvironment) -> RuntimeResult<Value> {
+    fn execute_let(&mut self, bindings: &[IrLetBinding], body: &[IrNode], env: &mut IrEnvironment, tail: bool) -> RuntimeResult<Value> {
         // Create new environment for let scope
-        let mut let_env = IrEnvironment::with_parent(Rc::new(env.clone()));
+        let mut let_env = IrEnvironment::with_parent(env);
         
         // Process bindings in order
         for binding in bindings {
             let value = self.execute_node(&binding.init_expr, &mut let_env)?;
-            
-            // Bind the value using the pattern's binding ID
-            if let IrNode::VariableBinding { id, .. } = &binding.pattern {
-                let_env.define(*id, value);
-            }
-            // TODO: Handle complex destructuring patterns
+            self.bind_pattern(&binding.pattern, binding.type_annotation.as_ref(), value, &mut let_env)?;
         }
         
         // Execute body
-        let mut result = Value::Nil;
-        for expr in body {
-            result = self.execute_node(expr, &mut let_env)?;
+        self.execute_body(body, &mut let_env, tail)
+    }
+    
+    /// Bind a value to the `VariableBinding` or `Destructure` node of a binding form
+    fn bind_pattern(
+        &mut self,
+        pattern: &IrNode,
+        type_annotation: Option<&IrType>,
+        value: Value,
+        env: &mut IrEnvironment,
+    ) -> RuntimeResult<()> {
+        match pattern {
+            IrNode::VariableBinding { id, name, .. } => {
+                if let Some(annotation) = type_annotation {
+                    check_binding_type(annotation, name, &value)?;
+                }
+                env.define(*id, value);
+                Ok(())
+            }
+            IrNode::Destructure { pattern, defaults, .. } => self.destructure(pattern, defaults, &value, env),
+            other => Err(RuntimeError::InvalidProgram(format!("Expected binding pattern, got {:?}", other))),
+        }
+    }
+    
+    /// Execute a loop, running the body again with fresh bindings whenever it ends in a `recur`
+    fn execute_loop(
+        &mut self,
+        id: NodeId,
+        bindings: &[IrLetBinding],
+        body: &[IrNode],
+        env: &mut IrEnvironment,
+        tail: bool,
+    ) -> RuntimeResult<Value> {
+        let outer = env.clone();
+        let mut loop_env = IrEnvironment::with_parent(&outer);
+        for binding in bindings {
+            let value = self.execute_node(&binding.init_expr, &mut loop_env)?;
+            self.bind_pattern(&binding.pattern, binding.type_annotation.as_ref(), value, &mut loop_env)?;
         }
         
-        Ok(result)
+        loop {
+            let result = self.execute_body(body, &mut loop_env, tail)?;
+            let values = match self.pending_recur.take() {
+                Some((loop_id, values)) if loop_id == id => values,
+                other => {
+                    self.pending_recur = other;
+                    return Ok(result);
+                }
+            };
+            loop_env = IrEnvironment::with_parent(&outer);
+            for (binding, value) in bindings.iter().zip(values) {
+                self.bind_pattern(&binding.pattern, binding.type_annotation.as_ref(), value, &mut loop_env)?;
+            }
+        }
+    }
+    
+    /// Execute `body` once per combination of the items bound by `clauses`, the
+    /// first clause outermost, pushing each value to `results` when collecting
+    fn execute_iteration(
+        &mut self,
+        clauses: &[IrIterationClause],
+        body: &[IrNode],
+        env: &mut IrEnvironment,
+        results: &mut Option<Vec<Value>>,
+    ) -> RuntimeResult<()> {
+        let (clause, rest) = match clauses.split_first() {
+            Some(split) => split,
+            None => {
+                let value = self.execute_do(body, env)?;
+                if let Some(results) = results {
+                    results.push(value);
+                }
+                return Ok(());
+            }
+        };
+        
+        // A range is counted through as it runs rather than collected up front
+        let (pattern, items): (_, Box<dyn Iterator<Item = Value>>) = match clause {
+            IrIterationClause::When(test) => {
+                if self.execute_node(test, env)?.is_truthy() {
+                    self.execute_iteration(rest, body, env, results)?;
+                }
+                return Ok(());
+            }
+            IrIterationClause::Binding { pattern, collection } => {
+                (pattern, Box::new(self.execute_node(collection, env)?.iteration_items()?.into_iter()))
+            }
+            IrIterationClause::Range { binding, count } => match self.execute_node(count, env)? {
+                Value::Integer(count) => (binding, Box::new((0..count).map(Value::Integer))),
+                other => {
+                    return Err(RuntimeError::TypeError {
+                        expected: "int".to_string(),
+                        actual: other.type_name().to_string(),
+                        operation: "dotimes".to_string(),
+                    })
+                }
+            },
+        };
+        
+        let outer = env.clone();
+        for item in items {
+            let mut item_env = IrEnvironment::with_parent(&outer);
+            self.bind_pattern(pattern, None, item, &mut item_env)?;
+            self.execute_iteration(rest, body, &mut item_env, results)?;
+        }
+        Ok(())
+    }
+    
+    /// Bind the variables of a destructuring pattern. Unlike matching this never fails on
+    /// absent parts: missing elements and keys bind nil, or the key's `:or` default.
+    fn destructure(
+        &mut self,
+        pattern: &IrPattern,
+        defaults: &[IrPatternDefault],
+        value: &Value,
+        env: &mut IrEnvironment,
+    ) -> RuntimeResult<()> {
+        match pattern {
+            IrPattern::Variable { binding_id, .. } => {
+                env.define(*binding_id, value.clone());
+                Ok(())
+            }
+            IrPattern::Wildcard => Ok(()),
+            IrPattern::As { binding_id, pattern, .. } => {
+                env.define(*binding_id, value.clone());
+                self.destructure(pattern, defaults, value, env)
+            }
+            IrPattern::Vector { elements, rest } => match value {
+                Value::Vector(items) => {
+                    for (i, element) in elements.iter().enumerate() {
+                        self.destructure(element, defaults, items.get(i).unwrap_or(&Value::Nil), env)?;
+                    }
+                    if let Some(rest) = rest {
+                        let rest_values = items.get(elements.len()..).unwrap_or_default().to_vec();
+                        self.destructure(rest, defaults, &Value::Vector(rest_values), env)?;
+                    }
+                    Ok(())
+                }
+                _ => Err(RuntimeError::TypeError {
+                    expected: "vector".to_string(),
+                    actual: value.type_name().to_string(),
+                    operation: "vector destructuring".to_string(),
+                }),
+            },
+            IrPattern::Map { entries, rest } => match value {
+                Value::Map(map) => {
+                    for entry in entries {
+                        let entry_value = match (map.get(&entry.key), &entry.pattern) {
+                            (Some(entry_value), _) => entry_value.clone(),
+                            (None, IrPattern::Variable { binding_id, .. }) => {
+                                match defaults.iter().find(|default| default.binding_id == *binding_id) {
+                                    Some(default) => self.execute_node(&default.value, env)?,
+                                    None => Value::Nil,
+                                }
+                            }
+                            (None, _) => Value::Nil,
+                        };
+                        self.destructure(&entry.pattern, defaults, &entry_value, env)?;
+                    }
+                    if let Some(rest) = rest {
+                        let remaining = map
+                            .iter()
+                            .filter(|(key, _)| !entries.iter().any(|entry| &entry.key == *key))
+                            .map(|(key, value)| (key.clone(), value.clone()))
+                            .collect();
+                        self.destructure(rest, defaults, &Value::Map(remaining), env)?;
+                    }
+                    Ok(())
+                }
+                _ => Err(RuntimeError::TypeError {
+                    expected: "map".to_string(),
+                    actual: value.type_name().to_string(),
+                    operation: "map destructuring".to_string(),
+                }),
+            },
+            IrPattern::Literal(_) | IrPattern::Type(_) => Err(RuntimeError::InvalidProgram(format!(
+                "Pattern cannot be used for destructuring: {:?}", pattern
+            ))),
+        }
     }
     
     /// Execute do block
     fn execute_do(&mut self, expressions: &[IrNode], env: &mut IrEnvironment) -> RuntimeResult<Value> {
-        let mut result = Value::Nil;
-        for expr in expressions {
-            result = self.execute_node(expr, env)?;
+        self.execute_body(expressions, env, false)
+    }
+    
+    /// Execute a body in order; only its last expression inherits `tail`
+    fn execute_body(&mut self, expressions: &[IrNode], env: &mut IrEnvironment, tail: bool) -> RuntimeResult<Value> {
+        let Some((last, init)) = expressions.split_last() else {
+            return Ok(Value::Nil);
+        };
+        env.declare_functions(expressions);
+        for expr in init {
+            self.execute_node(expr, env)?;
         }
-        Ok(result)
+        self.execute(last, env, tail)
     }
     
     /// Execute lambda creation with closure capture
     fn execute_lambda(
         &mut self,
-        _params: &[IrNode],
-        _body: &[IrNode],
+        params: &Rc<[IrNode]>,
+        variadic_param: Option<&IrNode>,
+        body: &Rc<[IrNode]>,
         captures: &[IrCapture],
         env: &mut IrEnvironment,
-    ) -> RuntimeResult<Value> {        // Capture free variables from current environment
-        let mut captured_env = Environment::new();
+    ) -> RuntimeResult<Value> {
+        // Copy the captured bindings and share the groups of the captured definitions,
+        // which may not be filled yet. A capture with neither is resolved by name when
+        // the function is called.
+        let mut closure = IrEnvironment::new();
+        let mut groups: Vec<Rc<Definitions>> = Vec::new();
         for capture in captures {
-            if let Some(value) = env.lookup(capture.binding_id) {
-                captured_env.define(&crate::ast::Symbol(capture.name.clone()), value.clone());
+            match env.slot(capture.binding_id) {
+                Some(Slot::Binding(value)) => closure.define(capture.binding_id, value),
+                Some(Slot::Definition(group)) => {
+                    if !groups.iter().any(|known| Rc::ptr_eq(known, &group)) {
+                        groups.push(group);
+                    }
+                }
+                None => {}
             }
         }
+        for group in groups {
+            closure = IrEnvironment::sharing(group, Some(&closure));
+        }
         
-        // Create user-defined function - simplified for now
-        let func = Function::UserDefined {
-            params: vec![], // Would convert from IR params
-            variadic_param: None,
-            body: vec![], // Would convert from IR body
-            closure: captured_env,
-        };
-        
-        Ok(Value::Function(func))
-    }    /// Execute a module definition
+        Ok(Value::Function(Function::IrLambda {
+            params: params.clone(),
+            variadic_param: variadic_param.map(|p| Box::new(p.clone())),
+            body: body.clone(),
+            closure,
+        }))
+    }
+    /// Execute a module definition
     fn execute_module(&mut self, name: &str, exports: &[String], definitions: &[IrNode], env: &mut IrEnvironment) -> RuntimeResult<Value> {
         // For now, we'll execute definitions in the current environment
         // In a full implementation, we would create a proper module environment
@@ -370,6 +933,7 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
                  name, exports.len(), definitions.len());
         
         // Execute all definitions in the module
+        env.declare_functions(definitions);
         for definition in definitions {
             self.execute_node(definition, env)?;
         }
@@ -434,37 +998,196 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         Ok(Value::Nil) // Import doesn't return a value
     }
     
-    // Placeholder implementations for remaining methods
-    fn execute_match(&mut self, _expression: &IrNode, _clauses: &[IrMatchClause], _env: &mut IrEnvironment) -> RuntimeResult<Value> {
-        // TODO: Implement pattern matching
-        Ok(Value::Nil)
+    /// Execute pattern matching; each clause binds into its own scope
+    fn execute_match(&mut self, expression: &IrNode, clauses: &[IrMatchClause], env: &mut IrEnvironment, tail: bool) -> RuntimeResult<Value> {
+        let value = self.execute_node(expression, env)?;
+        
+        for clause in clauses {
+            let mut clause_env = IrEnvironment::with_parent(env);
+            if !self.match_pattern(&clause.pattern, &value, &mut clause_env) {
+                continue;
+            }
+            if let Some(guard) = &clause.guard {
+                if !self.execute_node(guard, &mut clause_env)?.is_truthy() {
+                    continue;
+                }
+            }
+            return self.execute(&clause.body, &mut clause_env, tail);
+        }
+        
+        Err(RuntimeError::MatchError(format!("No matching clause for value: {}", value.to_string())))
     }
     
+    /// Match a value against a pattern, binding pattern variables in `env`
+    fn match_pattern(&self, pattern: &IrPattern, value: &Value, env: &mut IrEnvironment) -> bool {
+        match pattern {
+            IrPattern::Literal(literal) => {
+                matches!(self.execute_literal(literal), Ok(literal_value) if literal_value == *value)
+            }
+            IrPattern::Variable { binding_id, .. } => {
+                env.define(*binding_id, value.clone());
+                true
+            }
+            IrPattern::Wildcard => true,
+            IrPattern::Type(ir_type) => value_conforms_to(value, &ir_type.to_type_expr()),
+            IrPattern::Vector { elements, rest } => match value {
+                Value::Vector(items) if items.len() >= elements.len() => {
+                    let elements_match = elements
+                        .iter()
+                        .zip(items)
+                        .all(|(element, item)| self.match_pattern(element, item, env));
+                    if !elements_match {
+                        return false;
+                    }
+                    match rest {
+                        Some(rest) => self.match_pattern(rest, &Value::Vector(items[elements.len()..].to_vec()), env),
+                        None => true,
+                    }
+                }
+                _ => false,
+            },
+            IrPattern::Map { entries, rest } => match value {
+                Value::Map(map) => {
+                    for entry in entries {
+                        match map.get(&entry.key) {
+                            Some(entry_value) if self.match_pattern(&entry.pattern, entry_value, env) => {}
+                            _ => return false,
+                        }
+                    }
+                    match rest {
+                        Some(rest) => {
+                            let remaining = map
+                                .iter()
+                                .filter(|(key, _)| !entries.iter().any(|entry| &entry.key == *key))
+                                .map(|(key, value)| (key.clone(), value.clone()))
+                                .collect();
+                            self.match_pattern(rest, &Value::Map(remaining), env)
+                        }
+                        None => true,
+                    }
+                }
+                _ => false,
+            },
+            IrPattern::As { binding_id, pattern, .. } => {
+                if self.match_pattern(pattern, value, env) {
+                    env.define(*binding_id, value.clone());
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+    
+    /// Match an error value against a catch clause pattern
+    fn match_catch_pattern(&self, pattern: &IrPattern, error_value: &Value) -> bool {
+        match (pattern, error_value) {
+            (IrPattern::Literal(crate::ast::Literal::Keyword(keyword)), Value::Error(error)) => {
+                error.is_kind_of(keyword)
+            }
+            // A name that is not a defined type, as in `(catch Exception e ...)`, catches every error
+            (IrPattern::Type(IrType::TypeRef(_)), _) => true,
+            (IrPattern::Type(ir_type), _) => value_conforms_to(error_value, &ir_type.to_type_expr()),
+            // Symbol patterns are converted to wildcards and catch any error
+            (IrPattern::Wildcard, Value::Error(_)) => true,
+            _ => false,
+        }
+    }
+    
+    /// Execute try/catch; `finally` runs after the try body or the matching catch body,
+    /// and an error raised by `finally` replaces the result
     fn execute_try_catch(
         &mut self,
-        _try_body: &[IrNode],
-        _catch_clauses: &[IrCatchClause],
-        _finally_body: Option<&[IrNode]>,
-        _env: &mut IrEnvironment,
+        try_body: &[IrNode],
+        catch_clauses: &[IrCatchClause],
+        finally_body: Option<&[IrNode]>,
+        env: &mut IrEnvironment,
     ) -> RuntimeResult<Value> {
-        // TODO: Implement try-catch
-        Ok(Value::Nil)
+        let result = match self.execute_do(try_body, env) {
+            Ok(value) => Ok(value),
+            Err(error) => {
+                let error_value = error.to_value();
+                match catch_clauses
+                    .iter()
+                    .find(|clause| self.match_catch_pattern(&clause.error_pattern, &error_value))
+                {
+                    Some(clause) => {
+                        let mut catch_env = IrEnvironment::with_parent(env);
+                        if let Some(IrNode::VariableBinding { id, .. }) = &clause.binding {
+                            catch_env.define(*id, error_value);
+                        }
+                        self.execute_do(&clause.body, &mut catch_env)
+                    }
+                    // Re-throw after running finally
+                    None => Err(error),
+                }
+            }
+        };
+        
+        if let Some(finally_body) = finally_body {
+            self.execute_do(finally_body, env)?;
+        }
+        result
     }
     
-    fn execute_parallel(&mut self, _bindings: &[IrParallelBinding], _env: &mut IrEnvironment) -> RuntimeResult<Value> {
-        // TODO: Implement parallel execution
-        Ok(Value::Nil)
+    /// Execute parallel bindings, returning a map from binding name to value
+    fn execute_parallel(&mut self, bindings: &[IrParallelBinding], env: &mut IrEnvironment) -> RuntimeResult<Value> {
+        // Bindings run in order; true concurrency needs a thread-safe runtime
+        let mut results = HashMap::new();
+        for binding in bindings {
+            let value = self.execute_node(&binding.init_expr, env)?;
+            let name = match &binding.binding {
+                IrNode::VariableBinding { name, .. } => name.clone(),
+                other => {
+                    return Err(RuntimeError::InvalidProgram(format!(
+                        "Expected variable binding in parallel, got {:?}",
+                        other
+                    )))
+                }
+            };
+            results.insert(MapKey::Keyword(Keyword(name)), value);
+        }
+        Ok(Value::Map(results))
     }
     
+    /// Execute a with-resource block; the resource is released whether or not the body fails
     fn execute_with_resource(
         &mut self,
-        _binding: &IrNode,
-        _init_expr: &IrNode,
-        _body: &[IrNode],
-        _env: &mut IrEnvironment,
+        binding: &IrNode,
+        init_expr: &IrNode,
+        body: &[IrNode],
+        env: &mut IrEnvironment,
     ) -> RuntimeResult<Value> {
-        // TODO: Implement resource management
-        Ok(Value::Nil)
+        let mut handle = match self.execute_node(init_expr, env)? {
+            Value::Resource(handle) => handle,
+            other => {
+                return Err(RuntimeError::TypeError {
+                    expected: "resource handle".to_string(),
+                    actual: other.type_name().to_string(),
+                    operation: "with-resource".to_string(),
+                })
+            }
+        };
+        handle.state = ResourceState::Active;
+        
+        let mut resource_env = IrEnvironment::with_parent(env);
+        if let IrNode::VariableBinding { id, .. } = binding {
+            resource_env.define(*id, Value::Resource(handle.clone()));
+        }
+        
+        let result = self.execute_do(body, &mut resource_env);
+        self.cleanup_resource(&mut handle)?;
+        result
+    }
+    
+    /// Release a resource handle
+    fn cleanup_resource(&self, handle: &mut ResourceHandle) -> RuntimeResult<()> {
+        if handle.state == ResourceState::Released {
+            return Ok(());
+        }
+        println!("Cleaning up {}: {}", handle.resource_type, handle.id);
+        handle.state = ResourceState::Released;
+        Ok(())
     }
     
     fn execute_log_step(
@@ -487,21 +1210,25 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
             log_values
         );
         
-        Ok(Value::Nil)
+        // Return the last value or nil
+        let result = log_values.last().cloned().unwrap_or(Value::Nil);
+        if let Some(step_id) = location {
+            self.task_context.record_step_result(step_id, result.clone());
+        }
+        
+        Ok(result)
     }
     
     fn execute_task_context_access(&self, field_name: &Keyword) -> RuntimeResult<Value> {
-        // TODO: Implement task context access
-        // For now, return nil
-        println!("Task context access: @{}", field_name.0);
-        Ok(Value::Nil)
+        self.task_context.resolve(field_name)
     }
     
     /// Check if an expression is pure (no side effects) for caching
     fn is_pure_expression(&self, node: &IrNode) -> bool {
         match node {
             IrNode::Literal { .. } => true,
-            IrNode::VariableRef { .. } => true, // Assuming immutable bindings
+            // Not cached: the same reference sees different values across calls
+            IrNode::VariableRef { .. } => false,
             IrNode::Apply { function, arguments, .. } => {
                 // Check if function is pure and all arguments are pure
                 self.is_pure_expression(function) && arguments.iter().all(|arg| self.is_pure_expression(arg))
@@ -555,6 +1282,12 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         }
     }
     
+    /// Set how many bytes of heap-allocated stack nested calls may use before
+    /// execution fails with `RuntimeError::StackOverflow`
+    pub fn set_stack_limit(&mut self, bytes: usize) {
+        self.stack.set_limit(bytes);
+    }
+    
     /// Get current call stack for debugging
     pub fn call_stack(&self) -> &[CallFrame] {
         &self.call_stack
@@ -563,6 +1296,16 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
         self.module_registry.add_module_path(path);
     }
 
+    /// Set the task context that `@field` resolves against
+    pub fn set_task_context(&mut self, context: TaskContext) {
+        self.task_context = context;
+    }
+
+    /// Get the task context, including step results recorded during execution
+    pub fn task_context(&self) -> &TaskContext {
+        &self.task_context
+    }
+
     /// Get access to the module registry (for testing)
     pub fn module_registry(&self) -> &ModuleRegistry {
         &self.module_registry
@@ -574,6 +1317,31 @@ impl IrRuntime {    /// Create a new IR runtime with standard library
     }
 }
 
+/// Lets higher-order builtins call function values through the IR runtime
+struct IrCaller<'a> {
+    runtime: &'a mut IrRuntime,
+    env: &'a mut IrEnvironment,
+}
+
+impl FunctionCaller for IrCaller<'_> {
+    fn call_function(&mut self, function: &Value, args: &[Value]) -> RuntimeResult<Value> {
+        self.runtime.call_function(function.clone(), args, self.env)
+    }
+}
+
+/// Check a value bound to a typed name against what its type requires at runtime:
+/// the predicates of refined types and the element type and shape of array types,
+/// wherever they occur in it
+fn check_binding_type(ir_type: &IrType, name: &str, value: &Value) -> RuntimeResult<()> {
+    match ir_type {
+        // Types without parts to check at runtime, as most annotations are
+        IrType::Int | IrType::Float | IrType::String | IrType::Bool | IrType::Nil | IrType::Keyword
+        | IrType::Symbol | IrType::Any | IrType::Function { .. } => Ok(()),
+        _ => schema::check_binding_type(&ir_type.to_type_expr(), value)
+            .map_err(|violation| violation.into_error(&ir_type.to_string(), name)),
+    }
+}
+
 /// Runtime error extensions for IR runtime
 impl RuntimeError {    pub fn with_call_stack(self, _call_stack: &[CallFrame]) -> Self {
         // Enhanced error reporting with call stack
@@ -581,3 +1349,23 @@ impl RuntimeError {    pub fn with_call_stack(self, _call_stack: &[CallFrame]) -
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir_converter::IrConverter;
+    use crate::parser::parse_expression;
+
+    #[test]
+    fn test_recursive_functions_do_not_keep_their_frame_alive() {
+        let source = "(do (defn ev? [n] (if (= n 0) true (od? (- n 1))))
+                          (defn od? [n] (if (= n 0) false (ev? (- n 1))))
+                          (ev? 4))";
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        let env = IrEnvironment::new();
+        let frame = Rc::downgrade(&env.frame);
+        assert_eq!(IrRuntime::new().execute_node(&ir, &mut env.clone()), Ok(Value::Boolean(true)));
+        drop(env);
+        assert!(frame.upgrade().is_none());
+    }
+}
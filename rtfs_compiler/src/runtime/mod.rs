@@ -8,11 +8,18 @@ pub mod environment;
 pub mod error;
 pub mod ir_runtime;
 pub mod module_runtime;
+pub mod task_context;
+pub mod task_runner;
+pub mod schema;
+pub mod array;
+pub mod stack;
 
 pub use evaluator::Evaluator;
 pub use values::Value;
 pub use environment::Environment;
 pub use error::{RuntimeError, RuntimeResult};
+pub use task_context::TaskContext;
+pub use task_runner::{CompletedTask, TaskRunner};
 
 /// Runtime execution strategy
 #[derive(Debug, Clone)]
@@ -55,6 +62,14 @@ impl Runtime {
         }
     }
     
+    /// Set the task context that `@field` resolves against in both runtimes
+    pub fn set_task_context(&mut self, context: TaskContext) {
+        if let Some(ir_runtime) = &mut self.ir_runtime {
+            ir_runtime.set_task_context(context.clone());
+        }
+        self.ast_evaluator.set_task_context(context);
+    }
+    
     pub fn evaluate_expression(&mut self, expr: &crate::ast::Expression) -> RuntimeResult<Value> {
         match self.strategy {
             RuntimeStrategy::Ast => {
@@ -66,6 +81,7 @@ impl Runtime {
                     let mut converter = crate::ir_converter::IrConverter::new();
                     match converter.convert(expr) {
                         Ok(ir_node) => {
+                            typecheck_ir(&ir_node, &std::collections::HashMap::new())?;
                             let mut env = ir_runtime::IrEnvironment::new();
                             ir_runtime.execute_node(&ir_node, &mut env)
                         },
@@ -80,6 +96,8 @@ impl Runtime {
                 if let Some(ir_runtime) = &mut self.ir_runtime {
                     let mut converter = crate::ir_converter::IrConverter::new();
                     match converter.convert(expr) {                        Ok(ir_node) => {
+                            // Type errors are mistakes in the program, not IR gaps, so no fallback
+                            typecheck_ir(&ir_node, &std::collections::HashMap::new())?;
                             let mut env = ir_runtime::IrEnvironment::new();
                             match ir_runtime.execute_node(&ir_node, &mut env) {
                                 Ok(result) => Ok(result),
@@ -95,3 +113,40 @@ impl Runtime {
         }
     }
 }
+
+/// Reject IR that fails static type or resource-lifetime checking before any of it runs.
+/// Type names the IR does not define itself are looked up in `types`.
+pub(crate) fn typecheck_ir(
+    node: &crate::ir::IrNode,
+    types: &std::collections::HashMap<String, crate::ast::TypeExpr>,
+) -> RuntimeResult<()> {
+    let errors = match crate::ir_typecheck::typecheck(node, types)
+        .and_then(|()| crate::ir_lifetimes::check_lifetimes(node))
+    {
+        Ok(()) => return Ok(()),
+        Err(errors) => errors,
+    };
+    // Report the first mismatch; the rest are often consequences of it
+    match errors.into_iter().next() {
+        Some(crate::ir_converter::IrConversionError::TypeMismatch { expected, found, location }) => {
+            let error = RuntimeError::TypeError {
+                expected: expected.to_string(),
+                actual: found.to_string(),
+                operation: "type check".to_string(),
+            };
+            Err(match location {
+                Some(location) => error.with_location(location),
+                None => error,
+            })
+        }
+        Some(crate::ir_converter::IrConversionError::ResourceMisuse { resource_type, message, location }) => {
+            let error = RuntimeError::ResourceError { resource_type, message };
+            Err(match location {
+                Some(location) => error.with_location(location),
+                None => error,
+            })
+        }
+        Some(other) => Err(RuntimeError::InternalError(format!("{:?}", other))),
+        None => Ok(()),
+    }
+}
@@ -218,10 +218,10 @@ impl ModuleRegistry {
 
     /// Parse module source into AST
     fn parse_module_source(&self, source: &str, path: &std::path::Path) -> RuntimeResult<crate::ast::ModuleDefinition> {
-        use crate::parser::parse;
+        use crate::parser::parse_with_file;
         
         // Parse the entire source file
-        let top_levels = parse(source).map_err(|err| {
+        let top_levels = parse_with_file(source, &path.display().to_string()).map_err(|err| {
             RuntimeError::ModuleError(format!(
                 "Failed to parse module file '{}': {:?}",
                 path.display(),
@@ -276,8 +276,20 @@ impl ModuleRegistry {
             }
         }
 
-        // Convert module definitions to IR
+        // Convert module definitions to IR, declaring the module's functions first
+        // so that they can call each other regardless of definition order
         let mut ir_converter = IrConverter::new();
+        let functions: Vec<crate::ast::Expression> = module_def
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                crate::ast::ModuleLevelDefinition::Defn(defn_expr) => {
+                    Some(crate::ast::Expression::Defn(Box::new(defn_expr.clone())))
+                }
+                _ => None,
+            })
+            .collect();
+        ir_converter.declare_functions(&functions);
         let mut ir_definitions = Vec::new();
         let mut exports = HashMap::new();
         
@@ -299,6 +311,19 @@ impl ModuleRegistry {
                         self.add_symbol_export(&symbol_name, &def_expr.value, &mut exports, &mut module_env)?;
                     }
                 }
+                crate::ast::ModuleLevelDefinition::DefType(deftype) => {
+                    let expr = crate::ast::Expression::DefType(Box::new(deftype.clone()));
+                    let ir_node = ir_converter.convert_expression(expr)
+                        .map_err(|e| RuntimeError::ModuleError(format!("IR conversion failed: {:?}", e)))?;
+                    
+                    let type_name = deftype.name.0.clone();
+                    if let IrNode::TypeDef { definition, .. } = &ir_node {
+                        if self.should_export_symbol(&type_name, &module_def.exports) {
+                            self.add_type_export(&type_name, definition, &mut exports);
+                        }
+                    }
+                    ir_definitions.push(ir_node);
+                }
                 crate::ast::ModuleLevelDefinition::Defn(defn_expr) => {
                     // Convert defn expression to Expression and then to IR
                     let expr = crate::ast::Expression::Defn(Box::new(defn_expr.clone()));
@@ -369,7 +394,18 @@ impl ModuleRegistry {
         });
         
         Ok(())
-    }    /// Add a function export to the module
+    }    /// Add a type export to the module, so that importers can annotate with `module/Name`
+    fn add_type_export(&self, type_name: &str, definition: &IrType, exports: &mut HashMap<String, ModuleExport>) {
+        exports.insert(type_name.to_string(), ModuleExport {
+            original_name: type_name.to_string(),
+            export_name: type_name.to_string(),
+            value: Value::Nil, // Types have no runtime value
+            ir_type: definition.clone(),
+            export_type: ExportType::Type,
+        });
+    }
+    
+    /// Add a function export to the module
     fn add_function_export(
         &self,
         symbol_name: &str,
@@ -506,7 +542,8 @@ impl ModuleRegistry {
 
     /// Check if a symbol is qualified (contains '/')
     pub fn is_qualified_symbol(symbol: &str) -> bool {
-        symbol.contains('/')
+        // The division operator `/` is not a qualified symbol
+        matches!(symbol.split_once('/'), Some((module, name)) if !module.is_empty() && !name.is_empty())
     }
 }
 
@@ -568,13 +605,13 @@ impl ModuleAwareRuntime {
             let mut module_env = IrEnvironment::new();
             let mut module_exports = HashMap::new();
 
-            // Execute module definitions
+            // Execute module definitions, whose functions may refer to those after them
+            module_env.declare_functions(definitions);
             for definition in definitions {
                 match definition {
-                    IrNode::FunctionDef { name: func_name, lambda, .. } => {
-                        let func_value = self.ir_runtime.execute_node(lambda, &mut module_env)?;
-                        let binding_id = module_env.binding_count() as u64 + 40000;
-                        module_env.define(binding_id, func_value.clone());
+                    IrNode::FunctionDef { name: func_name, .. } => {
+                        // Defined in the slot the module's other functions refer to
+                        let func_value = self.ir_runtime.execute_node(definition, &mut module_env)?;
 
                         // Add to exports if listed
                         if exports.contains(func_name) {
@@ -587,10 +624,8 @@ impl ModuleAwareRuntime {
                             });
                         }
                     }
-                    IrNode::VariableDef { name: var_name, init_expr, .. } => {
-                        let var_value = self.ir_runtime.execute_node(init_expr, &mut module_env)?;
-                        let binding_id = module_env.binding_count() as u64 + 50000;
-                        module_env.define(binding_id, var_value.clone());
+                    IrNode::VariableDef { name: var_name, .. } => {
+                        let var_value = self.ir_runtime.execute_node(definition, &mut module_env)?;
 
                         // Add to exports if listed
                         if exports.contains(var_name) {
@@ -706,6 +741,35 @@ mod tests {
         assert!(result.is_ok(), "Should resolve math.utils/add symbol");
     }
 
+    #[test]
+    fn test_modules_export_named_types() {
+        let mut registry = ModuleRegistry::new();
+        registry.add_module_path(std::path::PathBuf::from("test_modules"));
+        let mut ir_runtime = IrRuntime::new();
+        let module = registry.load_module("shared.schemas", &mut ir_runtime).unwrap();
+        for name in ["User", "Order", "WeatherReport"] {
+            assert_eq!(module.exports[name].export_type, ExportType::Type, "{}", name);
+        }
+        assert!(!module.exports.contains_key("UserId"));
+
+        // Importers annotate with the qualified name and get the exported definition
+        let mut converter = crate::ir_converter::IrConverter::new();
+        converter.set_module_registry(&registry);
+        let user = converter
+            .convert_type_annotation(crate::ast::TypeExpr::Alias(crate::ast::Symbol("shared.schemas/User".to_string())))
+            .unwrap();
+        assert_eq!(user, module.exports["User"].ir_type);
+        assert!(matches!(user, IrType::Map { .. }));
+        let check = |source: &str| {
+            let mut converter = crate::ir_converter::IrConverter::new();
+            converter.set_module_registry(&registry);
+            let ir = converter.convert(&crate::parser::parse_expression(source).unwrap()).unwrap();
+            crate::ir_typecheck::typecheck(&ir, &HashMap::new())
+        };
+        assert!(check(r#"((fn [u :shared.schemas/User] (get u :name)) {:id "u-1" :name "Ada"})"#).is_ok());
+        assert!(check(r#"((fn [u :shared.schemas/User] (get u :name)) {:id "u-1"})"#).is_err());
+    }
+
     #[test]
     fn test_circular_dependency_detection() {
         let mut registry = ModuleRegistry::new();
@@ -723,4 +787,34 @@ mod tests {
         // Test that we can access both IR runtime and module registry
         assert_eq!(runtime.module_registry().loaded_modules().len(), 0);
     }
+
+    #[test]
+    fn test_module_functions_call_each_other() {
+        let parse = |source: &str| crate::parser::parse_expression(source).unwrap();
+        let definitions = vec![
+            parse("(defn ev? [n] (if (= n 0) true (od? (- n 1))))"),
+            parse("(defn od? [n] (if (= n 0) false (ev? (- n 1))))"),
+        ];
+        let mut converter = crate::ir_converter::IrConverter::new();
+        converter.declare_functions(&definitions);
+        let module = IrNode::Module {
+            id: 0,
+            name: "parity".to_string(),
+            exports: vec!["ev?".to_string()],
+            definitions: definitions
+                .into_iter()
+                .map(|definition| converter.convert_expression(definition).unwrap())
+                .collect(),
+            source_location: None,
+        };
+
+        let mut runtime = ModuleAwareRuntime::new();
+        runtime.execute_module_definition(&module).unwrap();
+        let compiled = runtime.module_registry().get_module("parity").unwrap();
+        runtime.ir_runtime.module_registry_mut().register_module((*compiled).clone()).unwrap();
+
+        let call = crate::ir_converter::IrConverter::new().convert(&parse("(parity/ev? 7)")).unwrap();
+        let result = runtime.ir_runtime.execute_node(&call, &mut IrEnvironment::new());
+        assert_eq!(result, Ok(Value::Boolean(false)));
+    }
 }
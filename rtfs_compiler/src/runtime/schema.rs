@@ -0,0 +1,683 @@
+// Schema validation for RTFS
+// Reads type schemas written as data (as in task `:contracts`) and checks runtime values against them
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::ast::{Keyword, Literal, MapKey, MapTypeEntry, ParamType, PrimitiveType, Regex, Symbol, TypeExpr, TypePredicate};
+use crate::parser::printer::{print_array_shape, print_type_expr, print_type_predicate};
+use crate::runtime::array::ArrayValue;
+use crate::runtime::{RuntimeError, Value};
+
+/// One step from a checked value down to one of its parts
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    /// A map entry
+    Key(MapKey),
+    /// A vector or tuple element
+    Index(usize),
+}
+
+impl PathSegment {
+    /// The segment as a value: the key itself, or the element index
+    pub fn to_value(&self) -> Value {
+        match self {
+            PathSegment::Key(MapKey::Keyword(k)) => Value::Keyword(k.clone()),
+            PathSegment::Key(MapKey::String(s)) => Value::String(s.clone()),
+            PathSegment::Key(MapKey::Integer(i)) => Value::Integer(*i),
+            PathSegment::Index(i) => Value::Integer(*i as i64),
+        }
+    }
+}
+
+/// A value that does not conform to a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// Where the offending part sits inside the checked value; empty for the value itself
+    pub path: Vec<PathSegment>,
+    /// The type the offending part should have had
+    pub expected: String,
+    /// What was found instead
+    pub actual: String,
+}
+
+impl SchemaViolation {
+    fn new(expected: String, actual: String) -> Self {
+        SchemaViolation { path: Vec::new(), expected, actual }
+    }
+
+    fn at(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    /// The path as a vector value, e.g. `[:user :emails 0]`
+    pub fn path_value(&self) -> Value {
+        Value::Vector(self.path.iter().map(PathSegment::to_value).collect())
+    }
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "at {}: ", self.path_value().to_string())?;
+        }
+        write!(f, "expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+/// Read a schema written as data, e.g. `[:map [:id :string] [:tags {:optional true} [:vector :keyword]]]`.
+///
+/// Accepted forms are the primitive keywords (`:int`, `:string`, `:any`, ...), `[:vector T]`,
+/// `[:tuple T ...]`, `[:map [:key T] [:key {:optional true} T] [:* T]]`, `[:union T ...]`
+/// (or `[:or T ...]`), `[:and T ...]`, `[:val literal]`, `[:resource name]`, `[:=> ...]`
+/// (any function) and `[:array T [2 :?]]`, where `:?` is a dimension of any size. In `[:and T ...]`, parts such as `[:> 0]` are refinement predicates on `T`.
+pub fn schema_from_value(schema: &Value) -> Result<TypeExpr, String> {
+    schema_from_value_with(schema, &HashMap::new())
+}
+
+/// Read a schema written as data in which other keywords, e.g. `:User`, name the types in
+/// `types` (as defined with `deftype`)
+pub fn schema_from_value_with(schema: &Value, types: &HashMap<String, TypeExpr>) -> Result<TypeExpr, String> {
+    match schema {
+        Value::Keyword(keyword) => match types.get(&keyword.0) {
+            Some(definition) => Ok(definition.clone()),
+            None => primitive_schema(keyword),
+        },
+        Value::Vector(items) => {
+            let (head, args) = match items.split_first() {
+                Some((Value::Keyword(head), args)) => (head.0.as_str(), args),
+                _ => return Err(format!("expected a schema, got {}", schema.to_string())),
+            };
+            let all = |args: &[Value]| {
+                args.iter().map(|arg| schema_from_value_with(arg, types)).collect::<Result<Vec<_>, _>>()
+            };
+            match (head, args) {
+                ("vector", [element]) => Ok(TypeExpr::Vector(Box::new(schema_from_value_with(element, types)?))),
+                ("tuple", elements) if !elements.is_empty() => Ok(TypeExpr::Tuple(all(elements)?)),
+                ("union" | "or", alternatives) if !alternatives.is_empty() => Ok(TypeExpr::Union(all(alternatives)?)),
+                ("and", [base, rest @ ..]) if rest.iter().all(is_predicate_form) && !rest.is_empty() => {
+                    Ok(TypeExpr::Refined {
+                        base: Box::new(schema_from_value_with(base, types)?),
+                        predicates: rest.iter().map(predicate_from_value).collect::<Result<_, _>>()?,
+                    })
+                }
+                ("and", parts) if !parts.is_empty() => Ok(TypeExpr::Intersection(all(parts)?)),
+                ("val", [literal]) => Ok(TypeExpr::Literal(value_to_literal(literal)?)),
+                ("resource", [Value::Symbol(Symbol(name)) | Value::String(name)]) => {
+                    Ok(TypeExpr::Resource(Symbol(name.clone())))
+                }
+                ("map", entries) => map_schema(entries, types),
+                ("array", [element]) => Ok(TypeExpr::Array {
+                    element_type: Box::new(schema_from_value_with(element, types)?),
+                    shape: None,
+                }),
+                ("array", [element, Value::Vector(dimensions)]) => Ok(TypeExpr::Array {
+                    element_type: Box::new(schema_from_value_with(element, types)?),
+                    shape: Some(dimensions.iter().map(dimension_from_value).collect::<Result<_, _>>()?),
+                }),
+                ("=>", _) => Ok(TypeExpr::Function {
+                    param_types: Vec::new(),
+                    variadic_param_type: Some(Box::new(TypeExpr::Any)),
+                    return_type: Box::new(TypeExpr::Any),
+                }),
+                _ => Err(format!("invalid schema {}", schema.to_string())),
+            }
+        }
+        other => Err(format!("expected a schema, got {}", other.to_string())),
+    }
+}
+
+fn primitive_schema(keyword: &Keyword) -> Result<TypeExpr, String> {
+    if let Some(builtin) = builtin_type(&keyword.0) {
+        return Ok(builtin);
+    }
+    Ok(match keyword.0.as_str() {
+        "map" => TypeExpr::Map { entries: Vec::new(), wildcard: Some(Box::new(TypeExpr::Any)) },
+        "vector" => TypeExpr::Vector(Box::new(TypeExpr::Any)),
+        other => return Err(format!("unknown type :{}", other)),
+    })
+}
+
+/// The builtin type with this name, which source code writes as a symbol such as `int`
+fn builtin_type(name: &str) -> Option<TypeExpr> {
+    Some(match name {
+        "int" => TypeExpr::Primitive(PrimitiveType::Int),
+        "float" => TypeExpr::Primitive(PrimitiveType::Float),
+        "string" => TypeExpr::Primitive(PrimitiveType::String),
+        "bool" => TypeExpr::Primitive(PrimitiveType::Bool),
+        "nil" => TypeExpr::Primitive(PrimitiveType::Nil),
+        "keyword" => TypeExpr::Primitive(PrimitiveType::Keyword),
+        "symbol" => TypeExpr::Primitive(PrimitiveType::Symbol),
+        "any" => TypeExpr::Any,
+        "never" => TypeExpr::Never,
+        _ => return None,
+    })
+}
+
+fn map_schema(items: &[Value], types: &HashMap<String, TypeExpr>) -> Result<TypeExpr, String> {
+    let mut entries = Vec::new();
+    let mut wildcard = None;
+    for item in items {
+        // Entries are [:key type], [:key {:optional true} type] or [:* type]
+        let parts = match item {
+            Value::Vector(parts) if parts.len() == 2 || parts.len() == 3 => parts,
+            other => return Err(format!("expected a [:key type] map entry, got {}", other.to_string())),
+        };
+        let key = match &parts[0] {
+            Value::Keyword(key) => key.clone(),
+            other => return Err(format!("map entry keys must be keywords, got {}", other.to_string())),
+        };
+        let optional = match &parts[1..] {
+            [Value::Map(props), _] => props
+                .get(&MapKey::Keyword(Keyword("optional".to_string())))
+                .is_some_and(Value::is_truthy),
+            [_] => false,
+            _ => return Err(format!("expected [:key {{:optional true}} type], got {}", item.to_string())),
+        };
+        let value_type = schema_from_value_with(parts.last().unwrap(), types)?;
+        if key.0 == "*" {
+            wildcard = Some(Box::new(value_type));
+        } else {
+            entries.push(MapTypeEntry { key, value_type: Box::new(value_type), optional });
+        }
+    }
+    Ok(TypeExpr::Map { entries, wildcard })
+}
+
+fn dimension_from_value(value: &Value) -> Result<Option<usize>, String> {
+    match value {
+        Value::Integer(size) if *size >= 0 => Ok(Some(*size as usize)),
+        Value::Keyword(Keyword(name)) if name == "?" => Ok(None),
+        other => Err(format!("array dimensions are sizes or :?, got {}", other.to_string())),
+    }
+}
+
+/// Whether a schema part is written like a predicate: a vector headed by a predicate name
+fn is_predicate_form(value: &Value) -> bool {
+    const NAMES: [&str; 8] = [">", ">=", "<", "<=", "min-length", "max-length", "matches-regex", "one-of"];
+    matches!(value, Value::Vector(items) if matches!(items.first(), Some(Value::Keyword(k)) if NAMES.contains(&k.0.as_str())))
+}
+
+fn predicate_from_value(value: &Value) -> Result<TypePredicate, String> {
+    match value {
+        Value::Vector(items) => match items.split_first() {
+            Some((Value::Keyword(name), args)) => {
+                let args = args.iter().map(value_to_literal).collect::<Result<Vec<_>, _>>()?;
+                TypePredicate::from_parts(&name.0, args)
+            }
+            _ => Err(format!("expected a predicate, got {}", value.to_string())),
+        },
+        other => Err(format!("expected a predicate, got {}", other.to_string())),
+    }
+}
+
+fn value_to_literal(value: &Value) -> Result<Literal, String> {
+    Ok(match value {
+        Value::Integer(i) => Literal::Integer(*i),
+        Value::Float(f) => Literal::Float(*f),
+        Value::String(s) => Literal::String(s.clone()),
+        Value::Boolean(b) => Literal::Boolean(*b),
+        Value::Keyword(k) => Literal::Keyword(k.clone()),
+        Value::Nil => Literal::Nil,
+        other => return Err(format!("[:val ...] takes a literal, got {}", other.to_string())),
+    })
+}
+
+fn literal_matches(literal: &Literal, value: &Value) -> bool {
+    match (literal, value) {
+        (Literal::Integer(a), Value::Integer(b)) => a == b,
+        (Literal::Float(a), Value::Float(b)) => a == b,
+        (Literal::String(a), Value::String(b)) => a == b,
+        (Literal::Boolean(a), Value::Boolean(b)) => a == b,
+        (Literal::Keyword(a), Value::Keyword(b)) => a == b,
+        (Literal::Nil, Value::Nil) => true,
+        _ => false,
+    }
+}
+
+/// Check a value against a schema, reporting the first part of it that does not conform
+pub fn check_schema(schema: &TypeExpr, value: &Value) -> Result<(), SchemaViolation> {
+    let mismatch = || Err(SchemaViolation::new(print_type_expr(schema), describe(value)));
+    match schema {
+        TypeExpr::Spanned(inner, _) => check_schema(inner, value),
+        TypeExpr::Any => Ok(()),
+        TypeExpr::Never => mismatch(),
+        TypeExpr::Primitive(primitive) => {
+            let conforms = match primitive {
+                PrimitiveType::Int => matches!(value, Value::Integer(_)),
+                PrimitiveType::Float => matches!(value, Value::Float(_)),
+                PrimitiveType::String => matches!(value, Value::String(_)),
+                PrimitiveType::Bool => matches!(value, Value::Boolean(_)),
+                PrimitiveType::Nil => matches!(value, Value::Nil),
+                PrimitiveType::Keyword => matches!(value, Value::Keyword(_)),
+                PrimitiveType::Symbol => matches!(value, Value::Symbol(_)),
+                PrimitiveType::Custom(_) => false,
+            };
+            if conforms { Ok(()) } else { mismatch() }
+        }
+        TypeExpr::Literal(literal) => {
+            if literal_matches(literal, value) { Ok(()) } else { mismatch() }
+        }
+        TypeExpr::Vector(element) => match value {
+            Value::Vector(items) => items.iter().enumerate().try_for_each(|(i, item)| {
+                check_schema(element, item).map_err(|v| v.at(PathSegment::Index(i)))
+            }),
+            _ => mismatch(),
+        },
+        TypeExpr::Array { element_type, shape } => match value {
+            Value::Array(array) if array_conforms(&array_element(element_type), shape.as_deref(), array) => Ok(()),
+            _ => mismatch(),
+        },
+        TypeExpr::Tuple(elements) => match value {
+            Value::Vector(items) if items.len() == elements.len() => {
+                elements.iter().zip(items).enumerate().try_for_each(|(i, (element, item))| {
+                    check_schema(element, item).map_err(|v| v.at(PathSegment::Index(i)))
+                })
+            }
+            _ => mismatch(),
+        },
+        TypeExpr::Map { entries, wildcard } => {
+            let map = match value {
+                Value::Map(map) => map,
+                _ => return mismatch(),
+            };
+            for entry in entries {
+                let key = MapKey::Keyword(entry.key.clone());
+                match map.get(&key) {
+                    Some(item) => check_schema(&entry.value_type, item).map_err(|v| v.at(PathSegment::Key(key)))?,
+                    None if entry.optional => {}
+                    None => {
+                        return Err(SchemaViolation::new(print_type_expr(&entry.value_type), "nothing".to_string())
+                            .at(PathSegment::Key(key)))
+                    }
+                }
+            }
+            if let Some(wildcard) = wildcard {
+                // Check extra entries in key order so the reported violation is deterministic
+                let mut extra: Vec<(&MapKey, &Value)> = map
+                    .iter()
+                    .filter(|(key, _)| !matches!(key, MapKey::Keyword(k) if entries.iter().any(|e| e.key == *k)))
+                    .collect();
+                extra.sort_by_key(|(key, _)| format!("{:?}", key));
+                for (key, item) in extra {
+                    check_schema(wildcard, item).map_err(|v| v.at(PathSegment::Key(key.clone())))?;
+                }
+            }
+            Ok(())
+        }
+        TypeExpr::Union(types) => {
+            if types.iter().any(|t| check_schema(t, value).is_ok()) { Ok(()) } else { mismatch() }
+        }
+        TypeExpr::Intersection(types) => types.iter().try_for_each(|t| check_schema(t, value)),
+        TypeExpr::Refined { base, predicates } => {
+            check_schema(base, value)?;
+            check_predicates(predicates, value)
+        }
+        TypeExpr::Function { .. } => {
+            if matches!(value, Value::Function(_)) { Ok(()) } else { mismatch() }
+        }
+        TypeExpr::Resource(name) => match value {
+            Value::Resource(handle) if handle.resource_type == name.0 => Ok(()),
+            _ => mismatch(),
+        },
+        // Aliases are resolved before values are checked; an unresolved one matches nothing
+        TypeExpr::Alias(_) => mismatch(),
+        // Type variables are related to other types statically; at runtime they accept any value
+        TypeExpr::TypeVar(_) => Ok(()),
+    }
+}
+
+/// Whether a value conforms to a type, as `match` type patterns and typed `catch` clauses
+/// test it. Type names must already be resolved; see `resolve_type`.
+pub fn value_conforms_to(value: &Value, type_expr: &TypeExpr) -> bool {
+    check_schema(type_expr, value).is_ok()
+}
+
+/// Whether a value satisfies a refinement predicate. Predicates never hold for
+/// values they do not apply to, e.g. `[:> 0]` for a string.
+pub fn predicate_holds(predicate: &TypePredicate, value: &Value) -> bool {
+    let compare = |bound: &Literal| -> Option<std::cmp::Ordering> {
+        match (value, bound) {
+            (Value::Integer(a), Literal::Integer(b)) => Some(a.cmp(b)),
+            (Value::Integer(a), Literal::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Literal::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Literal::Float(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    };
+    let length = || match value {
+        Value::String(s) => Some(s.chars().count() as i64),
+        Value::Vector(items) => Some(items.len() as i64),
+        Value::Map(map) => Some(map.len() as i64),
+        _ => None,
+    };
+    match predicate {
+        TypePredicate::GreaterThan(bound) => compare(bound).is_some_and(|o| o.is_gt()),
+        TypePredicate::GreaterOrEqual(bound) => compare(bound).is_some_and(|o| o.is_ge()),
+        TypePredicate::LessThan(bound) => compare(bound).is_some_and(|o| o.is_lt()),
+        TypePredicate::LessOrEqual(bound) => compare(bound).is_some_and(|o| o.is_le()),
+        TypePredicate::MinLength(min) => length().is_some_and(|n| n >= *min),
+        TypePredicate::MaxLength(max) => length().is_some_and(|n| n <= *max),
+        TypePredicate::MatchesRegex(Regex(regex)) => match value {
+            Value::String(s) => regex.is_match(s),
+            _ => false,
+        },
+        TypePredicate::OneOf(options) => options.iter().any(|option| literal_matches(option, value)),
+    }
+}
+
+/// Check a value against refinement predicates, reporting the first that does not hold
+pub fn check_predicates(predicates: &[TypePredicate], value: &Value) -> Result<(), SchemaViolation> {
+    match predicates.iter().find(|p| !predicate_holds(p, value)) {
+        Some(failed) => Err(SchemaViolation::new(print_type_predicate(failed), describe(value))),
+        None => Ok(()),
+    }
+}
+
+/// Replace the names of types defined with `deftype` by their definitions, and builtin
+/// type names by the builtin types, wherever they occur in `type_expr`. Names missing
+/// from `types` are left as they are.
+pub fn resolve_type(type_expr: &TypeExpr, types: &HashMap<String, TypeExpr>) -> TypeExpr {
+    let resolve = |t: &TypeExpr| Box::new(resolve_type(t, types));
+    let resolve_all = |ts: &[TypeExpr]| ts.iter().map(|t| resolve_type(t, types)).collect();
+    match type_expr {
+        TypeExpr::Alias(Symbol(name)) => builtin_type(name)
+            .or_else(|| types.get(name).cloned())
+            .unwrap_or_else(|| type_expr.clone()),
+        TypeExpr::Spanned(inner, span) => TypeExpr::Spanned(resolve(inner), span.clone()),
+        TypeExpr::Vector(element) => TypeExpr::Vector(resolve(element)),
+        TypeExpr::Tuple(elements) => TypeExpr::Tuple(resolve_all(elements)),
+        TypeExpr::Array { element_type, shape } => TypeExpr::Array { element_type: resolve(element_type), shape: shape.clone() },
+        TypeExpr::Map { entries, wildcard } => TypeExpr::Map {
+            entries: entries
+                .iter()
+                .map(|entry| MapTypeEntry { value_type: resolve(&entry.value_type), ..entry.clone() })
+                .collect(),
+            wildcard: wildcard.as_deref().map(resolve),
+        },
+        TypeExpr::Function { param_types, variadic_param_type, return_type } => TypeExpr::Function {
+            param_types: param_types.iter().map(|ParamType::Simple(t)| ParamType::Simple(resolve(t))).collect(),
+            variadic_param_type: variadic_param_type.as_deref().map(resolve),
+            return_type: resolve(return_type),
+        },
+        TypeExpr::Union(members) => TypeExpr::Union(resolve_all(members)),
+        TypeExpr::Intersection(members) => TypeExpr::Intersection(resolve_all(members)),
+        TypeExpr::Refined { base, predicates } => TypeExpr::Refined { base: resolve(base), predicates: predicates.clone() },
+        TypeExpr::Primitive(_)
+        | TypeExpr::Resource(_)
+        | TypeExpr::Literal(_)
+        | TypeExpr::TypeVar(_)
+        | TypeExpr::Any
+        | TypeExpr::Never => {
+            type_expr.clone()
+        }
+    }
+}
+
+/// Name of the element type an array must hold, as given by `ArrayValue::element_type_name`;
+/// None when any element type is accepted
+pub fn array_element(element_type: &TypeExpr) -> Option<String> {
+    match element_type.unspanned() {
+        TypeExpr::Any => None,
+        other => Some(print_type_expr(other)),
+    }
+}
+
+/// Whether an array holds elements named `element` (any, if None) and has the given shape,
+/// where a `None` dimension accepts any size
+pub fn array_conforms(element: &Option<String>, shape: Option<&[Option<usize>]>, array: &ArrayValue) -> bool {
+    element.as_deref().is_none_or(|name| name == array.element_type_name())
+        && shape.is_none_or(|shape| {
+            shape.len() == array.shape().len()
+                && shape.iter().zip(array.shape()).all(|(expected, size)| expected.is_none_or(|e| e == *size))
+        })
+}
+
+/// Why a bound value does not have what its type requires at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindingViolation {
+    /// A refinement predicate does not hold
+    Predicate(SchemaViolation),
+    /// A part of the value is not of its type: an array of another element type or
+    /// shape, or a value matching no alternative of a union
+    Type(SchemaViolation),
+}
+
+impl BindingViolation {
+    fn at(self, segment: PathSegment) -> Self {
+        match self {
+            BindingViolation::Predicate(violation) => BindingViolation::Predicate(violation.at(segment)),
+            BindingViolation::Type(violation) => BindingViolation::Type(violation.at(segment)),
+        }
+    }
+
+    /// The error for binding `name`, declared with the type written `type_name`
+    pub fn into_error(self, type_name: &str, name: &str) -> RuntimeError {
+        let (expected, violation) = match self {
+            BindingViolation::Predicate(violation) => {
+                let at = if violation.path.is_empty() {
+                    String::new()
+                } else {
+                    format!(" at {}", violation.path_value().to_string())
+                };
+                (format!("{} ({} does not hold{})", type_name, violation.expected, at), violation)
+            }
+            BindingViolation::Type(violation) if violation.path.is_empty() => (type_name.to_string(), violation),
+            BindingViolation::Type(violation) => (format!("{} ({})", type_name, violation), violation),
+        };
+        RuntimeError::TypeError { expected, actual: violation.actual, operation: format!("binding {}", name) }
+    }
+}
+
+/// Check a value bound to a name declared with `type_expr` against what the type
+/// requires at runtime: the predicates of refined types and the element type and
+/// shape of array types, wherever they occur in it; a union with such parts must match
+/// one of its alternatives in full. The rest of the type is checked statically. Type
+/// names must already be resolved.
+pub fn check_binding_type(type_expr: &TypeExpr, value: &Value) -> Result<(), BindingViolation> {
+    if !has_runtime_checks(type_expr) {
+        return Ok(());
+    }
+    let mismatch = || Err(BindingViolation::Type(SchemaViolation::new(print_type_expr(type_expr), describe(value))));
+    match (type_expr, value) {
+        (TypeExpr::Spanned(inner, _), _) => check_binding_type(inner, value),
+        (TypeExpr::Refined { base, predicates }, _) => {
+            check_binding_type(base, value)?;
+            check_predicates(predicates, value).map_err(BindingViolation::Predicate)
+        }
+        (TypeExpr::Array { element_type, shape }, Value::Array(array))
+            if array_conforms(&array_element(element_type), shape.as_deref(), array) =>
+        {
+            Ok(())
+        }
+        (TypeExpr::Array { .. }, _) => mismatch(),
+        (TypeExpr::Vector(element), Value::Vector(items)) => items
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, item)| check_binding_type(element, item).map_err(|v| v.at(PathSegment::Index(i)))),
+        (TypeExpr::Tuple(elements), Value::Vector(items)) => {
+            elements.iter().zip(items).enumerate().try_for_each(|(i, (element, item))| {
+                check_binding_type(element, item).map_err(|v| v.at(PathSegment::Index(i)))
+            })
+        }
+        (TypeExpr::Map { entries, wildcard }, Value::Map(map)) => {
+            for entry in entries {
+                let key = MapKey::Keyword(entry.key.clone());
+                if let Some(item) = map.get(&key) {
+                    check_binding_type(&entry.value_type, item).map_err(|v| v.at(PathSegment::Key(key)))?;
+                }
+            }
+            if let Some(wildcard) = wildcard {
+                let mut extra: Vec<(&MapKey, &Value)> = map
+                    .iter()
+                    .filter(|(key, _)| !matches!(key, MapKey::Keyword(k) if entries.iter().any(|e| e.key == *k)))
+                    .collect();
+                extra.sort_by_key(|(key, _)| format!("{:?}", key));
+                for (key, item) in extra {
+                    check_binding_type(wildcard, item).map_err(|v| v.at(PathSegment::Key(key.clone())))?;
+                }
+            }
+            Ok(())
+        }
+        (TypeExpr::Union(members), _) => {
+            if members.iter().any(|member| check_schema(member, value).is_ok()) { Ok(()) } else { mismatch() }
+        }
+        (TypeExpr::Intersection(members), _) => members.iter().try_for_each(|member| check_binding_type(member, value)),
+        _ => Ok(()),
+    }
+}
+
+/// Whether a type has parts that are checked at runtime: refinements or array types
+fn has_runtime_checks(type_expr: &TypeExpr) -> bool {
+    match type_expr {
+        TypeExpr::Refined { .. } | TypeExpr::Array { .. } => true,
+        TypeExpr::Spanned(inner, _) | TypeExpr::Vector(inner) => has_runtime_checks(inner),
+        TypeExpr::Tuple(members) | TypeExpr::Union(members) | TypeExpr::Intersection(members) => {
+            members.iter().any(has_runtime_checks)
+        }
+        TypeExpr::Map { entries, wildcard } => {
+            entries.iter().any(|entry| has_runtime_checks(&entry.value_type))
+                || wildcard.as_deref().is_some_and(has_runtime_checks)
+        }
+        _ => false,
+    }
+}
+
+/// How a value is named in violations: its type, plus the value itself for scalars
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Vector(_) | Value::Map(_) | Value::Function(_) | Value::Resource(_) => value.type_name().to_string(),
+        Value::String(s) => format!("string {:?}", s),
+        Value::Array(array) => {
+            let shape: Vec<Option<usize>> = array.shape().iter().copied().map(Some).collect();
+            format!("array {} {}", array.element_type_name(), print_array_shape(&shape))
+        }
+        _ => format!("{} {}", value.type_name(), value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+    use crate::runtime::Evaluator;
+
+    fn eval(source: &str) -> Value {
+        Evaluator::new().evaluate(&parse_expression(source).unwrap()).unwrap()
+    }
+
+    fn check(schema: &str, value: &str) -> Result<(), SchemaViolation> {
+        check_schema(&schema_from_value(&eval(schema)).unwrap(), &eval(value))
+    }
+
+    fn path(violation: SchemaViolation) -> String {
+        violation.path_value().to_string()
+    }
+
+    #[test]
+    fn test_schema_forms() {
+        assert!(check(":int", "1").is_ok());
+        assert!(check(":int", "1.0").is_err());
+        assert!(check("[:vector :string]", "[\"a\" \"b\"]").is_ok());
+        assert!(check("[:tuple :int :keyword]", "[1 :a]").is_ok());
+        assert!(check("[:tuple :int :keyword]", "[1 :a 2]").is_err());
+        assert!(check("[:union :int :nil]", "nil").is_ok());
+        assert!(check("[:or :int :nil]", "\"x\"").is_err());
+        assert!(check("[:val :done]", ":done").is_ok());
+        assert!(check("[:val :done]", ":pending").is_err());
+        assert!(check("[:and :int [:union [:val 1] [:val 2]]]", "2").is_ok());
+        assert!(check("[:and :int [:union [:val 1] [:val 2]]]", "3").is_err());
+
+        assert!(schema_from_value(&eval("[:vector]")).is_err());
+        assert!(schema_from_value(&eval(":widget")).is_err());
+        assert!(schema_from_value(&eval("[:map [\"id\" :int]]")).is_err());
+    }
+
+    #[test]
+    fn test_map_schemas() {
+        let schema = "[:map [:id :string] [:age {:optional true} :int] [:* :keyword]]";
+        assert!(check(schema, "{:id \"a\"}").is_ok());
+        assert!(check(schema, "{:id \"a\" :age 3 :role :admin}").is_ok());
+
+        let missing = check(schema, "{:age 3}").unwrap_err();
+        assert_eq!(path(missing.clone()), "[:id]");
+        assert_eq!(missing.actual, "nothing");
+        assert_eq!(path(check(schema, "{:id \"a\" :age \"3\"}").unwrap_err()), "[:age]");
+        assert_eq!(path(check(schema, "{:id \"a\" :role 1}").unwrap_err()), "[:role]");
+    }
+
+    #[test]
+    fn test_refinement_predicates() {
+        assert!(check("[:and :int [:> 0]]", "3").is_ok());
+        assert!(check("[:and :int [:> 0]]", "0").is_err());
+        assert!(check("[:and :int [:>= 0] [:< 10]]", "10").is_err());
+        assert!(check("[:and :float [:<= 1]]", "0.5").is_ok());
+        assert!(check("[:and :string [:min-length 1] [:max-length 3]]", "\"abc\"").is_ok());
+        assert!(check("[:and :string [:min-length 1]]", "\"\"").is_err());
+        assert!(check("[:and [:vector :int] [:min-length 1]]", "[]").is_err());
+        assert!(check("[:and :string [:matches-regex \"^u-[0-9]+$\"]]", "\"u-42\"").is_ok());
+        assert!(check("[:and :string [:matches-regex \"^u-[0-9]+$\"]]", "\"x-42\"").is_err());
+        assert!(check("[:and :keyword [:one-of :low :high]]", ":high").is_ok());
+        assert!(check("[:and :keyword [:one-of :low :high]]", ":medium").is_err());
+
+        let violation = check("[:map [:age [:and :int [:> 0]]]]", "{:age -1}").unwrap_err();
+        assert_eq!(path(violation.clone()), "[:age]");
+        assert_eq!(violation.expected, "[:> 0]");
+
+        assert!(schema_from_value(&eval("[:and :int [:> \"zero\"]]")).is_err());
+        assert!(schema_from_value(&eval("[:and :string [:matches-regex \"(\"]]")).is_err());
+
+        // Patterns are compiled when the predicate is built, and compare as written
+        let regex = |pattern: &str| TypePredicate::from_parts("matches-regex", vec![Literal::String(pattern.to_string())]);
+        assert_eq!(regex("^a+$"), regex("^a+$"));
+        assert_ne!(regex("^a+$"), regex("^b+$"));
+        assert_eq!(regex("^a+$").unwrap().args(), vec![Literal::String("^a+$".to_string())]);
+    }
+
+    #[test]
+    fn test_array_schemas() {
+        let matrix = "(array [[1.0 2.0] [3.0 4.0]])";
+        assert!(check("[:array :float]", matrix).is_ok());
+        assert!(check("[:array :float [2 :?]]", matrix).is_ok());
+        assert!(check("[:array :any [2 2]]", matrix).is_ok());
+        assert!(check("[:array :int [2 2]]", matrix).is_err());
+        assert!(check("[:array :float [2 2 1]]", matrix).is_err());
+        assert!(check("[:array :float]", "[[1.0 2.0] [3.0 4.0]]").is_err());
+
+        let violation = check("[:map [:m [:array :float [3 :?]]]]", &format!("{{:m {}}}", matrix)).unwrap_err();
+        assert_eq!(path(violation.clone()), "[:m]");
+        assert_eq!(violation.expected, "[:array float [3 ?]]");
+        assert_eq!(violation.actual, "array float [2 2]");
+
+        assert!(schema_from_value(&eval("[:array :float [2 -1]]")).is_err());
+    }
+
+    #[test]
+    fn test_named_types() {
+        let evaluator = Evaluator::new();
+        for source in [
+            "(deftype UserId [:and string [:min-length 1]])",
+            "(deftype User [:map [:id UserId] [:tags [:vector keyword]]])",
+        ] {
+            evaluator.evaluate(&parse_expression(source).unwrap()).unwrap();
+        }
+        let types = evaluator.defined_types();
+        assert_eq!(print_type_expr(&types["User"]), "[:map [:id [:and string [:min-length 1]]] [:tags [:vector keyword]]]");
+
+        let schema = schema_from_value_with(&eval("[:map [:owner :User] [:editors [:vector :User]]]"), &types).unwrap();
+        assert!(check_schema(&schema, &eval("{:owner {:id \"u-1\" :tags []} :editors []}")).is_ok());
+        let violation = check_schema(&schema, &eval("{:owner {:id \"\" :tags []} :editors []}")).unwrap_err();
+        assert_eq!(path(violation), "[:owner :id]");
+        assert!(schema_from_value_with(&eval(":Customer"), &types).is_err());
+    }
+
+    #[test]
+    fn test_violation_paths_reach_nested_fields() {
+        let schema = "[:map [:user [:map [:emails [:vector :string]]]]]";
+        let violation = check(schema, "{:user {:emails [\"a@b\" 7]}}").unwrap_err();
+        assert_eq!(path(violation.clone()), "[:user :emails 1]");
+        assert_eq!(violation.expected, "string");
+        assert_eq!(violation.actual, "int 7");
+        assert_eq!(violation.to_string(), "at [:user :emails 1]: expected string, got int 7");
+    }
+}
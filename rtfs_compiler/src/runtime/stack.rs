@@ -0,0 +1,103 @@
+// Stack budget shared by both runtimes
+//
+// Calls in tail position run in constant stack, but any other nested call still
+// recurses. Each call checks how much of the current stack is left; when it runs
+// low, the call continues on a fresh segment allocated on the heap, so recursion
+// depth is not bounded by the host thread's stack. The segments in use count
+// against a byte budget, and a call that would exceed it fails with
+// `RuntimeError::StackOverflow` instead of exhausting memory.
+
+use std::cell::Cell;
+use crate::runtime::{RuntimeError, RuntimeResult};
+
+/// Default budget for heap-allocated stack segments: thousands of nested calls
+/// even in debug builds, where frames are several times larger than in release
+pub const DEFAULT_STACK_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Stack a single call may use before the next one checks again
+const RED_ZONE: usize = 256 * 1024;
+
+/// Size of each segment allocated when the current stack runs low
+const SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Grows nested calls onto heap-allocated stack segments, within a byte budget
+#[derive(Debug)]
+pub struct StackGuard {
+    limit: Cell<usize>,
+    /// Bytes of segments allocated by the calls in progress
+    allocated: Cell<usize>,
+    depth: Cell<usize>,
+}
+
+impl StackGuard {
+    pub fn new() -> Self {
+        StackGuard {
+            limit: Cell::new(DEFAULT_STACK_LIMIT),
+            allocated: Cell::new(0),
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Set the number of bytes of heap-allocated stack nested calls may use
+    pub fn set_limit(&self, bytes: usize) {
+        self.limit.set(bytes);
+    }
+
+    /// Run a nested call, on a new stack segment if the current one is low.
+    /// Fails without running `call` if that segment would exceed the budget.
+    pub fn call<T>(&self, call: impl FnOnce() -> RuntimeResult<T>) -> RuntimeResult<T> {
+        let depth = self.depth.get();
+        self.depth.set(depth + 1);
+        let result = if stacker::remaining_stack().is_some_and(|left| left >= RED_ZONE) {
+            call()
+        } else {
+            let allocated = self.allocated.get();
+            if allocated + SEGMENT_SIZE > self.limit.get() {
+                Err(RuntimeError::StackOverflow { depth })
+            } else {
+                self.allocated.set(allocated + SEGMENT_SIZE);
+                let result = stacker::grow(SEGMENT_SIZE, call);
+                self.allocated.set(allocated);
+                result
+            }
+        };
+        self.depth.set(depth);
+        result
+    }
+}
+
+impl Default for StackGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recurse(guard: &StackGuard, remaining: usize) -> RuntimeResult<usize> {
+        guard.call(|| {
+            let padding = std::hint::black_box([0u8; 1024]);
+            if remaining == 0 {
+                Ok(padding.len())
+            } else {
+                recurse(guard, remaining - 1)
+            }
+        })
+    }
+
+    #[test]
+    fn test_calls_grow_past_the_thread_stack_within_the_limit() {
+        let guard = StackGuard::new();
+        guard.set_limit(64 * 1024 * 1024);
+        // Several times more than the 2 MiB stack of a test thread
+        assert!(recurse(&guard, 10_000).is_ok());
+        match recurse(&guard, 1_000_000) {
+            Err(RuntimeError::StackOverflow { depth }) => assert!(depth > 10_000 && depth < 1_000_000),
+            other => panic!("expected a stack overflow, got {:?}", other),
+        }
+        // The segments are released once the calls return
+        assert!(recurse(&guard, 10_000).is_ok());
+    }
+}
@@ -1,10 +1,13 @@
 // Standard library implementation for RTFS
 // Contains all built-in functions and tool interfaces
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use crate::ast::{Symbol, Keyword, MapKey};
 use crate::runtime::{Value, RuntimeError, RuntimeResult, Environment};
-use crate::runtime::values::{Function, Arity};
+use crate::runtime::values::{Function, FunctionCaller, Arity};
+use crate::runtime::array::ArrayValue;
 
 pub struct StandardLibrary;
 
@@ -181,11 +184,53 @@ impl StandardLibrary {
             func: Self::map,
         }));
         
-        env.define(&Symbol("map-fn".to_string()), Value::Function(Function::Builtin {
+        env.define(&Symbol("map-fn".to_string()), Value::Function(Function::BuiltinWithCallback {
             name: "map-fn".to_string(),
             arity: Arity::AtLeast(2),
             func: Self::map_function,
         }));
+        
+        env.define(&Symbol("filter".to_string()), Value::Function(Function::BuiltinWithCallback {
+            name: "filter".to_string(),
+            arity: Arity::Exact(2),
+            func: Self::filter,
+        }));
+        
+        env.define(&Symbol("reduce".to_string()), Value::Function(Function::BuiltinWithCallback {
+            name: "reduce".to_string(),
+            arity: Arity::Range(2, 3),
+            func: Self::reduce,
+        }));
+        
+        env.define(&Symbol("sort-by".to_string()), Value::Function(Function::BuiltinWithCallback {
+            name: "sort-by".to_string(),
+            arity: Arity::Exact(2),
+            func: Self::sort_by,
+        }));
+        
+        env.define(&Symbol("some".to_string()), Value::Function(Function::BuiltinWithCallback {
+            name: "some".to_string(),
+            arity: Arity::Exact(2),
+            func: Self::some,
+        }));
+        
+        env.define(&Symbol("every?".to_string()), Value::Function(Function::BuiltinWithCallback {
+            name: "every?".to_string(),
+            arity: Arity::Exact(2),
+            func: Self::every_p,
+        }));
+        
+        env.define(&Symbol("apply".to_string()), Value::Function(Function::BuiltinWithCallback {
+            name: "apply".to_string(),
+            arity: Arity::AtLeast(2),
+            func: Self::apply,
+        }));
+        
+        env.define(&Symbol("array".to_string()), Value::Function(Function::Builtin {
+            name: "array".to_string(),
+            arity: Arity::Exact(1),
+            func: Self::array,
+        }));
     }
       /// Load type predicate functions (int?, float?, string?, etc.)
     fn load_type_predicate_functions(env: &mut Environment) {
@@ -327,6 +372,24 @@ impl StandardLibrary {
             arity: Arity::Range(1, 2),
             func: Self::tool_http_fetch,
         }));
+        
+        env.define(&Symbol("tool:tensor-elementwise-add".to_string()), Value::Function(Function::Builtin {
+            name: "tool:tensor-elementwise-add".to_string(),
+            arity: Arity::Exact(2),
+            func: Self::tool_tensor_elementwise_add,
+        }));
+        
+        env.define(&Symbol("tool:tensor-scalar-multiply".to_string()), Value::Function(Function::Builtin {
+            name: "tool:tensor-scalar-multiply".to_string(),
+            arity: Arity::Exact(2),
+            func: Self::tool_tensor_scalar_multiply,
+        }));
+        
+        env.define(&Symbol("tool:get-tensor-summary".to_string()), Value::Function(Function::Builtin {
+            name: "tool:get-tensor-summary".to_string(),
+            arity: Arity::Exact(1),
+            func: Self::tool_get_tensor_summary,
+        }));
     }
 }
 
@@ -780,6 +843,18 @@ impl StandardLibrary {
         Ok(Value::Vector(args.to_vec()))
     }
     
+    fn array(args: &[Value]) -> RuntimeResult<Value> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "array".to_string(),
+                expected: "1".to_string(),
+                actual: args.len(),
+            });
+        }
+        
+        Ok(Value::Array(Self::tensor_arg(&args[0], "array")?))
+    }
+    
     fn map(args: &[Value]) -> RuntimeResult<Value> {
         if args.len() % 2 != 0 {
             return Err(RuntimeError::ArityMismatch {
@@ -1404,7 +1479,98 @@ impl StandardLibrary {
         }
     }
     
+    // Tensor tools run on in-memory arrays; nested vectors of numbers are accepted as well
+    fn tool_tensor_elementwise_add(args: &[Value]) -> RuntimeResult<Value> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "tool:tensor-elementwise-add".to_string(),
+                expected: "2".to_string(),
+                actual: args.len(),
+            });
+        }
+        
+        let left = Self::tensor_arg(&args[0], "tool:tensor-elementwise-add")?;
+        let right = Self::tensor_arg(&args[1], "tool:tensor-elementwise-add")?;
+        left.elementwise_add(&right)
+            .map(Value::Array)
+            .map_err(Self::tensor_error)
+    }
+    
+    fn tool_tensor_scalar_multiply(args: &[Value]) -> RuntimeResult<Value> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "tool:tensor-scalar-multiply".to_string(),
+                expected: "2".to_string(),
+                actual: args.len(),
+            });
+        }
+        
+        let tensor = Self::tensor_arg(&args[0], "tool:tensor-scalar-multiply")?;
+        match &args[1] {
+            Value::Integer(_) | Value::Float(_) => tensor.scalar_multiply(&args[1])
+                .map(Value::Array)
+                .map_err(Self::tensor_error),
+            other => Err(RuntimeError::TypeError {
+                expected: "number".to_string(),
+                actual: other.type_name().to_string(),
+                operation: "tool:tensor-scalar-multiply scalar".to_string(),
+            }),
+        }
+    }
+    
+    fn tool_get_tensor_summary(args: &[Value]) -> RuntimeResult<Value> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "tool:get-tensor-summary".to_string(),
+                expected: "1".to_string(),
+                actual: args.len(),
+            });
+        }
+        
+        let tensor = Self::tensor_arg(&args[0], "tool:get-tensor-summary")?;
+        let elements: Vec<f64> = tensor.values().map(|v| match v {
+            Value::Integer(i) => i as f64,
+            Value::Float(f) => f,
+            _ => unreachable!("arrays hold only numbers"),
+        }).collect();
+        let sum: f64 = elements.iter().sum();
+        let statistic = |value: Option<f64>| value.map_or(Value::Nil, Value::Float);
+        
+        let mut summary = HashMap::new();
+        let mut insert = |key: &str, value: Value| {
+            summary.insert(MapKey::Keyword(Keyword(key.to_string())), value);
+        };
+        insert("shape", Value::Vector(tensor.shape().iter().map(|d| Value::Integer(*d as i64)).collect()));
+        insert("dtype", Value::Keyword(Keyword(tensor.element_type_name().to_string())));
+        insert("size", Value::Integer(elements.len() as i64));
+        insert("sum", Value::Float(sum));
+        insert("min", statistic(elements.iter().copied().reduce(f64::min)));
+        insert("max", statistic(elements.iter().copied().reduce(f64::max)));
+        insert("mean", statistic((!elements.is_empty()).then(|| sum / elements.len() as f64)));
+        Ok(Value::Map(summary))
+    }
+    
     // Helper functions
+    fn tensor_arg(value: &Value, operation: &str) -> RuntimeResult<ArrayValue> {
+        match value {
+            Value::Array(array) => Ok(array.clone()),
+            Value::Vector(_) => ArrayValue::from_nested(value).map_err(Self::tensor_error),
+            other => Err(RuntimeError::TypeError {
+                expected: "array or nested vectors of numbers".to_string(),
+                actual: other.type_name().to_string(),
+                operation: operation.to_string(),
+            }),
+        }
+    }
+    
+    fn tensor_error(message: String) -> RuntimeError {
+        RuntimeError::ApplicationError {
+            error_type: Keyword("error/tensor".to_string()),
+            message,
+            data: None,
+        }
+    }
+    
     fn value_to_map_key(value: &Value) -> RuntimeResult<MapKey> {
         match value {
             Value::Keyword(k) => Ok(MapKey::Keyword(k.clone())),
@@ -1418,51 +1584,159 @@ impl StandardLibrary {
         }
     }
     
-    fn map_function(args: &[Value]) -> RuntimeResult<Value> {
-        if args.len() < 2 {
-            return Err(RuntimeError::ArityMismatch {
-                function: "map-fn".to_string(),
-                expected: "at least 2".to_string(),
-                actual: args.len(),
-            });
+    // Higher-order functions, which call their function arguments through the runtime
+    
+    /// `(map-fn f coll & colls)`: call `f` with the items at each position, up to the shortest collection
+    fn map_function(args: &[Value], caller: &mut dyn FunctionCaller) -> RuntimeResult<Value> {
+        let (function, collections) = match args {
+            [function, collections @ ..] if !collections.is_empty() => (function, collections),
+            _ => return Err(Self::arity_error("map-fn", "at least 2", args.len())),
+        };
+        let collections = collections
+            .iter()
+            .map(|collection| Self::sequence_arg(collection, "map-fn"))
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        let len = collections.iter().map(|items| items.len()).min().unwrap_or(0);
+        
+        let mut results = Vec::with_capacity(len);
+        for i in 0..len {
+            let items: Vec<Value> = collections.iter().map(|items| items[i].clone()).collect();
+            results.push(caller.call_function(function, &items)?);
+        }
+        Ok(Value::Vector(results))
+    }
+    
+    /// `(filter pred coll)`: the items for which `pred` is truthy
+    fn filter(args: &[Value], caller: &mut dyn FunctionCaller) -> RuntimeResult<Value> {
+        let (predicate, items) = Self::function_and_sequence(args, "filter")?;
+        let mut results = Vec::new();
+        for item in items.iter() {
+            if caller.call_function(predicate, std::slice::from_ref(item))?.is_truthy() {
+                results.push(item.clone());
+            }
         }
-        
-        let function = &args[0];
-        let collections = &args[1..];
-        
-        // For now, support single collection mapping
-        if collections.len() != 1 {
-            return Err(RuntimeError::InternalError(
-                "map-fn currently supports only single collection".to_string()
-            ));
+        Ok(Value::Vector(results))
+    }
+    
+    /// `(reduce f coll)` or `(reduce f init coll)`. Without `init` the first item is used,
+    /// and an empty collection gives `(f)`.
+    fn reduce(args: &[Value], caller: &mut dyn FunctionCaller) -> RuntimeResult<Value> {
+        let (function, init, collection) = match args {
+            [function, collection] => (function, None, collection),
+            [function, init, collection] => (function, Some(init.clone()), collection),
+            _ => return Err(Self::arity_error("reduce", "2 or 3", args.len())),
+        };
+        let items = Self::sequence_arg(collection, "reduce")?;
+        let (mut accumulator, rest) = match (init, items.split_first()) {
+            (Some(init), _) => (init, &items[..]),
+            (None, Some((first, rest))) => (first.clone(), rest),
+            (None, None) => return caller.call_function(function, &[]),
+        };
+        for item in rest {
+            accumulator = caller.call_function(function, &[accumulator, item.clone()])?;
         }
-        
-        match &collections[0] {
-            Value::Vector(vec) => {
-                let mut results = Vec::new();
-                for item in vec {
-                    // This would require a way to call the function value
-                    // For now, simulate with a simple operation
-                    match function {
-                        Value::Function(_) => {
-                            // In a real implementation, we'd call the function here
-                            // For demonstration, just return the item unchanged
-                            results.push(item.clone());
-                        },
-                        _ => return Err(RuntimeError::TypeError {
-                            expected: "function".to_string(),
-                            actual: function.type_name().to_string(),
-                            operation: "map-fn".to_string(),
-                        }),
-                    }
-                }
-                Ok(Value::Vector(results))
-            },
-            _ => Err(RuntimeError::TypeError {
-                expected: "vector".to_string(),
-                actual: collections[0].type_name().to_string(),
-                operation: "map-fn".to_string(),
+        Ok(accumulator)
+    }
+    
+    /// `(sort-by keyfn coll)`: the items ordered by the numbers or strings `keyfn` gives them.
+    /// The sort is stable.
+    fn sort_by(args: &[Value], caller: &mut dyn FunctionCaller) -> RuntimeResult<Value> {
+        let (key_function, items) = Self::function_and_sequence(args, "sort-by")?;
+        let mut keyed = Vec::with_capacity(items.len());
+        for item in items.iter() {
+            keyed.push((caller.call_function(key_function, std::slice::from_ref(item))?, item.clone()));
+        }
+        
+        let mut error = None;
+        keyed.sort_by(|(a, _), (b, _)| {
+            Self::order_values(a, b, "sort-by").unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                Ordering::Equal
+            })
+        });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(Value::Vector(keyed.into_iter().map(|(_, item)| item).collect())),
+        }
+    }
+    
+    /// `(some pred coll)`: the first truthy result of `pred`, or nil
+    fn some(args: &[Value], caller: &mut dyn FunctionCaller) -> RuntimeResult<Value> {
+        let (predicate, items) = Self::function_and_sequence(args, "some")?;
+        for item in items.iter() {
+            let result = caller.call_function(predicate, std::slice::from_ref(item))?;
+            if result.is_truthy() {
+                return Ok(result);
+            }
+        }
+        Ok(Value::Nil)
+    }
+    
+    /// `(every? pred coll)`: whether `pred` is truthy for every item
+    fn every_p(args: &[Value], caller: &mut dyn FunctionCaller) -> RuntimeResult<Value> {
+        let (predicate, items) = Self::function_and_sequence(args, "every?")?;
+        for item in items.iter() {
+            if !caller.call_function(predicate, std::slice::from_ref(item))?.is_truthy() {
+                return Ok(Value::Boolean(false));
+            }
+        }
+        Ok(Value::Boolean(true))
+    }
+    
+    /// `(apply f arg* coll)`: call `f` with the leading arguments followed by the items of `coll`
+    fn apply(args: &[Value], caller: &mut dyn FunctionCaller) -> RuntimeResult<Value> {
+        let (function, leading, collection) = match args {
+            [function, leading @ .., collection] => (function, leading, collection),
+            _ => return Err(Self::arity_error("apply", "at least 2", args.len())),
+        };
+        let mut call_args = leading.to_vec();
+        call_args.extend_from_slice(&Self::sequence_arg(collection, "apply")?);
+        caller.call_function(function, &call_args)
+    }
+    
+    fn function_and_sequence<'a>(args: &'a [Value], operation: &str) -> RuntimeResult<(&'a Value, Cow<'a, [Value]>)> {
+        match args {
+            [function, collection] => Ok((function, Self::sequence_arg(collection, operation)?)),
+            _ => Err(Self::arity_error(operation, "2", args.len())),
+        }
+    }
+    
+    /// The items of a sequence argument, as `for` sees them (`Value::iteration_items`):
+    /// a map gives a `[key value]` vector per entry, and nil counts as empty
+    fn sequence_arg<'a>(value: &'a Value, operation: &str) -> RuntimeResult<Cow<'a, [Value]>> {
+        match value {
+            Value::Vector(items) => Ok(Cow::Borrowed(items)),
+            Value::Map(_) | Value::Nil => Ok(Cow::Owned(value.iteration_items()?)),
+            other => Err(RuntimeError::TypeError {
+                expected: "vector, map or nil".to_string(),
+                actual: other.type_name().to_string(),
+                operation: operation.to_string(),
             }),
         }
     }
+    
+    /// Order two numbers or two strings
+    fn order_values(a: &Value, b: &Value, operation: &str) -> RuntimeResult<Ordering> {
+        let ordering = match (a, b) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Integer(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+        ordering.ok_or_else(|| RuntimeError::TypeError {
+            expected: "comparable types".to_string(),
+            actual: format!("{} and {}", a.type_name(), b.type_name()),
+            operation: operation.to_string(),
+        })
+    }
+    
+    fn arity_error(function: &str, expected: &str, actual: usize) -> RuntimeError {
+        RuntimeError::ArityMismatch {
+            function: function.to_string(),
+            expected: expected.to_string(),
+            actual,
+        }
+    }
 }
@@ -0,0 +1,146 @@
+// Task context for RTFS
+// Host-provided data that plans read through `@field` task context access
+
+use std::collections::HashMap;
+use crate::ast::{Keyword, MapKey, Symbol};
+use crate::runtime::{RuntimeError, RuntimeResult, Value};
+
+/// Data a task's plan can read with `@field`.
+///
+/// `@field` resolves, in order, to:
+/// - `@id` / `@task-id`: the task id
+/// - `@input`: the whole input map
+/// - `@caller`: the caller metadata map
+/// - `@steps`: a map from step id to the result of that `log-step`
+/// - any task field set with `set_field` (`@intent`, `@contracts`, `@metadata`, ...)
+/// - an input with that name (`@user-id` reads input `:user-id`)
+/// - the result of the step with that id
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskContext {
+    /// Id of the running task
+    pub task_id: Option<String>,
+    /// Input data the task was invoked with
+    pub inputs: HashMap<MapKey, Value>,
+    /// Metadata about whoever invoked the task (agent, user, session, ...)
+    pub caller_metadata: HashMap<MapKey, Value>,
+    /// Results of completed steps, in execution order
+    step_results: Vec<(String, Value)>,
+    /// Other task fields exposed as `@name`
+    fields: HashMap<String, Value>,
+}
+
+impl TaskContext {
+    /// Create an empty task context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the task id
+    pub fn with_task_id(mut self, task_id: &str) -> Self {
+        self.task_id = Some(task_id.to_string());
+        self
+    }
+
+    /// Add an input, available as `@name` and under `:name` in `@input`
+    pub fn with_input(mut self, name: &str, value: Value) -> Self {
+        self.inputs.insert(MapKey::Keyword(Keyword(name.to_string())), value);
+        self
+    }
+
+    /// Add caller metadata, available under `:name` in `@caller`
+    pub fn with_caller_metadata(mut self, name: &str, value: Value) -> Self {
+        self.caller_metadata.insert(MapKey::Keyword(Keyword(name.to_string())), value);
+        self
+    }
+
+    /// Expose a task field (e.g. `intent`) as `@name`
+    pub fn set_field(&mut self, name: &str, value: Value) {
+        self.fields.insert(name.to_string(), value);
+    }
+
+    /// Record the result of a completed step
+    pub fn record_step_result(&mut self, step_id: &str, value: Value) {
+        self.step_results.push((step_id.to_string(), value));
+    }
+
+    /// Result of the most recent step with the given id
+    pub fn step_result(&self, step_id: &str) -> Option<&Value> {
+        self.step_results
+            .iter()
+            .rev()
+            .find(|(id, _)| id == step_id)
+            .map(|(_, value)| value)
+    }
+
+    /// All recorded step results, in execution order
+    pub fn step_results(&self) -> &[(String, Value)] {
+        &self.step_results
+    }
+
+    /// Resolve `@field` against this context
+    pub fn resolve(&self, field: &Keyword) -> RuntimeResult<Value> {
+        let name = field.0.as_str();
+        let value = match name {
+            "id" | "task-id" => Some(self.task_id.clone().map(Value::String).unwrap_or(Value::Nil)),
+            "input" => Some(Value::Map(self.inputs.clone())),
+            "caller" => Some(Value::Map(self.caller_metadata.clone())),
+            "steps" => Some(Value::Map(
+                self.step_results
+                    .iter()
+                    .map(|(id, value)| (MapKey::Keyword(Keyword(id.clone())), value.clone()))
+                    .collect(),
+            )),
+            _ => self
+                .fields
+                .get(name)
+                .or_else(|| self.inputs.get(&MapKey::Keyword(field.clone())))
+                .or_else(|| self.inputs.get(&MapKey::String(name.to_string())))
+                .or_else(|| self.step_result(name))
+                .cloned(),
+        };
+        value.ok_or_else(|| RuntimeError::UndefinedSymbol(Symbol(format!("@{}", name))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword(name: &str) -> Keyword {
+        Keyword(name.to_string())
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let mut context = TaskContext::new()
+            .with_task_id("task-1")
+            .with_input("user-id", Value::String("u-42".to_string()))
+            .with_input("intent", Value::Integer(1))
+            .with_caller_metadata("agent", Value::String("planner".to_string()));
+        context.set_field("intent", Value::Keyword(keyword("greet")));
+        context.record_step_result("fetch", Value::Integer(7));
+
+        assert_eq!(context.resolve(&keyword("id")), Ok(Value::String("task-1".to_string())));
+        assert_eq!(context.resolve(&keyword("user-id")), Ok(Value::String("u-42".to_string())));
+        // Task fields shadow inputs with the same name
+        assert_eq!(context.resolve(&keyword("intent")), Ok(Value::Keyword(keyword("greet"))));
+        assert_eq!(context.resolve(&keyword("fetch")), Ok(Value::Integer(7)));
+        assert_eq!(
+            context.resolve(&keyword("caller")),
+            Ok(Value::Map(context.caller_metadata.clone()))
+        );
+        assert!(matches!(
+            context.resolve(&keyword("missing")),
+            Err(RuntimeError::UndefinedSymbol(_))
+        ));
+    }
+
+    #[test]
+    fn test_latest_step_result_wins() {
+        let mut context = TaskContext::new();
+        context.record_step_result("retry", Value::Integer(1));
+        context.record_step_result("retry", Value::Integer(2));
+        assert_eq!(context.step_result("retry"), Some(&Value::Integer(2)));
+        assert_eq!(context.step_results().len(), 2);
+    }
+}
@@ -0,0 +1,577 @@
+// Task runner for RTFS
+// Executes whole `task` artifacts: validates them, runs the plan and records the execution trace
+
+use std::collections::HashMap;
+use crate::ast::{DefTypeExpr, Expression, Keyword, Literal, MapKey, Symbol, TaskDefinition, TypeExpr};
+use crate::ir_converter::IrConverter;
+use crate::match_analysis::{check_plan, MatchWarning};
+use crate::runtime::schema::{check_schema, schema_from_value_with, SchemaViolation};
+use crate::runtime::{typecheck_ir, Evaluator, RuntimeError, RuntimeResult, TaskContext, Value};
+
+/// Agent name recorded in trace entries unless overridden
+const DEFAULT_AGENT: &str = "rtfs-task-runner";
+
+/// A task after its plan has run
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletedTask {
+    /// The task artifact with the new `:execution-trace` entries appended
+    pub task: TaskDefinition,
+    /// The plan's result, or the error that stopped it
+    pub result: RuntimeResult<Value>,
+}
+
+/// Runs parsed task definitions
+pub struct TaskRunner {
+    evaluator: Evaluator,
+    agent: String,
+}
+
+impl TaskRunner {
+    /// Create a new task runner with the standard library loaded
+    pub fn new() -> Self {
+        TaskRunner {
+            evaluator: Evaluator::new(),
+            agent: DEFAULT_AGENT.to_string(),
+        }
+    }
+
+    /// Set the agent name recorded in `:execution-trace` entries
+    pub fn with_agent(mut self, agent: &str) -> Self {
+        self.agent = agent.to_string();
+        self
+    }
+
+    /// Name a type defined outside the task, e.g. by a top-level `deftype`, so that
+    /// `:contracts` schemas (as `:User`) and the plan can refer to it
+    pub fn define_type(&self, deftype: &DefTypeExpr) -> RuntimeResult<()> {
+        self.evaluator.evaluate(&Expression::DefType(Box::new(deftype.clone()))).map(|_| ())
+    }
+
+    /// Check that the task has an `:intent` and a plan that passes the static type and
+    /// resource-lifetime checks, and that `:contracts` written as data holds well-formed
+    /// schemas. Nothing in the task is evaluated: contracts that are computed are only
+    /// checked when the task runs.
+    pub fn validate(&self, task: &TaskDefinition) -> RuntimeResult<()> {
+        self.check(task)?;
+        if let Some(contracts) = task.contracts.as_ref().and_then(literal_data) {
+            let types = self.evaluator.defined_types();
+            contract_schema(&contracts, "input-schema", &types)?;
+            contract_schema(&contracts, "output-schema", &types)?;
+        }
+        Ok(())
+    }
+
+    /// Flag `match` expressions in the plan that miss cases, such as an `[:error e]` branch,
+    /// or have unreachable clauses. Warnings do not stop the plan from running.
+    pub fn plan_warnings(&self, task: &TaskDefinition) -> Vec<MatchWarning> {
+        task.plan.as_ref().map(check_plan).unwrap_or_default()
+    }
+
+    /// Validate the task, run its `:plan` against an input map and return the completed artifact
+    pub fn run(&self, task: &TaskDefinition, inputs: Value) -> RuntimeResult<CompletedTask> {
+        let mut context = TaskContext::new();
+        match inputs {
+            Value::Map(map) => context.inputs = map,
+            Value::Nil => {}
+            other => {
+                return Err(contract_violation(format!(
+                    "task input must be a map, got {}",
+                    other.type_name()
+                )))
+            }
+        }
+        self.run_with_context(task, context)
+    }
+
+    /// Validate the task, run its `:plan` against a host-populated context and return the
+    /// completed artifact.
+    ///
+    /// The task's id, intent, contracts and metadata are added to the context. Validation
+    /// failures are returned as errors. Once the plan has started, its outcome is recorded in
+    /// the trace and reported through `CompletedTask::result` instead.
+    pub fn run_with_context(&self, task: &TaskDefinition, mut context: TaskContext) -> RuntimeResult<CompletedTask> {
+        let prepared = self.prepare(task)?;
+        let inputs = Value::Map(context.inputs.clone());
+        if let Some(schema) = &prepared.input_schema {
+            check_contract(schema, &inputs, "input")?;
+        }
+
+        let mut trace = match &task.execution_trace {
+            Some(expr) => match expr.unspanned() {
+                Expression::Vector(entries) => entries.clone(),
+                _ => {
+                    return Err(RuntimeError::InvalidProgram(
+                        ":execution-trace must be a vector".to_string(),
+                    ))
+                }
+            },
+            None => Vec::new(),
+        };
+        trace.push(self.trace_entry("task-started", vec![("inputs", value_to_expression(&inputs))]));
+
+        if task.id.is_some() {
+            context.task_id = task.id.clone();
+        }
+        context.set_field("intent", prepared.intent.clone());
+        context.set_field("contracts", prepared.contracts.clone());
+        context.set_field("metadata", prepared.metadata.clone());
+        let steps_before = context.step_results().len();
+        self.evaluator.set_task_context(context);
+        let plan_result = self.evaluator.evaluate(prepared.plan);
+
+        let context = self.evaluator.take_task_context();
+        for (step_id, value) in &context.step_results()[steps_before..] {
+            trace.push(self.trace_entry(
+                "step-executed",
+                vec![
+                    ("step-id", Expression::Literal(Literal::String(step_id.clone()))),
+                    ("result", success_result(value)),
+                ],
+            ));
+        }
+
+        let result = plan_result.and_then(|value| {
+            if let Some(schema) = &prepared.output_schema {
+                check_contract(schema, &value, "output")?;
+            }
+            Ok(value)
+        });
+        trace.push(match &result {
+            Ok(value) => self.trace_entry("task-completed", vec![("result", success_result(value))]),
+            Err(error) => self.trace_entry("task-failed", vec![("result", error_result(error))]),
+        });
+
+        let mut completed = task.clone();
+        completed.execution_trace = Some(Expression::Vector(trace));
+        Ok(CompletedTask { task: completed, result })
+    }
+
+    /// The checks that need nothing evaluated; returns the plan and the intent
+    fn check<'t>(&self, task: &'t TaskDefinition) -> RuntimeResult<(&'t Expression, &'t Expression)> {
+        let plan = task.plan.as_ref().ok_or_else(|| {
+            RuntimeError::InvalidProgram("task has no :plan".to_string())
+        })?;
+        let intent = task.intent.as_ref().ok_or_else(|| {
+            RuntimeError::InvalidProgram("task has no :intent".to_string())
+        })?;
+        // The plan runs on the AST evaluator but is checked through its IR, as the IR
+        // strategies check programs; a plan the converter cannot handle is left to the
+        // evaluator
+        if let Ok(ir) = IrConverter::new().convert(plan) {
+            typecheck_ir(&ir, &self.evaluator.defined_types())?;
+        }
+        Ok((plan, intent))
+    }
+
+    fn prepare<'t>(&self, task: &'t TaskDefinition) -> RuntimeResult<PreparedTask<'t>> {
+        let (plan, intent) = self.check(task)?;
+        // The intent may be any value: a map, a string describing the goal, ...
+        let intent = self.evaluator.evaluate(intent)?;
+
+        let contracts = match &task.contracts {
+            Some(expr) => self.evaluator.evaluate(expr)?,
+            None => Value::Map(HashMap::new()),
+        };
+        let types = self.evaluator.defined_types();
+        let input_schema = contract_schema(&contracts, "input-schema", &types)?;
+        let output_schema = contract_schema(&contracts, "output-schema", &types)?;
+
+        let metadata = match &task.metadata {
+            Some(expr) => self.evaluator.evaluate(expr)?,
+            None => Value::Nil,
+        };
+
+        Ok(PreparedTask {
+            plan,
+            intent,
+            contracts,
+            metadata,
+            input_schema,
+            output_schema,
+        })
+    }
+
+    fn trace_entry(&self, event: &str, fields: Vec<(&str, Expression)>) -> Expression {
+        let mut entry = HashMap::new();
+        entry.insert(keyword_key("event"), keyword_expr(event));
+        entry.insert(keyword_key("agent"), Expression::Literal(Literal::String(self.agent.clone())));
+        for (key, value) in fields {
+            entry.insert(keyword_key(key), value);
+        }
+        Expression::Map(entry)
+    }
+}
+
+impl Default for TaskRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Task fields evaluated once before the plan runs
+struct PreparedTask<'t> {
+    plan: &'t Expression,
+    intent: Value,
+    contracts: Value,
+    metadata: Value,
+    input_schema: Option<TypeExpr>,
+    output_schema: Option<TypeExpr>,
+}
+
+/// Read the schema stored under `name` in the contracts map, resolving the named `types`
+fn contract_schema(contracts: &Value, name: &str, types: &HashMap<String, TypeExpr>) -> RuntimeResult<Option<TypeExpr>> {
+    let contracts = match contracts {
+        Value::Map(map) => map,
+        other => {
+            return Err(RuntimeError::TypeError {
+                expected: "map".to_string(),
+                actual: other.type_name().to_string(),
+                operation: "task :contracts".to_string(),
+            })
+        }
+    };
+    match contracts.get(&keyword_key(name)) {
+        Some(schema) => schema_from_value_with(schema, types).map(Some).map_err(|reason| {
+            RuntimeError::InvalidProgram(format!("invalid :{} in task :contracts: {}", name, reason))
+        }),
+        None => Ok(None),
+    }
+}
+
+/// The value of an expression written as data: literals, and vectors and maps of them.
+/// None if getting it would need evaluation, as for a call or a symbol.
+fn literal_data(expr: &Expression) -> Option<Value> {
+    match expr.unspanned() {
+        Expression::Literal(literal) => Some(Value::from(literal)),
+        Expression::Vector(items) => items.iter().map(literal_data).collect::<Option<_>>().map(Value::Vector),
+        Expression::Map(entries) => entries
+            .iter()
+            .map(|(key, value)| literal_data(value).map(|value| (key.clone(), value)))
+            .collect::<Option<_>>()
+            .map(Value::Map),
+        _ => None,
+    }
+}
+
+/// Check the task input or output against its contract schema
+fn check_contract(schema: &TypeExpr, value: &Value, direction: &str) -> RuntimeResult<()> {
+    check_schema(schema, value).map_err(|violation| schema_violation(&violation, direction))
+}
+
+/// A contract violation carrying `{:direction :path :expected :actual}` as its data
+fn schema_violation(violation: &SchemaViolation, direction: &str) -> RuntimeError {
+    let mut data = HashMap::new();
+    data.insert(keyword_key("direction"), Value::Keyword(Keyword(direction.to_string())));
+    data.insert(keyword_key("path"), violation.path_value());
+    data.insert(keyword_key("expected"), Value::String(violation.expected.clone()));
+    data.insert(keyword_key("actual"), Value::String(violation.actual.clone()));
+    RuntimeError::ApplicationError {
+        error_type: Keyword("error/contract-violation".to_string()),
+        message: format!("task {} violates its contract: {}", direction, violation),
+        data: Some(Value::Map(data)),
+    }
+}
+
+fn contract_violation(message: String) -> RuntimeError {
+    RuntimeError::ApplicationError {
+        error_type: Keyword("error/contract-violation".to_string()),
+        message,
+        data: None,
+    }
+}
+
+fn success_result(value: &Value) -> Expression {
+    let mut result = HashMap::new();
+    result.insert(keyword_key("status"), keyword_expr("success"));
+    result.insert(keyword_key("value"), value_to_expression(value));
+    Expression::Map(result)
+}
+
+fn error_result(error: &RuntimeError) -> Expression {
+    let mut result = HashMap::new();
+    result.insert(keyword_key("status"), keyword_expr("error"));
+    result.insert(keyword_key("error"), value_to_expression(&error.to_value()));
+    Expression::Map(result)
+}
+
+fn keyword_key(name: &str) -> MapKey {
+    MapKey::Keyword(Keyword(name.to_string()))
+}
+
+fn keyword_expr(name: &str) -> Expression {
+    Expression::Literal(Literal::Keyword(Keyword(name.to_string())))
+}
+
+/// Convert a runtime value back into a literal expression for the trace
+fn value_to_expression(value: &Value) -> Expression {
+    match value {
+        Value::Integer(n) => Expression::Literal(Literal::Integer(*n)),
+        Value::Float(f) => Expression::Literal(Literal::Float(*f)),
+        Value::String(s) => Expression::Literal(Literal::String(s.clone())),
+        Value::Boolean(b) => Expression::Literal(Literal::Boolean(*b)),
+        Value::Keyword(k) => Expression::Literal(Literal::Keyword(k.clone())),
+        Value::Symbol(s) => Expression::Symbol(s.clone()),
+        Value::Nil => Expression::Literal(Literal::Nil),
+        Value::Vector(items) => Expression::Vector(items.iter().map(value_to_expression).collect()),
+        Value::Map(map) => Expression::Map(
+            map.iter()
+                .map(|(key, value)| (key.clone(), value_to_expression(value)))
+                .collect(),
+        ),
+        Value::Array(array) => Expression::FunctionCall {
+            callee: Box::new(Expression::Symbol(Symbol("array".to_string()))),
+            arguments: vec![value_to_expression(&array.to_nested())],
+        },
+        Value::Ok(inner) => Expression::Vector(vec![keyword_expr("ok"), value_to_expression(inner)]),
+        Value::Error(error) => {
+            let mut map = HashMap::new();
+            map.insert(keyword_key("type"), Expression::Literal(Literal::Keyword(error.error_type.clone())));
+            map.insert(keyword_key("message"), Expression::Literal(Literal::String(error.message.clone())));
+            Expression::Map(map)
+        }
+        // Functions and resources have no literal form
+        Value::Function(_) | Value::Resource(_) => Expression::Literal(Literal::String(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::TopLevel;
+    use crate::parser::parse;
+
+    fn parse_task(source: &str) -> TaskDefinition {
+        match parse(source).unwrap().into_iter().next() {
+            Some(TopLevel::Task(task)) => task,
+            other => panic!("expected a task, got {:?}", other),
+        }
+    }
+
+    fn eval_map(source: &str) -> Value {
+        Evaluator::new().evaluate(&crate::parser::parse_expression(source).unwrap()).unwrap()
+    }
+
+    fn inputs(entries: &[(&str, Value)]) -> Value {
+        Value::Map(entries.iter().map(|(k, v)| (keyword_key(k), v.clone())).collect())
+    }
+
+    fn trace_events(task: &TaskDefinition) -> Vec<String> {
+        match task.execution_trace.as_ref().map(Expression::unspanned) {
+            Some(Expression::Vector(entries)) => entries
+                .iter()
+                .map(|entry| match entry.unspanned() {
+                    Expression::Map(map) => match map.get(&keyword_key("event")).map(Expression::unspanned) {
+                        Some(Expression::Literal(Literal::Keyword(k))) => k.0.clone(),
+                        other => panic!("trace entry without :event: {:?}", other),
+                    },
+                    other => format!("{:?}", other),
+                })
+                .collect(),
+            other => panic!("expected a trace vector, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_run_plan_with_context_access() {
+        let task = parse_task(r#"
+            (task :id "task-1"
+              :intent {:action :greet :greeting "hello"}
+              :contracts {:input-schema [:map [:user-id :string]]}
+              :plan (do
+                      (log-step :id "greet" (str (get @intent :greeting) " " @user-id))
+                      {:task @id :user (get @input :user-id)}))
+        "#);
+        let completed = TaskRunner::new()
+            .run(&task, inputs(&[("user-id", Value::String("u-42".to_string()))]))
+            .unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert(keyword_key("task"), Value::String("task-1".to_string()));
+        expected.insert(keyword_key("user"), Value::String("u-42".to_string()));
+        assert_eq!(completed.result, Ok(Value::Map(expected)));
+        assert_eq!(
+            trace_events(&completed.task),
+            vec!["task-started", "step-executed", "task-completed"]
+        );
+    }
+
+    #[test]
+    fn test_trace_is_appended_to_existing_entries() {
+        let task = parse_task(r#"
+            (task :intent {:action :noop}
+              :plan 1
+              :execution-trace [{:event :task-created}])
+        "#);
+        let completed = TaskRunner::new().run(&task, Value::Nil).unwrap();
+        assert_eq!(completed.result, Ok(Value::Integer(1)));
+        assert_eq!(
+            trace_events(&completed.task),
+            vec!["task-created", "task-started", "task-completed"]
+        );
+    }
+
+    #[test]
+    fn test_plan_failure_is_recorded() {
+        let task = parse_task("(task :intent {:action :fail} :plan (/ 1 0))");
+        let completed = TaskRunner::new().run(&task, Value::Nil).unwrap();
+        assert!(completed.result.is_err());
+        assert_eq!(trace_events(&completed.task), vec!["task-started", "task-failed"]);
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let runner = TaskRunner::new();
+        assert!(runner.validate(&parse_task("(task :plan 1)")).is_err());
+        assert!(runner.validate(&parse_task("(task :intent {:a 1})")).is_err());
+        assert!(runner
+            .validate(&parse_task("(task :intent {:a 1} :contracts {:input-schema [:vector]} :plan 1)"))
+            .is_err());
+        assert!(runner
+            .validate(&parse_task("(task :intent {:a 1} :contracts {:input-schema [:map [:x :int]]} :plan 1)"))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_intent_may_be_any_value() {
+        let runner = TaskRunner::new();
+        let task = parse_task(r#"(task :intent "greet the user" :plan @intent)"#);
+        assert!(runner.validate(&task).is_ok());
+        let completed = runner.run(&task, Value::Nil).unwrap();
+        assert_eq!(completed.result, Ok(Value::String("greet the user".to_string())));
+    }
+
+    #[test]
+    fn test_validation_evaluates_nothing() {
+        let runner = TaskRunner::new();
+        // Each of these fails when evaluated
+        let task = parse_task("(task :intent (/ 1 0) :contracts {:input-schema (/ 3 0)} :metadata {:at (/ 2 0)} :plan 1)");
+        assert!(runner.validate(&task).is_ok());
+        let error = runner.run(&task, Value::Nil).unwrap_err();
+        assert_eq!(error.without_location(), &RuntimeError::DivisionByZero);
+    }
+
+    #[test]
+    fn test_plans_are_type_and_lifetime_checked_before_running() {
+        let runner = TaskRunner::new();
+        let ill_typed = parse_task(r#"(task :intent {:a 1} :plan (do (log-step :id "s" 1) (+ "a" 1)))"#);
+        let error = runner.validate(&ill_typed).unwrap_err();
+        assert!(matches!(error.without_location(), RuntimeError::TypeError { operation, .. } if operation == "type check"));
+        // Rejected before the plan starts, so nothing is traced
+        let error = runner.run(&ill_typed, Value::Nil).unwrap_err();
+        assert!(matches!(error.without_location(), RuntimeError::TypeError { .. }));
+
+        let escaping = parse_task(r#"(task :intent {:a 1} :plan (with-resource [f FileHandle (tool:open-file "x")] f))"#);
+        assert!(matches!(runner.validate(&escaping).unwrap_err().without_location(), RuntimeError::ResourceError { .. }));
+    }
+
+    #[test]
+    fn test_plan_warnings_flag_missing_error_branches() {
+        let task = parse_task(r#"
+            (task :intent {:action :read}
+              :plan (match (tool:get-env "HOME") [:ok home] home))
+        "#);
+        let warnings: Vec<String> = TaskRunner::new().plan_warnings(&task).iter().map(|w| w.to_string()).collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("non-exhaustive match, no clause handles [:error _]"));
+
+        let handled = parse_task("(task :intent {:a 1} :plan (match (tool:get-env \"HOME\") [:ok h] h [:error e] nil))");
+        assert!(TaskRunner::new().plan_warnings(&handled).is_empty());
+    }
+
+    #[test]
+    fn test_contract_keys_are_checked() {
+        let runner = TaskRunner::new();
+        let task = parse_task(r#"
+            (task :intent {:action :echo}
+              :contracts {:input-schema [:map [:x :int] [:y {:optional true} :int]]
+                          :output-schema [:map [:sum :int]]}
+              :plan {:total @x})
+        "#);
+
+        let missing_input = runner.run(&task, Value::Nil);
+        assert!(matches!(missing_input, Err(RuntimeError::ApplicationError { .. })));
+
+        let completed = runner.run(&task, inputs(&[("x", Value::Integer(1))])).unwrap();
+        assert!(matches!(completed.result, Err(RuntimeError::ApplicationError { .. })));
+        assert_eq!(trace_events(&completed.task), vec!["task-started", "task-failed"]);
+    }
+
+    #[test]
+    fn test_contracts_refer_to_named_types() {
+        let runner = TaskRunner::new();
+        let task = parse_task(r#"
+            (task :intent {:action :greet}
+              :contracts {:input-schema [:map [:user :User]] :output-schema :Greeting}
+              :plan (str "Hello " (get @user :name)))
+        "#);
+        assert!(runner.validate(&task).is_err());
+
+        for source in ["(deftype User [:map [:name string]])", "(deftype Greeting [:and string [:min-length 7]])"] {
+            match parse(source).unwrap().into_iter().next() {
+                Some(TopLevel::Expression(expr)) => match expr.unspanned() {
+                    Expression::DefType(deftype) => runner.define_type(deftype).unwrap(),
+                    other => panic!("expected deftype, got {:?}", other),
+                },
+                other => panic!("expected an expression, got {:?}", other),
+            }
+        }
+        let completed = runner.run(&task, inputs(&[("user", eval_map(r#"{:name "Ada"}"#))])).unwrap();
+        assert_eq!(completed.result, Ok(Value::String("Hello Ada".to_string())));
+
+        let bad_input = runner.run(&task, inputs(&[("user", eval_map("{:name 7}"))]));
+        assert_eq!(violation_data(&bad_input.unwrap_err()).1.to_string(), "[:user :name]");
+        let completed = runner.run(&task, inputs(&[("user", eval_map(r#"{:name ""}"#))])).unwrap();
+        assert!(completed.result.is_err());
+
+        // The plan's annotations are checked against the defined types too
+        let greet = |name: &str| parse_task(&format!("(task :intent {{}} :plan ((fn [g :Greeting] g) {}))", name));
+        assert!(runner.validate(&greet("\"Hello Ada\"")).is_ok());
+        assert!(runner.validate(&greet("\"Hi\"")).is_err());
+        assert!(runner.validate(&greet("7")).is_err());
+    }
+
+    fn violation_data(error: &RuntimeError) -> (Value, Value) {
+        match error {
+            RuntimeError::ApplicationError { data: Some(Value::Map(data)), .. } => (
+                data[&keyword_key("direction")].clone(),
+                data[&keyword_key("path")].clone(),
+            ),
+            other => panic!("expected a contract violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_contract_violations_report_field_paths() {
+        let runner = TaskRunner::new();
+        let task = parse_task(r#"
+            (task :intent {:action :lookup}
+              :contracts {:input-schema [:map [:user [:map [:id :string] [:tags [:vector :keyword]]]]]
+                          :output-schema [:map [:status [:union [:val :ok] [:val :retry]]]
+                                               [:* :int]]}
+              :plan {:status (get @input :status) :count 2 :extra (get @input :extra)})
+        "#);
+
+        let bad_input = runner.run(&task, inputs(&[("user", eval_map(r#"{:id "u-1" :tags [:a "b"]}"#))]));
+        let (direction, path) = violation_data(&bad_input.unwrap_err());
+        assert_eq!(direction, Value::Keyword(Keyword("input".to_string())));
+        assert_eq!(path.to_string(), "[:user :tags 1]");
+
+        let valid_user = ("user", eval_map(r#"{:id "u-1" :tags [:a]}"#));
+        let completed = runner
+            .run(&task, inputs(&[valid_user.clone(), ("status", Value::Keyword(Keyword("ok".to_string()))), ("extra", Value::Integer(1))]))
+            .unwrap();
+        assert!(completed.result.is_ok());
+
+        // The output wildcard rejects the nil :extra, the literal union rejects :done
+        let completed = runner
+            .run(&task, inputs(&[valid_user.clone(), ("status", Value::Keyword(Keyword("ok".to_string())))]))
+            .unwrap();
+        let (direction, path) = violation_data(completed.result.as_ref().unwrap_err());
+        assert_eq!(direction, Value::Keyword(Keyword("output".to_string())));
+        assert_eq!(path.to_string(), "[:extra]");
+        let completed = runner
+            .run(&task, inputs(&[valid_user, ("status", Value::Keyword(Keyword("done".to_string()))), ("extra", Value::Integer(1))]))
+            .unwrap();
+        assert_eq!(violation_data(completed.result.as_ref().unwrap_err()).1.to_string(), "[:status]");
+    }
+}
@@ -2,7 +2,9 @@
 // Represents values during execution (different from AST which represents parsed code)
 
 use std::collections::HashMap;
-use crate::ast::{Symbol, Keyword, MapKey};
+use std::rc::Rc;
+use crate::ast::{Symbol, Keyword, Literal, MapKey};
+use crate::runtime::array::ArrayValue;
 
 /// Runtime values in RTFS
 #[derive(Debug, Clone, PartialEq)]
@@ -19,6 +21,7 @@ pub enum Value {
     // Collection values
     Vector(Vec<Value>),
     Map(HashMap<MapKey, Value>),
+    Array(ArrayValue),
     
     // Function values
     Function(Function),
@@ -41,6 +44,14 @@ pub enum Function {
         func: fn(&[Value]) -> crate::runtime::RuntimeResult<Value>,
     },
     
+    /// Built-in higher-order functions, which call their function arguments through the
+    /// runtime that called them
+    BuiltinWithCallback {
+        name: String,
+        arity: Arity,
+        func: fn(&[Value], &mut dyn FunctionCaller) -> crate::runtime::RuntimeResult<Value>,
+    },
+    
     /// User-defined functions (defined in RTFS)
     UserDefined {
         params: Vec<crate::ast::ParamDef>,
@@ -48,6 +59,19 @@ pub enum Function {
         body: Vec<crate::ast::Expression>,
         closure: crate::runtime::Environment, // Captured environment
     },
+    
+    /// Functions compiled to IR (created by the IR runtime)
+    IrLambda {
+        params: Rc<[crate::ir::IrNode]>, // Shared with the lambda node in the IR
+        variadic_param: Option<Box<crate::ir::IrNode>>,
+        body: Rc<[crate::ir::IrNode]>,
+        closure: crate::runtime::ir_runtime::IrEnvironment, // Captured slots only
+    },
+}
+
+/// Calls function values on behalf of a builtin; implemented by each runtime
+pub trait FunctionCaller {
+    fn call_function(&mut self, function: &Value, args: &[Value]) -> crate::runtime::RuntimeResult<Value>;
 }
 
 /// Function arity specification
@@ -83,6 +107,17 @@ pub struct ErrorValue {
     pub data: Option<HashMap<String, Value>>,
 }
 
+impl ErrorValue {
+    /// Whether this error is of the given error type or below it in the keyword
+    /// hierarchy, where `:error/network` covers `:error/network/timeout` and
+    /// `:error` covers every `:error/...` type
+    pub fn is_kind_of(&self, error_type: &Keyword) -> bool {
+        let name = &self.error_type.0;
+        name == &error_type.0
+            || name.strip_prefix(error_type.0.as_str()).is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
 impl Value {
     /// Check if a value is truthy (everything except false and nil)
     pub fn is_truthy(&self) -> bool {
@@ -117,6 +152,7 @@ impl Value {
                 }).collect();
                 format!("{{{}}}", entries.join(" "))
             },
+            Value::Array(a) => format!("(array {})", a.to_nested().to_string()),
             Value::Function(_) => "#<function>".to_string(),
             Value::Resource(h) => format!("#<resource:{}>", h.resource_type),
             Value::Ok(v) => format!("[:ok {}]", v.to_string()),
@@ -136,12 +172,58 @@ impl Value {
             Value::Nil => "nil",
             Value::Vector(_) => "vector",
             Value::Map(_) => "map",
+            Value::Array(_) => "array",
             Value::Function(_) => "function",
             Value::Resource(_) => "resource",
             Value::Ok(_) => "ok",
             Value::Error(_) => "error",
         }
     }
+    
+    /// The items `for` and `doseq` iterate over: the elements of a vector, or a
+    /// `[key value]` vector per entry of a map in key order. Nil has no items.
+    pub fn iteration_items(&self) -> crate::runtime::RuntimeResult<Vec<Value>> {
+        match self {
+            Value::Vector(items) => Ok(items.clone()),
+            Value::Map(entries) => {
+                let mut entries: Vec<_> = entries.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                Ok(entries
+                    .into_iter()
+                    .map(|(key, value)| Value::Vector(vec![Value::from(key), value.clone()]))
+                    .collect())
+            }
+            Value::Nil => Ok(Vec::new()),
+            _ => Err(crate::runtime::RuntimeError::TypeError {
+                expected: "vector, map or nil".to_string(),
+                actual: self.type_name().to_string(),
+                operation: "iteration".to_string(),
+            }),
+        }
+    }
+}
+
+impl From<&MapKey> for Value {
+    fn from(key: &MapKey) -> Self {
+        match key {
+            MapKey::Keyword(k) => Value::Keyword(k.clone()),
+            MapKey::String(s) => Value::String(s.clone()),
+            MapKey::Integer(i) => Value::Integer(*i),
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Integer(n) => Value::Integer(*n),
+            Literal::Float(f) => Value::Float(*f),
+            Literal::String(s) => Value::String(s.clone()),
+            Literal::Boolean(b) => Value::Boolean(*b),
+            Literal::Keyword(k) => Value::Keyword(k.clone()),
+            Literal::Nil => Value::Nil,
+        }
+    }
 }
 
 // Implement PartialEq for Function manually since function pointers don't implement it
@@ -152,10 +234,19 @@ impl PartialEq for Function {
              Function::Builtin { name: n2, arity: a2, .. }) => {
                 n1 == n2 && a1 == a2
             },
+            (Function::BuiltinWithCallback { name: n1, arity: a1, .. },
+             Function::BuiltinWithCallback { name: n2, arity: a2, .. }) => {
+                n1 == n2 && a1 == a2
+            },
             (Function::UserDefined { params: p1, variadic_param: v1, body: b1, .. },
              Function::UserDefined { params: p2, variadic_param: v2, body: b2, .. }) => {
                 p1 == p2 && v1 == v2 && b1 == b2
             },
+            (Function::IrLambda { params: p1, variadic_param: v1, body: b1, .. },
+             Function::IrLambda { params: p2, variadic_param: v2, body: b2, .. }) => {
+                // Closures of the same lambda share its IR, so compare by identity
+                Rc::ptr_eq(p1, p2) && v1 == v2 && Rc::ptr_eq(b1, b2)
+            },
             _ => false,
         }
     }
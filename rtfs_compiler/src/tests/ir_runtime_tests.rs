@@ -0,0 +1,782 @@
+// IR Runtime Tests
+// Checks that the IR runtime gives the same answers as the AST evaluator
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::ast::{Keyword, Literal, MapKey};
+    use crate::ir::{IrNode, IrType};
+    use crate::ir_converter::IrConverter;
+    use crate::parser::parse_expression;
+    use crate::runtime::ir_runtime::{IrEnvironment, IrRuntime};
+    use crate::runtime::values::Function;
+    use crate::runtime::{Evaluator, Runtime, RuntimeError, RuntimeResult, RuntimeStrategy, Value};
+
+    fn eval_ast(source: &str) -> RuntimeResult<Value> {
+        Evaluator::new().evaluate(&parse_expression(source).unwrap())
+    }
+
+    fn eval_ir(source: &str) -> RuntimeResult<Value> {
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        IrRuntime::new().execute_node(&ir, &mut IrEnvironment::new())
+    }
+
+    fn assert_same_result(source: &str, expected: RuntimeResult<Value>) {
+        let strip = |result: RuntimeResult<Value>| result.map_err(|e| e.without_location().clone());
+        assert_eq!(strip(eval_ast(source)), expected, "AST: {}", source);
+        assert_eq!(strip(eval_ir(source)), expected, "IR: {}", source);
+    }
+
+    #[test]
+    fn test_specialized_arithmetic() {
+        use crate::ir_optimizer::EnhancedIrOptimizer;
+
+        let eval_optimized = |source: &str| {
+            let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+            let ir = EnhancedIrOptimizer::new().optimize_with_control_flow(ir);
+            IrRuntime::new().execute_node(&ir, &mut IrEnvironment::new()).map_err(|e| e.without_location().clone())
+        };
+        for source in ["((fn [x :int] (+ x 1)) 41)", "((fn [x :int y :float] (- x y 0.5)) 3 1.0)", "((fn [x :int] (- x)) 4)"] {
+            assert_eq!(eval_optimized(source), eval_ast(source), "{}", source);
+        }
+        assert_eq!(
+            eval_optimized("(* 9223372036854775807 2)"),
+            Err(RuntimeError::InvalidArgument("integer overflow in *".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        assert_same_result("(match 2 1 :one 2 :two _ :other)", Ok(Value::Keyword(Keyword("two".to_string()))));
+        assert_same_result("(match :b :a 1 :b 2)", Ok(Value::Integer(2)));
+        assert_same_result("(match 5 x when (> x 9) 0 y (+ y 1))", Ok(Value::Integer(6)));
+        assert_same_result("(let [n 3] (match n 3 (* n 2) _ 0))", Ok(Value::Integer(6)));
+        assert_same_result(
+            "(match 7 1 :one)",
+            Err(RuntimeError::MatchError("No matching clause for value: 7".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_vector_and_map_constructors() {
+        let keyword = |name: &str| MapKey::Keyword(Keyword(name.to_string()));
+        assert_same_result("[1 (+ 1 1) \"x\"]", Ok(Value::Vector(vec![
+            Value::Integer(1),
+            Value::Integer(2),
+            Value::String("x".to_string()),
+        ])));
+        assert_same_result("[]", Ok(Value::Vector(vec![])));
+        assert_same_result("()", Ok(Value::Vector(vec![])));
+
+        let mut expected = std::collections::HashMap::new();
+        expected.insert(keyword("a"), Value::Integer(1));
+        expected.insert(keyword("b"), Value::Vector(vec![Value::Integer(2), Value::Integer(6)]));
+        assert_same_result("(let [x 3] {:a 1 :b [2 (* x 2)]})", Ok(Value::Map(expected)));
+
+        // Element errors propagate out of the constructor
+        assert_same_result("[1 (/ 1 0)]", Err(RuntimeError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_match_on_collections() {
+        assert_same_result("(match [1 [2 3]] [a [b c]] (+ a b c))", Ok(Value::Integer(6)));
+        assert_same_result("(match {:kind :add :n 4} {:kind :sub} 0 {:kind :add :n n} (+ n 1))", Ok(Value::Integer(5)));
+    }
+
+    #[test]
+    fn test_match_rest_bindings() {
+        let ints = |items: &[i64]| Value::Vector(items.iter().map(|i| Value::Integer(*i)).collect());
+        assert_same_result("(match [1 2 3] [a & r] r)", Ok(ints(&[2, 3])));
+        assert_same_result("(match [1] [a & r] r)", Ok(ints(&[])));
+        assert_same_result("(match [] [a & r] :some _ :none)", Ok(Value::Keyword(Keyword("none".to_string()))));
+
+        let mut rest = std::collections::HashMap::new();
+        rest.insert(MapKey::Keyword(Keyword("b".to_string())), Value::Integer(2));
+        assert_same_result("(match {:a 1 :b 2} {:a x & r} r)", Ok(Value::Map(rest)));
+        assert_same_result("(match {:a 1} {:a x & r} r)", Ok(Value::Map(std::collections::HashMap::new())));
+    }
+
+    #[test]
+    fn test_destructuring() {
+        let ints = |items: &[i64]| Value::Vector(items.iter().map(|i| Value::Integer(*i)).collect());
+        assert_same_result(
+            "(let [[a b & more :as all] [1 2 3 4]] [a more all])",
+            Ok(Value::Vector(vec![Value::Integer(1), ints(&[3, 4]), ints(&[1, 2, 3, 4])])),
+        );
+        assert_same_result("(let [[a b c] [1]] [a c])", Ok(Value::Vector(vec![Value::Integer(1), Value::Nil])));
+        assert_same_result("(let [[a b & more] [1]] more)", Ok(ints(&[])));
+        assert_same_result("(let [{:point [x y]} {:point [3 4]}] (+ x y))", Ok(Value::Integer(7)));
+
+        let mut other = std::collections::HashMap::new();
+        other.insert(MapKey::Keyword(Keyword("c".to_string())), Value::Integer(3));
+        assert_same_result(
+            "(let [{:keys [a] :b b & other :as m} {:a 1 :b 2 :c 3}] [(+ a b) other (get m :c)])",
+            Ok(Value::Vector(vec![Value::Integer(3), Value::Map(other), Value::Integer(3)])),
+        );
+        // `:as` without a rest binding names the whole map, and `:as` only as a whole keyword
+        assert_same_result("(let [{:keys [a] :as m} {:a 1 :c 3}] [a (get m :c)])", Ok(ints(&[1, 3])));
+        assert_same_result("(let [{:b b :as m} {:b 2}] (count m))", Ok(Value::Integer(1)));
+        assert_same_result("(let [{:asx x} {:asx 4}] x)", Ok(Value::Integer(4)));
+
+        assert_same_result("((fn [[a & more]] more) [1 2])", Ok(ints(&[2])));
+        assert_same_result("(let [[a] 5] a)", Err(RuntimeError::TypeError {
+            expected: "vector".to_string(),
+            actual: "int".to_string(),
+            operation: "vector destructuring".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_destructuring_defaults() {
+        assert_same_result(
+            "(let [{:keys [host port] :or {port 8080}} {:host \"h\"}] [host port])",
+            Ok(Value::Vector(vec![Value::String("h".to_string()), Value::Integer(8080)])),
+        );
+        // Defaults only replace absent keys, not nil values
+        assert_same_result("(let [{:keys [port] :or {port 8080}} {:port nil}] port)", Ok(Value::Nil));
+        assert_same_result("(let [base 10 {:n n :or {n (+ base 1)}} {}] n)", Ok(Value::Integer(11)));
+
+        // Named arguments
+        assert_same_result(
+            "(let [connect (fn [url {:keys [timeout retries] :or {timeout 30 retries (* 2 3)}}] [url timeout retries])]
+               (connect \"u\" {:retries 1}))",
+            Ok(Value::Vector(vec![Value::String("u".to_string()), Value::Integer(30), Value::Integer(1)])),
+        );
+    }
+
+    #[test]
+    fn test_lambdas_and_closures() {
+        assert_same_result("((fn [x] (+ x 1)) 41)", Ok(Value::Integer(42)));
+        assert_same_result("(let [n 10 add-n (fn [x] (+ x n))] (+ (add-n 1) (add-n 2)))", Ok(Value::Integer(23)));
+        assert_same_result("(let [a 1] (((fn [b] (fn [c] (+ a b c))) 2) 3))", Ok(Value::Integer(6)));
+        assert_same_result("((fn [x & more] more) 1 2 3)", Ok(Value::Vector(vec![
+            Value::Integer(2),
+            Value::Integer(3),
+        ])));
+
+        let error = eval_ir("((fn [x] x) 1 2)").unwrap_err();
+        assert!(matches!(error.without_location(), RuntimeError::ArityMismatch { .. }));
+    }
+
+    fn lambda_captures(node: &IrNode) -> Vec<String> {
+        match node {
+            IrNode::Lambda { captures, .. } => captures.iter().map(|c| c.name.clone()).collect(),
+            other => panic!("expected a lambda, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_higher_order_functions() {
+        let ints = |items: &[i64]| Value::Vector(items.iter().map(|i| Value::Integer(*i)).collect());
+        assert_same_result("(map-fn (fn [x] (* x 2)) [1 2 3])", Ok(ints(&[2, 4, 6])));
+        assert_same_result("(map-fn + [1 2 3] [10 20])", Ok(ints(&[11, 22])));
+        assert_same_result("(filter (fn [x] (> x 1)) [1 2 3])", Ok(ints(&[2, 3])));
+        assert_same_result("(reduce + [1 2 3 4])", Ok(Value::Integer(10)));
+        assert_same_result("(reduce (fn [acc x] (conj acc (* x x))) [] [1 2])", Ok(ints(&[1, 4])));
+        assert_same_result("(reduce + 5 [])", Ok(Value::Integer(5)));
+        assert_same_result("(some (fn [x] (> x 2)) [1 2 3])", Ok(Value::Boolean(true)));
+        assert_same_result("(some (fn [x] (> x 5)) [1 2 3])", Ok(Value::Nil));
+        assert_same_result("(every? (fn [x] (> x 0)) [1 2 3])", Ok(Value::Boolean(true)));
+        assert_same_result("(apply + 1 2 [3 4])", Ok(Value::Integer(10)));
+
+        // Stable sort on the key the function gives each item
+        assert_same_result(
+            "(map-fn (fn [p] (get p :name)) (sort-by (fn [p] (get p :age)) [{:name \"a\" :age 30} {:name \"b\" :age 20} {:name \"c\" :age 30}]))",
+            Ok(Value::Vector(["b", "a", "c"].iter().map(|s| Value::String(s.to_string())).collect())),
+        );
+
+        // A map gives its entries as [key value] vectors, in key order
+        let entry = |key: &str, value: i64| Value::Vector(vec![Value::Keyword(Keyword(key.to_string())), Value::Integer(value)]);
+        assert_same_result("(map-fn (fn [[k v]] v) {:b 2 :a 1})", Ok(ints(&[1, 2])));
+        assert_same_result("(filter (fn [[k v]] (> v 1)) {:b 2 :a 1})", Ok(Value::Vector(vec![entry("b", 2)])));
+        assert_same_result("(reduce (fn [acc [k v]] (+ acc v)) 0 {:b 2 :a 1})", Ok(Value::Integer(3)));
+        assert_same_result("(sort-by (fn [[k v]] (- v)) {:b 2 :a 1})", Ok(Value::Vector(vec![entry("b", 2), entry("a", 1)])));
+        assert_same_result("(some (fn [[k v]] (= v 2)) {:b 2 :a 1})", Ok(Value::Boolean(true)));
+        assert_same_result("(every? (fn [[k v]] (> v 1)) {:b 2 :a 1})", Ok(Value::Boolean(false)));
+        assert_same_result("(apply vector {:a 1})", Ok(Value::Vector(vec![entry("a", 1)])));
+        let checked = Runtime::with_strategy(RuntimeStrategy::Ir)
+            .evaluate_expression(&parse_expression("(map-fn (fn [[k v]] v) {:b 2 :a 1})").unwrap());
+        assert_eq!(checked, Ok(ints(&[1, 2])));
+
+        // Closures see their captured bindings when called back
+        assert_same_result("(let [limit 2] (filter (fn [x] (> x limit)) [1 2 3 4]))", Ok(ints(&[3, 4])));
+
+        // Errors raised by the function argument propagate
+        assert_same_result("(map-fn (fn [x] (/ 1 x)) [1 0])", Err(RuntimeError::DivisionByZero));
+        assert_same_result("(sort-by (fn [x] x) [1 \"a\"])", Err(RuntimeError::TypeError {
+            expected: "comparable types".to_string(),
+            actual: "string and int".to_string(),
+            operation: "sort-by".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_loop_and_recur() {
+        assert_same_result("(loop [i 0 acc 0] (if (> i 100) acc (recur (+ i 1) (+ acc i))))", Ok(Value::Integer(5050)));
+        // Deep enough that recursion on the Rust stack would overflow
+        assert_same_result("(loop [n 100000] (if (= n 0) :done (recur (- n 1))))", Ok(Value::Keyword(Keyword("done".to_string()))));
+        // Paginate until a page comes back empty
+        assert_same_result(
+            "(let [pages [[1 2] [3] []]]
+               (loop [page 0 seen []]
+                 (let [items (get pages page)]
+                   (match (count items)
+                     0 seen
+                     _ (recur (+ page 1) (reduce conj seen items))))))",
+            Ok(Value::Vector(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)])),
+        );
+        // An inner loop's recur rebinds only the inner loop
+        assert_same_result(
+            "(loop [i 0 total 0] (if (= i 3) total (recur (+ i 1) (+ total (loop [j 0] (if (< j i) (recur (+ j 1)) j))))))",
+            Ok(Value::Integer(3)),
+        );
+    }
+
+    #[test]
+    fn test_for_doseq_and_dotimes() {
+        let ints = |items: &[i64]| Value::Vector(items.iter().map(|i| Value::Integer(*i)).collect());
+        assert_same_result("(for [x [1 2 3 4] :when (> x 2)] (* x 10))", Ok(ints(&[30, 40])));
+        assert_same_result(
+            "(for [x [1 2] y [10 20]] (+ x y))",
+            Ok(ints(&[11, 21, 12, 22])),
+        );
+        // Map entries come as [key value] pairs, in key order
+        assert_same_result(
+            "(for [[k v] {:b 2 :a 1}] [k v])",
+            Ok(Value::Vector(vec![
+                Value::Vector(vec![Value::Keyword(Keyword("a".to_string())), Value::Integer(1)]),
+                Value::Vector(vec![Value::Keyword(Keyword("b".to_string())), Value::Integer(2)]),
+            ])),
+        );
+        assert_same_result("(for [x nil] x)", Ok(ints(&[])));
+        assert_same_result("(doseq [[k v] {:a 1}] v)", Ok(Value::Nil));
+        assert_same_result("(dotimes [i 3] i)", Ok(Value::Nil));
+        assert_same_result(
+            "(for [x 5] x)",
+            Err(RuntimeError::TypeError {
+                expected: "vector, map or nil".to_string(),
+                actual: "int".to_string(),
+                operation: "iteration".to_string(),
+            }),
+        );
+        assert_same_result(
+            "(dotimes [i \"3\"] i)",
+            Err(RuntimeError::TypeError {
+                expected: "int".to_string(),
+                actual: "string".to_string(),
+                operation: "dotimes".to_string(),
+            }),
+        );
+        // Errors in the body stop the iteration
+        assert_same_result("(doseq [x [1 0]] (/ 1 x))", Err(RuntimeError::DivisionByZero));
+        // The range is not materialized, so a huge count only costs the iterations that run
+        assert_same_result("(dotimes [i 9223372036854775807] (when (= i 3) (/ 1 0)))", Err(RuntimeError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_tail_calls_run_in_constant_stack() {
+        // Far deeper than the stack budget allows for nested calls
+        assert_same_result(
+            "(let [count-down (fn [f n acc] (if (= n 0) acc (f f (- n 1) (+ acc 1))))]
+               (count-down count-down 10000 0))",
+            Ok(Value::Integer(10000)),
+        );
+        // Tail position carries through let, do and match, and between different functions
+        assert_same_result(
+            "(let [ev (fn [ev od n] (match n 0 true _ (let [m (- n 1)] (do m (od ev od m)))))
+                   od (fn [ev od n] (if (= n 0) false (ev ev od (- n 1))))]
+               (ev ev od 10001))",
+            Ok(Value::Boolean(false)),
+        );
+    }
+
+    #[test]
+    fn test_deep_recursion_is_a_runtime_error() {
+        let source = "(let [depth (fn [f n] (if (= n 0) 0 (+ 1 (f f (- n 1)))))] (depth depth 1000000))";
+        for result in [eval_ast(source), eval_ir(source)] {
+            match result.map_err(|e| e.without_location().clone()) {
+                Err(RuntimeError::StackOverflow { depth }) => assert!(depth > 3000 && depth < 1000000),
+                other => panic!("expected a stack overflow, got {:?}", other),
+            }
+        }
+        // Recursion thousands of calls deep that is not in tail position still works,
+        // well past what fits on the thread's own stack
+        assert_same_result(
+            "(do (defn deep [n] (if (= n 0) 0 (+ 1 (deep (- n 1))))) (deep 3000))",
+            Ok(Value::Integer(3000)),
+        );
+    }
+
+    #[test]
+    fn test_recursive_definitions() {
+        assert_same_result(
+            "(do (defn fact [n] (if (= n 0) 1 (* n (fact (- n 1))))) (fact 5))",
+            Ok(Value::Integer(120)),
+        );
+        // A definition may call one that comes after it
+        let parity = "(defn ev? [n] (if (= n 0) true (od? (- n 1))))
+                      (defn od? [n] (if (= n 0) false (ev? (- n 1))))";
+        assert_same_result(&format!("(do {} (ev? 10))", parity), Ok(Value::Boolean(true)));
+        assert_same_result(&format!("(let [k 3] {} (od? k))", parity), Ok(Value::Boolean(true)));
+        // Self tail calls through a defn run in constant stack
+        assert_same_result(
+            "(do (defn count-down [n] (if (= n 0) :done (count-down (- n 1)))) (count-down 10000))",
+            Ok(Value::Keyword(Keyword("done".to_string()))),
+        );
+    }
+
+    #[test]
+    fn test_redefinitions_are_seen_by_earlier_functions() {
+        assert_same_result("(do (def x 1) (defn f [] x) (def x 2) (f))", Ok(Value::Integer(2)));
+        assert_same_result("(do (defn g [] 1) (defn h [] (g)) (defn g [] 2) (h))", Ok(Value::Integer(2)));
+        // The value of a redefinition still sees the previous one
+        assert_same_result("(do (def x 1) (def x (+ x 1)) x)", Ok(Value::Integer(2)));
+        // A local binding of the name is shadowed, not redefined
+        assert_same_result("(let [x 1 f (fn [] x)] (def x 2) [(f) x])", Ok(Value::Vector(vec![
+            Value::Integer(1),
+            Value::Integer(2),
+        ])));
+        // Mutually recursive functions still work once they leave their scope
+        assert_same_result(
+            "(((fn [] (defn ev? [n] (if (= n 0) true (od? (- n 1))))
+                      (defn od? [n] (if (= n 0) false (ev? (- n 1))))
+                      od?)) 7)",
+            Ok(Value::Boolean(true)),
+        );
+    }
+
+    #[test]
+    fn test_letfn() {
+        assert_same_result(
+            "(letfn [(ev? [n] (if (= n 0) true (od? (- n 1))))
+                     (od? [n] (if (= n 0) false (ev? (- n 1))))]
+               [(ev? 4) (od? 7)])",
+            Ok(Value::Vector(vec![Value::Boolean(true), Value::Boolean(true)])),
+        );
+        // The functions close over the enclosing scope and are local to the letfn
+        assert_same_result(
+            "(let [step 2] (letfn [(down [n] (if (< n 1) n (down (- n step))))] (down 9)))",
+            Ok(Value::Integer(-1)),
+        );
+        assert!(eval_ast("(do (letfn [(f [] 1)] (f)) (f))").is_err());
+        assert!(IrConverter::new().convert(&parse_expression("(do (letfn [(f [] 1)] (f)) (f))").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_conditional_forms() {
+        let keyword = |name: &str| Ok(Value::Keyword(Keyword(name.to_string())));
+        let sign = |n: i64| format!("(cond (< {0} 0) :neg (= {0} 0) :zero :else :pos)", n);
+        assert_same_result(&sign(-4), keyword("neg"));
+        assert_same_result(&sign(0), keyword("zero"));
+        assert_same_result(&sign(9), keyword("pos"));
+        assert_same_result("(cond false 1)", Ok(Value::Nil));
+
+        assert_same_result("(when (> 2 1) 1 2)", Ok(Value::Integer(2)));
+        assert_same_result("(when false 1)", Ok(Value::Nil));
+        assert_same_result("(when-not false :ran)", keyword("ran"));
+        assert_same_result("(when-not true :ran)", Ok(Value::Nil));
+
+        // The pattern is bound in the then branch only
+        assert_same_result("(let [a 0] (if-let [[a b] [1 2]] (+ a b) a))", Ok(Value::Integer(3)));
+        assert_same_result("(let [a 0] (if-let [[a b] nil] (+ a b) a))", Ok(Value::Integer(0)));
+        assert_same_result("(if-let [x false] x)", Ok(Value::Nil));
+        assert_same_result("(when-let [{:keys [n]} {:n 4}] (+ n 1))", Ok(Value::Integer(5)));
+        assert_same_result("(when-let [x nil] (/ 1 0))", Ok(Value::Nil));
+
+        let case = |value: &str| format!("(case {} 1 :one (2 3) :few \"s\" :string :many)", value);
+        assert_same_result(&case("1"), keyword("one"));
+        assert_same_result(&case("3"), keyword("few"));
+        assert_same_result(&case("\"s\""), keyword("string"));
+        assert_same_result(&case("7"), keyword("many"));
+        assert_same_result(
+            "(case 7 1 :one)",
+            Err(RuntimeError::MatchError("No matching clause for value: 7".to_string())),
+        );
+
+        // They are in tail position of an enclosing loop
+        assert_same_result("(loop [i 0] (when (< i 5) (recur (+ i 1))))", Ok(Value::Nil));
+        assert_same_result(
+            "(loop [i 0 acc []] (cond (= i 3) acc :else (recur (+ i 1) (conj acc i))))",
+            Ok(Value::Vector(vec![Value::Integer(0), Value::Integer(1), Value::Integer(2)])),
+        );
+    }
+
+    #[test]
+    fn test_threading_forms() {
+        assert_same_result("(-> 5 (- 2) (* 3))", Ok(Value::Integer(9)));
+        assert_same_result("(->> 5 (- 2) (* 3))", Ok(Value::Integer(-9)));
+        assert_same_result("(-> {:a {:b 1}} :a :b)", Ok(Value::Integer(1)));
+        assert_same_result("(-> {:a 1} (:b 7))", Ok(Value::Integer(7)));
+        assert_same_result("(-> 2 ((fn [x] (* x x))) str)", Ok(Value::String("4".to_string())));
+        assert_same_result("(->> [1 2 3] (filter (fn [x] (> x 1))) count)", Ok(Value::Integer(2)));
+        // some-> stops at the first nil
+        assert_same_result("(some-> {:a {:b 2}} :a :b (+ 1))", Ok(Value::Integer(3)));
+        assert_same_result("(some-> {:a nil} :a :b (/ 0))", Ok(Value::Nil));
+        assert_same_result("(some-> 1)", Ok(Value::Integer(1)));
+        // The steps after the nil test pass the checker with a value that may be nil
+        let run = |c: &str| {
+            let source = format!("((fn [c] (let [m (if c {{:a 1}} nil)] (some-> m (get :a)))) {})", c);
+            Runtime::with_strategy(RuntimeStrategy::Ir).evaluate_expression(&parse_expression(&source).unwrap())
+        };
+        assert_eq!(run("true"), Ok(Value::Integer(1)));
+        assert_eq!(run("false"), Ok(Value::Nil));
+        // A value known to be nil never reaches the steps, so they are not checked against nil
+        for source in ["(some-> nil (get :a))", "(some-> nil :a)", "(some-> nil (+ 1))"] {
+            assert_same_result(source, Ok(Value::Nil));
+            let checked = Runtime::with_strategy(RuntimeStrategy::Ir).evaluate_expression(&parse_expression(source).unwrap());
+            assert_eq!(checked, Ok(Value::Nil), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_log_step_returns_its_value() {
+        assert_same_result(r#"(let [r (log-step :id "sum" (+ 1 2))] r)"#, Ok(Value::Integer(3)));
+        // The checker sees the logged value's type too
+        let source = r#"(+ 1 (log-step :id "two" 2))"#;
+        let checked = Runtime::with_strategy(RuntimeStrategy::Ir).evaluate_expression(&parse_expression(source).unwrap());
+        assert_eq!(checked, Ok(Value::Integer(3)));
+    }
+
+    #[test]
+    fn test_closures_see_the_binding_they_captured() {
+        // Rebinding a name later in the same let does not change what a closure saw
+        assert_same_result(
+            "(let [x 1 f (fn [] x) x 2] [(f) x])",
+            Ok(Value::Vector(vec![Value::Integer(1), Value::Integer(2)])),
+        );
+    }
+
+    #[test]
+    fn test_capture_analysis() {
+        let ir = IrConverter::new()
+            .convert(&parse_expression("(let [a 1 b 2 unused 3] (fn [x] (fn [y] (+ a b x y))))").unwrap())
+            .unwrap();
+        let outer = match ir {
+            IrNode::Let { body, .. } => body.into_iter().next().unwrap(),
+            other => panic!("expected a let, got {:?}", other),
+        };
+        // The outer lambda also captures what its inner lambda needs from further out
+        assert_eq!(lambda_captures(&outer), vec!["a", "b"]);
+        let inner = match &outer {
+            IrNode::Lambda { body, .. } => body[0].clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(lambda_captures(&inner), vec!["a", "b", "x"]);
+    }
+
+    #[test]
+    fn test_closure_holds_only_captured_slots() {
+        let source = "(let [big [1 2 3] n 5] (fn [x] (+ x n)))";
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        let closure = match IrRuntime::new().execute_node(&ir, &mut IrEnvironment::new()).unwrap() {
+            Value::Function(Function::IrLambda { closure, .. }) => closure,
+            other => panic!("expected an IR lambda, got {:?}", other),
+        };
+        assert_eq!(closure.binding_count(), 1);
+    }
+
+    #[test]
+    fn test_closures_share_the_lambda_ir() {
+        let ir = IrConverter::new().convert(&parse_expression("(fn [x] (+ x 1))").unwrap()).unwrap();
+        let IrNode::Lambda { body: lambda_body, .. } = &ir else {
+            panic!("expected a lambda, got {:?}", ir);
+        };
+        let mut runtime = IrRuntime::new();
+        let first = runtime.execute_node(&ir, &mut IrEnvironment::new()).unwrap();
+        let second = runtime.execute_node(&ir, &mut IrEnvironment::new()).unwrap();
+        match &first {
+            Value::Function(Function::IrLambda { body, .. }) => assert!(Rc::ptr_eq(body, lambda_body)),
+            other => panic!("expected an IR lambda, got {:?}", other),
+        }
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_ir_strategy_rejects_type_errors_before_running() {
+        let mut runtime = Runtime::with_strategy(RuntimeStrategy::Ir);
+        let plan = parse_expression(r#"(do (log-step :id "side-effect" 1) (+ "a" 1))"#).unwrap();
+        let error = runtime.evaluate_expression(&plan).unwrap_err();
+        assert!(error.location().is_some());
+        assert!(matches!(error.without_location(), RuntimeError::TypeError { operation, .. } if operation == "type check"));
+    }
+
+    #[test]
+    fn test_ir_strategy_checks_variadic_arguments_by_element_type() {
+        let run = |source: &str| {
+            Runtime::with_strategy(RuntimeStrategy::Ir).evaluate_expression(&parse_expression(source).unwrap())
+        };
+        let ints = |items: &[i64]| Value::Vector(items.iter().map(|&i| Value::Integer(i)).collect());
+        assert_eq!(run("((fn [a & rest] rest) 1 2 3)"), Ok(ints(&[2, 3])));
+        assert_eq!(run("((fn [& xs] (count xs)) 1 2 3)"), Ok(Value::Integer(3)));
+        assert_eq!(run("(do (defn g [a & more] (conj more a)) (g 1 2 3))"), Ok(ints(&[2, 3, 1])));
+        assert_eq!(run("((fn [& xs :[:vector int]] xs) 4 5)"), Ok(ints(&[4, 5])));
+        let error = run("((fn [& xs :[:vector int]] xs) 4 \"five\")").unwrap_err();
+        assert!(matches!(error.without_location(), RuntimeError::TypeError { expected, .. } if expected == "int"));
+    }
+
+    #[test]
+    fn test_ir_strategy_accepts_inferred_unions_once_narrowed() {
+        let run = |source: &str| {
+            Runtime::with_strategy(RuntimeStrategy::Ir).evaluate_expression(&parse_expression(source).unwrap())
+        };
+        let maybe = |body: &str| format!("((fn [c] (let [x (if c 1 nil)] {})) true)", body);
+        assert_eq!(run(&maybe("(if x (+ x 1) 0)")), Ok(Value::Integer(2)));
+        assert_eq!(run(&maybe("(if (nil? x) 0 (+ x 1))")), Ok(Value::Integer(2)));
+        // Without a test the value may still be nil
+        let error = run(&maybe("(+ x 1)")).unwrap_err();
+        assert!(matches!(error.without_location(), RuntimeError::TypeError { operation, .. } if operation == "type check"));
+        // Arithmetic on untyped parameters is untyped, not any number
+        assert_eq!(run("((fn [f :[:=> [int] int]] (f 1)) (fn [x] (+ x 1)))"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_refined_bindings_are_checked_at_runtime() {
+        let positive = "(fn [n :[:and int [:> 0]]] (* n 2))";
+        assert_same_result(&format!("({} 4)", positive), Ok(Value::Integer(8)));
+        for result in [eval_ast(&format!("({} (- 0 4))", positive)), eval_ir(&format!("({} (- 0 4))", positive))] {
+            match result.map_err(|e| e.without_location().clone()) {
+                Err(RuntimeError::TypeError { expected, operation, .. }) => {
+                    assert_eq!(expected, "[:and int [:> 0]] ([:> 0] does not hold)");
+                    assert_eq!(operation, "binding n");
+                }
+                other => panic!("expected a refinement error, got {:?}", other),
+            }
+        }
+        let code = "(do (def code :[:and string [:matches-regex \"^[A-Z]{3}$\"]] (str \"EU\" \"R\")) code)";
+        assert_same_result(code, Ok(Value::String("EUR".to_string())));
+        assert!(eval_ast(&code.replace("\"R\"", "\"RO\"")).is_err());
+        assert!(eval_ir(&code.replace("\"R\"", "\"RO\"")).is_err());
+    }
+
+    #[test]
+    fn test_nested_refinements_are_checked_at_runtime() {
+        let positives = "(fn [xs :[:vector [:and int [:> 0]]]] (count xs))";
+        assert_same_result(&format!("({} [1 2])", positives), Ok(Value::Integer(2)));
+        for result in [eval_ast(&format!("({} [1 -1])", positives)), eval_ir(&format!("({} [1 -1])", positives))] {
+            match result.map_err(|e| e.without_location().clone()) {
+                Err(RuntimeError::TypeError { expected, operation, .. }) => {
+                    assert_eq!(expected, "[:vector [:and int [:> 0]]] ([:> 0] does not hold at [1])");
+                    assert_eq!(operation, "binding xs");
+                }
+                other => panic!("expected a refinement error, got {:?}", other),
+            }
+        }
+        let maybe = "(fn [n :[:union nil [:and int [:> 0]]]] n)";
+        assert_same_result(&format!("({} nil)", maybe), Ok(Value::Nil));
+        assert_same_result(&format!("({} 3)", maybe), Ok(Value::Integer(3)));
+        assert!(eval_ast(&format!("({} -1)", maybe)).is_err());
+        assert!(eval_ir(&format!("({} -1)", maybe)).is_err());
+        let user = "((fn [u :[:map [:age [:and int [:>= 0]]]]] u) {:age -2})";
+        assert!(eval_ast(user).is_err());
+        assert!(eval_ir(user).is_err());
+    }
+
+    #[test]
+    fn test_array_bindings_check_shapes() {
+        let sum_rows = "(fn [m :[:array float [? 2]]] (get (tool:get-tensor-summary m) :sum))";
+        assert_same_result(&format!("({} (array [[1.0 2.0] [3.0 4.0]]))", sum_rows), Ok(Value::Float(10.0)));
+        for source in [
+            format!("({} (array [[1.0 2.0 3.0]]))", sum_rows),
+            format!("({} (array [[1 2]]))", sum_rows),
+            format!("({} [[1.0 2.0]])", sum_rows),
+        ] {
+            match eval_ast(&source).map_err(|e| e.without_location().clone()) {
+                Err(RuntimeError::TypeError { expected, operation, .. }) => {
+                    assert_eq!(expected, "[:array float [? 2]]");
+                    assert_eq!(operation, "binding m");
+                }
+                other => panic!("expected a shape error, got {:?}", other),
+            }
+            assert!(eval_ir(&source).is_err(), "IR: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_named_types_are_checked_at_bindings() {
+        let positive = "(deftype Positive [:and int [:> 0]])";
+        assert_same_result(&format!("(do {} ((fn [n :Positive] n) (+ 2 3)))", positive), Ok(Value::Integer(5)));
+        let negative = format!("(do {} ((fn [n :Positive] n) (- 0 5)))", positive);
+        match eval_ast(&negative).map_err(|e| e.without_location().clone()) {
+            Err(RuntimeError::TypeError { expected, operation, .. }) => {
+                assert_eq!(expected, "Positive ([:> 0] does not hold)");
+                assert_eq!(operation, "binding n");
+            }
+            other => panic!("expected a refinement error, got {:?}", other),
+        }
+        assert!(eval_ir(&negative).is_err());
+    }
+
+    #[test]
+    fn test_tensor_tools() {
+        let array = |source: &str| eval_ast(&format!("(array {})", source)).unwrap();
+        assert_same_result("(tool:tensor-elementwise-add (array [[1 2] [3 4]]) [[10 20] [30 40]])", Ok(array("[[11 22] [33 44]]")));
+        assert_same_result("(tool:tensor-scalar-multiply (array [1 2]) 0.5)", Ok(array("[0.5 1.0]")));
+        assert_same_result(
+            "(try (tool:tensor-elementwise-add (array [1 2]) (array [1 2 3])) (catch :error/tensor e :mismatch))",
+            Ok(Value::Keyword(Keyword("mismatch".to_string()))),
+        );
+        assert_same_result("(get (tool:get-tensor-summary (array [[1 2] [3 6]])) :shape)", Ok(Value::Vector(vec![
+            Value::Integer(2),
+            Value::Integer(2),
+        ])));
+        assert_same_result("(get (tool:get-tensor-summary (array [[1 2] [3 6]])) :mean)", Ok(Value::Float(3.0)));
+        assert_same_result("(get (tool:get-tensor-summary (array [1.5])) :dtype)", Ok(Value::Keyword(Keyword("float".to_string()))));
+        assert!(eval_ast("(array [[1 2] [3]])").is_err());
+    }
+
+    #[test]
+    fn test_try_catch() {
+        assert_same_result("(try (+ 1 2) (catch :error/arithmetic e 0))", Ok(Value::Integer(3)));
+        assert_same_result("(try (/ 1 0) (catch :error/arithmetic e 42))", Ok(Value::Integer(42)));
+        assert_same_result("(try (/ 1 0) (catch :error/other e 42))", Err(RuntimeError::DivisionByZero));
+        assert_same_result("(try (/ 1 0) (catch :error/arithmetic e 1) (finally (/ 2 0)))", Err(RuntimeError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_type_patterns() {
+        let keyword = |name: &str| Ok(Value::Keyword(Keyword(name.to_string())));
+        assert_same_result("(match 5 :string :s :int :i)", keyword("i"));
+        assert_same_result("(match \"a\" :int :i :string :s)", keyword("s"));
+        assert_same_result("(match [1 2] [:vector string] :strings [:vector int] :ints)", keyword("ints"));
+        assert_same_result("(match {:id 1} [:map [:id string]] :named [:map [:id int]] :numbered)", keyword("numbered"));
+        assert_same_result("(match 5 [:and int [:> 10]] :big _ :small)", keyword("small"));
+        assert_same_result("(match 7 (:as n :int) (+ n 1) _ 0)", Ok(Value::Integer(8)));
+        // Other keywords still match themselves
+        assert_same_result("(match :ok :error 1 :ok 2)", Ok(Value::Integer(2)));
+        assert_same_result(
+            "(match nil :int 1)",
+            Err(RuntimeError::MatchError("No matching clause for value: nil".to_string())),
+        );
+    }
+
+    #[test]
+    fn test_catch_follows_error_hierarchy() {
+        assert_same_result("(try (/ 1 0) (catch :error/network e 1) (catch :error e 2))", Ok(Value::Integer(2)));
+        assert_same_result("(try (/ 1 0) (catch :error/arith e 1) (catch :error/arithmetic e 2))", Ok(Value::Integer(2)));
+        assert_same_result("(try (/ 1 0) (catch :err e 1))", Err(RuntimeError::DivisionByZero));
+        // A name that is not a type catches every error
+        assert_same_result("(try (/ 1 0) (catch Exception e 3))", Ok(Value::Integer(3)));
+        assert_same_result("(try (/ 1 0) (catch [:map] e 4))", Err(RuntimeError::DivisionByZero));
+
+        let error = |error_type: &str| crate::runtime::values::ErrorValue {
+            error_type: Keyword(error_type.to_string()),
+            message: String::new(),
+            data: None,
+        };
+        assert!(error("error/network/timeout").is_kind_of(&Keyword("error/network".to_string())));
+        assert!(error("error/network").is_kind_of(&Keyword("error".to_string())));
+        assert!(!error("error/networking").is_kind_of(&Keyword("error/network".to_string())));
+        assert!(!error("error").is_kind_of(&Keyword("error/network".to_string())));
+    }
+
+    #[test]
+    fn test_catch_binds_error_value() {
+        let result = eval_ir("(try (/ 1 0) (catch :error/arithmetic e e))").unwrap();
+        match result {
+            Value::Error(error) => assert_eq!(error.error_type, Keyword("error/arithmetic".to_string())),
+            other => panic!("expected an error value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_finally_runs_after_catch_body() {
+        let source = r#"(try (/ 1 0)
+                          (catch :error/arithmetic e (log-step :id "catch" 1))
+                          (finally (log-step :id "finally" 2)))"#;
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        let mut runtime = IrRuntime::new();
+        runtime.execute_node(&ir, &mut IrEnvironment::new()).unwrap();
+
+        let steps: Vec<&str> = runtime
+            .task_context()
+            .step_results()
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect();
+        assert_eq!(steps, vec!["catch", "finally"]);
+    }
+
+    #[test]
+    fn test_parallel() {
+        let mut expected = std::collections::HashMap::new();
+        expected.insert(MapKey::Keyword(Keyword("a".to_string())), Value::Integer(3));
+        expected.insert(MapKey::Keyword(Keyword("b".to_string())), Value::Integer(2));
+        assert_same_result("(parallel [a (+ 1 2)] [b (* 1 2)])", Ok(Value::Map(expected)));
+    }
+
+    fn open_file_call(id: u64) -> IrNode {
+        // Unresolved binding ids fall back to a global lookup by name
+        IrNode::Apply {
+            id,
+            function: Box::new(IrNode::VariableRef {
+                id: id + 1,
+                name: "tool:open-file".to_string(),
+                binding_id: 9_999,
+                ir_type: IrType::Any,
+                source_location: None,
+            }),
+            arguments: vec![IrNode::Literal {
+                id: id + 2,
+                value: Literal::String("out.txt".to_string()),
+                ir_type: IrType::String,
+                source_location: None,
+            }],
+            ir_type: IrType::Any,
+            source_location: None,
+        }
+    }
+
+    fn with_resource(init_expr: IrNode, body: IrNode) -> IrNode {
+        IrNode::WithResource {
+            id: 1,
+            binding: Box::new(IrNode::VariableBinding {
+                id: 2,
+                name: "f".to_string(),
+                ir_type: IrType::Resource("FileHandle".to_string()),
+                source_location: None,
+            }),
+            init_expr: Box::new(init_expr),
+            body: vec![body],
+            ir_type: IrType::Any,
+            source_location: None,
+        }
+    }
+
+    fn resource_ref() -> IrNode {
+        IrNode::VariableRef {
+            id: 10,
+            name: "f".to_string(),
+            binding_id: 2,
+            ir_type: IrType::Any,
+            source_location: None,
+        }
+    }
+
+    #[test]
+    fn test_with_resource() {
+        let result = IrRuntime::new()
+            .execute_node(&with_resource(open_file_call(20), resource_ref()), &mut IrEnvironment::new())
+            .unwrap();
+        assert!(matches!(result, Value::Resource(handle) if handle.resource_type == "FileHandle"));
+
+        // Errors in the body propagate after cleanup
+        let failing_body = parse_expression("(/ 1 0)").unwrap();
+        let failing_body = IrConverter::new().convert(&failing_body).unwrap();
+        let error = IrRuntime::new()
+            .execute_node(&with_resource(open_file_call(20), failing_body), &mut IrEnvironment::new())
+            .unwrap_err();
+        assert_eq!(error.without_location(), &RuntimeError::DivisionByZero);
+
+        // Only resource handles can be managed
+        let not_a_resource = IrNode::Literal {
+            id: 20,
+            value: Literal::Integer(1),
+            ir_type: IrType::Int,
+            source_location: None,
+        };
+        let error = IrRuntime::new()
+            .execute_node(&with_resource(not_a_resource, resource_ref()), &mut IrEnvironment::new())
+            .unwrap_err();
+        assert!(matches!(error, RuntimeError::TypeError { .. }));
+    }
+}
@@ -3,3 +3,6 @@
 
 pub mod module_loading_tests;
 pub mod cross_module_ir_tests;
+pub mod source_location_tests;
+pub mod task_context_tests;
+pub mod ir_runtime_tests;
@@ -0,0 +1,66 @@
+// Source Location Tests
+// Verifies that parser spans reach IR nodes and runtime errors
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::IrNode;
+    use crate::ir_converter::{IrConversionError, IrConverter};
+    use crate::parser::parse_expression;
+    use crate::runtime::ir_runtime::{IrEnvironment, IrRuntime};
+    use crate::runtime::{Evaluator, RuntimeError};
+
+    const PROGRAM: &str = "(let [x 10]\n  (+ x (/ x 0)))";
+
+    #[test]
+    fn test_ir_nodes_carry_source_locations() {
+        let ast = parse_expression(PROGRAM).unwrap();
+        let ir = IrConverter::new().convert(&ast).unwrap();
+
+        let location = ir.source_location().expect("let node should have a location");
+        assert_eq!((location.line, location.column), (1, 1));
+
+        let IrNode::Let { body, .. } = &ir else {
+            panic!("Expected let node, got {:?}", ir);
+        };
+        let body_location = body[0].source_location().unwrap();
+        assert_eq!((body_location.line, body_location.column), (2, 3));
+        assert_eq!(&PROGRAM[body_location.start..body_location.end], "(+ x (/ x 0))");
+    }
+
+    #[test]
+    fn test_conversion_errors_carry_source_locations() {
+        let ast = parse_expression("(do 1\n  (+ 1 missing))").unwrap();
+        match IrConverter::new().convert(&ast) {
+            Err(IrConversionError::UndefinedSymbol { symbol, location: Some(location) }) => {
+                assert_eq!(symbol, "missing");
+                assert_eq!((location.line, location.column), (2, 8));
+            }
+            other => panic!("Expected located UndefinedSymbol, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_evaluator_errors_point_at_failing_form() {
+        let ast = parse_expression(PROGRAM).unwrap();
+        let error = Evaluator::new().evaluate(&ast).unwrap_err();
+
+        assert_eq!(error.without_location(), &RuntimeError::DivisionByZero);
+        let location = error.location().expect("error should carry a location");
+        assert_eq!((location.line, location.column), (2, 8));
+        assert_eq!(&PROGRAM[location.start..location.end], "(/ x 0)");
+    }
+
+    #[test]
+    fn test_ir_runtime_errors_point_at_failing_form() {
+        let ast = parse_expression(PROGRAM).unwrap();
+        let ir = IrConverter::new().convert(&ast).unwrap();
+        let error = IrRuntime::new()
+            .execute_node(&ir, &mut IrEnvironment::new())
+            .unwrap_err();
+
+        assert_eq!(error.without_location(), &RuntimeError::DivisionByZero);
+        let location = error.location().expect("error should carry a location");
+        assert_eq!(&PROGRAM[location.start..location.end], "(/ x 0)");
+        assert!(error.to_string().ends_with("(at 2:8)"));
+    }
+}
@@ -0,0 +1,62 @@
+// Task Context Tests
+// Verifies that both runtimes resolve `@field` against a host-populated task context
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Keyword;
+    use crate::ir_converter::IrConverter;
+    use crate::parser::parse_expression;
+    use crate::runtime::ir_runtime::{IrEnvironment, IrRuntime};
+    use crate::runtime::{Evaluator, RuntimeError, TaskContext, Value};
+
+    fn context() -> TaskContext {
+        TaskContext::new()
+            .with_task_id("task-7")
+            .with_input("user-id", Value::String("u-42".to_string()))
+            .with_input("limit", Value::Integer(10))
+            .with_caller_metadata("agent", Value::String("planner".to_string()))
+    }
+
+    fn eval_ast(source: &str) -> Result<Value, RuntimeError> {
+        let evaluator = Evaluator::new();
+        evaluator.set_task_context(context());
+        evaluator.evaluate(&parse_expression(source).unwrap())
+    }
+
+    fn eval_ir(source: &str) -> Result<Value, RuntimeError> {
+        let ir = IrConverter::new().convert(&parse_expression(source).unwrap()).unwrap();
+        let mut runtime = IrRuntime::new();
+        runtime.set_task_context(context());
+        runtime.execute_node(&ir, &mut IrEnvironment::new())
+    }
+
+    #[test]
+    fn test_both_runtimes_resolve_context_fields() {
+        let cases = [
+            ("@user-id", Value::String("u-42".to_string())),
+            ("@:user-id", Value::String("u-42".to_string())),
+            ("@id", Value::String("task-7".to_string())),
+            ("@input", context().resolve(&Keyword("input".to_string())).unwrap()),
+            ("(+ @limit 1)", Value::Integer(11)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(eval_ast(source), Ok(expected.clone()), "AST: {}", source);
+            assert_eq!(eval_ir(source), Ok(expected), "IR: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_missing_field_is_an_error_in_both_runtimes() {
+        assert!(eval_ast("@missing").is_err());
+        assert!(eval_ir("@missing").is_err());
+    }
+
+    #[test]
+    fn test_step_results_are_recorded() {
+        let evaluator = Evaluator::new();
+        evaluator.set_task_context(context());
+        let plan = parse_expression(r#"(do (log-step :id "fetch" 7) (+ @fetch 1))"#).unwrap();
+        assert_eq!(evaluator.evaluate(&plan), Ok(Value::Integer(8)));
+        assert_eq!(evaluator.take_task_context().step_result("fetch"), Some(&Value::Integer(7)));
+    }
+}